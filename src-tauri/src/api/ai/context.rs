@@ -0,0 +1,246 @@
+use super::conversations::{insert_summary_message, list_ai_messages_internal, Message};
+use super::local::load_discovered_models;
+use super::rig_client::{ChatMessage, RigProvider};
+use super::service::{load_custom_models, ModelConfig, SaveAiConfigRequest};
+use super::usage::{estimate_tokens, truncate_to_tokens, UsageContext};
+use super::send_message_to_provider;
+use crate::db::{self, UnifiedDbManager};
+
+// 未知模型的默认上下文窗口
+const DEFAULT_CONTEXT_LIMIT: usize = 8192;
+// 为回答预留的比例
+const RESPONSE_RESERVE_RATIO: f64 = 0.25;
+// 摘要后最近消息保留到预算的一半，避免每轮都重新摘要
+const KEEP_RATIO: f64 = 0.5;
+// 至少保留最近的消息条数（一问一答）
+const MIN_KEEP_MESSAGES: usize = 2;
+// 每条消息的角色等额外开销
+const MESSAGE_OVERHEAD: usize = 4;
+
+const SUMMARY_PROMPT: &str = "Summarize the earlier part of this conversation so it can replace those messages as context. \
+Keep facts, decisions, names, numbers, code identifiers and open questions. Write in the conversation's language, as concise bullet points.";
+
+/// 按模型名估计上下文窗口
+pub fn default_context_limit(model_name: &str) -> usize {
+    let model = model_name.to_lowercase();
+    let known: &[(&str, usize)] = &[
+        ("gemini", 1_000_000),
+        ("claude", 200_000),
+        ("gpt-4.1", 1_000_000),
+        ("gpt-4o", 128_000),
+        ("gpt-4-turbo", 128_000),
+        ("o1", 128_000),
+        ("o3", 200_000),
+        ("o4", 200_000),
+        ("gpt-3.5", 16_385),
+        ("gpt-4", 8_192),
+        ("deepseek", 64_000),
+        ("qwen", 32_768),
+        ("glm", 128_000),
+        ("doubao", 32_768),
+        ("moonshot", 128_000),
+        ("mixtral", 32_768),
+        ("llama3", 8_192),
+        ("llama-3", 8_192),
+    ];
+    known
+        .iter()
+        .find(|(prefix, _)| model.contains(prefix))
+        .map(|(_, limit)| *limit)
+        .unwrap_or(DEFAULT_CONTEXT_LIMIT)
+}
+
+/// 读取模型配置中的上下文窗口，未配置时按模型名估计
+pub async fn resolve_context_limit(conn: &libsql::Connection, provider_id: &str, model_name: &str) -> usize {
+    let configured = if let Some(id) = provider_id.strip_prefix("custom_") {
+        load_custom_models(conn)
+            .await
+            .ok()
+            .and_then(|models| models.into_iter().find(|m| m.id == id))
+            .and_then(|m| m.context_limit)
+    } else {
+        db::get_setting(conn, "ai_providers_config")
+            .await
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str::<SaveAiConfigRequest>(&json).ok())
+            .and_then(|config| config.providers.get(provider_id).cloned())
+            .and_then(|provider| {
+                provider
+                    .models
+                    .into_iter()
+                    .filter_map(|model| serde_json::from_value::<ModelConfig>(model).ok())
+                    .find(|model| model.name == model_name)
+            })
+            .and_then(|model| model.context_limit)
+    };
+    if let Some(limit) = configured.filter(|limit| *limit > 0) {
+        return limit;
+    }
+    // 本地服务发现时报告的上下文长度
+    load_discovered_models(conn, provider_id)
+        .await
+        .into_iter()
+        .find(|model| model.name == model_name)
+        .and_then(|model| model.context_length)
+        .filter(|limit| *limit > 0)
+        .unwrap_or_else(|| default_context_limit(model_name))
+}
+
+/// 历史消息可用的 token 预算：扣除回答预留和固定部分（系统提示、当前问题等）
+pub fn history_budget(context_limit: usize, fixed_tokens: usize) -> usize {
+    let prompt_budget = (context_limit as f64 * (1.0 - RESPONSE_RESERVE_RATIO)) as usize;
+    prompt_budget.saturating_sub(fixed_tokens)
+}
+
+pub fn message_tokens(message: &Message) -> usize {
+    estimate_tokens(&message.content) + MESSAGE_OVERHEAD
+}
+
+/// 计算需要摘要的消息条数；未超出预算时返回 None
+pub fn plan_compaction(token_counts: &[usize], budget: usize) -> Option<usize> {
+    if token_counts.iter().sum::<usize>() <= budget {
+        return None;
+    }
+    let keep_budget = (budget as f64 * KEEP_RATIO) as usize;
+    let mut kept_tokens = 0;
+    let mut keep = 0;
+    for tokens in token_counts.iter().rev() {
+        if keep >= MIN_KEEP_MESSAGES && kept_tokens + tokens > keep_budget {
+            break;
+        }
+        kept_tokens += tokens;
+        keep += 1;
+    }
+    let cut = token_counts.len() - keep;
+    (cut > 0).then_some(cut)
+}
+
+/// 摘要请求的对话记录，超出预算时保留末尾
+fn summary_transcript(messages: &[Message], max_tokens: usize) -> String {
+    let transcript = messages
+        .iter()
+        .map(|m| match (m.role.as_str(), m.summary_until.is_some()) {
+            (_, true) => format!("[Earlier summary]\n{}", m.content),
+            (role, false) => format!("[{}]\n{}", role, m.content),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if estimate_tokens(&transcript) <= max_tokens {
+        return transcript;
+    }
+    // 从后往前截取，较早的内容已由之前的摘要覆盖
+    let reversed: String = transcript.chars().rev().collect();
+    truncate_to_tokens(&reversed, max_tokens).chars().rev().collect()
+}
+
+/// 只保留能放进预算的最近消息
+fn drop_oldest(history: Vec<Message>, budget: usize) -> Vec<Message> {
+    let mut total = 0;
+    let keep = history
+        .iter()
+        .rev()
+        .take_while(|m| {
+            total += message_tokens(m);
+            total <= budget
+        })
+        .count();
+    let skip = history.len() - keep.max(1).min(history.len());
+    history.into_iter().skip(skip).collect()
+}
+
+/// 待发送模型和固定部分的 token 数
+pub struct ContextTarget<'a> {
+    pub provider_id: &'a str,
+    pub provider: &'a RigProvider,
+    pub model_name: &'a str,
+    pub fixed_tokens: usize,
+}
+
+/// 读取会话上下文；超出模型上下文窗口时将较早的消息滚动摘要为一条 system 消息并保存，
+/// 摘要失败时丢弃最早的消息
+pub async fn load_conversation_context(
+    conversation_id: &str,
+    target: &ContextTarget<'_>,
+    db_manager: &UnifiedDbManager,
+    usage: &UsageContext,
+) -> Result<Vec<Message>, String> {
+    let history = list_ai_messages_internal(conversation_id.to_string(), db_manager.clone()).await?;
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let limit = resolve_context_limit(&conn, target.provider_id, target.model_name).await;
+    let budget = history_budget(limit, target.fixed_tokens);
+
+    let token_counts: Vec<usize> = history.iter().map(message_tokens).collect();
+    let Some(cut) = plan_compaction(&token_counts, budget) else {
+        return Ok(history);
+    };
+
+    let transcript = summary_transcript(&history[..cut], history_budget(limit, estimate_tokens(SUMMARY_PROMPT)));
+    let request = vec![ChatMessage::system(SUMMARY_PROMPT.to_string()), ChatMessage::user(transcript)];
+    let summary = match send_message_to_provider(target.provider, target.model_name, request, db_manager, usage).await {
+        Ok(summary) if !summary.trim().is_empty() => summary,
+        Ok(_) => return Ok(drop_oldest(history, budget)),
+        Err(e) => {
+            tracing::warn!("Failed to summarize conversation {}: {}", conversation_id, e);
+            return Ok(drop_oldest(history, budget));
+        }
+    };
+
+    // 截断位置为被摘要的最后一条普通消息（若全部是旧摘要则无需保存）
+    let Some(until) = history[..cut].iter().rev().find(|m| m.summary_until.is_none()).map(|m| m.id.clone()) else {
+        return Ok(drop_oldest(history, budget));
+    };
+    let summary_message = insert_summary_message(&conn, conversation_id, summary.trim(), &until).await?;
+    tracing::info!("Compacted {} messages of conversation {}", cut, conversation_id);
+
+    let compacted: Vec<Message> = std::iter::once(summary_message).chain(history.into_iter().skip(cut)).collect();
+    let total: usize = compacted.iter().map(message_tokens).sum();
+    Ok(if total > budget { drop_oldest(compacted, budget) } else { compacted })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guesses_context_limit_from_model_name() {
+        assert_eq!(default_context_limit("gpt-4o-mini"), 128_000);
+        assert_eq!(default_context_limit("gpt-4"), 8_192);
+        assert_eq!(default_context_limit("claude-3-5-sonnet"), 200_000);
+        assert_eq!(default_context_limit("my-local-model"), DEFAULT_CONTEXT_LIMIT);
+    }
+
+    #[test]
+    fn reserves_room_for_reply() {
+        assert_eq!(history_budget(1000, 100), 650);
+        assert_eq!(history_budget(100, 500), 0);
+    }
+
+    #[test]
+    fn plans_compaction_only_on_overflow() {
+        assert_eq!(plan_compaction(&[10, 10, 10], 30), None);
+        // 保留到预算一半（50）
+        assert_eq!(plan_compaction(&[40, 30, 20, 20, 10], 100), Some(2));
+        // 至少保留最近一问一答
+        assert_eq!(plan_compaction(&[10, 80, 80], 100), Some(1));
+        assert_eq!(plan_compaction(&[200, 200], 100), None);
+    }
+
+    #[test]
+    fn transcript_keeps_latest_text() {
+        let message = |content: &str| Message {
+            id: content.to_string(),
+            conversation_id: "c".to_string(),
+            role: "user".to_string(),
+            content: content.to_string(),
+            timestamp: 0,
+            citations: Vec::new(),
+            tool_calls: Vec::new(),
+            parent_id: None,
+            summary_until: None,
+        };
+        let transcript = summary_transcript(&[message("aaaa aaaa"), message("bbbb")], 3);
+        assert!(transcript.ends_with("bbbb"));
+        assert!(!transcript.contains("aaaa"));
+    }
+}
@@ -1,7 +1,10 @@
+use super::rag::Citation;
+use super::tools::ToolCallRecord;
 use crate::db::UnifiedDbManager;
 use chrono::Utc;
 use libsql::params;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use tauri::State;
 use uuid::Uuid;
 
@@ -12,6 +15,8 @@ pub struct Conversation {
     pub model: String,
     pub created_at: i64,
     pub updated_at: i64,
+    #[serde(default)]
+    pub active_message_id: Option<String>, // 当前分支的最后一条消息
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -21,6 +26,22 @@ pub struct Message {
     pub role: String,
     pub content: String,
     pub timestamp: i64, // 保持前端兼容性，仍使用timestamp字段名
+    #[serde(default)]
+    pub citations: Vec<Citation>, // 基于笔记回答时引用的来源
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCallRecord>, // 生成回答时的工具调用记录
+    #[serde(default)]
+    pub parent_id: Option<String>, // 上一条消息，编辑或重新生成时产生兄弟分支
+    #[serde(default)]
+    pub summary_until: Option<String>, // 摘要消息：概括到此消息（含）为止的历史
+}
+
+/// 当前路径上存在多个分支的位置
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BranchPoint {
+    pub parent_id: Option<String>,
+    pub message_ids: Vec<String>, // 同一父消息下的兄弟消息，按创建时间排序
+    pub active_index: usize,
 }
 
 #[tauri::command]
@@ -30,7 +51,7 @@ pub async fn list_ai_conversations(
     let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
     let mut rows = conn
         .query(
-            "SELECT id, title, model, created_at, updated_at FROM ai_conversations ORDER BY updated_at DESC",
+            "SELECT id, title, model, created_at, updated_at, active_message_id FROM ai_conversations ORDER BY updated_at DESC",
             ()
         )
         .await
@@ -44,47 +65,232 @@ pub async fn list_ai_conversations(
             model: row.get::<String>(2).map_err(|e| e.to_string())?,
             created_at: row.get::<i64>(3).map_err(|e| e.to_string())?,
             updated_at: row.get::<i64>(4).map_err(|e| e.to_string())?,
+            active_message_id: row.get::<Option<String>>(5).map_err(|e| e.to_string())?,
         });
     }
 
     Ok(conversations)
 }
 
+/// 当前分支上的消息；已被摘要的部分仍然保留，摘要消息插在截断位置之后
 #[tauri::command]
 pub async fn list_ai_messages(
     conversation_id: String,
     db_manager: State<'_, UnifiedDbManager>,
 ) -> Result<Vec<Message>, String> {
-    list_ai_messages_internal(conversation_id, db_manager.inner().clone()).await
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let messages = load_conversation_messages(&conn, &conversation_id).await?;
+    let leaf = get_active_message_id(&conn, &conversation_id).await?;
+    let path = active_path(&messages, leaf.as_deref());
+    let summaries = load_summary_messages(&conn, &conversation_id).await?;
+    Ok(with_summary_marker(path, &summaries))
 }
 
+/// 当前分支路径上的消息，用作对话上下文；最近一次摘要之前的消息由摘要代替
 pub async fn list_ai_messages_internal(
     conversation_id: String,
     db_manager: UnifiedDbManager,
 ) -> Result<Vec<Message>, String> {
     let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let messages = load_conversation_messages(&conn, &conversation_id).await?;
+    let leaf = get_active_message_id(&conn, &conversation_id).await?;
+    let path = active_path(&messages, leaf.as_deref());
+    let summaries = load_summary_messages(&conn, &conversation_id).await?;
+    Ok(apply_summary(path, &summaries))
+}
+
+const MESSAGE_COLUMNS: &str = "id, conversation_id, role, content, created_at, citations, tool_calls, parent_id, summary_until";
+
+fn message_from_row(row: &libsql::Row) -> Result<Message, String> {
+    Ok(Message {
+        id: row.get::<String>(0).map_err(|e| e.to_string())?,
+        conversation_id: row.get::<String>(1).map_err(|e| e.to_string())?,
+        role: row.get::<String>(2).map_err(|e| e.to_string())?,
+        content: row.get::<String>(3).map_err(|e| e.to_string())?,
+        timestamp: row.get::<i64>(4).map_err(|e| e.to_string())?, // 从created_at读取但返回为timestamp
+        citations: row
+            .get::<Option<String>>(5)
+            .map_err(|e| e.to_string())?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+        tool_calls: row
+            .get::<Option<String>>(6)
+            .map_err(|e| e.to_string())?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+        parent_id: row.get::<Option<String>>(7).map_err(|e| e.to_string())?,
+        summary_until: row.get::<Option<String>>(8).map_err(|e| e.to_string())?,
+    })
+}
+
+async fn query_messages(conn: &libsql::Connection, conversation_id: &str, filter: &str) -> Result<Vec<Message>, String> {
     let mut rows = conn
         .query(
-            "SELECT id, conversation_id, role, content, created_at FROM ai_messages WHERE conversation_id = ? ORDER BY created_at ASC",
-            params![conversation_id]
+            &format!(
+                "SELECT {} FROM ai_messages WHERE conversation_id = ? AND {} ORDER BY created_at ASC, rowid ASC",
+                MESSAGE_COLUMNS, filter
+            ),
+            params![conversation_id],
         )
         .await
         .map_err(|e| e.to_string())?;
 
     let mut messages = Vec::new();
     while let Some(row) = rows.next().await.map_err(|e| e.to_string())? {
-        messages.push(Message {
-            id: row.get::<String>(0).map_err(|e| e.to_string())?,
-            conversation_id: row.get::<String>(1).map_err(|e| e.to_string())?,
-            role: row.get::<String>(2).map_err(|e| e.to_string())?,
-            content: row.get::<String>(3).map_err(|e| e.to_string())?,
-            timestamp: row.get::<i64>(4).map_err(|e| e.to_string())?, // 从created_at读取但返回为timestamp
-        });
+        messages.push(message_from_row(&row)?);
     }
-
     Ok(messages)
 }
 
+/// 读取会话的全部消息（包括所有分支，不含摘要），按创建时间排序
+async fn load_conversation_messages(conn: &libsql::Connection, conversation_id: &str) -> Result<Vec<Message>, String> {
+    query_messages(conn, conversation_id, "summary_until IS NULL").await
+}
+
+/// 读取会话的摘要消息，按创建时间排序
+async fn load_summary_messages(conn: &libsql::Connection, conversation_id: &str) -> Result<Vec<Message>, String> {
+    query_messages(conn, conversation_id, "summary_until IS NOT NULL").await
+}
+
+/// 路径上最靠后的摘要及其截断位置
+fn latest_summary<'a>(path: &[Message], summaries: &'a [Message]) -> Option<(usize, &'a Message)> {
+    summaries
+        .iter()
+        .filter_map(|summary| {
+            let until = summary.summary_until.as_deref()?;
+            path.iter().position(|m| m.id == until).map(|index| (index, summary))
+        })
+        .max_by_key(|(index, summary)| (*index, summary.timestamp))
+}
+
+/// 用摘要代替截断位置及之前的消息
+pub fn apply_summary(path: Vec<Message>, summaries: &[Message]) -> Vec<Message> {
+    match latest_summary(&path, summaries) {
+        Some((index, summary)) => std::iter::once(summary.clone()).chain(path.into_iter().skip(index + 1)).collect(),
+        None => path,
+    }
+}
+
+/// 在截断位置之后插入摘要消息，供界面显示
+pub fn with_summary_marker(mut path: Vec<Message>, summaries: &[Message]) -> Vec<Message> {
+    if let Some((index, summary)) = latest_summary(&path, summaries) {
+        path.insert(index + 1, summary.clone());
+    }
+    path
+}
+
+/// 保存滚动摘要，不改变分支结构
+pub async fn insert_summary_message(
+    conn: &libsql::Connection,
+    conversation_id: &str,
+    content: &str,
+    summary_until: &str,
+) -> Result<Message, String> {
+    let message = Message {
+        id: Uuid::new_v4().to_string(),
+        conversation_id: conversation_id.to_string(),
+        role: "system".to_string(),
+        content: content.to_string(),
+        timestamp: Utc::now().timestamp_millis(),
+        citations: Vec::new(),
+        tool_calls: Vec::new(),
+        parent_id: None,
+        summary_until: Some(summary_until.to_string()),
+    };
+    conn.execute(
+        "INSERT INTO ai_messages (id, conversation_id, role, content, created_at, summary_until) VALUES (?, ?, ?, ?, ?, ?)",
+        params![
+            message.id.clone(),
+            message.conversation_id.clone(),
+            message.role.clone(),
+            message.content.clone(),
+            message.timestamp,
+            summary_until
+        ],
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(message)
+}
+
+async fn get_active_message_id(conn: &libsql::Connection, conversation_id: &str) -> Result<Option<String>, String> {
+    let mut rows = conn
+        .query(
+            "SELECT active_message_id FROM ai_conversations WHERE id = ?",
+            params![conversation_id],
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    match rows.next().await.map_err(|e| e.to_string())? {
+        Some(row) => row.get::<Option<String>>(0).map_err(|e| e.to_string()),
+        None => Err(format!("Conversation with id '{}' does not exist", conversation_id)),
+    }
+}
+
+async fn set_active_message_id(conn: &libsql::Connection, conversation_id: &str, message_id: Option<&str>) -> Result<(), String> {
+    conn.execute(
+        "UPDATE ai_conversations SET active_message_id = ?, updated_at = ? WHERE id = ?",
+        params![message_id, Utc::now().timestamp_millis(), conversation_id],
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 从叶子消息回溯到根，得到当前分支路径；未设置或已失效时使用最新的消息
+pub fn active_path(messages: &[Message], leaf: Option<&str>) -> Vec<Message> {
+    let by_id: HashMap<&str, &Message> = messages.iter().map(|m| (m.id.as_str(), m)).collect();
+    let mut current = leaf
+        .and_then(|id| by_id.get(id).copied())
+        .or_else(|| messages.last());
+
+    let mut path = Vec::new();
+    let mut visited = HashSet::new();
+    while let Some(message) = current {
+        if !visited.insert(message.id.as_str()) {
+            break;
+        }
+        path.push(message.clone());
+        current = message.parent_id.as_deref().and_then(|id| by_id.get(id).copied());
+    }
+    path.reverse();
+    path
+}
+
+/// 沿最新的子消息向下找到分支末端
+pub fn latest_descendant<'a>(messages: &'a [Message], message_id: &'a str) -> &'a str {
+    let mut current = message_id;
+    let mut visited = HashSet::new();
+    while visited.insert(current) {
+        match messages.iter().rev().find(|m| m.parent_id.as_deref() == Some(current)) {
+            Some(child) => current = &child.id,
+            None => break,
+        }
+    }
+    current
+}
+
+/// 当前路径上有兄弟消息的位置
+pub fn branch_points(messages: &[Message], path: &[Message]) -> Vec<BranchPoint> {
+    path.iter()
+        .filter_map(|message| {
+            let siblings: Vec<String> = messages
+                .iter()
+                .filter(|m| m.parent_id == message.parent_id)
+                .map(|m| m.id.clone())
+                .collect();
+            if siblings.len() < 2 {
+                return None;
+            }
+            Some(BranchPoint {
+                parent_id: message.parent_id.clone(),
+                active_index: siblings.iter().position(|id| *id == message.id).unwrap_or(0),
+                message_ids: siblings,
+            })
+        })
+        .collect()
+}
+
 #[tauri::command]
 pub async fn create_ai_conversation(
     title: String,
@@ -99,6 +305,7 @@ pub async fn create_ai_conversation(
         model,
         created_at: now,
         updated_at: now,
+        active_message_id: None,
     };
     conn.execute(
         "INSERT INTO ai_conversations (id, title, model, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
@@ -137,11 +344,11 @@ pub async fn clear_ai_conversation(
     let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
     conn.execute(
         "DELETE FROM ai_messages WHERE conversation_id = ?",
-        params![conversation_id],
+        params![conversation_id.clone()],
     )
     .await
     .map_err(|e| e.to_string())?;
-    Ok(())
+    set_active_message_id(&conn, &conversation_id, None).await
 }
 
 #[tauri::command]
@@ -166,6 +373,7 @@ pub async fn add_ai_message(
     conversation_id: String,
     role: String,
     content: String,
+    citations: Option<Vec<Citation>>,
     db_manager: State<'_, UnifiedDbManager>,
 ) -> Result<Message, String> {
     let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
@@ -182,35 +390,258 @@ pub async fn add_ai_message(
     if rows.next().await.map_err(|e| e.to_string())?.is_none() {
         return Err(format!("Conversation with id '{}' does not exist", conversation_id));
     }
-    
+
+    insert_ai_message(&conn, &conversation_id, &role, &content, &citations.unwrap_or_default(), &[]).await
+}
+
+/// 在当前分支末尾写入一条消息
+pub async fn insert_ai_message(
+    conn: &libsql::Connection,
+    conversation_id: &str,
+    role: &str,
+    content: &str,
+    citations: &[Citation],
+    tool_calls: &[ToolCallRecord],
+) -> Result<Message, String> {
+    let parent_id = resolve_active_leaf(conn, conversation_id).await?;
+    insert_message_with_parent(conn, conversation_id, parent_id, role, content, citations, tool_calls).await
+}
+
+/// 当前分支的末端消息；未记录或已被删除时取最新的消息
+async fn resolve_active_leaf(conn: &libsql::Connection, conversation_id: &str) -> Result<Option<String>, String> {
+    let mut rows = conn
+        .query(
+            "SELECT COALESCE(
+                (SELECT m.id FROM ai_messages m JOIN ai_conversations c ON m.id = c.active_message_id WHERE c.id = ?),
+                (SELECT id FROM ai_messages WHERE conversation_id = ? AND summary_until IS NULL ORDER BY created_at DESC, rowid DESC LIMIT 1)
+            )",
+            params![conversation_id, conversation_id],
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+    match rows.next().await.map_err(|e| e.to_string())? {
+        Some(row) => row.get::<Option<String>>(0).map_err(|e| e.to_string()),
+        None => Ok(None),
+    }
+}
+
+/// 写入一条消息，设为当前分支末端并更新会话时间
+async fn insert_message_with_parent(
+    conn: &libsql::Connection,
+    conversation_id: &str,
+    parent_id: Option<String>,
+    role: &str,
+    content: &str,
+    citations: &[Citation],
+    tool_calls: &[ToolCallRecord],
+) -> Result<Message, String> {
     let now = Utc::now().timestamp_millis();
     let message = Message {
         id: Uuid::new_v4().to_string(),
-        conversation_id: conversation_id.clone(),
-        role,
-        content,
+        conversation_id: conversation_id.to_string(),
+        role: role.to_string(),
+        content: content.to_string(),
         timestamp: now,
+        citations: citations.to_vec(),
+        tool_calls: tool_calls.to_vec(),
+        parent_id,
+        summary_until: None,
     };
-    
+    let citations_json = if citations.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(citations).map_err(|e| e.to_string())?)
+    };
+    let tool_calls_json = if tool_calls.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(tool_calls).map_err(|e| e.to_string())?)
+    };
+
     conn.execute(
-        "INSERT INTO ai_messages (id, conversation_id, role, content, created_at) VALUES (?, ?, ?, ?, ?)",
+        "INSERT INTO ai_messages (id, conversation_id, role, content, created_at, citations, tool_calls, parent_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
         params![
             message.id.clone(),
             message.conversation_id.clone(),
             message.role.clone(),
             message.content.clone(),
-            message.timestamp
+            message.timestamp,
+            citations_json,
+            tool_calls_json,
+            message.parent_id.clone()
         ],
     )
     .await
     .map_err(|e| e.to_string())?;
 
     conn.execute(
-        "UPDATE ai_conversations SET updated_at = ? WHERE id = ?",
-        params![now, conversation_id],
+        "UPDATE ai_conversations SET active_message_id = ?, updated_at = ? WHERE id = ?",
+        params![message.id.clone(), now, conversation_id],
     )
     .await
     .map_err(|e| e.to_string())?;
 
     Ok(message)
 }
+
+fn find_message<'a>(messages: &'a [Message], message_id: &str) -> Result<&'a Message, String> {
+    messages
+        .iter()
+        .find(|m| m.id == message_id)
+        .ok_or_else(|| format!("Message with id '{}' does not exist", message_id))
+}
+
+/// 编辑之前的提问：在同一父消息下新建兄弟消息并切换到新分支，原分支保留
+#[tauri::command(rename_all = "snake_case")]
+pub async fn edit_ai_message(
+    conversation_id: String,
+    message_id: String,
+    content: String,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<Message, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let messages = load_conversation_messages(&conn, &conversation_id).await?;
+    let original = find_message(&messages, &message_id)?;
+    if original.role != "user" {
+        return Err("Only user messages can be edited".to_string());
+    }
+    insert_message_with_parent(&conn, &conversation_id, original.parent_id.clone(), "user", &content, &[], &[]).await
+}
+
+/// 重新生成回答：当前分支回退到该回答的上一条消息，之后写入的回答成为其兄弟分支；返回新的上下文
+#[tauri::command(rename_all = "snake_case")]
+pub async fn regenerate_ai_message(
+    conversation_id: String,
+    message_id: String,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<Vec<Message>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let messages = load_conversation_messages(&conn, &conversation_id).await?;
+    let original = find_message(&messages, &message_id)?;
+    if original.role != "assistant" {
+        return Err("Only assistant messages can be regenerated".to_string());
+    }
+    let parent_id = original
+        .parent_id
+        .clone()
+        .ok_or_else(|| "Message has no prompt to regenerate from".to_string())?;
+    set_active_message_id(&conn, &conversation_id, Some(&parent_id)).await?;
+    Ok(active_path(&messages, Some(&parent_id)))
+}
+
+/// 当前路径上的分支位置
+#[tauri::command(rename_all = "snake_case")]
+pub async fn list_ai_message_branches(
+    conversation_id: String,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<Vec<BranchPoint>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let messages = load_conversation_messages(&conn, &conversation_id).await?;
+    let leaf = get_active_message_id(&conn, &conversation_id).await?;
+    let path = active_path(&messages, leaf.as_deref());
+    Ok(branch_points(&messages, &path))
+}
+
+/// 切换到包含指定消息的分支（沿最新的后续消息到末端），返回新的当前路径
+#[tauri::command(rename_all = "snake_case")]
+pub async fn switch_ai_branch(
+    conversation_id: String,
+    message_id: String,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<Vec<Message>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let messages = load_conversation_messages(&conn, &conversation_id).await?;
+    find_message(&messages, &message_id)?;
+    let leaf = latest_descendant(&messages, &message_id).to_string();
+    set_active_message_id(&conn, &conversation_id, Some(&leaf)).await?;
+    Ok(active_path(&messages, Some(&leaf)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, parent_id: Option<&str>) -> Message {
+        Message {
+            id: id.to_string(),
+            conversation_id: "c".to_string(),
+            role: "user".to_string(),
+            content: id.to_string(),
+            timestamp: 0,
+            citations: Vec::new(),
+            tool_calls: Vec::new(),
+            parent_id: parent_id.map(str::to_string),
+            summary_until: None,
+        }
+    }
+
+    fn summary(id: &str, until: &str, timestamp: i64) -> Message {
+        Message {
+            role: "system".to_string(),
+            timestamp,
+            parent_id: None,
+            summary_until: Some(until.to_string()),
+            ..message(id, None)
+        }
+    }
+
+    fn ids(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.id.as_str()).collect()
+    }
+
+    // a -> b -> c, 编辑 b 得到 b2 -> d
+    fn tree() -> Vec<Message> {
+        vec![
+            message("a", None),
+            message("b", Some("a")),
+            message("c", Some("b")),
+            message("b2", Some("a")),
+            message("d", Some("b2")),
+        ]
+    }
+
+    #[test]
+    fn builds_path_from_leaf() {
+        let messages = tree();
+        assert_eq!(ids(&active_path(&messages, Some("c"))), vec!["a", "b", "c"]);
+        assert_eq!(ids(&active_path(&messages, Some("d"))), vec!["a", "b2", "d"]);
+        // 未设置时使用最新的消息
+        assert_eq!(ids(&active_path(&messages, None)), vec!["a", "b2", "d"]);
+        assert_eq!(ids(&active_path(&messages, Some("missing"))), vec!["a", "b2", "d"]);
+    }
+
+    #[test]
+    fn follows_latest_child_when_switching() {
+        let messages = tree();
+        assert_eq!(latest_descendant(&messages, "b"), "c");
+        assert_eq!(latest_descendant(&messages, "a"), "d");
+        assert_eq!(latest_descendant(&messages, "d"), "d");
+    }
+
+    #[test]
+    fn reports_branch_points_on_path() {
+        let messages = tree();
+        let points = branch_points(&messages, &active_path(&messages, Some("c")));
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].parent_id.as_deref(), Some("a"));
+        assert_eq!(points[0].message_ids, vec!["b", "b2"]);
+        assert_eq!(points[0].active_index, 0);
+    }
+
+    #[test]
+    fn survives_parent_cycles() {
+        let messages = vec![message("x", Some("y")), message("y", Some("x"))];
+        assert_eq!(active_path(&messages, Some("x")).len(), 2);
+        assert_eq!(latest_descendant(&messages, "x"), "x");
+    }
+
+    #[test]
+    fn replaces_summarized_prefix() {
+        let path = active_path(&tree(), Some("d"));
+        let summaries = vec![summary("s1", "a", 1), summary("s2", "b2", 2), summary("s3", "c", 3)];
+        // s3 不在当前分支上
+        assert_eq!(ids(&apply_summary(path.clone(), &summaries)), vec!["s2", "d"]);
+        assert_eq!(ids(&with_summary_marker(path.clone(), &summaries)), vec!["a", "b2", "s2", "d"]);
+        assert_eq!(ids(&apply_summary(path, &[])), vec!["a", "b2", "d"]);
+    }
+}
@@ -0,0 +1,282 @@
+use super::rig_client::RigProvider;
+use super::service::ModelInfo;
+use super::usage::{enforce_budget, estimate_tokens, UsageCall, UsageContext};
+use crate::db::{self, EmbeddingIndexStats, SemanticSearchResult, UnifiedDbManager};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+// 每次请求嵌入的最大分块数
+const EMBEDDING_BATCH_SIZE: usize = 32;
+// 每轮索引读取的笔记数
+const INDEX_PAGE_SIZE: i32 = 200;
+
+static INDEX_RUNNING: AtomicBool = AtomicBool::new(false);
+static INDEX_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// 已配置的嵌入模型
+struct EmbeddingModelConfig {
+    provider: RigProvider,
+    model_name: String,
+    key: String, // provider/model，用于区分不同模型生成的向量
+}
+
+/// 检索模式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SemanticSearchMode {
+    Semantic,
+    #[default]
+    Hybrid,
+}
+
+async fn load_embedding_model(conn: &libsql::Connection) -> Result<Option<EmbeddingModelConfig>, String> {
+    let Some(value) = db::get_setting(conn, "default_embedding_model").await.map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+    let info: ModelInfo = serde_json::from_str(&value).map_err(|e| format!("Invalid embedding model setting: {}", e))?;
+    let (provider, _) = super::create_provider_from_config(&info.provider, conn).await?;
+    Ok(Some(EmbeddingModelConfig {
+        provider,
+        key: format!("{}/{}", info.provider, info.name),
+        model_name: info.name,
+    }))
+}
+
+async fn embed_all(
+    model: &EmbeddingModelConfig,
+    texts: Vec<String>,
+    db_manager: &UnifiedDbManager,
+) -> Result<Vec<Vec<f32>>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    enforce_budget(&conn, model.provider.provider_name()).await?;
+
+    let mut vectors = Vec::with_capacity(texts.len());
+    for batch in texts.chunks(EMBEDDING_BATCH_SIZE) {
+        let prompt_tokens = batch.iter().map(|t| estimate_tokens(t)).sum();
+        let call = UsageCall::start(
+            model.provider.provider_name(),
+            &model.model_name,
+            "embedding",
+            &UsageContext::default(),
+            prompt_tokens,
+        );
+        match model.provider.embed(&model.model_name, batch.to_vec(), db_manager).await {
+            Ok(embedded) => {
+                call.finish(db_manager, None, "", None).await;
+                vectors.extend(embedded);
+            }
+            Err(e) => {
+                call.finish(db_manager, None, "", Some(e.to_string())).await;
+                return Err(e.to_string());
+            }
+        }
+    }
+    Ok(vectors)
+}
+
+/// 为变化过的笔记（含音频转写）重新计算向量，返回本次嵌入的笔记数；单篇笔记嵌入失败时记录并跳过
+async fn run_embedding_index(app: &AppHandle) -> Result<usize, String> {
+    let db_manager = app.state::<UnifiedDbManager>().inner().clone();
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let Some(model) = load_embedding_model(&conn).await? else {
+        return Ok(0);
+    };
+
+    db::prune_tip_embeddings(&conn, &model.key).await.map_err(|e| e.to_string())?;
+
+    let mut embedded = 0;
+    let mut failed = 0;
+    let mut last_error = None;
+    let mut seen = HashSet::new();
+    loop {
+        let candidates = db::list_embedding_candidates(&conn, &model.key, INDEX_PAGE_SIZE)
+            .await
+            .map_err(|e| e.to_string())?;
+        let page_len = candidates.len();
+        // 本轮处理过仍被选中的笔记（如更新时间晚于当前时间）不再重复处理
+        let candidates: Vec<_> = candidates.into_iter().filter(|c| seen.insert(c.tip_id.clone())).collect();
+        if candidates.is_empty() {
+            break;
+        }
+
+        for candidate in &candidates {
+            let hash = candidate.content_hash(&model.key);
+            let stored = db::get_embedding_hash(&conn, &candidate.tip_id, &model.key)
+                .await
+                .map_err(|e| e.to_string())?;
+            if stored.as_deref() == Some(hash.as_str()) {
+                db::touch_tip_embeddings(&conn, &candidate.tip_id, &model.key)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                continue;
+            }
+
+            let chunks = candidate.chunks();
+            let texts = chunks.iter().map(|c| c.text.clone()).collect();
+            let vectors = match embed_all(&model, texts, &db_manager).await {
+                Ok(vectors) => vectors,
+                Err(e) => {
+                    tracing::warn!("Failed to embed tip {}: {}", candidate.tip_id, e);
+                    failed += 1;
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+            db::replace_tip_embeddings(&conn, &candidate.tip_id, &model.key, &hash, &chunks, &vectors)
+                .await
+                .map_err(|e| e.to_string())?;
+            embedded += 1;
+        }
+
+        app.emit("embedding-index-progress", serde_json::json!({ "embedded": embedded, "failed": failed })).ok();
+        if page_len < INDEX_PAGE_SIZE as usize {
+            break;
+        }
+    }
+
+    // 全部失败时（如服务不可用）作为错误上报
+    match last_error {
+        Some(e) if embedded == 0 => Err(format!("Failed to embed {} tips: {}", failed, e)),
+        _ => Ok(embedded),
+    }
+}
+
+/// 在后台更新向量索引；正在运行时只标记需要再跑一轮
+pub fn schedule_embedding_index(app: &AppHandle) {
+    INDEX_PENDING.store(true, Ordering::SeqCst);
+    if INDEX_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            while INDEX_PENDING.swap(false, Ordering::SeqCst) {
+                match run_embedding_index(&app).await {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!("Embedded {} tips", count),
+                    Err(e) => {
+                        tracing::warn!("Embedding index update failed: {}", e);
+                        app.emit("embedding-index-error", serde_json::json!({ "error": e })).ok();
+                    }
+                }
+            }
+            INDEX_RUNNING.store(false, Ordering::SeqCst);
+            // 退出前有新的请求且没有其他任务接手时继续
+            if !INDEX_PENDING.load(Ordering::SeqCst) || INDEX_RUNNING.swap(true, Ordering::SeqCst) {
+                break;
+            }
+        }
+        app.emit("embedding-index-done", serde_json::json!({})).ok();
+    });
+}
+
+/// 更新向量索引（force 时清空后全部重建）
+#[tauri::command]
+pub async fn rebuild_tip_embeddings(
+    app: AppHandle,
+    force: Option<bool>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<(), String> {
+    if force.unwrap_or(false) {
+        let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+        db::clear_tip_embeddings(&conn).await.map_err(|e| e.to_string())?;
+    }
+    schedule_embedding_index(&app);
+    Ok(())
+}
+
+/// 获取向量索引状态
+#[tauri::command]
+pub async fn get_embedding_index_status(
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<EmbeddingIndexStats, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let model = load_embedding_model(&conn).await?.map(|m| m.key);
+    db::get_embedding_index_stats(&conn, model.as_deref()).await.map_err(|e| e.to_string())
+}
+
+/// 语义检索笔记：余弦相似度 top-k，混合模式下再与关键词结果做倒数排名融合
+#[tauri::command]
+pub async fn semantic_search_tips(
+    query: String,
+    limit: Option<usize>,
+    mode: Option<SemanticSearchMode>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<Vec<SemanticSearchResult>, String> {
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    search_with_embeddings(&conn, db_manager.inner(), &query, limit.unwrap_or(20), mode.unwrap_or_default())
+        .await?
+        .ok_or_else(|| "No embedding model configured".to_string())
+}
+
+/// 向量检索（可与关键词结果融合）；未配置嵌入模型时返回 None
+pub(super) async fn search_with_embeddings(
+    conn: &libsql::Connection,
+    db_manager: &UnifiedDbManager,
+    query: &str,
+    limit: usize,
+    mode: SemanticSearchMode,
+) -> Result<Option<Vec<SemanticSearchResult>>, String> {
+    let limit = limit.max(1);
+    let Some(model) = load_embedding_model(conn).await? else {
+        return Ok(None);
+    };
+
+    let query_vector = embed_all(&model, vec![query.to_string()], db_manager)
+        .await?
+        .pop()
+        .ok_or("Embedding provider returned no vector")?;
+    let hits = db::semantic_search(conn, &model.key, &query_vector, limit * 2)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let keyword_ids: Vec<String> = if mode == SemanticSearchMode::Hybrid {
+        db::search_tips_summary_fast(conn, query, (limit * 2) as i32)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|s| s.id)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let semantic_ids: Vec<String> = hits.iter().map(|h| h.tip_id.clone()).collect();
+    let ranked: Vec<(String, f64)> = match mode {
+        SemanticSearchMode::Semantic => hits.iter().map(|h| (h.tip_id.clone(), h.score as f64)).collect(),
+        SemanticSearchMode::Hybrid => db::reciprocal_rank_fusion(&[semantic_ids, keyword_ids.clone()]),
+    };
+    let ranked: Vec<(String, f64)> = ranked.into_iter().take(limit).collect();
+
+    let ids: Vec<String> = ranked.iter().map(|(id, _)| id.clone()).collect();
+    let mut summaries: HashMap<String, _> = db::get_tip_summaries_by_ids(conn, &ids)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|s| (s.id.clone(), s))
+        .collect();
+    let hits: HashMap<&str, &db::SemanticHit> = hits.iter().map(|h| (h.tip_id.as_str(), h)).collect();
+
+    Ok(Some(
+        ranked
+            .into_iter()
+            .filter_map(|(id, score)| {
+                let tip = summaries.remove(&id)?;
+                let hit = hits.get(id.as_str());
+                Some(SemanticSearchResult {
+                    tip,
+                    score,
+                    semantic_score: hit.map(|h| h.score),
+                    keyword_rank: keyword_ids.iter().position(|k| *k == id).map(|p| p + 1),
+                    matched_chunk: hit.map(|h| h.chunk_text.clone()),
+                })
+            })
+            .collect(),
+    ))
+}
@@ -0,0 +1,608 @@
+use crate::api::encryption::{derive_key, generate_nonce, generate_salt, KEY_LENGTH, NONCE_LENGTH};
+use crate::db::{self, UnifiedDbManager};
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Key, Nonce,
+};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use libsql::params;
+use once_cell::sync::{Lazy, OnceCell};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use tokio::sync::Mutex as TokioMutex;
+use zeroize::Zeroizing;
+
+use super::service::{AiProviderConfig, CustomModelConfig, SaveAiConfigRequest};
+
+// 本机密钥和本机密钥库文件（位于应用数据目录，不参与同步）
+const MACHINE_KEY_FILE: &str = "ai_keys.secret";
+const LOCAL_STORE_FILE: &str = "ai_keys.json";
+
+// 设置项：保护方式和主密码盐、校验值只描述本机密钥库，不参与同步
+const PROTECTION_SETTING: &str = "ai_key_protection";
+const PASSWORD_SALT_SETTING: &str = "ai_key_password_salt";
+const PASSWORD_CHECK_SETTING: &str = "ai_key_password_check";
+// 同步的密钥库及其主密码盐、校验值，随数据库同步到其他设备
+const SYNC_SETTING: &str = "ai_keys_sync";
+const SYNCED_STORE_SETTING: &str = "ai_sealed_keys";
+const SYNCED_SALT_SETTING: &str = "ai_sealed_keys_salt";
+const SYNCED_CHECK_SETTING: &str = "ai_sealed_keys_check";
+
+const SEALED_PREFIX: &str = "enc:v1:";
+const REDACTED_PREFIX: &str = "••••";
+const PASSWORD_CHECK_TEXT: &str = "mytips-ai-keys";
+
+type Kek = Zeroizing<[u8; KEY_LENGTH]>;
+
+static KEY_DIR: OnceCell<PathBuf> = OnceCell::new();
+// 主密码模式下解锁后的密钥，只保存在内存中
+static UNLOCKED_KEK: Lazy<std::sync::Mutex<Option<Kek>>> = Lazy::new(|| std::sync::Mutex::new(None));
+// 串行化密钥库的读改写
+static STORE_LOCK: Lazy<TokioMutex<()>> = Lazy::new(|| TokioMutex::new(()));
+
+/// 密钥加密密钥的来源
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyProtection {
+    Machine,  // 本机密钥文件
+    Password, // 主密码派生
+}
+
+/// 密钥保护状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyProtectionStatus {
+    pub protection: KeyProtection,
+    pub locked: bool,
+    pub sync_keys: bool,
+    pub stored_keys: usize,
+}
+
+/// 服务商密钥ID
+pub fn provider_key_id(provider_id: &str) -> String {
+    format!("provider:{}", provider_id)
+}
+
+/// 自定义模型密钥ID
+pub fn custom_key_id(model_id: &str) -> String {
+    format!("custom:{}", model_id)
+}
+
+/// 返回给前端的脱敏密钥，只保留末4位
+pub fn redact_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    let tail: String = chars[chars.len().saturating_sub(4)..].iter().collect();
+    if chars.len() <= 8 {
+        REDACTED_PREFIX.to_string()
+    } else {
+        format!("{}{}", REDACTED_PREFIX, tail)
+    }
+}
+
+/// 是否为脱敏值（保存配置时表示“不修改”）
+pub fn is_redacted(value: &str) -> bool {
+    value.starts_with(REDACTED_PREFIX)
+}
+
+// ============ 加解密 ============
+
+fn seal(kek: &Kek, plaintext: &str) -> Result<String> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&kek[..]));
+    let nonce = generate_nonce();
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|e| anyhow!("Failed to encrypt API key: {}", e))?;
+    let mut payload = nonce.to_vec();
+    payload.extend(ciphertext);
+    Ok(format!("{}{}", SEALED_PREFIX, general_purpose::STANDARD.encode(payload)))
+}
+
+fn open(kek: &Kek, sealed: &str) -> Result<String> {
+    let encoded = sealed
+        .strip_prefix(SEALED_PREFIX)
+        .ok_or_else(|| anyhow!("Unsupported sealed key format"))?;
+    let payload = general_purpose::STANDARD.decode(encoded)?;
+    if payload.len() <= NONCE_LENGTH {
+        return Err(anyhow!("Sealed key is truncated"));
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_LENGTH);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&kek[..]));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("Failed to decrypt API key (wrong key-encryption key?)"))?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+// ============ 密钥加密密钥 ============
+
+fn key_dir() -> Result<&'static Path> {
+    KEY_DIR
+        .get()
+        .map(PathBuf::as_path)
+        .ok_or_else(|| anyhow!("API key store is not initialized"))
+}
+
+/// 写入仅当前用户可读写的文件
+fn write_private_file(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&tmp)?;
+        std::io::Write::write_all(&mut file, contents)?;
+        file.sync_all()?;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&tmp, std::fs::Permissions::from_mode(0o600))?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// 读取本机密钥，不存在时生成
+fn machine_kek() -> Result<Kek> {
+    let path = key_dir()?.join(MACHINE_KEY_FILE);
+    let mut kek = Zeroizing::new([0u8; KEY_LENGTH]);
+    match std::fs::read(&path) {
+        Ok(bytes) if bytes.len() == KEY_LENGTH => kek.copy_from_slice(&bytes),
+        Ok(_) => return Err(anyhow!("Machine key file {} is corrupted", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            rand::rngs::OsRng.fill_bytes(&mut kek[..]);
+            write_private_file(&path, &kek[..])?;
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(kek)
+}
+
+fn password_kek(password: &str, salt: &[u8]) -> Kek {
+    Zeroizing::new(derive_key(password, salt))
+}
+
+/// 同步密钥时总是使用主密码，否则按本机设置
+async fn get_protection(conn: &libsql::Connection) -> Result<KeyProtection> {
+    if sync_enabled(conn).await? {
+        return Ok(KeyProtection::Password);
+    }
+    Ok(match db::get_setting(conn, PROTECTION_SETTING).await?.as_deref() {
+        Some("password") => KeyProtection::Password,
+        _ => KeyProtection::Machine,
+    })
+}
+
+/// 当前密钥库对应的主密码盐和校验值设置项
+fn password_settings(synced: bool) -> (&'static str, &'static str) {
+    if synced {
+        (SYNCED_SALT_SETTING, SYNCED_CHECK_SETTING)
+    } else {
+        (PASSWORD_SALT_SETTING, PASSWORD_CHECK_SETTING)
+    }
+}
+
+/// 复制主密码盐和校验值，在本机和同步的密钥库之间切换时使用
+async fn copy_password_settings(conn: &libsql::Connection, from_synced: bool) -> Result<()> {
+    let (from_salt, from_check) = password_settings(from_synced);
+    let (to_salt, to_check) = password_settings(!from_synced);
+    for (from, to) in [(from_salt, to_salt), (from_check, to_check)] {
+        let value = db::get_setting(conn, from)
+            .await?
+            .ok_or_else(|| anyhow!("No master password is set"))?;
+        db::save_setting(conn, to, &value).await?;
+    }
+    Ok(())
+}
+
+async fn sync_enabled(conn: &libsql::Connection) -> Result<bool> {
+    Ok(db::get_setting(conn, SYNC_SETTING).await?.as_deref() == Some("true"))
+}
+
+fn unlocked_kek() -> Option<Kek> {
+    UNLOCKED_KEK.lock().ok().and_then(|kek| kek.clone())
+}
+
+fn set_unlocked_kek(kek: Option<Kek>) {
+    if let Ok(mut slot) = UNLOCKED_KEK.lock() {
+        *slot = kek;
+    }
+}
+
+/// 当前可用的密钥加密密钥；主密码模式未解锁时报错
+async fn current_kek(conn: &libsql::Connection) -> Result<Kek> {
+    match get_protection(conn).await? {
+        KeyProtection::Machine => machine_kek(),
+        KeyProtection::Password => {
+            unlocked_kek().ok_or_else(|| anyhow!("AI API keys are locked; unlock them with the master password"))
+        }
+    }
+}
+
+/// 用主密码派生密钥并校验
+async fn verify_password(conn: &libsql::Connection, password: &str) -> Result<Kek> {
+    let (salt_setting, check_setting) = password_settings(sync_enabled(conn).await?);
+    let salt = db::get_setting(conn, salt_setting)
+        .await?
+        .ok_or_else(|| anyhow!("No master password is set"))?;
+    let check = db::get_setting(conn, check_setting)
+        .await?
+        .ok_or_else(|| anyhow!("No master password is set"))?;
+    let kek = password_kek(password, &general_purpose::STANDARD.decode(salt)?);
+    match open(&kek, &check) {
+        Ok(text) if text == PASSWORD_CHECK_TEXT => Ok(kek),
+        _ => Err(anyhow!("Incorrect master password")),
+    }
+}
+
+// ============ 密钥库 ============
+
+async fn load_store(conn: &libsql::Connection) -> Result<HashMap<String, String>> {
+    let json = if sync_enabled(conn).await? {
+        db::get_setting(conn, SYNCED_STORE_SETTING).await?
+    } else {
+        match std::fs::read_to_string(key_dir()?.join(LOCAL_STORE_FILE)) {
+            Ok(json) => Some(json),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        }
+    };
+    match json {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(HashMap::new()),
+    }
+}
+
+async fn save_store(conn: &libsql::Connection, store: &HashMap<String, String>, synced: bool) -> Result<()> {
+    let json = serde_json::to_string(store)?;
+    if synced {
+        db::save_setting(conn, SYNCED_STORE_SETTING, &json).await
+    } else {
+        write_private_file(&key_dir()?.join(LOCAL_STORE_FILE), json.as_bytes())
+    }
+}
+
+/// 读取并解密密钥
+pub async fn get_api_key(conn: &libsql::Connection, key_id: &str) -> Result<Option<String>> {
+    let store = load_store(conn).await?;
+    let Some(sealed) = store.get(key_id) else {
+        return Ok(None);
+    };
+    let kek = current_kek(conn).await?;
+    open(&kek, sealed).map(Some)
+}
+
+/// 加密保存密钥；空值表示删除
+pub async fn set_api_key(conn: &libsql::Connection, key_id: &str, api_key: Option<&str>) -> Result<()> {
+    let _guard = STORE_LOCK.lock().await;
+    let mut store = load_store(conn).await?;
+    match api_key.map(str::trim).filter(|k| !k.is_empty()) {
+        Some(api_key) => {
+            let kek = current_kek(conn).await?;
+            store.insert(key_id.to_string(), seal(&kek, api_key)?);
+        }
+        None => {
+            if store.remove(key_id).is_none() {
+                return Ok(());
+            }
+        }
+    }
+    save_store(conn, &store, sync_enabled(conn).await?).await
+}
+
+/// 密钥是否已保存（不解密）
+pub async fn has_api_key(conn: &libsql::Connection, key_id: &str) -> Result<bool> {
+    Ok(load_store(conn).await?.contains_key(key_id))
+}
+
+/// 返回已保存密钥的脱敏值；未解锁时只返回占位
+pub async fn redacted_api_key(conn: &libsql::Connection, key_id: &str) -> Option<String> {
+    match get_api_key(conn, key_id).await {
+        Ok(key) => key.map(|k| redact_key(&k)),
+        Err(_) => has_api_key(conn, key_id).await.ok()?.then(|| REDACTED_PREFIX.to_string()),
+    }
+}
+
+/// 处理前端提交的密钥：脱敏值保持不变，空值删除，其他值加密保存
+pub async fn store_submitted_key(conn: &libsql::Connection, key_id: &str, submitted: Option<&str>) -> Result<()> {
+    match submitted {
+        Some(value) if is_redacted(value) => Ok(()),
+        Some(value) => set_api_key(conn, key_id, Some(value)).await,
+        None => Ok(()),
+    }
+}
+
+// ============ 迁移 ============
+
+/// 将配置中的明文密钥加密移入密钥库，并删除旧的明文设置项，返回迁移的密钥数
+pub async fn migrate_plaintext_api_keys(conn: &libsql::Connection) -> Result<usize> {
+    let mut migrated = 0;
+
+    if let Some(json) = db::get_setting(conn, "ai_providers_config").await? {
+        let mut config: SaveAiConfigRequest = serde_json::from_str(&json)?;
+        let mut changed = false;
+        for (provider_id, provider) in config.providers.iter_mut() {
+            if let Some(api_key) = provider.api_key.take() {
+                if !api_key.trim().is_empty() && !is_redacted(&api_key) {
+                    set_api_key(conn, &provider_key_id(provider_id), Some(&api_key)).await?;
+                    migrated += 1;
+                }
+                changed = true;
+            }
+            conn.execute(
+                "DELETE FROM app_settings WHERE key = ?",
+                params![format!("{}_api_key", provider_id)],
+            )
+            .await?;
+        }
+        if changed {
+            db::save_setting(conn, "ai_providers_config", &serde_json::to_string(&config)?).await?;
+        }
+    }
+
+    if let Some(json) = db::get_setting(conn, "custom_ai_models").await? {
+        let mut models: Vec<CustomModelConfig> = serde_json::from_str(&json)?;
+        let mut changed = false;
+        for model in models.iter_mut() {
+            if let Some(api_key) = model.api_key.take() {
+                if !api_key.trim().is_empty() && !is_redacted(&api_key) {
+                    set_api_key(conn, &custom_key_id(&model.id), Some(&api_key)).await?;
+                    migrated += 1;
+                }
+                changed = true;
+            }
+        }
+        if changed {
+            db::save_setting(conn, "custom_ai_models", &serde_json::to_string(&models)?).await?;
+        }
+    }
+
+    Ok(migrated)
+}
+
+/// 应用启动时初始化密钥库并迁移明文密钥；主密码未解锁时推迟到解锁后
+pub async fn init_key_store(app: &AppHandle, db_manager: &UnifiedDbManager) -> Result<()> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| anyhow!("Failed to get app data directory: {}", e))?;
+    std::fs::create_dir_all(&dir)?;
+    KEY_DIR.get_or_init(|| dir);
+
+    let conn = db_manager.get_conn().await?;
+    if current_kek(&conn).await.is_err() {
+        return Ok(());
+    }
+    let migrated = migrate_plaintext_api_keys(&conn).await?;
+    if migrated > 0 {
+        tracing::info!("Encrypted {} plaintext AI API keys", migrated);
+    }
+    Ok(())
+}
+
+/// 用新的密钥加密密钥重新加密所有密钥
+async fn reseal_store(conn: &libsql::Connection, old_kek: &Kek, new_kek: &Kek, synced: bool) -> Result<()> {
+    let mut store = load_store(conn).await?;
+    for sealed in store.values_mut() {
+        let plaintext = Zeroizing::new(open(old_kek, sealed)?);
+        *sealed = seal(new_kek, &plaintext)?;
+    }
+    save_store(conn, &store, synced).await
+}
+
+// ============ 命令 ============
+
+/// 获取密钥保护状态
+#[tauri::command]
+pub async fn get_ai_key_protection_status(
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<KeyProtectionStatus, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let protection = get_protection(&conn).await.map_err(|e| e.to_string())?;
+    Ok(KeyProtectionStatus {
+        protection,
+        locked: protection == KeyProtection::Password && unlocked_kek().is_none(),
+        sync_keys: sync_enabled(&conn).await.map_err(|e| e.to_string())?,
+        stored_keys: load_store(&conn).await.map_err(|e| e.to_string())?.len(),
+    })
+}
+
+async fn set_master_password(
+    conn: &libsql::Connection,
+    new_password: Option<String>,
+    current_password: Option<String>,
+) -> Result<()> {
+    let _guard = STORE_LOCK.lock().await;
+
+    let old_kek = match get_protection(conn).await? {
+        KeyProtection::Machine => machine_kek()?,
+        KeyProtection::Password => match current_password {
+            Some(password) => verify_password(conn, &password).await?,
+            None => return Err(anyhow!("Current master password is required")),
+        },
+    };
+    let mut synced = sync_enabled(conn).await?;
+
+    match new_password.filter(|p| !p.is_empty()) {
+        Some(password) => {
+            let salt = generate_salt();
+            let new_kek = password_kek(&password, &salt);
+            reseal_store(conn, &old_kek, &new_kek, synced).await?;
+            let (salt_setting, check_setting) = password_settings(synced);
+            db::save_setting(conn, salt_setting, &general_purpose::STANDARD.encode(salt)).await?;
+            db::save_setting(conn, check_setting, &seal(&new_kek, PASSWORD_CHECK_TEXT)?).await?;
+            db::save_setting(conn, PROTECTION_SETTING, "password").await?;
+            set_unlocked_kek(Some(new_kek));
+        }
+        None => {
+            // 本机密钥无法在其他设备解密，改回本机模式时停止同步密钥
+            let new_kek = machine_kek()?;
+            if synced {
+                let store = load_store(conn).await?;
+                save_store(conn, &store, false).await?;
+                db::save_setting(conn, SYNC_SETTING, "false").await?;
+                db::save_setting(conn, SYNCED_STORE_SETTING, "{}").await?;
+                synced = false;
+            }
+            reseal_store(conn, &old_kek, &new_kek, synced).await?;
+            db::save_setting(conn, PROTECTION_SETTING, "machine").await?;
+            conn.execute(
+                "DELETE FROM app_settings WHERE key IN (?, ?, ?, ?)",
+                params![PASSWORD_SALT_SETTING, PASSWORD_CHECK_SETTING, SYNCED_SALT_SETTING, SYNCED_CHECK_SETTING],
+            )
+            .await?;
+            set_unlocked_kek(None);
+        }
+    }
+    Ok(())
+}
+
+/// 设置、修改或移除主密码（移除后改用本机密钥），已有密钥会重新加密
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_ai_key_master_password(
+    new_password: Option<String>,
+    current_password: Option<String>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<(), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    set_master_password(&conn, new_password, current_password)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 用主密码解锁密钥，并迁移尚未加密的密钥
+#[tauri::command]
+pub async fn unlock_ai_keys(password: String, db_manager: State<'_, UnifiedDbManager>) -> Result<(), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let kek = verify_password(&conn, &password).await.map_err(|e| e.to_string())?;
+    set_unlocked_kek(Some(kek));
+    migrate_plaintext_api_keys(&conn).await.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// 锁定密钥（清除内存中的密钥加密密钥）
+#[tauri::command]
+pub async fn lock_ai_keys() -> Result<(), String> {
+    set_unlocked_kek(None);
+    Ok(())
+}
+
+async fn set_key_sync(conn: &libsql::Connection, enabled: bool) -> Result<()> {
+    let _guard = STORE_LOCK.lock().await;
+
+    if enabled && get_protection(conn).await? != KeyProtection::Password {
+        return Err(anyhow!("Syncing API keys requires a master password"));
+    }
+    if sync_enabled(conn).await? == enabled {
+        return Ok(());
+    }
+
+    let store = load_store(conn).await?;
+    save_store(conn, &store, enabled).await?;
+    // 主密码盐和校验值随密钥库一起切换；停止同步后本机改用同一主密码保护
+    copy_password_settings(conn, !enabled).await?;
+    if !enabled {
+        db::save_setting(conn, PROTECTION_SETTING, "password").await?;
+    }
+    db::save_setting(conn, SYNC_SETTING, if enabled { "true" } else { "false" }).await?;
+    // 清空原位置
+    save_store(conn, &HashMap::new(), !enabled).await
+}
+
+/// 是否将加密后的密钥随数据库同步，需要先设置主密码
+#[tauri::command]
+pub async fn set_ai_key_sync(enabled: bool, db_manager: State<'_, UnifiedDbManager>) -> Result<(), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    set_key_sync(&conn, enabled).await.map_err(|e| e.to_string())
+}
+
+/// 为前端返回的服务商配置填入脱敏密钥
+pub async fn redact_provider_config(conn: &libsql::Connection, provider_id: &str, config: &mut AiProviderConfig) {
+    config.api_key = redacted_api_key(conn, &provider_key_id(provider_id)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_kek(byte: u8) -> Kek {
+        Zeroizing::new([byte; KEY_LENGTH])
+    }
+
+    #[test]
+    fn seal_round_trip() {
+        let sealed = seal(&test_kek(7), "sk-secret-123").unwrap();
+        assert!(sealed.starts_with(SEALED_PREFIX));
+        assert!(!sealed.contains("sk-secret"));
+        assert_eq!(open(&test_kek(7), &sealed).unwrap(), "sk-secret-123");
+        assert!(open(&test_kek(8), &sealed).is_err());
+        assert!(open(&test_kek(7), "sk-plaintext").is_err());
+    }
+
+    #[test]
+    fn redacts_keys() {
+        assert_eq!(redact_key("sk-abcdefgh1234"), "••••1234");
+        assert_eq!(redact_key("short"), "••••");
+        assert!(is_redacted(&redact_key("sk-abcdefgh1234")));
+        assert!(!is_redacted("sk-abcdefgh1234"));
+    }
+
+    async fn settings_db() -> (libsql::Database, libsql::Connection) {
+        let db = libsql::Builder::new_local(":memory:").build().await.unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL, created_at INTEGER NOT NULL, updated_at INTEGER NOT NULL);",
+        )
+        .await
+        .unwrap();
+        (db, conn)
+    }
+
+    #[tokio::test]
+    async fn synced_keys_decrypt_on_another_device() {
+        let dir = KEY_DIR.get_or_init(|| std::env::temp_dir().join(format!("mytips-keys-test-{}", std::process::id())));
+        std::fs::create_dir_all(dir).unwrap();
+
+        let (_db_a, device_a) = settings_db().await;
+        set_master_password(&device_a, Some("pw".to_string()), None).await.unwrap();
+        set_api_key(&device_a, "provider:openai", Some("sk-synced-key")).await.unwrap();
+        set_key_sync(&device_a, true).await.unwrap();
+
+        // 按同步过滤条件把设置复制到另一台设备
+        let (_db_b, device_b) = settings_db().await;
+        db::save_setting(&device_b, PROTECTION_SETTING, "machine").await.unwrap();
+        let filter = crate::sync::table_descriptors::descriptor_for("app_settings").unwrap().row_filter.unwrap();
+        let mut rows = device_a
+            .query(&format!("SELECT key, value FROM app_settings WHERE {}", filter), ())
+            .await
+            .unwrap();
+        while let Some(row) = rows.next().await.unwrap() {
+            let (key, value): (String, String) = (row.get(0).unwrap(), row.get(1).unwrap());
+            assert!(![PROTECTION_SETTING, PASSWORD_SALT_SETTING, PASSWORD_CHECK_SETTING].contains(&key.as_str()));
+            db::save_setting(&device_b, &key, &value).await.unwrap();
+        }
+
+        set_unlocked_kek(None);
+        assert_eq!(get_protection(&device_b).await.unwrap(), KeyProtection::Password);
+        assert!(get_api_key(&device_b, "provider:openai").await.is_err());
+        assert!(verify_password(&device_b, "wrong").await.is_err());
+        set_unlocked_kek(Some(verify_password(&device_b, "pw").await.unwrap()));
+        assert_eq!(get_api_key(&device_b, "provider:openai").await.unwrap().as_deref(), Some("sk-synced-key"));
+        set_unlocked_kek(None);
+    }
+
+    #[test]
+    fn password_kek_depends_on_salt() {
+        let a = password_kek("pw", &[1u8; 32]);
+        let b = password_kek("pw", &[2u8; 32]);
+        assert_ne!(a[..], b[..]);
+        assert_eq!(password_kek("pw", &[1u8; 32])[..], a[..]);
+    }
+}
@@ -0,0 +1,412 @@
+use super::service::{AiModelInfo, SaveAiConfigRequest};
+use crate::db::{self, UnifiedDbManager};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+use tauri::State;
+
+// 本地服务响应应当很快，避免在服务未启动时长时间等待
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(10);
+const OLLAMA_DEFAULT_BASE: &str = "http://localhost:11434";
+const OPENAI_COMPATIBLE_DEFAULT_BASE: &str = "http://localhost:1234/v1";
+
+/// 本地模型运行时
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocalRuntime {
+    Ollama,           // 原生 /api/tags、/api/show
+    OpenAiCompatible, // LM Studio、llama.cpp、vLLM 等 /v1/models
+}
+
+impl LocalRuntime {
+    pub fn from_provider_id(provider_id: &str) -> Option<Self> {
+        match provider_id {
+            "ollama" => Some(Self::Ollama),
+            "local" => Some(Self::OpenAiCompatible),
+            _ => None,
+        }
+    }
+
+    pub fn default_base(self) -> &'static str {
+        match self {
+            Self::Ollama => OLLAMA_DEFAULT_BASE,
+            Self::OpenAiCompatible => OPENAI_COMPATIBLE_DEFAULT_BASE,
+        }
+    }
+
+    /// 规范化服务地址：OpenAI 兼容服务统一以 /v1 结尾
+    pub fn normalize_base(self, api_base: &str) -> String {
+        let base = api_base.trim().trim_end_matches('/');
+        match self {
+            Self::OpenAiCompatible if !base.ends_with("/v1") => format!("{}/v1", base),
+            _ => base.to_string(),
+        }
+    }
+}
+
+/// 是否为无需 API 密钥的本地服务商
+pub fn is_local_provider(provider_id: &str) -> bool {
+    provider_id.to_lowercase().contains("ollama") || LocalRuntime::from_provider_id(provider_id).is_some()
+}
+
+/// 本地服务上发现的模型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocalModelInfo {
+    pub name: String,
+    pub context_length: Option<usize>,
+    pub supports_chat: bool,
+    pub supports_embeddings: bool,
+    pub size_bytes: Option<u64>,
+}
+
+/// 按名称判断嵌入模型，服务未报告能力时使用
+pub fn looks_like_embedding_model(name: &str) -> bool {
+    let name = name.to_lowercase();
+    ["embed", "bge-", "bge:", "e5-", "minilm", "gte-", "text2vec"].iter().any(|marker| name.contains(marker))
+}
+
+fn model_info(name: String, context_length: Option<usize>, embeddings: bool, size_bytes: Option<u64>) -> LocalModelInfo {
+    LocalModelInfo { name, context_length, supports_chat: !embeddings, supports_embeddings: embeddings, size_bytes }
+}
+
+/// 解析 Ollama /api/show：`model_info` 中的 `<架构>.context_length` 和 `capabilities`
+fn apply_ollama_show(model: &mut LocalModelInfo, show: &serde_json::Value) {
+    if let Some(info) = show["model_info"].as_object() {
+        model.context_length = info
+            .iter()
+            .find(|(key, _)| key.ends_with(".context_length"))
+            .and_then(|(_, value)| value.as_u64())
+            .map(|n| n as usize);
+    }
+    if let Some(capabilities) = show["capabilities"].as_array() {
+        let has = |name: &str| capabilities.iter().any(|c| c.as_str() == Some(name));
+        model.supports_chat = has("completion");
+        model.supports_embeddings = has("embedding");
+    }
+}
+
+async fn get_json(client: &reqwest::Client, url: &str) -> Result<serde_json::Value> {
+    let response = client.get(url).timeout(DISCOVERY_TIMEOUT).send().await.map_err(|e| anyhow!("Request failed: {}", e))?;
+    if !response.status().is_success() {
+        return Err(anyhow!("API error {}: {}", response.status(), response.text().await.unwrap_or_default()));
+    }
+    response.json().await.map_err(|e| anyhow!("Failed to parse response: {}", e))
+}
+
+async fn discover_ollama_models(client: &reqwest::Client, base: &str) -> Result<Vec<LocalModelInfo>> {
+    let tags = get_json(client, &format!("{}/api/tags", base)).await?;
+    let mut models: Vec<LocalModelInfo> = tags["models"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|m| {
+            let name = m["name"].as_str().or_else(|| m["model"].as_str())?.to_string();
+            let embeddings = looks_like_embedding_model(&name)
+                || m["details"]["family"].as_str().is_some_and(|family| family.contains("bert"));
+            Some(model_info(name, None, embeddings, m["size"].as_u64()))
+        })
+        .collect();
+
+    // 上下文长度和能力需要逐个查询，单个失败不影响列表
+    let shows = futures::future::join_all(models.iter().map(|model| async move {
+        let response = client
+            .post(format!("{}/api/show", base))
+            .timeout(DISCOVERY_TIMEOUT)
+            .json(&json!({ "model": model.name }))
+            .send()
+            .await
+            .ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        response.json::<serde_json::Value>().await.ok()
+    }))
+    .await;
+    for (model, show) in models.iter_mut().zip(shows) {
+        if let Some(show) = show {
+            apply_ollama_show(model, &show);
+        }
+    }
+    Ok(models)
+}
+
+async fn discover_openai_compatible_models(client: &reqwest::Client, base: &str) -> Result<Vec<LocalModelInfo>> {
+    let list = get_json(client, &format!("{}/models", base)).await?;
+    let context_length = |m: &serde_json::Value| {
+        // vLLM: max_model_len；LM Studio: max_context_length；llama.cpp: meta.n_ctx_train
+        [&m["max_model_len"], &m["context_length"], &m["max_context_length"], &m["meta"]["n_ctx_train"]]
+            .into_iter()
+            .find_map(|v| v.as_u64())
+            .map(|n| n as usize)
+    };
+    Ok(list["data"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|m| {
+            let name = m["id"].as_str()?.to_string();
+            let embeddings = m["type"].as_str() == Some("embeddings") || looks_like_embedding_model(&name);
+            Some(model_info(name, context_length(m), embeddings, m["meta"]["size"].as_u64()))
+        })
+        .collect())
+}
+
+/// 查询本地服务上已安装的模型
+pub async fn discover_models(client: &reqwest::Client, runtime: LocalRuntime, api_base: &str) -> Result<Vec<LocalModelInfo>> {
+    let base = runtime.normalize_base(api_base);
+    let mut models = match runtime {
+        LocalRuntime::Ollama => discover_ollama_models(client, &base).await?,
+        LocalRuntime::OpenAiCompatible => discover_openai_compatible_models(client, &base).await?,
+    };
+    models.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(models)
+}
+
+fn discovered_models_key(provider_id: &str) -> String {
+    format!("discovered_models_{}", provider_id)
+}
+
+/// 上次发现的模型信息
+pub async fn load_discovered_models(conn: &libsql::Connection, provider_id: &str) -> Vec<LocalModelInfo> {
+    db::get_setting(conn, &discovered_models_key(provider_id))
+        .await
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+/// 本地服务不走代理
+pub(super) fn local_client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder().no_proxy().build().map_err(|e| e.to_string())
+}
+
+/// 列出本地服务上的模型，并记录上下文长度供上下文压缩使用
+#[tauri::command(rename_all = "snake_case")]
+pub async fn discover_local_models(
+    provider_id: String,
+    api_base: Option<String>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<AiModelInfo, String> {
+    let runtime = LocalRuntime::from_provider_id(&provider_id)
+        .ok_or_else(|| format!("'{}' is not a local model provider", provider_id))?;
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    let configured_base = db::get_setting(&conn, "ai_providers_config")
+        .await
+        .map_err(|e| e.to_string())?
+        .and_then(|json| serde_json::from_str::<SaveAiConfigRequest>(&json).ok())
+        .and_then(|config| config.providers.get(&provider_id).and_then(|p| p.api_base.clone()));
+    let api_base = api_base
+        .or(configured_base)
+        .filter(|base| !base.trim().is_empty())
+        .unwrap_or_else(|| runtime.default_base().to_string());
+
+    let models = discover_models(&local_client()?, runtime, &api_base).await.map_err(|e| e.to_string())?;
+    let json = serde_json::to_string(&models).map_err(|e| e.to_string())?;
+    db::save_setting(&conn, &discovered_models_key(&provider_id), &json).await.map_err(|e| e.to_string())?;
+
+    Ok(AiModelInfo {
+        provider: provider_id,
+        models: models.iter().map(|m| m.name.clone()).collect(),
+        details: models,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::rig_client::{ChatMessage, CustomProvider};
+    use super::*;
+    use futures_util::StreamExt;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    struct MockRoute {
+        method: &'static str,
+        path: &'static str,
+        body_contains: Option<&'static str>,
+        content_type: &'static str,
+        response: String,
+    }
+
+    fn route(method: &'static str, path: &'static str, response: serde_json::Value) -> MockRoute {
+        MockRoute { method, path, body_contains: None, content_type: "application/json", response: response.to_string() }
+    }
+
+    async fn read_request(socket: &mut tokio::net::TcpStream) -> String {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = socket.read(&mut buf).await.unwrap_or(0);
+            if n == 0 {
+                break;
+            }
+            data.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&data);
+            if let Some(header_end) = text.find("\r\n\r\n") {
+                let content_length = text[..header_end]
+                    .lines()
+                    .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap_or(0)))
+                    .unwrap_or(0);
+                if data.len() >= header_end + 4 + content_length {
+                    break;
+                }
+            }
+        }
+        String::from_utf8_lossy(&data).into_owned()
+    }
+
+    /// 最小的 HTTP 服务：按方法、路径和请求体匹配返回固定响应
+    async fn mock_server(routes: Vec<MockRoute>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let routes = std::sync::Arc::new(routes);
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let routes = routes.clone();
+                tokio::spawn(async move {
+                    let request = read_request(&mut socket).await;
+                    let matched = routes.iter().find(|r| {
+                        request.starts_with(&format!("{} {} ", r.method, r.path))
+                            && r.body_contains.is_none_or(|needle| request.contains(needle))
+                    });
+                    let (status, content_type, body) = match matched {
+                        Some(r) => ("200 OK", r.content_type, r.response.as_str()),
+                        None => ("404 Not Found", "text/plain", "not found"),
+                    };
+                    let response = format!(
+                        "HTTP/1.1 {}\r\ncontent-type: {}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                        status,
+                        content_type,
+                        body.len(),
+                        body
+                    );
+                    socket.write_all(response.as_bytes()).await.ok();
+                    socket.shutdown().await.ok();
+                });
+            }
+        });
+        format!("http://{}", addr)
+    }
+
+    fn ollama(base: &str) -> CustomProvider {
+        CustomProvider { endpoint: base.to_string(), api_key: String::new(), provider_type: "ollama".to_string() }
+    }
+
+    #[test]
+    fn normalizes_bases_and_detects_embedding_models() {
+        assert_eq!(LocalRuntime::OpenAiCompatible.normalize_base("http://localhost:1234/"), "http://localhost:1234/v1");
+        assert_eq!(LocalRuntime::OpenAiCompatible.normalize_base("http://localhost:8000/v1"), "http://localhost:8000/v1");
+        assert_eq!(LocalRuntime::Ollama.normalize_base("http://localhost:11434/"), "http://localhost:11434");
+        assert!(looks_like_embedding_model("nomic-embed-text:latest"));
+        assert!(looks_like_embedding_model("bge-m3"));
+        assert!(!looks_like_embedding_model("llama3.2:latest"));
+        assert!(is_local_provider("ollama") && is_local_provider("local") && !is_local_provider("openai"));
+    }
+
+    #[tokio::test]
+    async fn discovers_ollama_models_with_context_and_capabilities() {
+        let base = mock_server(vec![
+            route(
+                "GET",
+                "/api/tags",
+                json!({ "models": [
+                    { "name": "llama3.2:latest", "size": 2019393189u64, "details": { "family": "llama" } },
+                    { "name": "nomic-embed-text:latest", "size": 274302450u64, "details": { "family": "nomic-bert" } },
+                    { "name": "broken:latest" }
+                ]}),
+            ),
+            MockRoute {
+                body_contains: Some("llama3.2"),
+                ..route(
+                    "POST",
+                    "/api/show",
+                    json!({
+                        "model_info": { "general.architecture": "llama", "llama.context_length": 131072 },
+                        "capabilities": ["completion", "tools"]
+                    }),
+                )
+            },
+            MockRoute {
+                body_contains: Some("nomic-embed"),
+                ..route(
+                    "POST",
+                    "/api/show",
+                    json!({ "model_info": { "nomic-bert.context_length": 2048 }, "capabilities": ["embedding"] }),
+                )
+            },
+        ])
+        .await;
+
+        let models = discover_models(&reqwest::Client::new(), LocalRuntime::Ollama, &base).await.unwrap();
+        assert_eq!(models.len(), 3);
+        // 查询详情失败的模型仍然列出
+        assert_eq!(models[0], model_info("broken:latest".to_string(), None, false, None));
+        assert_eq!(models[1].name, "llama3.2:latest");
+        assert_eq!(models[1].context_length, Some(131072));
+        assert!(models[1].supports_chat && !models[1].supports_embeddings);
+        assert_eq!(models[2].context_length, Some(2048));
+        assert!(models[2].supports_embeddings && !models[2].supports_chat);
+    }
+
+    #[tokio::test]
+    async fn discovers_openai_compatible_models() {
+        let base = mock_server(vec![route(
+            "GET",
+            "/v1/models",
+            json!({ "object": "list", "data": [
+                { "id": "qwen2.5-7b-instruct", "object": "model", "max_model_len": 32768 },
+                { "id": "text-embedding-bge-m3", "object": "model", "type": "embeddings" }
+            ]}),
+        )])
+        .await;
+
+        let models = discover_models(&reqwest::Client::new(), LocalRuntime::OpenAiCompatible, &base).await.unwrap();
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].context_length, Some(32768));
+        assert!(models[0].supports_chat);
+        assert!(models[1].supports_embeddings);
+
+        let error = discover_models(&reqwest::Client::new(), LocalRuntime::Ollama, &base).await.unwrap_err();
+        assert!(error.to_string().contains("404"));
+    }
+
+    #[tokio::test]
+    async fn streams_and_embeds_through_ollama_api() {
+        let stream_body = [
+            json!({ "message": { "role": "assistant", "content": "Hel" }, "done": false }),
+            json!({ "message": { "role": "assistant", "content": "lo" }, "done": false }),
+            json!({ "message": { "role": "assistant", "content": "" }, "done": true, "prompt_eval_count": 12, "eval_count": 2 }),
+        ]
+        .iter()
+        .map(|line| format!("{}\n", line))
+        .collect::<String>();
+        let base = mock_server(vec![
+            MockRoute {
+                method: "POST",
+                path: "/api/chat",
+                body_contains: None,
+                content_type: "application/x-ndjson",
+                response: stream_body,
+            },
+            route("POST", "/api/embed", json!({ "embeddings": [[0.1, 0.2], [0.3, 0.4]] })),
+        ])
+        .await;
+
+        let client = reqwest::Client::new();
+        let provider = ollama(&base);
+        let (stream, usage) = provider
+            .send_stream_request_with_client(&client, "llama3.2", vec![ChatMessage::user("Hi".to_string())])
+            .await
+            .unwrap();
+        let chunks: Vec<String> = stream.map(|chunk| chunk.unwrap()).collect().await;
+        assert_eq!(chunks.concat(), "Hello");
+        let usage = usage.lock().unwrap().expect("usage from final line");
+        assert_eq!((usage.prompt_tokens, usage.completion_tokens), (12, 2));
+
+        let vectors = provider
+            .send_embedding_request_with_client(&client, "nomic-embed-text", vec!["a".to_string(), "b".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(vectors, vec![vec![0.1, 0.2], vec![0.3, 0.4]]);
+    }
+}
@@ -1,9 +1,20 @@
+pub mod context;
 pub mod conversations;
+pub mod embeddings;
+pub mod keys;
+pub mod local;
+pub mod prompts;
+pub mod rag;
+pub mod resilience;
 pub mod rig_client;
 pub mod roles;
 pub mod service;
+pub mod suggest;
+pub mod tools;
+pub mod usage;
+pub mod writing;
 
-use crate::db::{self, UnifiedDbManager};
+use crate::db::{self, AiUsageRecord, UnifiedDbManager};
 use anyhow::Result;
 use base64::{engine::general_purpose, Engine as _};
 use futures_util::StreamExt;
@@ -15,7 +26,10 @@ use tauri::{AppHandle, Emitter, State};
 use tokio::sync::Mutex as TokioMutex;
 use self::service::SaveAiConfigRequest;
 use roles::get_ai_role_internal;
-use conversations::list_ai_messages_internal;
+use context::{load_conversation_context, ContextTarget};
+use local::LocalRuntime;
+use resilience::{call_with_resilience, ResilienceEvent, ResilientOutcome};
+use usage::{enforce_budget, estimate_messages_tokens, estimate_tokens, TrackedStream, UsageCall, UsageContext};
 
 // System prompt constant
 const SYSTEM_PROMPT: &str = "";
@@ -58,6 +72,7 @@ pub async fn send_ai_message(
     )
     .await?;
     
+    let usage = UsageContext::new(role_id.clone(), conversation_id.clone());
     let role_description = if let Some(ref role_id) = role_id {
         if let Ok(role) = get_ai_role_internal(role_id.clone(), db_manager.inner().clone()).await {
             role.description.unwrap_or_default()
@@ -82,7 +97,13 @@ pub async fn send_ai_message(
     // Load history messages, ensure no duplicates
     let mut seen_contents = std::collections::HashSet::new();
     if let Some(conv_id) = conversation_id {
-        let history = list_ai_messages_internal(conv_id.clone(), db_manager.inner().clone()).await.map_err(|e| e.to_string())?;
+        let target = ContextTarget {
+            provider_id: &provider_id,
+            provider: &provider,
+            model_name: &model_name,
+            fixed_tokens: estimate_messages_tokens(&chat_messages) + estimate_tokens(&message),
+        };
+        let history = load_conversation_context(&conv_id, &target, &db_manager, &usage).await?;
         
         println!("Loaded {} history messages for conversation {}", history.len(), conv_id);
         
@@ -106,16 +127,31 @@ pub async fn send_ai_message(
 
     println!("chat_messages count (non-stream): {}", chat_messages.len());
 
-    let result_str = send_message_to_provider(&provider, &model_name, chat_messages, &db_manager)
-        .await
-        .map_err(|e| {
-            println!("Request failed: {}", e);
-            e.to_string()
-        })?;
-    
+    // 失败时按配置重试并切换备用服务商，过程记录在返回结果中
+    let events = std::sync::Mutex::new(Vec::new());
+    let record_event = |event: &ResilienceEvent| events.lock().unwrap_or_else(|e| e.into_inner()).push(event.clone());
+    let outcome = call_with_resilience(&provider_id, &db_manager, None, &record_event, |provider, model| {
+        let messages = chat_messages.clone();
+        let db_manager = db_manager.inner().clone();
+        let usage = usage.clone();
+        async move { send_message_to_provider(&provider, &model, messages, &db_manager, &usage).await }
+    })
+    .await
+    .map_err(|e| {
+        println!("Request failed: {}", e);
+        e
+    })?;
+    let result_str = outcome.value;
+
     println!("Received response: {}", if result_str.is_empty() { "[empty]" } else { &result_str[..std::cmp::min(50, result_str.len())] });
 
-    Ok(serde_json::json!({ "reply": result_str }))
+    Ok(serde_json::json!({
+        "reply": result_str,
+        "provider_id": outcome.provider_id,
+        "model": outcome.model,
+        "attempts": outcome.attempts,
+        "events": events.into_inner().unwrap_or_default(),
+    }))
 }
 
 /// Stream AI message
@@ -190,6 +226,7 @@ async fn handle_stream_request(
     )
     .await?;
 
+    let usage = UsageContext::new(role_id.clone(), conversation_id.clone());
     let role_description = if let Some(ref role_id_str) = role_id {
         get_ai_role_internal(role_id_str.clone(), db_manager.clone()).await
             .map(|role| role.description.unwrap_or_default())
@@ -212,7 +249,13 @@ async fn handle_stream_request(
     // Load history messages, ensure no duplicates
     let mut seen_contents = std::collections::HashSet::new();
     if let Some(conv_id) = conversation_id {
-        let history = list_ai_messages_internal(conv_id.clone(), db_manager.clone()).await.unwrap_or_default();
+        let target = ContextTarget {
+            provider_id: &provider_id,
+            provider: &provider,
+            model_name: &model_name,
+            fixed_tokens: estimate_messages_tokens(&chat_messages) + estimate_tokens(&message),
+        };
+        let history = load_conversation_context(&conv_id, &target, &db_manager, &usage).await.unwrap_or_default();
         
         println!("Loaded {} history messages for conversation {}", history.len(), conv_id);
         
@@ -244,10 +287,20 @@ async fn handle_stream_request(
 
     println!("Sending stream request to model: {}", model_name);
     
-    let stream_result = stream_message_from_provider(&provider, &model_name, chat_messages, &db_manager).await;
-    
+    // 只对建立连接重试和切换服务商，已开始输出的流出错时不再重试
+    let report_event = |event: &ResilienceEvent| {
+        app.emit("ai-stream-status", serde_json::json!({ "id": stream_id, "event": event })).ok();
+    };
+    let stream_result = call_with_resilience(&provider_id, &db_manager, Some(should_cancel.as_ref()), &report_event, |provider, model| {
+        let messages = chat_messages.clone();
+        let db_manager = db_manager.clone();
+        let usage = usage.clone();
+        async move { stream_message_from_provider(&provider, &model, messages, &db_manager, &usage).await }
+    })
+    .await;
+
     match stream_result {
-        Ok(mut stream) => {
+        Ok(ResilientOutcome { value: mut stream, .. }) => {
             println!("Stream connection established, waiting for events...");
             
             while let Some(chunk_result) = stream.next().await {
@@ -304,6 +357,7 @@ pub async fn send_ai_message_with_images(
     )
     .await?;
 
+    let usage = UsageContext::new(role_id.clone(), None);
     let final_message = if let Some(role_id) = role_id {
         if let Ok(role) = get_ai_role_internal(role_id, db_manager.inner().clone()).await {
             format!("{}\n{}", role.description.unwrap_or_default(), text_message)
@@ -323,7 +377,7 @@ pub async fn send_ai_message_with_images(
     }
 
     let messages = vec![ChatMessage::user(enhanced_message)];
-    let result_str = send_message_to_provider(&provider, &model_name, messages, &db_manager)
+    let result_str = send_message_to_provider(&provider, &model_name, messages, &db_manager, &usage)
         .await
         .map_err(|e| e.to_string())?;
 
@@ -399,6 +453,7 @@ async fn handle_stream_request_with_images(
     )
     .await?;
 
+    let usage = UsageContext::new(role_id.clone(), None);
     let final_message = if let Some(role_id_str) = role_id {
         if let Ok(role) = get_ai_role_internal(role_id_str, db_manager.clone()).await {
             format!("{}\n{}", role.description.unwrap_or_default(), text_message)
@@ -419,7 +474,7 @@ async fn handle_stream_request_with_images(
 
     let messages = vec![ChatMessage::user(enhanced_message)];
     
-    let stream_result = stream_message_from_provider(&provider, &model_name, messages, &db_manager).await;
+    let stream_result = stream_message_from_provider(&provider, &model_name, messages, &db_manager, &usage).await;
 
     match stream_result {
         Ok(mut stream) => {
@@ -520,9 +575,13 @@ async fn create_provider_from_config(
         let m = custom_models.into_iter().find(|c| c.id == id)
             .ok_or(format!("Custom model {} not found", id))?;
         
+        let api_key = keys::get_api_key(conn, &keys::custom_key_id(&m.id))
+            .await.map_err(|e| e.to_string())?
+            .unwrap_or_default();
+        
         let provider = RigProvider::new(
             &m.adapter_type,
-            m.api_key.unwrap_or_default(),
+            api_key,
             Some(m.endpoint),
         ).map_err(|e| e.to_string())?;
         
@@ -538,10 +597,14 @@ async fn create_provider_from_config(
         let provider_config = ai_config.providers.get(provider_id)
             .ok_or_else(|| format!("Provider '{}' not found in config", provider_id))?;
         
-        let api_key = if provider_id.to_lowercase().contains("ollama") {
-            String::new()
+        let api_key = if local::is_local_provider(provider_id) {
+            // 本地服务通常不需要密钥，配置了也一并发送
+            keys::get_api_key(conn, &keys::provider_key_id(provider_id))
+                .await.map_err(|e| e.to_string())?
+                .unwrap_or_default()
         } else {
-            provider_config.api_key.clone()
+            keys::get_api_key(conn, &keys::provider_key_id(provider_id))
+                .await.map_err(|e| e.to_string())?
                 .ok_or_else(|| format!("API key for '{}' not found", provider_id))?
         };
         
@@ -553,7 +616,7 @@ async fn create_provider_from_config(
                 "deepseek" => Some("https://api.deepseek.com/v1".to_string()),
                 "qwen" => Some("https://dashscope.aliyuncs.com/api/v1".to_string()),
                 "doubao" => Some("https://ark.cn-beijing.volces.com/api/v3".to_string()),
-                "ollama" => Some("http://localhost:11434".to_string()),
+                "ollama" | "local" => LocalRuntime::from_provider_id(provider_id).map(|r| r.default_base().to_string()),
                 "groq" => Some("https://api.groq.com/openai/v1".to_string()),
                 "cohere" => Some("https://api.cohere.ai".to_string()),
                 "zhipu" => Some("https://open.bigmodel.cn".to_string()),
@@ -561,6 +624,11 @@ async fn create_provider_from_config(
             }
         });
 
+        let endpoint = match LocalRuntime::from_provider_id(provider_id) {
+            Some(runtime) => endpoint.map(|base| runtime.normalize_base(&base)),
+            None => endpoint,
+        };
+
         let provider = RigProvider::new(provider_id, api_key, endpoint)
             .map_err(|e| e.to_string())?;
         
@@ -568,43 +636,71 @@ async fn create_provider_from_config(
     }
 }
 
-/// Send message to provider
+/// Send message to provider, recording usage and enforcing budgets
 async fn send_message_to_provider(
     provider: &RigProvider,
     model_name: &str,
     messages: Vec<ChatMessage>,
     db_manager: &UnifiedDbManager,
+    usage: &UsageContext,
 ) -> Result<String, String> {
-    match provider {
-        RigProvider::Custom(custom) => {
-            custom.send_request(model_name, messages, db_manager).await
-                .map_err(|e| e.to_string())
+    send_message_with_usage(provider, model_name, messages, db_manager, usage, "chat")
+        .await
+        .map(|(reply, _)| reply)
+}
+
+/// Send message to provider and also return the recorded usage
+async fn send_message_with_usage(
+    provider: &RigProvider,
+    model_name: &str,
+    messages: Vec<ChatMessage>,
+    db_manager: &UnifiedDbManager,
+    usage: &UsageContext,
+    operation: &'static str,
+) -> Result<(String, AiUsageRecord), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    enforce_budget(&conn, provider.provider_name()).await?;
+
+    let call = UsageCall::start(provider.provider_name(), model_name, operation, usage, estimate_messages_tokens(&messages));
+    let result = match provider {
+        RigProvider::Custom(custom) => custom.send_request_with_usage(model_name, messages, db_manager).await,
+        // For rig-core native providers, chat() combines messages into a single prompt
+        _ => provider.chat(model_name, messages).await.map(|reply| (reply, None)),
+    };
+
+    match result {
+        Ok((reply, reported)) => {
+            let record = call.finish(db_manager, reported, &reply, None).await;
+            Ok((reply, record))
         }
-        _ => {
-            // For rig-core native providers, convert to combined prompt
-            let combined_prompt = messages
-                .iter()
-                .map(|msg| format!("{}: {}", msg.role, msg.content))
-                .collect::<Vec<_>>()
-                .join("\n");
-            
-            provider.prompt(model_name, &combined_prompt).await
-                .map_err(|e| e.to_string())
+        Err(e) => {
+            call.finish(db_manager, None, "", Some(e.to_string())).await;
+            Err(e.to_string())
         }
     }
 }
 
-/// Stream message from provider
+/// Stream message from provider; usage is recorded when the stream ends or is dropped
 async fn stream_message_from_provider(
     provider: &RigProvider,
     model_name: &str,
     messages: Vec<ChatMessage>,
     db_manager: &UnifiedDbManager,
+    usage: &UsageContext,
 ) -> Result<std::pin::Pin<Box<dyn futures_util::Stream<Item = Result<String, anyhow::Error>> + Send>>, String> {
     match provider {
         RigProvider::Custom(custom) => {
-            custom.send_stream_request(model_name, messages, db_manager).await
-                .map_err(|e| e.to_string())
+            let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+            enforce_budget(&conn, provider.provider_name()).await?;
+
+            let call = UsageCall::start(provider.provider_name(), model_name, "stream", usage, estimate_messages_tokens(&messages));
+            match custom.send_stream_request_with_usage(model_name, messages, db_manager).await {
+                Ok((stream, usage_slot)) => Ok(Box::pin(TrackedStream::new(stream, call, db_manager.clone(), Some(usage_slot)))),
+                Err(e) => {
+                    call.finish(db_manager, None, "", Some(e.to_string())).await;
+                    Err(e.to_string())
+                }
+            }
         }
         _ => {
             Err("Streaming not implemented for standard providers".to_string())
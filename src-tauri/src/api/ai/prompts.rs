@@ -0,0 +1,328 @@
+use super::rig_client::ChatMessage;
+use super::usage::{estimate_tokens, UsageContext};
+use super::{create_provider_from_config, send_message_with_usage};
+use crate::db::{self, operations, PromptRun, PromptRunBatch, TipType, UnifiedDbManager};
+use chrono::Utc;
+use futures::future::join_all;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, State};
+use uuid::Uuid;
+
+// 一次最多同时运行的服务商数
+const MAX_PROVIDERS_PER_RUN: usize = 8;
+
+lazy_static::lazy_static! {
+    // {{name}}、{{name:type}}、{{name=默认值}}、{{name:type=默认值}}
+    static ref VARIABLE_RE: Regex =
+        Regex::new(r"\{\{\s*([^\s{}:=]+)\s*(?::\s*([A-Za-z]+)\s*)?(?:=([^}]*))?\}\}").unwrap();
+}
+
+/// 变量类型
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptVariableType {
+    #[default]
+    Text,
+    Number,
+    Boolean,
+}
+
+impl PromptVariableType {
+    fn parse(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "text" | "string" => Some(Self::Text),
+            "number" | "int" | "float" => Some(Self::Number),
+            "boolean" | "bool" => Some(Self::Boolean),
+            _ => None,
+        }
+    }
+
+    /// 校验并规范化取值
+    fn normalize(&self, name: &str, value: &str) -> Result<String, String> {
+        match self {
+            Self::Text => Ok(value.to_string()),
+            Self::Number => value
+                .trim()
+                .parse::<f64>()
+                .map(|_| value.trim().to_string())
+                .map_err(|_| format!("Variable '{}' must be a number", name)),
+            Self::Boolean => match value.trim().to_lowercase().as_str() {
+                "true" | "yes" | "1" => Ok("true".to_string()),
+                "false" | "no" | "0" => Ok("false".to_string()),
+                _ => Err(format!("Variable '{}' must be true or false", name)),
+            },
+        }
+    }
+}
+
+/// 提示词中的变量，按首次出现的顺序
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptVariable {
+    pub name: String,
+    pub var_type: PromptVariableType,
+    pub default: Option<String>,
+}
+
+/// 渲染结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderedPrompt {
+    pub prompt: String,
+    pub values: BTreeMap<String, String>,
+}
+
+/// 提取变量；同名变量以首次声明的类型为准，默认值取第一个给出的
+pub fn parse_prompt_variables(template: &str) -> Vec<PromptVariable> {
+    let mut variables: Vec<PromptVariable> = Vec::new();
+    for caps in VARIABLE_RE.captures_iter(template) {
+        let name = caps[1].to_string();
+        let var_type = caps.get(2).and_then(|t| PromptVariableType::parse(t.as_str()));
+        let default = caps.get(3).map(|d| d.as_str().trim().to_string());
+        match variables.iter_mut().find(|v| v.name == name) {
+            Some(existing) => {
+                if existing.default.is_none() {
+                    existing.default = default;
+                }
+            }
+            None => variables.push(PromptVariable {
+                name,
+                var_type: var_type.unwrap_or_default(),
+                default,
+            }),
+        }
+    }
+    variables
+}
+
+fn value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+/// 用给定值替换变量，未提供时使用默认值；缺少取值或类型不符时报错
+pub fn render_prompt(template: &str, values: &HashMap<String, Value>) -> Result<RenderedPrompt, String> {
+    let mut resolved = BTreeMap::new();
+    let mut missing = Vec::new();
+    for variable in parse_prompt_variables(template) {
+        let value = values.get(&variable.name).and_then(value_to_string).or_else(|| variable.default.clone());
+        match value {
+            Some(value) => {
+                let value = variable.var_type.normalize(&variable.name, &value)?;
+                resolved.insert(variable.name, value);
+            }
+            None => missing.push(variable.name),
+        }
+    }
+    if !missing.is_empty() {
+        return Err(format!("Missing values for variables: {}", missing.join(", ")));
+    }
+
+    let prompt = VARIABLE_RE
+        .replace_all(template, |caps: &regex::Captures| resolved.get(&caps[1]).cloned().unwrap_or_default())
+        .into_owned();
+    Ok(RenderedPrompt { prompt, values: resolved })
+}
+
+/// 读取提示词笔记的正文
+async fn load_prompt_template(conn: &libsql::Connection, tip_id: &str) -> Result<String, String> {
+    let tip = operations::get_tip_by_id(conn, tip_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Tip {} not found", tip_id))?;
+    if !matches!(tip.tip_type, TipType::AIPrompt) {
+        return Err("Tip is not an AI prompt".to_string());
+    }
+    if tip.is_encrypted.unwrap_or(false) {
+        return Err("Encrypted prompts must be unlocked first".to_string());
+    }
+    Ok(tip.content)
+}
+
+/// 在一个服务商上运行，失败时记录错误而不是中断整批
+async fn run_on_provider(
+    conn: &libsql::Connection,
+    db_manager: &UnifiedDbManager,
+    mut run: PromptRun,
+    usage: &UsageContext,
+) -> PromptRun {
+    let started = Instant::now();
+    let provider = create_provider_from_config(&run.provider_id, conn).await;
+    let result = match &provider {
+        Ok((provider, model_name)) => {
+            run.model = model_name.clone();
+            let messages = vec![ChatMessage::user(run.rendered_prompt.clone())];
+            send_message_with_usage(provider, model_name, messages, db_manager, usage, "prompt").await
+        }
+        Err(e) => Err(e.clone()),
+    };
+
+    match result {
+        Ok((output, record)) => {
+            run.prompt_tokens = record.prompt_tokens;
+            run.completion_tokens = record.completion_tokens;
+            run.estimated = record.estimated;
+            run.latency_ms = record.latency_ms;
+            run.output = Some(output);
+        }
+        Err(e) => {
+            run.latency_ms = started.elapsed().as_millis() as i64;
+            run.prompt_tokens = estimate_tokens(&run.rendered_prompt) as i64;
+            run.estimated = true;
+            run.error = Some(e);
+        }
+    }
+    run
+}
+
+/// 提示词笔记中的变量
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_prompt_variables(
+    tip_id: String,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<Vec<PromptVariable>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let template = load_prompt_template(&conn, &tip_id).await?;
+    Ok(parse_prompt_variables(&template))
+}
+
+/// 用给定的变量值渲染提示词（不调用模型）
+#[tauri::command(rename_all = "snake_case")]
+pub async fn render_prompt_tip(
+    tip_id: String,
+    values: Option<HashMap<String, Value>>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<RenderedPrompt, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let template = load_prompt_template(&conn, &tip_id).await?;
+    render_prompt(&template, &values.unwrap_or_default())
+}
+
+/// 渲染提示词并在多个服务商上并发运行；每个完成时发送 ai-prompt-run 事件，结果保存为笔记的运行历史
+#[tauri::command(rename_all = "snake_case")]
+pub async fn run_prompt_tip(
+    app: AppHandle,
+    tip_id: String,
+    values: Option<HashMap<String, Value>>,
+    provider_ids: Vec<String>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<PromptRunBatch, String> {
+    let mut providers: Vec<String> = Vec::new();
+    for id in provider_ids {
+        if !id.trim().is_empty() && !providers.contains(&id) {
+            providers.push(id);
+        }
+    }
+    if providers.is_empty() {
+        return Err("Select at least one provider".to_string());
+    }
+    if providers.len() > MAX_PROVIDERS_PER_RUN {
+        return Err(format!("At most {} providers can be compared at once", MAX_PROVIDERS_PER_RUN));
+    }
+
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let template = load_prompt_template(&conn, &tip_id).await?;
+    let rendered = render_prompt(&template, &values.unwrap_or_default())?;
+
+    let batch_id = Uuid::new_v4().to_string();
+    let created_at = Utc::now().timestamp_millis();
+    let usage = UsageContext::default();
+    let runs = join_all(providers.iter().map(|provider_id| {
+        let run = PromptRun::new(&tip_id, &batch_id, provider_id, &rendered.values, &rendered.prompt, created_at);
+        let (app, conn, db_manager, usage) = (&app, &conn, db_manager.inner(), &usage);
+        async move {
+            let run = run_on_provider(conn, db_manager, run, usage).await;
+            app.emit("ai-prompt-run", &run).ok();
+            run
+        }
+    }))
+    .await;
+
+    // 按选择顺序保存，便于并排展示
+    for run in &runs {
+        db::insert_prompt_run(&conn, run).await.map_err(|e| e.to_string())?;
+    }
+
+    Ok(PromptRunBatch {
+        batch_id,
+        tip_id,
+        variables: rendered.values,
+        rendered_prompt: rendered.prompt,
+        created_at,
+        runs,
+    })
+}
+
+/// 提示词笔记的运行历史，按批次分组
+#[tauri::command(rename_all = "snake_case")]
+pub async fn list_prompt_runs(
+    tip_id: String,
+    limit: Option<i64>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<Vec<PromptRunBatch>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::list_prompt_runs(&conn, &tip_id, limit.unwrap_or(20).clamp(1, 200))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 删除运行历史；指定 batch_id 时只删除该批次
+#[tauri::command(rename_all = "snake_case")]
+pub async fn delete_prompt_runs(
+    tip_id: String,
+    batch_id: Option<String>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<u64, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::delete_prompt_runs(&conn, &tip_id, batch_id.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_variables_with_types_and_defaults() {
+        let template = "Translate {{text}} into {{ language = English }} in {{count:number=3}} ways. {{text}} {{formal:bool}}";
+        let variables = parse_prompt_variables(template);
+        let names: Vec<&str> = variables.iter().map(|v| v.name.as_str()).collect();
+        assert_eq!(names, vec!["text", "language", "count", "formal"]);
+        assert_eq!(variables[1].default.as_deref(), Some("English"));
+        assert_eq!(variables[2].var_type, PromptVariableType::Number);
+        assert_eq!(variables[2].default.as_deref(), Some("3"));
+        assert_eq!(variables[3].var_type, PromptVariableType::Boolean);
+        assert_eq!(variables[0].default, None);
+    }
+
+    #[test]
+    fn renders_with_values_and_defaults() {
+        let template = "Summarize {{主题}} in {{words:number=50}} words, formal: {{formal:boolean=no}}. {{主题}}";
+        let values = HashMap::from([("主题".to_string(), json!("Rust")), ("words".to_string(), json!(80))]);
+        let rendered = render_prompt(template, &values).unwrap();
+        assert_eq!(rendered.prompt, "Summarize Rust in 80 words, formal: false. Rust");
+        assert_eq!(rendered.values.get("words").map(String::as_str), Some("80"));
+    }
+
+    #[test]
+    fn rejects_missing_and_invalid_values() {
+        let err = render_prompt("{{a}} {{b}}", &HashMap::new()).unwrap_err();
+        assert!(err.contains("a, b"));
+        let values = HashMap::from([("n".to_string(), json!("many"))]);
+        assert!(render_prompt("{{n:number}}", &values).is_err());
+    }
+
+    #[test]
+    fn leaves_plain_text_untouched() {
+        let rendered = render_prompt("No variables, just {braces}.", &HashMap::new()).unwrap();
+        assert_eq!(rendered.prompt, "No variables, just {braces}.");
+        assert!(rendered.values.is_empty());
+    }
+}
@@ -0,0 +1,464 @@
+use super::context::{load_conversation_context, ContextTarget};
+use super::conversations::insert_ai_message;
+use super::embeddings::{search_with_embeddings, SemanticSearchMode};
+use super::rig_client::{ChatMessage, RigProvider};
+use super::roles::get_ai_role_internal;
+use super::usage::{estimate_tokens, truncate_to_tokens, UsageContext};
+use super::{create_provider_from_config, send_message_to_provider, stream_message_from_provider, STREAM_CANCEL_MAP};
+use crate::db::{self, operations, UnifiedDbManager};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+
+// 默认检索的笔记数和上下文预算
+const DEFAULT_TOP_K: usize = 6;
+const DEFAULT_CONTEXT_TOKENS: usize = 3000;
+// 单条来源的最大字符数
+const MAX_PASSAGE_CHARS: usize = 1500;
+// 关键词定位时命中位置之前保留的字符数
+const PASSAGE_LEAD_CHARS: usize = 200;
+// 剩余预算不足时不再追加来源
+const MIN_PASSAGE_TOKENS: usize = 50;
+
+const RAG_INSTRUCTIONS: &str = "Answer the question using the numbered notes below. \
+Cite every statement that relies on a note with its number in square brackets, e.g. [1] or [1][3]. \
+If the notes do not contain the answer, say so instead of guessing.";
+
+/// 检索方式，auto 在配置了嵌入模型时使用混合检索，否则使用关键词
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RagRetrieval {
+    #[default]
+    Auto,
+    Keyword,
+    Semantic,
+    Hybrid,
+}
+
+/// RAG 选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RagOptions {
+    #[serde(default)]
+    pub retrieval: RagRetrieval,
+    pub top_k: Option<usize>,
+    pub max_context_tokens: Option<usize>,
+}
+
+/// 放入上下文的笔记片段，start/end 为笔记正文中的字符偏移（左闭右开）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RagSource {
+    pub index: usize,
+    pub tip_id: String,
+    pub title: String,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// 回答中的引用
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Citation {
+    pub index: usize, // 回答中的 [n]
+    pub tip_id: String,
+    pub title: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// RAG 回答
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RagAnswer {
+    pub reply: String,
+    pub citations: Vec<Citation>,
+    pub sources: Vec<RagSource>,
+    pub message_id: Option<String>, // 写入会话时的助手消息ID
+}
+
+// ============ 纯函数 ============
+
+/// 在正文中定位片段：优先匹配检索命中的分块，其次匹配查询词，否则取开头
+pub fn locate_passage(content: &str, title: &str, chunk: Option<&str>, query: &str) -> (usize, usize) {
+    let chars: Vec<char> = content.chars().collect();
+    let window = |start: usize, len: usize| (start, (start + len.min(MAX_PASSAGE_CHARS)).min(chars.len()));
+    let char_offset = |byte_index: usize| content[..byte_index].chars().count();
+
+    if let Some(chunk) = chunk {
+        // 分块以标题开头（见向量索引），定位前去掉
+        let body = chunk.strip_prefix(title.trim()).map(|b| b.trim_start_matches('\n')).unwrap_or(chunk);
+        let first_paragraph = body.split("\n\n").next().unwrap_or_default().trim();
+        if !first_paragraph.is_empty() {
+            if let Some(byte_index) = content.find(first_paragraph) {
+                return window(char_offset(byte_index), body.chars().count());
+            }
+        }
+    }
+
+    let lower = content.to_lowercase();
+    // 小写后长度可能变化，仅在长度一致时按偏移使用
+    if lower.len() == content.len() {
+        let hit = query
+            .split_whitespace()
+            .filter(|term| term.chars().count() >= 2)
+            .filter_map(|term| lower.find(&term.to_lowercase()))
+            .min();
+        if let Some(byte_index) = hit {
+            let start = char_offset(byte_index).saturating_sub(PASSAGE_LEAD_CHARS);
+            return window(start, MAX_PASSAGE_CHARS);
+        }
+    }
+
+    window(0, MAX_PASSAGE_CHARS)
+}
+
+/// 解析回答中的 [n]、[n, m] 引用标记，按首次出现顺序返回
+pub fn parse_citation_markers(answer: &str) -> Vec<usize> {
+    let mut seen = HashSet::new();
+    let mut markers = Vec::new();
+    let mut rest = answer;
+    while let Some(open) = rest.find('[') {
+        rest = &rest[open + 1..];
+        let Some(close) = rest.find(']') else { break };
+        let inner = &rest[..close];
+        let numbers: Option<Vec<usize>> = inner.split(',').map(|n| n.trim().parse().ok()).collect();
+        if let Some(numbers) = numbers {
+            for n in numbers {
+                if seen.insert(n) {
+                    markers.push(n);
+                }
+            }
+            rest = &rest[close + 1..];
+        }
+    }
+    markers
+}
+
+/// 按回答中的引用标记映射回来源
+pub fn resolve_citations(answer: &str, sources: &[RagSource]) -> Vec<Citation> {
+    parse_citation_markers(answer)
+        .into_iter()
+        .filter_map(|n| sources.iter().find(|s| s.index == n))
+        .map(|s| Citation {
+            index: s.index,
+            tip_id: s.tip_id.clone(),
+            title: s.title.clone(),
+            start: s.start,
+            end: s.end,
+        })
+        .collect()
+}
+
+/// 拼接放入系统提示的来源列表
+fn format_sources(sources: &[RagSource]) -> String {
+    sources
+        .iter()
+        .map(|s| format!("[{}] {}\n{}", s.index, s.title, s.text))
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+// ============ 检索 ============
+
+/// 检索相关笔记并在 token 预算内截取片段
+async fn retrieve_sources(
+    conn: &libsql::Connection,
+    db_manager: &UnifiedDbManager,
+    query: &str,
+    options: &RagOptions,
+) -> Result<Vec<RagSource>, String> {
+    let top_k = options.top_k.unwrap_or(DEFAULT_TOP_K).max(1);
+    let semantic_mode = match options.retrieval {
+        RagRetrieval::Keyword => None,
+        RagRetrieval::Semantic => Some(SemanticSearchMode::Semantic),
+        RagRetrieval::Hybrid | RagRetrieval::Auto => Some(SemanticSearchMode::Hybrid),
+    };
+
+    // (笔记ID, 命中分块)
+    let mut ranked: Option<Vec<(String, Option<String>)>> = None;
+    if let Some(mode) = semantic_mode {
+        match search_with_embeddings(conn, db_manager, query, top_k, mode).await? {
+            Some(results) => {
+                ranked = Some(results.into_iter().map(|r| (r.tip.id, r.matched_chunk)).collect());
+            }
+            None if options.retrieval != RagRetrieval::Auto => {
+                return Err("No embedding model configured".to_string());
+            }
+            None => {}
+        }
+    }
+    let ranked = match ranked {
+        Some(ranked) => ranked,
+        None => db::search_tips_summary_fast(conn, query, top_k as i32)
+            .await
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .map(|s| (s.id, None))
+            .collect(),
+    };
+
+    let mut budget = options.max_context_tokens.unwrap_or(DEFAULT_CONTEXT_TOKENS);
+    let mut sources = Vec::new();
+    for (tip_id, chunk) in ranked {
+        if budget < MIN_PASSAGE_TOKENS {
+            break;
+        }
+        let Some(tip) = operations::get_tip_by_id(conn, &tip_id).await.map_err(|e| e.to_string())? else {
+            continue;
+        };
+        if tip.is_encrypted.unwrap_or(false) || tip.content.trim().is_empty() {
+            continue;
+        }
+
+        let (start, end) = locate_passage(&tip.content, &tip.title, chunk.as_deref(), query);
+        let passage: String = tip.content.chars().skip(start).take(end - start).collect();
+        let header_tokens = estimate_tokens(&tip.title) + 4;
+        let text = truncate_to_tokens(&passage, budget.saturating_sub(header_tokens)).to_string();
+        if text.trim().is_empty() {
+            continue;
+        }
+        budget = budget.saturating_sub(header_tokens + estimate_tokens(&text));
+
+        sources.push(RagSource {
+            index: sources.len() + 1,
+            tip_id,
+            title: tip.title,
+            end: start + text.chars().count(),
+            start,
+            text,
+        });
+    }
+    Ok(sources)
+}
+
+/// 组装发送给模型的消息：说明 + 来源 + 历史 + 问题
+async fn build_rag_messages(
+    db_manager: &UnifiedDbManager,
+    message: &str,
+    role_id: Option<String>,
+    conversation_id: Option<&str>,
+    sources: &[RagSource],
+    target: ContextTarget<'_>,
+    usage: &UsageContext,
+) -> Vec<ChatMessage> {
+    let role_description = match role_id {
+        Some(role_id) => get_ai_role_internal(role_id, db_manager.clone())
+            .await
+            .map(|role| role.description.unwrap_or_default())
+            .unwrap_or_default(),
+        None => String::new(),
+    };
+
+    let mut system_content = String::new();
+    if !role_description.trim().is_empty() {
+        system_content.push_str(&role_description);
+        system_content.push_str("\n\n");
+    }
+    system_content.push_str(RAG_INSTRUCTIONS);
+    system_content.push_str("\n\nNotes:\n");
+    if sources.is_empty() {
+        system_content.push_str("(no matching notes)");
+    } else {
+        system_content.push_str(&format_sources(sources));
+    }
+
+    // 来源占用的 token 计入固定部分，历史超出时先摘要
+    let target = ContextTarget {
+        fixed_tokens: estimate_tokens(&system_content) + estimate_tokens(message) + 8,
+        ..target
+    };
+    let mut chat_messages = vec![ChatMessage::system(system_content)];
+    if let Some(conv_id) = conversation_id {
+        let history = load_conversation_context(conv_id, &target, db_manager, usage).await.unwrap_or_default();
+        for m in history {
+            chat_messages.push(match m.role.as_str() {
+                "assistant" => ChatMessage::assistant(m.content),
+                "system" => ChatMessage::system(m.content),
+                _ => ChatMessage::user(m.content),
+            });
+        }
+    }
+    chat_messages.push(ChatMessage::user(message.to_string()));
+    chat_messages
+}
+
+/// 写入问题和带引用的回答，返回助手消息ID
+async fn persist_rag_exchange(
+    conn: &libsql::Connection,
+    conversation_id: Option<&str>,
+    message: &str,
+    reply: &str,
+    citations: &[Citation],
+) -> Result<Option<String>, String> {
+    let Some(conversation_id) = conversation_id else {
+        return Ok(None);
+    };
+    insert_ai_message(conn, conversation_id, "user", message, &[], &[]).await?;
+    let assistant = insert_ai_message(conn, conversation_id, "assistant", reply, citations, &[]).await?;
+    Ok(Some(assistant.id))
+}
+
+// ============ 命令 ============
+
+/// 基于笔记回答问题（非流式），传入 conversation_id 时问答写入会话
+#[tauri::command]
+pub async fn send_rag_message(
+    message: String,
+    provider_id: String,
+    role_id: Option<String>,
+    conversation_id: Option<String>,
+    options: Option<RagOptions>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<RagAnswer, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let options = options.unwrap_or_default();
+    let (provider, model_name) = create_provider_from_config(&provider_id, &conn).await?;
+
+    let sources = retrieve_sources(&conn, db_manager.inner(), &message, &options).await?;
+    let usage = UsageContext::new(role_id.clone(), conversation_id.clone());
+    let target = ContextTarget { provider_id: &provider_id, provider: &provider, model_name: &model_name, fixed_tokens: 0 };
+    let chat_messages = build_rag_messages(db_manager.inner(), &message, role_id, conversation_id.as_deref(), &sources, target, &usage).await;
+
+    let reply = send_message_to_provider(&provider, &model_name, chat_messages, &db_manager, &usage).await?;
+    let citations = resolve_citations(&reply, &sources);
+    let message_id = persist_rag_exchange(&conn, conversation_id.as_deref(), &message, &reply, &citations).await?;
+
+    Ok(RagAnswer { reply, citations, sources, message_id })
+}
+
+/// 基于笔记回答问题（流式），结束时通过 ai-stream-citations 事件发送引用
+#[tauri::command]
+pub async fn send_rag_message_stream(
+    app: AppHandle,
+    message: String,
+    stream_id: String,
+    provider_id: String,
+    role_id: Option<String>,
+    conversation_id: Option<String>,
+    options: Option<RagOptions>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<(), String> {
+    let should_cancel = Arc::new(AtomicBool::new(false));
+    STREAM_CANCEL_MAP.lock().await.insert(stream_id.clone(), should_cancel.clone());
+
+    let db_manager = db_manager.inner().clone();
+    tauri::async_runtime::spawn(async move {
+        let result = handle_rag_stream(
+            &app,
+            &message,
+            &stream_id,
+            &provider_id,
+            role_id,
+            conversation_id,
+            options.unwrap_or_default(),
+            &db_manager,
+            should_cancel,
+        )
+        .await;
+
+        if let Err(e) = result {
+            app.emit("ai-stream-error", serde_json::json!({ "id": stream_id, "error": e })).ok();
+        }
+        app.emit("ai-stream-chunk", serde_json::json!({ "id": stream_id, "chunk": "", "done": true })).ok();
+        STREAM_CANCEL_MAP.lock().await.remove(&stream_id);
+    });
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_rag_stream(
+    app: &AppHandle,
+    message: &str,
+    stream_id: &str,
+    provider_id: &str,
+    role_id: Option<String>,
+    conversation_id: Option<String>,
+    options: RagOptions,
+    db_manager: &UnifiedDbManager,
+    should_cancel: Arc<AtomicBool>,
+) -> Result<(), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let (provider, model_name) = create_provider_from_config(provider_id, &conn).await?;
+
+    let sources = retrieve_sources(&conn, db_manager, message, &options).await?;
+    app.emit("ai-stream-sources", serde_json::json!({ "id": stream_id, "sources": sources })).ok();
+    let usage = UsageContext::new(role_id.clone(), conversation_id.clone());
+    let target = ContextTarget { provider_id, provider: &provider, model_name: &model_name, fixed_tokens: 0 };
+    let chat_messages = build_rag_messages(db_manager, message, role_id, conversation_id.as_deref(), &sources, target, &usage).await;
+
+    // 内置 SDK 的服务商不支持流式，整段返回
+    let reply = match stream_message_from_provider(&provider, &model_name, chat_messages.clone(), db_manager, &usage).await {
+        Ok(mut stream) => {
+            let mut reply = String::new();
+            while let Some(chunk) = stream.next().await {
+                if should_cancel.load(Ordering::SeqCst) {
+                    break;
+                }
+                let chunk = chunk.map_err(|e| e.to_string())?;
+                if !chunk.is_empty() {
+                    reply.push_str(&chunk);
+                    app.emit("ai-stream-chunk", serde_json::json!({ "id": stream_id, "chunk": chunk, "done": false })).ok();
+                }
+            }
+            reply
+        }
+        Err(_) if !matches!(provider, RigProvider::Custom(_)) => {
+            let reply = send_message_to_provider(&provider, &model_name, chat_messages, db_manager, &usage).await?;
+            app.emit("ai-stream-chunk", serde_json::json!({ "id": stream_id, "chunk": reply, "done": false })).ok();
+            reply
+        }
+        Err(e) => return Err(e),
+    };
+
+    let citations = resolve_citations(&reply, &sources);
+    let message_id = persist_rag_exchange(&conn, conversation_id.as_deref(), message, &reply, &citations).await?;
+    app.emit(
+        "ai-stream-citations",
+        serde_json::json!({ "id": stream_id, "citations": citations, "message_id": message_id }),
+    ).ok();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(index: usize) -> RagSource {
+        RagSource {
+            index,
+            tip_id: format!("tip{}", index),
+            title: format!("Note {}", index),
+            start: 0,
+            end: 5,
+            text: "hello".into(),
+        }
+    }
+
+    #[test]
+    fn parses_citation_markers() {
+        assert_eq!(parse_citation_markers("A [2] and B [1, 3][2]. See [x] or [ 4 ]"), vec![2, 1, 3, 4]);
+        assert!(parse_citation_markers("no citations [").is_empty());
+    }
+
+    #[test]
+    fn resolves_only_known_sources() {
+        let sources = vec![source(1), source(2)];
+        let citations = resolve_citations("Fact [2], other [5]", &sources);
+        assert_eq!(citations.len(), 1);
+        assert_eq!(citations[0].tip_id, "tip2");
+    }
+
+    #[test]
+    fn locates_passages_in_characters() {
+        let content = "前言\n\nRust ownership rules\n\nmore text";
+        let (start, end) = locate_passage(content, "Title", Some("Title\nRust ownership rules"), "");
+        let located: String = content.chars().skip(start).take(end - start).collect();
+        assert_eq!(located, "Rust ownership rules");
+
+        let (start, _) = locate_passage(content, "Title", None, "MORE");
+        assert_eq!(start, 0); // 命中位置之前保留的字符超出开头
+        assert_eq!(locate_passage("", "T", None, "x"), (0, 0));
+    }
+}
@@ -0,0 +1,613 @@
+use super::create_provider_from_config;
+use super::rig_client::RigProvider;
+use crate::db::{self, UnifiedDbManager};
+use crate::sync::RetryStrategy;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::State;
+
+const RESILIENCE_CONFIG_KEY: &str = "ai_resilience_config";
+// 客户端限流的统计窗口
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+// 等待期间检查取消标记的间隔
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+lazy_static::lazy_static! {
+    // 各服务商的限流和熔断状态，进程内共享
+    static ref PROVIDER_STATES: Mutex<HashMap<String, ProviderState>> = Mutex::new(HashMap::new());
+}
+
+// ============ 配置 ============
+
+/// 备用服务商，`model` 为空时使用该服务商的默认模型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FallbackTarget {
+    pub provider_id: String,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// 指数退避重试设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub max_attempts: u32, // 每个服务商的最大尝试次数（含首次）
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64, // Retry-After 超过该值时直接切换到备用服务商
+    pub backoff_multiplier: f64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self { max_attempts: 3, base_delay_ms: 1000, max_delay_ms: 30_000, backoff_multiplier: 2.0 }
+    }
+}
+
+impl RetryConfig {
+    fn strategy(&self) -> RetryStrategy {
+        RetryStrategy {
+            max_attempts: self.max_attempts.max(1),
+            base_delay_ms: self.base_delay_ms,
+            max_delay_ms: self.max_delay_ms,
+            backoff_multiplier: self.backoff_multiplier,
+            jitter: true,
+        }
+    }
+}
+
+/// 熔断设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32, // 连续失败多少次后熔断
+    pub cooldown_secs: u64,     // 熔断后多久允许试探请求
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self { failure_threshold: 5, cooldown_secs: 60 }
+    }
+}
+
+/// AI 调用的容错配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiResilienceConfig {
+    pub fallback_chains: HashMap<String, Vec<FallbackTarget>>, // 主服务商 -> 依次尝试的备用服务商
+    pub retry: RetryConfig,
+    pub rate_limits: HashMap<String, u32>, // 服务商 -> 每分钟最多请求数
+    pub circuit_breaker: CircuitBreakerConfig,
+}
+
+impl AiResilienceConfig {
+    /// 主服务商在前，随后是去重后的备用服务商
+    pub fn chain_for(&self, primary_provider_id: &str) -> Vec<FallbackTarget> {
+        let mut chain = vec![FallbackTarget { provider_id: primary_provider_id.to_string(), model: None }];
+        for target in self.fallback_chains.get(primary_provider_id).into_iter().flatten() {
+            if !chain.contains(target) {
+                chain.push(target.clone());
+            }
+        }
+        chain
+    }
+}
+
+pub async fn load_resilience_config(conn: &libsql::Connection) -> Result<AiResilienceConfig, String> {
+    let json = db::get_setting(conn, RESILIENCE_CONFIG_KEY).await.map_err(|e| e.to_string())?;
+    Ok(json.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default())
+}
+
+// ============ 错误分类 ============
+
+/// 服务商错误类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AiErrorKind {
+    RateLimited,
+    Server,
+    Timeout,
+    Network,
+    Auth,
+    Client,
+    Budget,
+    Other,
+}
+
+impl AiErrorKind {
+    /// 稍后重试可能成功的错误
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::RateLimited | Self::Server | Self::Timeout | Self::Network)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassifiedError {
+    pub kind: AiErrorKind,
+    pub status: Option<u16>,
+    pub retry_after: Option<Duration>,
+}
+
+/// 解析 `Retry-After` 头：秒数或 HTTP 日期
+pub fn parse_retry_after(value: &str) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(secs);
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some((date.timestamp() - chrono::Utc::now().timestamp()).max(0) as u64)
+}
+
+/// 按错误信息分类；HTTP 状态码和 Retry-After 由 rig_client 写入错误信息
+pub fn classify_ai_error(message: &str) -> ClassifiedError {
+    let lower = message.to_lowercase();
+    let status = lower.match_indices("error ").find_map(|(i, m)| {
+        let digits = lower.get(i + m.len()..i + m.len() + 3)?;
+        digits.parse::<u16>().ok().filter(|s| (100..600).contains(s))
+    });
+    let retry_after = lower.find("retry-after: ").and_then(|i| {
+        let rest = &lower[i + "retry-after: ".len()..];
+        let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+        rest[..end].parse::<u64>().ok().map(Duration::from_secs)
+    });
+
+    let kind = match status {
+        Some(429) => AiErrorKind::RateLimited,
+        Some(408) => AiErrorKind::Timeout,
+        Some(401) | Some(403) => AiErrorKind::Auth,
+        Some(s) if s >= 500 => AiErrorKind::Server,
+        Some(s) if s >= 400 => AiErrorKind::Client,
+        _ if lower.contains("budget") => AiErrorKind::Budget,
+        _ if lower.contains("timed out") || lower.contains("timeout") => AiErrorKind::Timeout,
+        _ if lower.contains("request failed") || lower.contains("connection") || lower.contains("stream error") => {
+            AiErrorKind::Network
+        }
+        _ => AiErrorKind::Other,
+    };
+    ClassifiedError { kind, status, retry_after }
+}
+
+// ============ 限流与熔断 ============
+
+/// 滑动窗口限流
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    requests: VecDeque<Instant>,
+}
+
+impl RateLimiter {
+    /// 有空位时记录请求并返回 None，否则返回需要等待的时间
+    pub fn try_acquire(&mut self, now: Instant, limit: u32) -> Option<Duration> {
+        while self.requests.front().is_some_and(|t| now.duration_since(*t) >= RATE_LIMIT_WINDOW) {
+            self.requests.pop_front();
+        }
+        if limit == 0 || self.requests.len() < limit as usize {
+            self.requests.push_back(now);
+            return None;
+        }
+        self.requests.front().map(|oldest| RATE_LIMIT_WINDOW.saturating_sub(now.duration_since(*oldest)))
+    }
+
+    fn recent(&self, now: Instant) -> usize {
+        self.requests.iter().filter(|t| now.duration_since(**t) < RATE_LIMIT_WINDOW).count()
+    }
+}
+
+/// 熔断器状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+/// 连续失败达到阈值后熔断，冷却结束后放行一次试探请求
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    probe_in_flight: bool,
+}
+
+impl CircuitBreaker {
+    pub fn state(&self, now: Instant) -> CircuitState {
+        match self.open_until {
+            Some(until) if now < until => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+            None => CircuitState::Closed,
+        }
+    }
+
+    /// 放行时返回是否为试探请求；熔断中或已有试探请求在进行时返回剩余冷却时间
+    pub fn allow(&mut self, now: Instant) -> Result<bool, Duration> {
+        match self.state(now) {
+            CircuitState::Open => Err(self.open_until.map(|until| until - now).unwrap_or_default()),
+            CircuitState::HalfOpen if self.probe_in_flight => Err(Duration::ZERO),
+            CircuitState::HalfOpen => {
+                self.probe_in_flight = true;
+                Ok(true)
+            }
+            CircuitState::Closed => Ok(false),
+        }
+    }
+
+    /// 试探请求未得出结论（取消、非重试类错误等）时结束试探，下一个请求重新试探
+    pub fn finish_probe(&mut self) {
+        self.probe_in_flight = false;
+    }
+
+    pub fn record_success(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn record_failure(&mut self, now: Instant, config: &CircuitBreakerConfig) {
+        self.consecutive_failures += 1;
+        if self.probe_in_flight || self.consecutive_failures >= config.failure_threshold.max(1) {
+            self.open_until = Some(now + Duration::from_secs(config.cooldown_secs));
+            self.probe_in_flight = false;
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ProviderState {
+    limiter: RateLimiter,
+    breaker: CircuitBreaker,
+}
+
+fn with_provider_state<T>(provider_id: &str, f: impl FnOnce(&mut ProviderState) -> T) -> T {
+    let mut states = PROVIDER_STATES.lock().unwrap_or_else(|e| e.into_inner());
+    f(states.entry(provider_id.to_string()).or_default())
+}
+
+/// 试探请求结束（包括提前返回）时释放试探名额
+struct ProbeGuard<'a> {
+    provider_id: &'a str,
+}
+
+impl Drop for ProbeGuard<'_> {
+    fn drop(&mut self) {
+        with_provider_state(self.provider_id, |s| s.breaker.finish_probe());
+    }
+}
+
+/// 服务商当前的健康状况
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    pub provider_id: String,
+    pub state: CircuitState,
+    pub consecutive_failures: u32,
+    pub retry_in_ms: u64, // 熔断剩余时间
+    pub requests_last_minute: usize,
+    pub rate_limit: Option<u32>,
+}
+
+// ============ 执行 ============
+
+/// 容错过程中的事件，流式调用时随 ai-stream-status 发送给前端
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResilienceEvent {
+    RateLimited { provider_id: String, wait_ms: u64 },
+    Retrying { provider_id: String, attempt: u32, delay_ms: u64, error: String },
+    CircuitOpen { provider_id: String, retry_in_ms: u64 },
+    FallingBack { from: String, to: String, error: Option<String> },
+    Succeeded { provider_id: String, model: String, attempts: u32 },
+}
+
+/// 成功的调用及实际使用的服务商
+pub struct ResilientOutcome<T> {
+    pub value: T,
+    pub provider_id: String,
+    pub model: String,
+    pub attempts: u32,
+}
+
+fn is_cancelled(cancel: Option<&AtomicBool>) -> bool {
+    cancel.is_some_and(|c| c.load(Ordering::SeqCst))
+}
+
+/// 等待指定时间，期间被取消时立即返回错误
+async fn sleep_unless_cancelled(delay: Duration, cancel: Option<&AtomicBool>) -> Result<(), String> {
+    let deadline = tokio::time::Instant::now() + delay;
+    loop {
+        if is_cancelled(cancel) {
+            return Err("Request cancelled".to_string());
+        }
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            return Ok(());
+        }
+        let remaining = deadline - now;
+        tokio::time::sleep(if cancel.is_some() { remaining.min(CANCEL_POLL_INTERVAL) } else { remaining }).await;
+    }
+}
+
+async fn wait_for_rate_limit(
+    provider_id: &str,
+    limit: Option<u32>,
+    cancel: Option<&AtomicBool>,
+    on_event: &(dyn Fn(&ResilienceEvent) + Send + Sync),
+) -> Result<(), String> {
+    let limit = limit.unwrap_or(0);
+    loop {
+        let Some(wait) = with_provider_state(provider_id, |s| s.limiter.try_acquire(Instant::now(), limit)) else {
+            return Ok(());
+        };
+        on_event(&ResilienceEvent::RateLimited { provider_id: provider_id.to_string(), wait_ms: wait.as_millis() as u64 });
+        sleep_unless_cancelled(wait, cancel).await?;
+    }
+}
+
+/// 依次尝试主服务商和备用服务商：每个服务商按退避策略重试（优先遵循 Retry-After），
+/// 并受客户端限流和熔断约束。`call` 收到服务商和模型名，返回一次调用的结果。
+pub async fn call_with_resilience<T, F, Fut>(
+    primary_provider_id: &str,
+    db_manager: &UnifiedDbManager,
+    cancel: Option<&AtomicBool>,
+    on_event: &(dyn Fn(&ResilienceEvent) + Send + Sync),
+    mut call: F,
+) -> Result<ResilientOutcome<T>, String>
+where
+    F: FnMut(Arc<RigProvider>, String) -> Fut,
+    Fut: Future<Output = Result<T, String>>,
+{
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let config = load_resilience_config(&conn).await?;
+    let strategy = config.retry.strategy();
+    let chain = config.chain_for(primary_provider_id);
+
+    let mut last_error: Option<String> = None;
+    let mut attempts = 0;
+    for (index, target) in chain.iter().enumerate() {
+        if index > 0 {
+            on_event(&ResilienceEvent::FallingBack {
+                from: chain[index - 1].provider_id.clone(),
+                to: target.provider_id.clone(),
+                error: last_error.clone(),
+            });
+        }
+
+        let probe = match with_provider_state(&target.provider_id, |s| s.breaker.allow(Instant::now())) {
+            Ok(probe) => probe,
+            Err(remaining) => {
+                on_event(&ResilienceEvent::CircuitOpen {
+                    provider_id: target.provider_id.clone(),
+                    retry_in_ms: remaining.as_millis() as u64,
+                });
+                last_error = Some(format!("Provider '{}' is temporarily disabled after repeated failures", target.provider_id));
+                continue;
+            }
+        };
+        let _probe_guard = probe.then(|| ProbeGuard { provider_id: &target.provider_id });
+
+        let (provider, default_model) = match create_provider_from_config(&target.provider_id, &conn).await {
+            Ok(created) => created,
+            Err(e) => {
+                last_error = Some(e);
+                continue;
+            }
+        };
+        let provider = Arc::new(provider);
+        let model = target.model.clone().unwrap_or(default_model);
+
+        let mut attempt = 0;
+        loop {
+            if is_cancelled(cancel) {
+                return Err("Request cancelled".to_string());
+            }
+            attempt += 1;
+            attempts += 1;
+            wait_for_rate_limit(&target.provider_id, config.rate_limits.get(&target.provider_id).copied(), cancel, on_event)
+                .await?;
+
+            let error = match call(provider.clone(), model.clone()).await {
+                Ok(value) => {
+                    with_provider_state(&target.provider_id, |s| s.breaker.record_success());
+                    on_event(&ResilienceEvent::Succeeded {
+                        provider_id: target.provider_id.clone(),
+                        model: model.clone(),
+                        attempts,
+                    });
+                    return Ok(ResilientOutcome { value, provider_id: target.provider_id.clone(), model, attempts });
+                }
+                Err(e) => e,
+            };
+
+            let classified = classify_ai_error(&error);
+            tracing::warn!("AI call to '{}' failed (attempt {}): {}", target.provider_id, attempt, error);
+            last_error = Some(error.clone());
+            if !classified.kind.is_retryable() {
+                break;
+            }
+            let open = with_provider_state(&target.provider_id, |s| {
+                s.breaker.record_failure(Instant::now(), &config.circuit_breaker);
+                s.breaker.state(Instant::now()) == CircuitState::Open
+            });
+            if open || attempt >= strategy.max_attempts {
+                break;
+            }
+            // 服务商要求等待过久时直接切换
+            let delay = classified.retry_after.unwrap_or_else(|| strategy.calculate_delay(attempt));
+            if delay > Duration::from_millis(strategy.max_delay_ms) {
+                break;
+            }
+            on_event(&ResilienceEvent::Retrying {
+                provider_id: target.provider_id.clone(),
+                attempt,
+                delay_ms: delay.as_millis() as u64,
+                error,
+            });
+            sleep_unless_cancelled(delay, cancel).await?;
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| "No AI provider available".to_string()))
+}
+
+// ============ 命令 ============
+
+/// 获取容错配置
+#[tauri::command]
+pub async fn get_ai_resilience_config(db_manager: State<'_, UnifiedDbManager>) -> Result<AiResilienceConfig, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    load_resilience_config(&conn).await
+}
+
+/// 保存容错配置
+#[tauri::command]
+pub async fn save_ai_resilience_config(
+    config: AiResilienceConfig,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<(), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let json = serde_json::to_string(&config).map_err(|e| e.to_string())?;
+    db::save_setting(&conn, RESILIENCE_CONFIG_KEY, &json).await.map_err(|e| e.to_string())
+}
+
+/// 各服务商的熔断和限流状态
+#[tauri::command]
+pub async fn get_ai_provider_health(db_manager: State<'_, UnifiedDbManager>) -> Result<Vec<ProviderHealth>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let config = load_resilience_config(&conn).await?;
+    let now = Instant::now();
+    let states = PROVIDER_STATES.lock().unwrap_or_else(|e| e.into_inner());
+    let mut health: Vec<ProviderHealth> = states
+        .iter()
+        .map(|(provider_id, state)| ProviderHealth {
+            provider_id: provider_id.clone(),
+            state: state.breaker.state(now),
+            consecutive_failures: state.breaker.consecutive_failures,
+            retry_in_ms: state.breaker.open_until.map(|until| until.saturating_duration_since(now).as_millis() as u64).unwrap_or(0),
+            requests_last_minute: state.limiter.recent(now),
+            rate_limit: config.rate_limits.get(provider_id).copied(),
+        })
+        .collect();
+    health.sort_by(|a, b| a.provider_id.cmp(&b.provider_id));
+    Ok(health)
+}
+
+/// 手动恢复熔断的服务商；不指定时全部恢复
+#[tauri::command(rename_all = "snake_case")]
+pub async fn reset_ai_provider_health(provider_id: Option<String>) -> Result<(), String> {
+    let mut states = PROVIDER_STATES.lock().unwrap_or_else(|e| e.into_inner());
+    match provider_id {
+        Some(provider_id) => {
+            if let Some(state) = states.get_mut(&provider_id) {
+                state.breaker.record_success();
+            }
+        }
+        None => states.values_mut().for_each(|s| s.breaker.record_success()),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_provider_errors() {
+        let limited = classify_ai_error("API error 429 Too Many Requests: slow down (retry-after: 12s)");
+        assert_eq!(limited.kind, AiErrorKind::RateLimited);
+        assert_eq!(limited.status, Some(429));
+        assert_eq!(limited.retry_after, Some(Duration::from_secs(12)));
+
+        assert_eq!(classify_ai_error("Stream API error 503 Service Unavailable: ").kind, AiErrorKind::Server);
+        assert_eq!(classify_ai_error("API error 401 Unauthorized: bad key").kind, AiErrorKind::Auth);
+        assert_eq!(classify_ai_error("API error 400 Bad Request: context too long").kind, AiErrorKind::Client);
+        assert_eq!(classify_ai_error("Request failed: error sending request").kind, AiErrorKind::Network);
+        assert_eq!(classify_ai_error("Monthly AI budget for 'openai' exceeded (120% used)").kind, AiErrorKind::Budget);
+        assert!(!AiErrorKind::Client.is_retryable());
+        assert!(AiErrorKind::Server.is_retryable());
+    }
+
+    #[test]
+    fn parses_retry_after_values() {
+        assert_eq!(parse_retry_after(" 30 "), Some(30));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), Some(0));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn rate_limiter_uses_sliding_window() {
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        assert_eq!(limiter.try_acquire(start, 2), None);
+        assert_eq!(limiter.try_acquire(start + Duration::from_secs(10), 2), None);
+        assert_eq!(limiter.try_acquire(start + Duration::from_secs(20), 2), Some(Duration::from_secs(40)));
+        assert_eq!(limiter.try_acquire(start + Duration::from_secs(60), 2), None);
+        assert_eq!(limiter.try_acquire(start, 0), None);
+    }
+
+    #[test]
+    fn circuit_breaker_opens_and_recovers() {
+        let config = CircuitBreakerConfig { failure_threshold: 2, cooldown_secs: 30 };
+        let mut breaker = CircuitBreaker::default();
+        let now = Instant::now();
+        breaker.record_failure(now, &config);
+        assert!(breaker.allow(now).is_ok());
+        breaker.record_failure(now, &config);
+        assert_eq!(breaker.allow(now + Duration::from_secs(10)), Err(Duration::from_secs(20)));
+
+        // 冷却结束后放行试探请求，再次失败立即熔断
+        let later = now + Duration::from_secs(31);
+        assert!(breaker.allow(later).is_ok());
+        assert_eq!(breaker.state(later), CircuitState::HalfOpen);
+        breaker.record_failure(later, &config);
+        assert_eq!(breaker.state(later), CircuitState::Open);
+
+        breaker.record_success();
+        assert_eq!(breaker.state(later), CircuitState::Closed);
+    }
+
+    #[test]
+    fn half_open_allows_a_single_probe() {
+        let config = CircuitBreakerConfig { failure_threshold: 1, cooldown_secs: 30 };
+        let mut breaker = CircuitBreaker::default();
+        let now = Instant::now();
+        breaker.record_failure(now, &config);
+
+        let later = now + Duration::from_secs(31);
+        assert_eq!(breaker.allow(later), Ok(true));
+        assert_eq!(breaker.allow(later), Err(Duration::ZERO));
+
+        // 试探未得出结论时下一个请求重新试探
+        breaker.finish_probe();
+        assert_eq!(breaker.allow(later), Ok(true));
+        breaker.record_success();
+        assert_eq!(breaker.allow(later), Ok(false));
+    }
+
+    #[tokio::test]
+    async fn retry_wait_stops_when_cancelled() {
+        let cancel = AtomicBool::new(true);
+        let waited = tokio::time::timeout(
+            Duration::from_secs(1),
+            sleep_unless_cancelled(Duration::from_secs(60), Some(&cancel)),
+        )
+        .await
+        .expect("cancelled wait should return immediately");
+        assert!(waited.is_err());
+        assert!(sleep_unless_cancelled(Duration::from_millis(1), None).await.is_ok());
+    }
+
+    #[test]
+    fn chain_starts_with_primary_and_skips_duplicates() {
+        let mut config = AiResilienceConfig::default();
+        config.fallback_chains.insert(
+            "openai".to_string(),
+            vec![
+                FallbackTarget { provider_id: "openai".to_string(), model: None },
+                FallbackTarget { provider_id: "deepseek".to_string(), model: Some("deepseek-chat".to_string()) },
+            ],
+        );
+        let chain = config.chain_for("openai");
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[1].provider_id, "deepseek");
+        assert_eq!(config.chain_for("qwen").len(), 1);
+    }
+}
@@ -1,12 +1,16 @@
+use super::local;
 use crate::api::settings::get_client_with_proxy;
 use crate::db::UnifiedDbManager;
 use anyhow::{anyhow, Result};
 use futures_util::StreamExt;
 use rig::completion::Prompt;
 use rig::providers::{anthropic, gemini, openai};
-use rig::client::CompletionClient;
+use rig::client::{CompletionClient, EmbeddingsClient};
+use rig::embeddings::EmbeddingModel;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use futures::Stream;
 
 /// Message structure for chat conversations
@@ -39,6 +43,31 @@ impl ChatMessage {
     }
 }
 
+/// Token usage reported by the provider
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+}
+
+/// Usage reported in the final chunk of a stream, filled in while the stream is consumed
+pub type StreamUsageSlot = Arc<Mutex<Option<TokenUsage>>>;
+
+/// A tool call requested by the model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallRequest {
+    pub id: String,
+    pub name: String,
+    pub arguments: serde_json::Value,
+}
+
+/// Model turn when tools are offered: text content and/or tool calls
+#[derive(Debug, Clone, Default)]
+pub struct ToolTurn {
+    pub content: String,
+    pub tool_calls: Vec<ToolCallRequest>,
+}
+
 /// Unified AI provider wrapper for rig-core
 pub enum RigProvider {
     OpenAI(openai::Client),
@@ -85,6 +114,16 @@ impl RigProvider {
         }
     }
 
+    /// Provider identifier used for usage accounting and budgets
+    pub fn provider_name(&self) -> &str {
+        match self {
+            RigProvider::OpenAI(_) => "openai",
+            RigProvider::Anthropic(_) => "anthropic",
+            RigProvider::Gemini(_) => "gemini",
+            RigProvider::Custom(custom) => &custom.provider_type,
+        }
+    }
+
     /// Send a simple prompt and get response (non-streaming)
     pub async fn prompt(&self, model_name: &str, prompt_text: &str) -> Result<String> {
         match self {
@@ -134,6 +173,39 @@ impl RigProvider {
         }
     }
 
+    /// Compute embeddings for a batch of texts (one vector per input, same order)
+    pub async fn embed(
+        &self,
+        model_name: &str,
+        texts: Vec<String>,
+        db_manager: &UnifiedDbManager,
+    ) -> Result<Vec<Vec<f32>>> {
+        let to_f32 = |embeddings: Vec<rig::embeddings::Embedding>| {
+            embeddings
+                .into_iter()
+                .map(|e| e.vec.into_iter().map(|v| v as f32).collect())
+                .collect()
+        };
+        match self {
+            RigProvider::OpenAI(client) => {
+                let embeddings = client.embedding_model(model_name).embed_texts(texts).await
+                    .map_err(|e| anyhow!("OpenAI embedding failed: {}", e))?;
+                Ok(to_f32(embeddings))
+            }
+            RigProvider::Gemini(client) => {
+                let embeddings = client.embedding_model(model_name).embed_texts(texts).await
+                    .map_err(|e| anyhow!("Gemini embedding failed: {}", e))?;
+                Ok(to_f32(embeddings))
+            }
+            RigProvider::Anthropic(_) => {
+                Err(anyhow!("Anthropic does not provide an embedding API"))
+            }
+            RigProvider::Custom(custom) => {
+                custom.send_embedding_request(model_name, texts, db_manager).await
+            }
+        }
+    }
+
     /// Stream a prompt response
     pub async fn prompt_stream(
         &self,
@@ -154,6 +226,16 @@ impl RigProvider {
 
 /// Custom provider implementation for non-standard AI services
 impl CustomProvider {
+    /// HTTP client for this provider; local runtimes bypass the configured proxy
+    async fn http_client(&self, db_manager: &UnifiedDbManager) -> Result<reqwest::Client> {
+        let client = if local::is_local_provider(&self.provider_type) {
+            local::local_client()
+        } else {
+            get_client_with_proxy(db_manager).await
+        };
+        client.map_err(|e| anyhow!("Failed to create HTTP client: {}", e))
+    }
+
     /// Send simple prompt request (for rig-core compatibility)
     pub async fn send_request_simple(
         &self,
@@ -191,9 +273,7 @@ impl CustomProvider {
             .map_err(|e| anyhow!("Request failed: {}", e))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow!("API error {}: {}", status, error_text));
+            return Err(api_error("API error", response).await);
         }
 
         let response_json: serde_json::Value = response
@@ -211,9 +291,29 @@ impl CustomProvider {
         messages: Vec<ChatMessage>,
         db_manager: &UnifiedDbManager,
     ) -> Result<String> {
-        let client = get_client_with_proxy(db_manager).await
-            .map_err(|e| anyhow!("Failed to create HTTP client: {}", e))?;
+        self.send_request_with_usage(model_name, messages, db_manager)
+            .await
+            .map(|(content, _)| content)
+    }
 
+    /// Send non-streaming request, also returning the usage reported by the provider
+    pub async fn send_request_with_usage(
+        &self,
+        model_name: &str,
+        messages: Vec<ChatMessage>,
+        db_manager: &UnifiedDbManager,
+    ) -> Result<(String, Option<TokenUsage>)> {
+        let client = self.http_client(db_manager).await?;
+        self.send_request_with_client(&client, model_name, messages).await
+    }
+
+    /// Send non-streaming request with the given HTTP client
+    pub async fn send_request_with_client(
+        &self,
+        client: &reqwest::Client,
+        model_name: &str,
+        messages: Vec<ChatMessage>,
+    ) -> Result<(String, Option<TokenUsage>)> {
         let messages_json: Vec<serde_json::Value> = messages
             .iter()
             .map(|msg| {
@@ -247,9 +347,7 @@ impl CustomProvider {
             .map_err(|e| anyhow!("Request failed: {}", e))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow!("API error {}: {}", status, error_text));
+            return Err(api_error("API error", response).await);
         }
 
         let response_json: serde_json::Value = response
@@ -257,7 +355,8 @@ impl CustomProvider {
             .await
             .map_err(|e| anyhow!("Failed to parse response: {}", e))?;
 
-        self.extract_content(&response_json)
+        let content = self.extract_content(&response_json)?;
+        Ok((content, extract_usage(&response_json)))
     }
 
     /// Send streaming request
@@ -267,9 +366,29 @@ impl CustomProvider {
         messages: Vec<ChatMessage>,
         db_manager: &UnifiedDbManager,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<String>> + Send>>> {
-        let client = get_client_with_proxy(db_manager).await
-            .map_err(|e| anyhow!("Failed to create HTTP client: {}", e))?;
+        self.send_stream_request_with_usage(model_name, messages, db_manager)
+            .await
+            .map(|(stream, _)| stream)
+    }
+
+    /// Send streaming request; the returned slot holds the usage once the provider reports it
+    pub async fn send_stream_request_with_usage(
+        &self,
+        model_name: &str,
+        messages: Vec<ChatMessage>,
+        db_manager: &UnifiedDbManager,
+    ) -> Result<(Pin<Box<dyn Stream<Item = Result<String>> + Send>>, StreamUsageSlot)> {
+        let client = self.http_client(db_manager).await?;
+        self.send_stream_request_with_client(&client, model_name, messages).await
+    }
 
+    /// Send streaming request with the given HTTP client
+    pub async fn send_stream_request_with_client(
+        &self,
+        client: &reqwest::Client,
+        model_name: &str,
+        messages: Vec<ChatMessage>,
+    ) -> Result<(Pin<Box<dyn Stream<Item = Result<String>> + Send>>, StreamUsageSlot)> {
         let messages_json: Vec<serde_json::Value> = messages
             .iter()
             .map(|msg| {
@@ -305,18 +424,27 @@ impl CustomProvider {
             .map_err(|e| anyhow!("Stream request failed: builder error - {}. URL: {}, Provider: {}", e, url, self.provider_type))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(anyhow!("Stream API error {}: {}", status, error_text));
+            return Err(api_error("Stream API error", response).await);
+        }
+
+        let usage_slot: StreamUsageSlot = Arc::new(Mutex::new(None));
+        if self.provider_type == "ollama" {
+            return Ok((Self::ndjson_stream(response, usage_slot.clone()), usage_slot));
         }
 
         let stream = response.bytes_stream();
         let provider_type = self.provider_type.clone();
+        let stream_usage = usage_slot.clone();
         
         let mapped_stream = stream.map(move |chunk_result| {
             match chunk_result {
                 Ok(bytes) => {
                     let text = String::from_utf8_lossy(&bytes);
+                    if let Some(usage) = Self::parse_sse_usage(&text) {
+                        if let Ok(mut slot) = stream_usage.lock() {
+                            *slot = Some(usage);
+                        }
+                    }
                     Self::parse_sse_chunk(&text, &provider_type)
                 }
                 Err(e) => Err(anyhow!("Stream error: {}", e)),
@@ -330,7 +458,239 @@ impl CustomProvider {
             }
         });
 
-        Ok(Box::pin(mapped_stream))
+        Ok((Box::pin(mapped_stream), usage_slot))
+    }
+
+    /// Ollama's native `/api/chat` streams newline-delimited JSON instead of SSE;
+    /// a line may be split across chunks, so incomplete lines are buffered
+    fn ndjson_stream(response: reqwest::Response, usage_slot: StreamUsageSlot) -> Pin<Box<dyn Stream<Item = Result<String>> + Send>> {
+        let stream = response
+            .bytes_stream()
+            .scan(Vec::new(), move |buffer: &mut Vec<u8>, chunk_result| {
+                let item = chunk_result
+                    .map_err(|e| anyhow!("Stream error: {}", e))
+                    .and_then(|bytes| {
+                        buffer.extend_from_slice(&bytes);
+                        let (content, usage) = Self::drain_ndjson_lines(buffer)?;
+                        if let (Some(usage), Ok(mut slot)) = (usage, usage_slot.lock()) {
+                            *slot = Some(usage);
+                        }
+                        Ok(content)
+                    });
+                futures::future::ready(Some(item))
+            })
+            .filter_map(|result| async move {
+                match result {
+                    Ok(content) if content.is_empty() => None,
+                    other => Some(other),
+                }
+            });
+        Box::pin(stream)
+    }
+
+    /// Take the complete lines out of `buffer`, returning their text and the usage from the final line
+    fn drain_ndjson_lines(buffer: &mut Vec<u8>) -> Result<(String, Option<TokenUsage>)> {
+        let mut content = String::new();
+        let mut usage = None;
+        while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=newline).collect();
+            let Ok(json) = serde_json::from_slice::<serde_json::Value>(&line) else { continue };
+            if let Some(error) = json["error"].as_str() {
+                return Err(anyhow!("Stream error: {}", error));
+            }
+            if let Some(text) = json["message"]["content"].as_str() {
+                content.push_str(text);
+            }
+            if json["done"].as_bool() == Some(true) {
+                usage = extract_usage(&json);
+            }
+        }
+        Ok((content, usage))
+    }
+
+    /// Whether the service accepts OpenAI-style `tools` in chat requests
+    pub fn supports_tools(&self) -> bool {
+        !matches!(self.provider_type.as_str(), "cohere")
+    }
+
+    /// Send a chat request with OpenAI-style tool definitions; `messages` are raw
+    /// chat messages so that assistant tool calls and tool results can be included
+    pub async fn send_tool_request(
+        &self,
+        model_name: &str,
+        messages: &[serde_json::Value],
+        tools: &[serde_json::Value],
+        db_manager: &UnifiedDbManager,
+    ) -> Result<(ToolTurn, Option<TokenUsage>)> {
+        let client = self.http_client(db_manager).await?;
+
+        let mut request_body = json!({
+            "model": model_name,
+            "messages": messages,
+            "stream": false
+        });
+        if !tools.is_empty() {
+            request_body["tools"] = json!(tools);
+        }
+
+        let mut request = client
+            .post(self.get_completion_url())
+            .header("Content-Type", "application/json");
+
+        if !self.api_key.is_empty() {
+            request = request.header("Authorization", format!("Bearer {}", self.api_key));
+        }
+
+        let response = request
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(api_error("API error", response).await);
+        }
+
+        let response_json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to parse response: {}", e))?;
+
+        Ok((Self::extract_tool_turn(&response_json)?, extract_usage(&response_json)))
+    }
+
+    fn extract_tool_turn(response: &serde_json::Value) -> Result<ToolTurn> {
+        // OpenAI format: choices[0].message; Ollama format: message
+        let message = if response["choices"][0]["message"].is_object() {
+            &response["choices"][0]["message"]
+        } else if response["message"].is_object() {
+            &response["message"]
+        } else {
+            return Err(anyhow!("Unable to extract message from response: {:?}", response));
+        };
+
+        let tool_calls = message["tool_calls"]
+            .as_array()
+            .map(|calls| {
+                calls
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, call)| {
+                        let function = &call["function"];
+                        let name = function["name"].as_str()?.to_string();
+                        // OpenAI sends arguments as a JSON string, Ollama as an object
+                        let arguments = match &function["arguments"] {
+                            serde_json::Value::String(raw) => serde_json::from_str(raw).unwrap_or(json!({})),
+                            serde_json::Value::Null => json!({}),
+                            other => other.clone(),
+                        };
+                        let id = call["id"].as_str().map(str::to_string).unwrap_or_else(|| format!("call_{}", i));
+                        Some(ToolCallRequest { id, name, arguments })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(ToolTurn {
+            content: message["content"].as_str().unwrap_or_default().to_string(),
+            tool_calls,
+        })
+    }
+
+    /// Send embedding request (OpenAI-compatible `/embeddings` or Ollama `/api/embed`)
+    pub async fn send_embedding_request(
+        &self,
+        model_name: &str,
+        texts: Vec<String>,
+        db_manager: &UnifiedDbManager,
+    ) -> Result<Vec<Vec<f32>>> {
+        let client = self.http_client(db_manager).await?;
+        self.send_embedding_request_with_client(&client, model_name, texts).await
+    }
+
+    /// Send embedding request with the given HTTP client
+    pub async fn send_embedding_request_with_client(
+        &self,
+        client: &reqwest::Client,
+        model_name: &str,
+        texts: Vec<String>,
+    ) -> Result<Vec<Vec<f32>>> {
+        let expected = texts.len();
+        let request_body = json!({
+            "model": model_name,
+            "input": texts
+        });
+
+        let mut request = client
+            .post(self.get_embedding_url())
+            .header("Content-Type", "application/json");
+
+        if !self.api_key.is_empty() {
+            request = request.header("Authorization", format!("Bearer {}", self.api_key));
+        }
+
+        let response = request
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(api_error("API error", response).await);
+        }
+
+        let response_json: serde_json::Value = response
+            .json()
+            .await
+            .map_err(|e| anyhow!("Failed to parse response: {}", e))?;
+
+        let vectors = Self::extract_embeddings(&response_json)?;
+        if vectors.len() != expected {
+            return Err(anyhow!("Expected {} embeddings, got {}", expected, vectors.len()));
+        }
+        Ok(vectors)
+    }
+
+    fn get_embedding_url(&self) -> String {
+        let endpoint = self.endpoint.trim_end_matches('/');
+        match self.provider_type.as_str() {
+            "ollama" => format!("{}/api/embed", endpoint),
+            "groq" => format!("{}/openai/v1/embeddings", endpoint),
+            "zhipu" => format!("{}/api/paas/v4/embeddings", endpoint),
+            _ => format!("{}/embeddings", endpoint),
+        }
+    }
+
+    fn extract_embeddings(response: &serde_json::Value) -> Result<Vec<Vec<f32>>> {
+        let to_vector = |value: &serde_json::Value| -> Option<Vec<f32>> {
+            value.as_array()?.iter().map(|v| v.as_f64().map(|f| f as f32)).collect()
+        };
+
+        // OpenAI format: data[].embedding, ordered by index
+        if let Some(data) = response["data"].as_array() {
+            let mut items: Vec<(u64, Vec<f32>)> = data
+                .iter()
+                .enumerate()
+                .map(|(i, item)| {
+                    let index = item["index"].as_u64().unwrap_or(i as u64);
+                    to_vector(&item["embedding"]).map(|v| (index, v))
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(|| anyhow!("Malformed embedding response"))?;
+            items.sort_by_key(|(index, _)| *index);
+            return Ok(items.into_iter().map(|(_, v)| v).collect());
+        }
+
+        // Ollama format: embeddings[]
+        if let Some(embeddings) = response["embeddings"].as_array() {
+            return embeddings
+                .iter()
+                .map(to_vector)
+                .collect::<Option<_>>()
+                .ok_or_else(|| anyhow!("Malformed embedding response"));
+        }
+
+        Err(anyhow!("Unable to extract embeddings from response: {:?}", response))
     }
 
     fn get_completion_url(&self) -> String {
@@ -375,6 +735,15 @@ impl CustomProvider {
         Err(anyhow!("Unable to extract content from response: {:?}", response))
     }
 
+    /// Find usage in SSE data lines (sent with the final chunk by most OpenAI-compatible services)
+    fn parse_sse_usage(text: &str) -> Option<TokenUsage> {
+        text.lines()
+            .filter_map(|line| line.trim().strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str::<serde_json::Value>(data).ok())
+            .filter_map(|json| extract_usage(&json))
+            .last()
+    }
+
     fn parse_sse_chunk(text: &str, _provider_type: &str) -> Result<Option<String>> {
         // Handle SSE format: data: {...}
         for line in text.lines() {
@@ -427,3 +796,40 @@ impl CustomProvider {
         Ok(None)
     }
 }
+
+/// Build the error for a non-success response; the status code and any `Retry-After`
+/// hint stay in the message so retries can be classified from the error string
+async fn api_error(prefix: &str, response: reqwest::Response) -> anyhow::Error {
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(super::resilience::parse_retry_after);
+    let error_text = response.text().await.unwrap_or_default();
+    match retry_after {
+        Some(secs) => anyhow!("{} {}: {} (retry-after: {}s)", prefix, status, error_text, secs),
+        None => anyhow!("{} {}: {}", prefix, status, error_text),
+    }
+}
+
+/// Extract token usage from a provider response, if reported
+pub fn extract_usage(response: &serde_json::Value) -> Option<TokenUsage> {
+    let pair = |prompt: &serde_json::Value, completion: &serde_json::Value| {
+        let prompt_tokens = prompt.as_i64();
+        let completion_tokens = completion.as_i64();
+        (prompt_tokens.is_some() || completion_tokens.is_some()).then(|| TokenUsage {
+            prompt_tokens: prompt_tokens.unwrap_or(0),
+            completion_tokens: completion_tokens.unwrap_or(0),
+        })
+    };
+
+    // OpenAI-compatible: usage.prompt_tokens / usage.completion_tokens
+    pair(&response["usage"]["prompt_tokens"], &response["usage"]["completion_tokens"])
+        // DashScope / Anthropic style: usage.input_tokens / usage.output_tokens
+        .or_else(|| pair(&response["usage"]["input_tokens"], &response["usage"]["output_tokens"]))
+        // Ollama: prompt_eval_count / eval_count
+        .or_else(|| pair(&response["prompt_eval_count"], &response["eval_count"]))
+        // Cohere: meta.billed_units
+        .or_else(|| pair(&response["meta"]["billed_units"]["input_tokens"], &response["meta"]["billed_units"]["output_tokens"]))
+}
@@ -4,6 +4,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tauri::{AppHandle, State};
 use crate::api::ai::rig_client::RigProvider;
+use crate::api::ai::keys;
+use crate::api::ai::local::{self, LocalModelInfo, LocalRuntime};
+use crate::api::ai::usage::{load_usage_stats, AiUsageStats};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AiServiceInfo {
@@ -16,6 +19,8 @@ pub struct AiServiceInfo {
 pub struct AiModelInfo {
     pub provider: String,
     pub models: Vec<String>,
+    #[serde(default)]
+    pub details: Vec<LocalModelInfo>, // 本地服务发现的上下文长度和能力
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -76,8 +81,12 @@ pub struct ModelInfo {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelConfig {
     pub name: String,
+    #[serde(default)]
     pub provider: String,
+    #[serde(default)]
     pub config: serde_json::Value,
+    #[serde(default)]
+    pub context_limit: Option<usize>, // 上下文窗口（token），未设置时按模型名估计
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -88,14 +97,22 @@ pub struct CustomModelConfig {
     pub model_name: String,
     pub adapter_type: String,
     pub api_key: Option<String>,
+    #[serde(default)]
+    pub context_limit: Option<usize>,
 }
 
 // 测试AI连接
 #[tauri::command]
 pub async fn test_ai_connection(
-    request: TestConnectionRequest,
+    mut request: TestConnectionRequest,
     db_manager: State<'_, UnifiedDbManager>,
 ) -> Result<TestConnectionResponse, String> {
+    // 前端回传的是脱敏密钥时使用已保存的密钥
+    if request.api_key.as_deref().is_some_and(keys::is_redacted) {
+        let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+        request.api_key = resolve_stored_key(&conn, &request).await?;
+    }
+
     match request.provider.as_str() {
         "openai" => test_openai_connection(request, db_manager).await,
         "anthropic" => test_anthropic_connection(request, db_manager).await,
@@ -105,11 +122,34 @@ pub async fn test_ai_connection(
         "doubao" => test_doubao_connection(request, db_manager).await,
         "xai" => test_xai_connection(request, db_manager).await,
         "ollama" => test_ollama_connection(request, db_manager).await,
+        "local" => test_local_connection(request).await,
         "custom" => test_custom_connection(request, db_manager).await,
         _ => Err(format!("Unsupported provider: {}", request.provider)),
     }
 }
 
+/// 按服务商（自定义模型按地址）查找已保存的密钥
+async fn resolve_stored_key(conn: &libsql::Connection, request: &TestConnectionRequest) -> Result<Option<String>, String> {
+    let key_id = if request.provider == "custom" {
+        let models = load_custom_models(conn).await?;
+        match models.iter().find(|m| Some(&m.endpoint) == request.api_base.as_ref()) {
+            Some(model) => keys::custom_key_id(&model.id),
+            None => return Ok(None),
+        }
+    } else {
+        keys::provider_key_id(&request.provider)
+    };
+    keys::get_api_key(conn, &key_id).await.map_err(|e| e.to_string())
+}
+
+pub(super) async fn load_custom_models(conn: &libsql::Connection) -> Result<Vec<CustomModelConfig>, String> {
+    match db::get_setting(conn, "custom_ai_models").await.map_err(|e| e.to_string())? {
+        Some(json) => serde_json::from_str::<Vec<CustomModelConfig>>(&json)
+            .map_err(|e| format!("Failed to parse custom models: {}", e)),
+        None => Ok(Vec::new()),
+    }
+}
+
 async fn test_openai_connection(
     request: TestConnectionRequest,
     db_manager: State<'_, UnifiedDbManager>,
@@ -430,16 +470,12 @@ pub async fn list_custom_model_configs(
 ) -> Result<Vec<CustomModelConfig>, String> {
     let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
 
-    let config_json_opt = db::get_setting(&conn, "custom_ai_models").await.map_err(|e| e.to_string())?;
-    
-    if let Some(config_json) = config_json_opt {
-        match serde_json::from_str::<Vec<CustomModelConfig>>(&config_json) {
-            Ok(models) => Ok(models),
-            Err(e) => Err(format!("Failed to parse custom models: {}", e)),
-        }
-    } else {
-        Ok(Vec::new())
+    let mut models = load_custom_models(&conn).await?;
+    // 密钥单独加密保存，这里只返回脱敏值
+    for model in &mut models {
+        model.api_key = keys::redacted_api_key(&conn, &keys::custom_key_id(&model.id)).await;
     }
+    Ok(models)
 }
 
 // 添加自定义模型配置
@@ -462,8 +498,11 @@ pub async fn add_custom_model_config(
         return Err(format!("Custom model with ID {} already exists", config.id));
     }
     
-    // 添加新配置
-    models.push(config);
+    // 添加新配置，密钥加密后单独保存
+    keys::store_submitted_key(&conn, &keys::custom_key_id(&config.id), config.api_key.as_deref())
+        .await
+        .map_err(|e| e.to_string())?;
+    models.push(CustomModelConfig { api_key: None, ..config });
     
     // 保存
     let json = serde_json::to_string(&models).map_err(|e| e.to_string())?;
@@ -489,7 +528,7 @@ pub async fn update_custom_model_config(
     let mut found = false;
     for model in &mut models {
         if model.id == config.id {
-            *model = config.clone();
+            *model = CustomModelConfig { api_key: None, ..config.clone() };
             found = true;
             break;
         }
@@ -498,6 +537,10 @@ pub async fn update_custom_model_config(
     if !found {
         return Err(format!("Custom model with ID {} not found", config.id));
     }
+
+    keys::store_submitted_key(&conn, &keys::custom_key_id(&config.id), config.api_key.as_deref())
+        .await
+        .map_err(|e| e.to_string())?;
     
     // 保存
     let json = serde_json::to_string(&models).map_err(|e| e.to_string())?;
@@ -526,6 +569,9 @@ pub async fn delete_custom_model_config(
     if models.len() == initial_len {
         return Err(format!("Custom model with ID {} not found", model_id));
     }
+    keys::set_api_key(&conn, &keys::custom_key_id(&model_id), None)
+        .await
+        .map_err(|e| e.to_string())?;
     
     // 保存
     let json = serde_json::to_string(&models).map_err(|e| e.to_string())?;
@@ -583,6 +629,26 @@ async fn test_ollama_connection(
     })
 }
 
+// 测试本地 OpenAI 兼容服务（LM Studio、llama.cpp、vLLM 等）
+async fn test_local_connection(request: TestConnectionRequest) -> Result<TestConnectionResponse, String> {
+    let runtime = LocalRuntime::OpenAiCompatible;
+    let api_base = request.api_base.unwrap_or_else(|| runtime.default_base().to_string());
+    let client = local::local_client()?;
+
+    match local::discover_models(&client, runtime, &api_base).await {
+        Ok(models) => Ok(TestConnectionResponse {
+            success: true,
+            message: format!("Local model server connection successful, found {} models", models.len()),
+            models: Some(models.into_iter().map(|m| m.name).collect()),
+        }),
+        Err(e) => Ok(TestConnectionResponse {
+            success: false,
+            message: format!("Local model server connection test failed: {}", e),
+            models: None,
+        }),
+    }
+}
+
 async fn test_custom_connection(
     request: TestConnectionRequest,
     db_manager: State<'_, UnifiedDbManager>,
@@ -748,7 +814,12 @@ pub async fn get_ai_config(
 
     if let Some(config_json) = config_json_opt {
         match serde_json::from_str::<SaveAiConfigRequest>(&config_json) {
-            Ok(config) => Ok(Some(AiConfigResponse { providers: config.providers })),
+            Ok(mut config) => {
+                for (provider_id, provider_config) in config.providers.iter_mut() {
+                    keys::redact_provider_config(&conn, provider_id, provider_config).await;
+                }
+                Ok(Some(AiConfigResponse { providers: config.providers }))
+            }
             Err(e) => Err(format!("Failed to parse AI config: {}", e)),
         }
     } else {
@@ -759,19 +830,19 @@ pub async fn get_ai_config(
 // 保存AI配置
 #[tauri::command]
 pub async fn save_ai_config(
-    config: SaveAiConfigRequest,
+    mut config: SaveAiConfigRequest,
     db_manager: State<'_, UnifiedDbManager>,
     _app: AppHandle,
 ) -> Result<(), String> {
     let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
 
     // 保存每个提供商的配置
-    for (provider_id, provider_config) in &config.providers {
-        // 保存API密钥
-        if let Some(api_key) = &provider_config.api_key {
-            let key = format!("{}_api_key", provider_id);
-            db::save_setting(&conn, &key, api_key).await.map_err(|e| e.to_string())?;
-        }
+    for (provider_id, provider_config) in config.providers.iter_mut() {
+        // 加密保存API密钥，配置JSON中不保留密钥
+        keys::store_submitted_key(&conn, &keys::provider_key_id(provider_id), provider_config.api_key.as_deref())
+            .await
+            .map_err(|e| e.to_string())?;
+        provider_config.api_key = None;
 
         // 保存API基础URL
         if let Some(api_base) = &provider_config.api_base {
@@ -801,23 +872,15 @@ pub async fn save_ai_config(
     Ok(())
 }
 
-// 获取AI使用统计
+// 获取AI使用统计（可选时间范围，毫秒）
 #[tauri::command]
 pub async fn get_ai_usage_stats(
-) -> Result<serde_json::Value, String> {
-    // 这里可以实现统计逻辑，目前返回一个示例
-    let stats = serde_json::json!({
-        "conversations": 0,
-        "messages": 0,
-        "tokens": {
-            "input": 0,
-            "output": 0,
-            "total": 0
-        },
-        "providers": {}
-    });
-
-    Ok(stats)
+    from: Option<i64>,
+    to: Option<i64>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<AiUsageStats, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    load_usage_stats(&conn, from, to).await
 }
 
 // 重新加载AI服务
@@ -0,0 +1,535 @@
+use super::rig_client::ChatMessage;
+use super::service::ModelInfo;
+use super::usage::{truncate_to_tokens, UsageContext};
+use super::{create_provider_from_config, send_message_to_provider};
+use crate::api::tips::{persist_tip, TipData};
+use crate::db::{self, operations, Tip, UnifiedDbManager};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tauri::{AppHandle, State};
+
+// 每条笔记最多建议的标签数
+const MAX_TAG_SUGGESTIONS: usize = 5;
+// 关键词匹配的最低分
+const MIN_KEYWORD_SCORE: f32 = 0.05;
+// 正文中直接出现标签名时的加分
+const LITERAL_MATCH_BOOST: f32 = 0.3;
+// 提供给模型的标签词表上限
+const MAX_VOCABULARY_FOR_LLM: usize = 200;
+// 提供给模型的正文 token 上限
+const MAX_CONTENT_TOKENS_FOR_LLM: usize = 3000;
+// 批量建议一次处理的笔记数上限
+const MAX_BATCH_SIZE: i64 = 200;
+
+const STOPWORDS: &[&str] = &[
+    "the", "and", "for", "are", "but", "not", "you", "with", "this", "that", "from", "have", "was", "were", "will", "can",
+    "all", "any", "our", "your", "its", "into", "than", "then", "them", "they", "their", "there", "what", "when", "which",
+    "who", "how", "use", "using", "also", "one", "two", "may", "more", "most", "other", "some", "such", "only", "just",
+    "的", "了", "是", "在", "和", "也", "就", "都", "而", "及", "与", "着", "或", "一个", "我们", "可以", "这个", "没有",
+];
+
+const SUGGEST_SYSTEM_PROMPT: &str = "You organize a personal knowledge base. Given a note, choose tags and a notebook for it. \
+Prefer existing tags; propose a new tag only when none fits. Nested tags use '/'. \
+Reply with only JSON: {\"tags\": [\"...\"], \"notebook_id\": \"<id or null>\"}";
+
+/// 建议来源
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SuggestionSource {
+    Llm,
+    Keyword,
+}
+
+/// 建议的标签
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagSuggestion {
+    pub name: String,
+    pub score: f32,
+    pub existing: bool, // 是否为已有标签
+}
+
+/// 建议的笔记本
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategorySuggestion {
+    pub id: String,
+    pub name: String,
+    pub score: f32,
+}
+
+/// 一条笔记的建议，供用户确认
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TipSuggestions {
+    pub tip_id: String,
+    pub title: String,
+    pub current_tags: Vec<String>,
+    pub current_category_id: Option<String>,
+    pub tags: Vec<TagSuggestion>,
+    pub category: Option<CategorySuggestion>,
+    pub source: SuggestionSource,
+}
+
+/// 用户确认后的建议
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcceptedSuggestion {
+    pub tip_id: String,
+    #[serde(default)]
+    pub tags: Vec<String>, // 追加到已有标签
+    pub category_id: Option<String>, // 为空时不移动
+}
+
+// ============ 关键词（TF-IDF）匹配 ============
+
+/// 分词：英文按单词，中日韩文字按二元组，去掉停用词
+pub fn tokenize_terms(text: &str) -> Vec<String> {
+    fn is_cjk(c: char) -> bool {
+        matches!(c as u32, 0x4E00..=0x9FFF | 0x3400..=0x4DBF | 0x3040..=0x30FF | 0xAC00..=0xD7AF)
+    }
+
+    let mut terms = Vec::new();
+    let mut word = String::new();
+    let mut cjk_run: Vec<char> = Vec::new();
+    let flush_word = |word: &mut String, terms: &mut Vec<String>| {
+        if word.chars().count() >= 2 && !word.chars().all(|c| c.is_ascii_digit()) && !STOPWORDS.contains(&word.as_str()) {
+            terms.push(word.clone());
+        }
+        word.clear();
+    };
+    let flush_cjk = |run: &mut Vec<char>, terms: &mut Vec<String>| {
+        for pair in run.windows(2) {
+            let bigram: String = pair.iter().collect();
+            if !STOPWORDS.contains(&bigram.as_str()) {
+                terms.push(bigram);
+            }
+        }
+        run.clear();
+    };
+
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if is_cjk(c) {
+            flush_word(&mut word, &mut terms);
+            cjk_run.push(c);
+        } else if c.is_alphanumeric() || c == '_' || c == '-' {
+            flush_cjk(&mut cjk_run, &mut terms);
+            word.push(c);
+        } else {
+            flush_word(&mut word, &mut terms);
+            flush_cjk(&mut cjk_run, &mut terms);
+        }
+    }
+    flush_word(&mut word, &mut terms);
+    flush_cjk(&mut cjk_run, &mut terms);
+    terms
+}
+
+fn term_weights(terms: &[String], idf: &HashMap<String, f32>) -> HashMap<String, f32> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for term in terms {
+        *counts.entry(term.as_str()).or_default() += 1;
+    }
+    let mut weights: HashMap<String, f32> = counts
+        .into_iter()
+        .filter_map(|(term, count)| idf.get(term).map(|idf| (term.to_string(), (1.0 + (count as f32).ln()) * idf)))
+        .collect();
+    let norm = weights.values().map(|w| w * w).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        weights.values_mut().for_each(|w| *w /= norm);
+    }
+    weights
+}
+
+/// 每个标签或笔记本的 TF-IDF 向量，按余弦相似度排序
+pub struct LabelIndex {
+    vectors: Vec<(String, HashMap<String, f32>)>,
+    idf: HashMap<String, f32>,
+}
+
+impl LabelIndex {
+    pub fn build(documents: Vec<(String, String)>) -> Self {
+        let tokenized: Vec<(String, Vec<String>)> =
+            documents.into_iter().map(|(key, text)| (key, tokenize_terms(&text))).collect();
+        let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+        for (_, terms) in &tokenized {
+            for term in terms.iter().map(String::as_str).collect::<HashSet<_>>() {
+                *document_frequency.entry(term).or_default() += 1;
+            }
+        }
+        let total = tokenized.len() as f32;
+        let idf: HashMap<String, f32> = document_frequency
+            .into_iter()
+            .map(|(term, df)| (term.to_string(), ((total + 1.0) / (df as f32 + 1.0)).ln() + 1.0))
+            .collect();
+        let vectors = tokenized.iter().map(|(key, terms)| (key.clone(), term_weights(terms, &idf))).collect();
+        Self { vectors, idf }
+    }
+
+    pub fn rank(&self, text: &str) -> Vec<(String, f32)> {
+        let query = term_weights(&tokenize_terms(text), &self.idf);
+        let mut ranked: Vec<(String, f32)> = self
+            .vectors
+            .iter()
+            .map(|(key, vector)| {
+                let score = query.iter().filter_map(|(term, w)| vector.get(term).map(|v| v * w)).sum();
+                (key.clone(), score)
+            })
+            .filter(|(_, score)| *score > 0.0)
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+    }
+}
+
+/// 基于已有标签和笔记本的建议引擎
+pub struct SuggestionEngine {
+    tag_index: LabelIndex,
+    tag_names: Vec<String>,
+    category_index: LabelIndex,
+    categories: HashMap<String, String>, // id -> 名称
+}
+
+impl SuggestionEngine {
+    pub fn new(tag_corpus: HashMap<String, String>, category_corpus: HashMap<String, (String, String)>) -> Self {
+        // 标签名本身重复几次，提高权重
+        let tag_documents = tag_corpus
+            .iter()
+            .map(|(name, text)| (name.clone(), format!("{} {} {} {}", name, name, name, text)))
+            .collect();
+        let category_documents = category_corpus
+            .iter()
+            .map(|(id, (name, text))| (id.clone(), format!("{} {} {} {}", name, name, name, text)))
+            .collect();
+        let mut tag_names: Vec<String> = tag_corpus.into_keys().collect();
+        tag_names.sort();
+        Self {
+            tag_index: LabelIndex::build(tag_documents),
+            tag_names,
+            category_index: LabelIndex::build(category_documents),
+            categories: category_corpus.into_iter().map(|(id, (name, _))| (id, name)).collect(),
+        }
+    }
+
+    pub async fn load(conn: &libsql::Connection) -> Result<Self, String> {
+        let tags = db::load_tag_corpus(conn).await.map_err(|e| e.to_string())?;
+        let categories = db::load_category_corpus(conn).await.map_err(|e| e.to_string())?;
+        Ok(Self::new(tags, categories))
+    }
+
+    fn find_tag(&self, name: &str) -> Option<&String> {
+        self.tag_names.iter().find(|t| t.eq_ignore_ascii_case(name))
+    }
+
+    /// 关键词建议：TF-IDF 相似度，正文直接出现标签名时加分
+    pub fn suggest_keywords(&self, text: &str, current_tags: &[String]) -> (Vec<TagSuggestion>, Option<CategorySuggestion>) {
+        let lower = text.to_lowercase();
+        let mut scores: HashMap<String, f32> = self.tag_index.rank(text).into_iter().collect();
+        for name in &self.tag_names {
+            let label = name.rsplit(db::TAG_PATH_SEPARATOR).next().unwrap_or(name).to_lowercase();
+            if label.chars().count() >= 2 && lower.contains(&label) {
+                *scores.entry(name.clone()).or_default() += LITERAL_MATCH_BOOST;
+            }
+        }
+
+        let mut tags: Vec<TagSuggestion> = scores
+            .into_iter()
+            .filter(|(name, score)| *score >= MIN_KEYWORD_SCORE && !current_tags.iter().any(|t| t.eq_ignore_ascii_case(name)))
+            .map(|(name, score)| TagSuggestion { name, score, existing: true })
+            .collect();
+        tags.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+        tags.truncate(MAX_TAG_SUGGESTIONS);
+
+        let category = self
+            .category_index
+            .rank(text)
+            .into_iter()
+            .find(|(_, score)| *score >= MIN_KEYWORD_SCORE)
+            .map(|(id, score)| CategorySuggestion { name: self.categories[&id].clone(), id, score });
+        (tags, category)
+    }
+
+    /// 模型使用的标签词表和笔记本列表
+    fn llm_context(&self) -> String {
+        let tags = self.tag_names.iter().take(MAX_VOCABULARY_FOR_LLM).cloned().collect::<Vec<_>>().join(", ");
+        let mut notebooks: Vec<String> = self.categories.iter().map(|(id, name)| format!("- {}: {}", id, name)).collect();
+        notebooks.sort();
+        format!(
+            "Existing tags: {}\n\nNotebooks:\n{}",
+            if tags.is_empty() { "(none)" } else { tags.as_str() },
+            if notebooks.is_empty() { "(none)".to_string() } else { notebooks.join("\n") }
+        )
+    }
+
+    /// 解析模型返回的 JSON
+    pub fn parse_llm_reply(&self, reply: &str, current_tags: &[String]) -> Option<(Vec<TagSuggestion>, Option<CategorySuggestion>)> {
+        let json = reply.get(reply.find('{')?..=reply.rfind('}')?)?;
+        let value: serde_json::Value = serde_json::from_str(json).ok()?;
+
+        let mut tags: Vec<TagSuggestion> = Vec::new();
+        for name in value["tags"].as_array()?.iter().filter_map(|t| t.as_str()) {
+            let Some(name) = db::normalize_tag_name(name) else { continue };
+            let (name, existing) = match self.find_tag(&name) {
+                Some(existing) => (existing.clone(), true),
+                None => (name, false),
+            };
+            if current_tags.iter().any(|t| t.eq_ignore_ascii_case(&name)) || tags.iter().any(|t| t.name == name) {
+                continue;
+            }
+            tags.push(TagSuggestion { name, score: 1.0, existing });
+        }
+        tags.truncate(MAX_TAG_SUGGESTIONS);
+
+        // 模型可能返回名称而不是 ID
+        let category = value["notebook_id"].as_str().and_then(|key| {
+            self.categories
+                .iter()
+                .find(|(id, name)| *id == key || name.eq_ignore_ascii_case(key))
+                .map(|(id, name)| CategorySuggestion { id: id.clone(), name: name.clone(), score: 1.0 })
+        });
+        Some((tags, category))
+    }
+}
+
+fn tip_text(tip: &Tip) -> String {
+    format!("{}\n{}", tip.title, tip.content)
+}
+
+async fn tip_tag_names(conn: &libsql::Connection, tip_id: &str) -> Result<Vec<String>, String> {
+    Ok(operations::get_tip_tags(conn, tip_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect())
+}
+
+async fn suggest_with_llm(
+    engine: &SuggestionEngine,
+    tip: &Tip,
+    current_tags: &[String],
+    provider_id: &str,
+    db_manager: &UnifiedDbManager,
+) -> Result<(Vec<TagSuggestion>, Option<CategorySuggestion>), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let (provider, model_name) = create_provider_from_config(provider_id, &conn).await?;
+    let prompt = format!(
+        "{}\n\nCurrent tags: {}\n\nNote title: {}\n\n{}",
+        engine.llm_context(),
+        if current_tags.is_empty() { "(none)".to_string() } else { current_tags.join(", ") },
+        tip.title,
+        truncate_to_tokens(&tip.content, MAX_CONTENT_TOKENS_FOR_LLM)
+    );
+    let messages = vec![ChatMessage::system(SUGGEST_SYSTEM_PROMPT.to_string()), ChatMessage::user(prompt)];
+    let reply = send_message_to_provider(&provider, &model_name, messages, db_manager, &UsageContext::default()).await?;
+    engine
+        .parse_llm_reply(&reply, current_tags)
+        .ok_or_else(|| "Could not parse the model's suggestions".to_string())
+}
+
+/// 为一条笔记生成建议；配置了服务商时使用模型，失败或未配置时退回关键词匹配
+pub async fn suggest_for_tip(
+    engine: &SuggestionEngine,
+    tip: &Tip,
+    provider_id: Option<&str>,
+    db_manager: &UnifiedDbManager,
+) -> Result<TipSuggestions, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let current_tags = tip_tag_names(&conn, &tip.id).await?;
+
+    let llm = match provider_id {
+        Some(provider_id) => match suggest_with_llm(engine, tip, &current_tags, provider_id, db_manager).await {
+            Ok(result) => Some(result),
+            Err(e) => {
+                tracing::warn!("LLM tag suggestion failed for tip {}, using keywords: {}", tip.id, e);
+                None
+            }
+        },
+        None => None,
+    };
+    let (source, (tags, category)) = match llm {
+        Some(result) => (SuggestionSource::Llm, result),
+        None => (SuggestionSource::Keyword, engine.suggest_keywords(&tip_text(tip), &current_tags)),
+    };
+
+    Ok(TipSuggestions {
+        tip_id: tip.id.clone(),
+        title: tip.title.clone(),
+        current_tags,
+        current_category_id: tip.category_id.clone(),
+        // 已在该笔记本中时不再建议
+        category: category.filter(|c| tip.category_id.as_deref() != Some(c.id.as_str())),
+        tags,
+        source,
+    })
+}
+
+/// 设置中默认对话模型所属的服务商，未设置时返回 None（只用关键词匹配）
+pub async fn default_chat_provider(conn: &libsql::Connection) -> Result<Option<String>, String> {
+    let Some(value) = db::get_setting(conn, "default_chat_model").await.map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+    let info: ModelInfo = serde_json::from_str(&value).map_err(|e| format!("Invalid chat model setting: {}", e))?;
+    Ok(Some(info.provider))
+}
+
+/// 为未打标签的笔记批量生成建议；`since` 限定创建时间（如一次导入开始的时间）
+pub async fn suggest_for_untagged(
+    db_manager: &UnifiedDbManager,
+    provider_id: Option<&str>,
+    since: Option<i64>,
+    limit: i64,
+) -> Result<Vec<TipSuggestions>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let engine = SuggestionEngine::load(&conn).await?;
+    let ids = db::list_untagged_tip_ids(&conn, since, limit.clamp(1, MAX_BATCH_SIZE))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut suggestions = Vec::new();
+    for id in ids {
+        let Some(tip) = operations::get_tip_by_id(&conn, &id).await.map_err(|e| e.to_string())? else { continue };
+        let suggestion = suggest_for_tip(&engine, &tip, provider_id, db_manager).await?;
+        if !suggestion.tags.is_empty() || suggestion.category.is_some() {
+            suggestions.push(suggestion);
+        }
+    }
+    Ok(suggestions)
+}
+
+// ============ 命令 ============
+
+/// 为一条笔记建议标签和笔记本
+#[tauri::command(rename_all = "snake_case")]
+pub async fn suggest_tip_metadata(
+    tip_id: String,
+    provider_id: Option<String>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<TipSuggestions, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let tip = operations::get_tip_by_id(&conn, &tip_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Tip {} not found", tip_id))?;
+    if tip.is_encrypted.unwrap_or(false) {
+        return Err("Encrypted notes must be unlocked first".to_string());
+    }
+    let engine = SuggestionEngine::load(&conn).await?;
+    suggest_for_tip(&engine, &tip, provider_id.as_deref(), db_manager.inner()).await
+}
+
+/// 为所有未打标签的笔记生成建议，供用户逐条确认后调用 apply_tip_suggestions
+#[tauri::command(rename_all = "snake_case")]
+pub async fn suggest_metadata_for_untagged_tips(
+    provider_id: Option<String>,
+    limit: Option<i64>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<Vec<TipSuggestions>, String> {
+    suggest_for_untagged(db_manager.inner(), provider_id.as_deref(), None, limit.unwrap_or(50)).await
+}
+
+/// 应用用户确认的建议：追加标签、移动笔记本，并记录修订
+#[tauri::command]
+pub async fn apply_tip_suggestions(
+    items: Vec<AcceptedSuggestion>,
+    app: AppHandle,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<usize, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let mut applied = 0;
+    for item in items {
+        let Some(tip) = operations::get_tip_by_id(&conn, &item.tip_id).await.map_err(|e| e.to_string())? else { continue };
+        if tip.is_encrypted.unwrap_or(false) {
+            continue;
+        }
+        let mut tags = tip_tag_names(&conn, &tip.id).await?;
+        for tag in item.tags.iter().filter_map(|t| db::normalize_tag_name(t)) {
+            if !tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                tags.push(tag);
+            }
+        }
+        let category_id = item.category_id.or(tip.category_id);
+        persist_tip(
+            &conn,
+            TipData {
+                id: Some(tip.id),
+                title: tip.title,
+                content: tip.content,
+                tip_type: tip.tip_type.into(),
+                language: tip.language,
+                category_id,
+                tags,
+            },
+            "save",
+        )
+        .await?;
+        applied += 1;
+    }
+
+    if applied > 0 {
+        if db_manager.get_current_mode().await.supports_sync() {
+            let manager = db_manager.inner().clone();
+            tokio::spawn(async move {
+                if let Err(e) = manager.sync().await {
+                    tracing::warn!("Background sync failed after applying suggestions: {}", e);
+                }
+            });
+        }
+        crate::api::ai::embeddings::schedule_embedding_index(&app);
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn engine() -> SuggestionEngine {
+        let tags = HashMap::from([
+            ("lang/rust".to_string(), "ownership borrow checker cargo crates traits".to_string()),
+            ("cooking".to_string(), "recipe oven flour sugar bake 烘焙 面粉".to_string()),
+            ("unused".to_string(), String::new()),
+        ]);
+        let categories = HashMap::from([
+            ("c1".to_string(), ("Programming".to_string(), "cargo build compiler traits".to_string())),
+            ("c2".to_string(), ("Kitchen".to_string(), "recipe bake 烘焙".to_string())),
+        ]);
+        SuggestionEngine::new(tags, categories)
+    }
+
+    #[test]
+    fn tokenizes_words_and_cjk_bigrams() {
+        assert_eq!(tokenize_terms("The Rust borrow-checker!"), vec!["rust", "borrow-checker"]);
+        assert_eq!(tokenize_terms("烘焙面粉 42"), vec!["烘焙", "焙面", "面粉"]);
+    }
+
+    #[test]
+    fn suggests_matching_tags_and_notebook() {
+        let engine = engine();
+        let (tags, category) = engine.suggest_keywords("Fighting the borrow checker while adding cargo crates", &[]);
+        assert_eq!(tags[0].name, "lang/rust");
+        assert_eq!(category.unwrap().id, "c1");
+
+        let (tags, category) = engine.suggest_keywords("周末烘焙：面粉和糖", &[]);
+        assert_eq!(tags[0].name, "cooking");
+        assert_eq!(category.unwrap().name, "Kitchen");
+    }
+
+    #[test]
+    fn skips_current_tags_and_unrelated_text() {
+        let engine = engine();
+        let (tags, _) = engine.suggest_keywords("cargo traits", &["LANG/RUST".to_string()]);
+        assert!(tags.iter().all(|t| t.name != "lang/rust"));
+        let (tags, category) = engine.suggest_keywords("completely different topic", &[]);
+        assert!(tags.is_empty());
+        assert!(category.is_none());
+    }
+
+    #[test]
+    fn parses_llm_reply_against_vocabulary() {
+        let engine = engine();
+        let reply = "Sure:\n{\"tags\": [\"Cooking\", \"desserts / cakes\", \"lang/rust\"], \"notebook_id\": \"Kitchen\"}";
+        let (tags, category) = engine.parse_llm_reply(reply, &["lang/rust".to_string()]).unwrap();
+        assert_eq!(tags.len(), 2);
+        assert_eq!((tags[0].name.as_str(), tags[0].existing), ("cooking", true));
+        assert_eq!((tags[1].name.as_str(), tags[1].existing), ("desserts/cakes", false));
+        assert_eq!(category.unwrap().id, "c2");
+        assert!(engine.parse_llm_reply("no json here", &[]).is_none());
+    }
+}
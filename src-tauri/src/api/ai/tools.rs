@@ -0,0 +1,674 @@
+use super::context::{load_conversation_context, ContextTarget};
+use super::conversations::insert_ai_message;
+use super::rig_client::{ChatMessage, RigProvider, ToolCallRequest};
+use super::roles::get_ai_role_internal;
+use super::usage::{enforce_budget, estimate_tokens, UsageCall, UsageContext};
+use super::{create_provider_from_config, send_message_to_provider, STREAM_CANCEL_MAP};
+use crate::api::tips::{persist_tip, TipData};
+use crate::db::{self, operations, UnifiedDbManager};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::{oneshot, Mutex as TokioMutex};
+use uuid::Uuid;
+
+// 默认最多调用工具的轮数
+const DEFAULT_MAX_ITERATIONS: usize = 5;
+const MAX_ITERATIONS_LIMIT: usize = 20;
+// 等待用户确认写操作的时间
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(120);
+// 工具返回给模型的正文长度上限
+const MAX_TOOL_CONTENT_CHARS: usize = 8000;
+
+const TOOL_SYSTEM_PROMPT: &str = "You can use tools to search, read and create the user's notes. \
+Use them when the question is about the user's notes, then answer based on the results.";
+
+const TEXT_PROTOCOL_PROMPT: &str = "To call a tool, reply with only a fenced block of the form\n\
+```tool\n{\"tool\": \"<name>\", \"arguments\": {...}}\n```\n\
+and wait for the result. Call one tool at a time. When you have enough information, answer normally without a tool block.";
+
+lazy_static::lazy_static! {
+    // 等待前端确认的写操作
+    static ref PENDING_CONFIRMATIONS: TokioMutex<HashMap<String, oneshot::Sender<bool>>> = TokioMutex::new(HashMap::new());
+}
+
+/// 工具定义
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: Value, // JSON Schema
+    pub writes: bool,      // 写操作需用户确认
+}
+
+impl ToolDefinition {
+    fn new(name: &str, description: &str, parameters: Value, writes: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters,
+            writes,
+        }
+    }
+
+    /// OpenAI 格式的工具描述
+    fn to_openai(&self) -> Value {
+        json!({
+            "type": "function",
+            "function": {
+                "name": self.name,
+                "description": self.description,
+                "parameters": self.parameters,
+            }
+        })
+    }
+}
+
+/// 可用工具
+pub fn tool_definitions() -> Vec<ToolDefinition> {
+    vec![
+        ToolDefinition::new(
+            "search_tips",
+            "Full-text search the user's notes. Returns ids, titles and short previews.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "Search keywords" },
+                    "limit": { "type": "integer", "description": "Maximum results (default 10)" }
+                },
+                "required": ["query"]
+            }),
+            false,
+        ),
+        ToolDefinition::new(
+            "get_tip",
+            "Read a note by id, including its content and tags.",
+            json!({
+                "type": "object",
+                "properties": { "id": { "type": "string" } },
+                "required": ["id"]
+            }),
+            false,
+        ),
+        ToolDefinition::new(
+            "list_notebooks",
+            "List the user's notebooks (categories) with ids and parent ids.",
+            json!({ "type": "object", "properties": {} }),
+            false,
+        ),
+        ToolDefinition::new(
+            "create_tip",
+            "Create a new markdown note.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "title": { "type": "string" },
+                    "content": { "type": "string", "description": "Markdown content" },
+                    "notebook_id": { "type": "string", "description": "Optional notebook id" },
+                    "tags": { "type": "array", "items": { "type": "string" } }
+                },
+                "required": ["title", "content"]
+            }),
+            true,
+        ),
+        ToolDefinition::new(
+            "add_tag",
+            "Add a tag to an existing note. Nested tags use '/', e.g. lang/rust.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "tip_id": { "type": "string" },
+                    "tag": { "type": "string" }
+                },
+                "required": ["tip_id", "tag"]
+            }),
+            true,
+        ),
+    ]
+}
+
+/// 工具调用选项
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ToolChatOptions {
+    pub max_iterations: Option<usize>,
+    #[serde(default)]
+    pub auto_approve_writes: bool,
+    pub tools: Option<Vec<String>>, // 限定可用工具，默认全部
+}
+
+/// 工具调用状态
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCallStatus {
+    Ok,
+    Error,
+    Rejected,
+}
+
+/// 会话中保存的工具调用记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallRecord {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+    pub result: Value,
+    pub status: ToolCallStatus,
+    pub iteration: usize,
+}
+
+/// 工具对话结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolChatResult {
+    pub reply: String,
+    pub tool_calls: Vec<ToolCallRecord>,
+    pub iterations: usize,
+    pub hit_iteration_cap: bool,
+    pub message_id: Option<String>,
+}
+
+// ============ 纯函数 ============
+
+/// 解析文本协议中的工具调用（```tool 代码块或仅包含 JSON 的回复）
+pub fn parse_text_tool_call(reply: &str) -> Option<ToolCallRequest> {
+    let trimmed = reply.trim();
+    let body = match trimmed.find("```tool") {
+        Some(start) => {
+            let rest = &trimmed[start + "```tool".len()..];
+            &rest[..rest.find("```")?]
+        }
+        None if trimmed.starts_with('{') && trimmed.ends_with('}') => trimmed,
+        None => return None,
+    };
+    let value: Value = serde_json::from_str(body.trim()).ok()?;
+    let name = value["tool"].as_str()?.to_string();
+    let arguments = match &value["arguments"] {
+        Value::Null => json!({}),
+        arguments => arguments.clone(),
+    };
+    Some(ToolCallRequest {
+        id: format!("call_{}", Uuid::new_v4().simple()),
+        name,
+        arguments,
+    })
+}
+
+fn truncate_chars(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((index, _)) => format!("{}…", &text[..index]),
+        None => text.to_string(),
+    }
+}
+
+fn string_arg<'a>(arguments: &'a Value, key: &str) -> Result<&'a str, String> {
+    arguments[key]
+        .as_str()
+        .filter(|s| !s.trim().is_empty())
+        .ok_or_else(|| format!("Missing argument '{}'", key))
+}
+
+/// 写操作确认时给用户看的摘要
+fn describe_call(call: &ToolCallRequest) -> String {
+    match call.name.as_str() {
+        "create_tip" => format!("Create note \"{}\"", call.arguments["title"].as_str().unwrap_or_default()),
+        "add_tag" => format!(
+            "Add tag \"{}\" to note {}",
+            call.arguments["tag"].as_str().unwrap_or_default(),
+            call.arguments["tip_id"].as_str().unwrap_or_default()
+        ),
+        name => format!("Run {}", name),
+    }
+}
+
+// ============ 工具执行 ============
+
+async fn execute_tool(app: &AppHandle, conn: &libsql::Connection, call: &ToolCallRequest) -> Result<Value, String> {
+    let args = &call.arguments;
+    match call.name.as_str() {
+        "search_tips" => {
+            let query = string_arg(args, "query")?;
+            let limit = args["limit"].as_i64().unwrap_or(10).clamp(1, 50) as i32;
+            let results = db::search_tips_summary_fast(conn, query, limit).await.map_err(|e| e.to_string())?;
+            Ok(json!(results
+                .into_iter()
+                .map(|tip| json!({
+                    "id": tip.id,
+                    "title": tip.title,
+                    "tags": tip.tags.iter().map(|t| t.name.clone()).collect::<Vec<_>>(),
+                    "preview": if tip.is_encrypted { None } else { tip.content.map(|c| truncate_chars(&c, 300)) },
+                    "updated_at": tip.updated_at,
+                }))
+                .collect::<Vec<_>>()))
+        }
+        "get_tip" => {
+            let id = string_arg(args, "id")?;
+            let tip = operations::get_tip_by_id(conn, id)
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("Note {} not found", id))?;
+            if tip.is_encrypted.unwrap_or(false) {
+                return Err("Note is encrypted".to_string());
+            }
+            let tags = operations::get_tip_tags(conn, id).await.map_err(|e| e.to_string())?;
+            Ok(json!({
+                "id": tip.id,
+                "title": tip.title,
+                "content": truncate_chars(&tip.content, MAX_TOOL_CONTENT_CHARS),
+                "notebook_id": tip.category_id,
+                "tags": tags.into_iter().map(|t| t.name).collect::<Vec<_>>(),
+                "updated_at": tip.updated_at,
+            }))
+        }
+        "list_notebooks" => {
+            let categories = operations::list_categories(conn).await.map_err(|e| e.to_string())?;
+            Ok(json!(categories
+                .into_iter()
+                .map(|c| json!({ "id": c.id, "name": c.name, "parent_id": c.parent_id }))
+                .collect::<Vec<_>>()))
+        }
+        "create_tip" => {
+            let title = string_arg(args, "title")?.to_string();
+            let content = args["content"].as_str().unwrap_or_default().to_string();
+            let category_id = args["notebook_id"].as_str().filter(|s| !s.is_empty()).map(str::to_string);
+            if let Some(category_id) = &category_id {
+                operations::get_category_by_id(conn, category_id)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .ok_or_else(|| format!("Notebook {} not found", category_id))?;
+            }
+            let tags = args["tags"]
+                .as_array()
+                .map(|tags| tags.iter().filter_map(|t| t.as_str().map(str::to_string)).collect())
+                .unwrap_or_default();
+            let tip = persist_tip(
+                conn,
+                TipData {
+                    id: None,
+                    title,
+                    content,
+                    tip_type: "markdown".to_string(),
+                    language: None,
+                    category_id,
+                    tags,
+                },
+                "save",
+            )
+            .await?;
+            crate::api::ai::embeddings::schedule_embedding_index(app);
+            Ok(json!({ "id": tip.id, "title": tip.title }))
+        }
+        "add_tag" => {
+            let tip_id = string_arg(args, "tip_id")?;
+            let tag = string_arg(args, "tag")?.to_string();
+            if operations::get_tip_by_id(conn, tip_id).await.map_err(|e| e.to_string())?.is_none() {
+                return Err(format!("Note {} not found", tip_id));
+            }
+            let mut names: Vec<String> = operations::get_tip_tags(conn, tip_id)
+                .await
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .map(|t| t.name)
+                .collect();
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(&tag)) {
+                names.push(tag);
+            }
+            operations::set_tip_tags(conn, tip_id, &names).await.map_err(|e| e.to_string())?;
+            Ok(json!({ "id": tip_id, "tags": names }))
+        }
+        name => Err(format!("Unknown tool '{}'", name)),
+    }
+}
+
+/// 发送确认事件并等待前端答复，超时视为拒绝
+async fn request_confirmation(app: &AppHandle, request_id: &str, call: &ToolCallRequest) -> bool {
+    let confirmation_id = Uuid::new_v4().to_string();
+    let (tx, rx) = oneshot::channel();
+    PENDING_CONFIRMATIONS.lock().await.insert(confirmation_id.clone(), tx);
+
+    app.emit(
+        "ai-tool-confirm",
+        json!({
+            "id": request_id,
+            "confirmation_id": confirmation_id,
+            "name": call.name,
+            "arguments": call.arguments,
+            "summary": describe_call(call),
+        }),
+    )
+    .ok();
+
+    let approved = matches!(tokio::time::timeout(CONFIRM_TIMEOUT, rx).await, Ok(Ok(true)));
+    PENDING_CONFIRMATIONS.lock().await.remove(&confirmation_id);
+    approved
+}
+
+/// 执行一次工具调用（写操作先确认），返回调用记录
+async fn run_tool_call(
+    app: &AppHandle,
+    conn: &libsql::Connection,
+    request_id: &str,
+    call: ToolCallRequest,
+    definitions: &[ToolDefinition],
+    options: &ToolChatOptions,
+    iteration: usize,
+) -> ToolCallRecord {
+    let (status, result) = match definitions.iter().find(|d| d.name == call.name) {
+        None => (ToolCallStatus::Error, json!({ "error": format!("Tool '{}' is not available", call.name) })),
+        Some(definition) => {
+            let approved = !definition.writes || options.auto_approve_writes || request_confirmation(app, request_id, &call).await;
+            if !approved {
+                (ToolCallStatus::Rejected, json!({ "error": "The user declined this action" }))
+            } else {
+                match execute_tool(app, conn, &call).await {
+                    Ok(value) => (ToolCallStatus::Ok, value),
+                    Err(e) => (ToolCallStatus::Error, json!({ "error": e })),
+                }
+            }
+        }
+    };
+
+    let record = ToolCallRecord {
+        id: call.id,
+        name: call.name,
+        arguments: call.arguments,
+        result,
+        status,
+        iteration,
+    };
+    app.emit("ai-tool-call", json!({ "id": request_id, "call": record })).ok();
+    record
+}
+
+// ============ 对话循环 ============
+
+struct ToolLoop<'a> {
+    app: &'a AppHandle,
+    conn: &'a libsql::Connection,
+    db_manager: &'a UnifiedDbManager,
+    provider: &'a RigProvider,
+    model_name: &'a str,
+    request_id: &'a str,
+    usage: UsageContext,
+    definitions: Vec<ToolDefinition>,
+    options: ToolChatOptions,
+    should_cancel: Arc<AtomicBool>,
+}
+
+impl ToolLoop<'_> {
+    fn max_iterations(&self) -> usize {
+        self.options.max_iterations.unwrap_or(DEFAULT_MAX_ITERATIONS).clamp(1, MAX_ITERATIONS_LIMIT)
+    }
+
+    fn cancelled(&self) -> bool {
+        self.should_cancel.load(Ordering::SeqCst)
+    }
+
+    /// 原生工具调用（OpenAI 兼容接口）
+    async fn run_native(&self, history: Vec<ChatMessage>) -> Result<ToolChatResult, String> {
+        let RigProvider::Custom(custom) = self.provider else {
+            return Err("Native tool calling requires an OpenAI-compatible provider".to_string());
+        };
+        let tools: Vec<Value> = self.definitions.iter().map(ToolDefinition::to_openai).collect();
+        let mut messages: Vec<Value> = history.iter().map(|m| json!({ "role": m.role, "content": m.content })).collect();
+        let mut records = Vec::new();
+        let max_iterations = self.max_iterations();
+
+        for iteration in 1..=max_iterations + 1 {
+            // 达到上限后不再提供工具，要求直接回答
+            let offered: &[Value] = if iteration > max_iterations { &[] } else { &tools };
+            enforce_budget(self.conn, self.provider.provider_name()).await?;
+            let prompt_tokens = messages.iter().map(|m| estimate_tokens(&m.to_string())).sum();
+            let call = UsageCall::start(self.provider.provider_name(), self.model_name, "tool", &self.usage, prompt_tokens);
+            let turn = match custom.send_tool_request(self.model_name, &messages, offered, self.db_manager).await {
+                Ok((turn, reported)) => {
+                    call.finish(self.db_manager, reported, &turn.content, None).await;
+                    turn
+                }
+                Err(e) => {
+                    call.finish(self.db_manager, None, "", Some(e.to_string())).await;
+                    return Err(e.to_string());
+                }
+            };
+
+            if turn.tool_calls.is_empty() || iteration > max_iterations || self.cancelled() {
+                return Ok(ToolChatResult {
+                    reply: turn.content,
+                    tool_calls: records,
+                    iterations: iteration,
+                    hit_iteration_cap: iteration > max_iterations,
+                    message_id: None,
+                });
+            }
+
+            messages.push(json!({
+                "role": "assistant",
+                "content": turn.content,
+                "tool_calls": turn.tool_calls.iter().map(|c| json!({
+                    "id": c.id,
+                    "type": "function",
+                    "function": { "name": c.name, "arguments": c.arguments.to_string() },
+                })).collect::<Vec<_>>(),
+            }));
+            for tool_call in turn.tool_calls {
+                let record = run_tool_call(self.app, self.conn, self.request_id, tool_call, &self.definitions, &self.options, iteration).await;
+                messages.push(json!({
+                    "role": "tool",
+                    "tool_call_id": record.id,
+                    "name": record.name,
+                    "content": record.result.to_string(),
+                }));
+                records.push(record);
+            }
+        }
+        unreachable!("the last iteration always returns")
+    }
+
+    /// 文本协议：在提示词中描述工具，解析回复中的 ```tool 代码块
+    async fn run_text(&self, mut history: Vec<ChatMessage>) -> Result<ToolChatResult, String> {
+        let tool_list = self
+            .definitions
+            .iter()
+            .map(|d| format!("- {}: {} Parameters: {}", d.name, d.description, d.parameters))
+            .collect::<Vec<_>>()
+            .join("\n");
+        history.insert(0, ChatMessage::system(format!("{}\n\nTools:\n{}", TEXT_PROTOCOL_PROMPT, tool_list)));
+
+        let mut records = Vec::new();
+        let max_iterations = self.max_iterations();
+        for iteration in 1..=max_iterations + 1 {
+            if iteration > max_iterations {
+                history.push(ChatMessage::user("Tool limit reached. Answer now without calling tools.".to_string()));
+            }
+            let reply = send_message_to_provider(self.provider, self.model_name, history.clone(), self.db_manager, &self.usage).await?;
+
+            let tool_call = if iteration > max_iterations { None } else { parse_text_tool_call(&reply) };
+            let Some(tool_call) = tool_call.filter(|_| !self.cancelled()) else {
+                return Ok(ToolChatResult {
+                    reply,
+                    tool_calls: records,
+                    iterations: iteration,
+                    hit_iteration_cap: iteration > max_iterations,
+                    message_id: None,
+                });
+            };
+
+            history.push(ChatMessage::assistant(reply));
+            let record = run_tool_call(self.app, self.conn, self.request_id, tool_call, &self.definitions, &self.options, iteration).await;
+            history.push(ChatMessage::user(format!("Result of {}: {}", record.name, record.result)));
+            records.push(record);
+        }
+        unreachable!("the last iteration does not parse tool calls")
+    }
+}
+
+// ============ 命令 ============
+
+/// 可用工具列表
+#[tauri::command]
+pub async fn list_ai_tools() -> Result<Vec<ToolDefinition>, String> {
+    Ok(tool_definitions())
+}
+
+/// 前端答复写操作确认
+#[tauri::command(rename_all = "snake_case")]
+pub async fn confirm_ai_tool_call(confirmation_id: String, approved: bool) -> Result<(), String> {
+    let sender = PENDING_CONFIRMATIONS
+        .lock()
+        .await
+        .remove(&confirmation_id)
+        .ok_or_else(|| "Confirmation request not found or expired".to_string())?;
+    sender.send(approved).ok();
+    Ok(())
+}
+
+/// 带工具调用的对话；request_id 可用于 cancel_ai_stream 取消，写操作通过 ai-tool-confirm 事件确认
+#[tauri::command(rename_all = "snake_case")]
+#[allow(clippy::too_many_arguments)]
+pub async fn send_ai_message_with_tools(
+    app: AppHandle,
+    request_id: String,
+    message: String,
+    provider_id: String,
+    role_id: Option<String>,
+    conversation_id: Option<String>,
+    options: Option<ToolChatOptions>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<ToolChatResult, String> {
+    let should_cancel = Arc::new(AtomicBool::new(false));
+    STREAM_CANCEL_MAP.lock().await.insert(request_id.clone(), should_cancel.clone());
+
+    let result = run_tool_chat(
+        &app,
+        &request_id,
+        &message,
+        &provider_id,
+        role_id,
+        conversation_id,
+        options.unwrap_or_default(),
+        db_manager.inner(),
+        should_cancel,
+    )
+    .await;
+
+    STREAM_CANCEL_MAP.lock().await.remove(&request_id);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_tool_chat(
+    app: &AppHandle,
+    request_id: &str,
+    message: &str,
+    provider_id: &str,
+    role_id: Option<String>,
+    conversation_id: Option<String>,
+    options: ToolChatOptions,
+    db_manager: &UnifiedDbManager,
+    should_cancel: Arc<AtomicBool>,
+) -> Result<ToolChatResult, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let (provider, model_name) = create_provider_from_config(provider_id, &conn).await?;
+
+    let mut system_content = TOOL_SYSTEM_PROMPT.to_string();
+    if let Some(role_id) = role_id.clone() {
+        if let Ok(role) = get_ai_role_internal(role_id, db_manager.clone()).await {
+            let description = role.description.unwrap_or_default();
+            if !description.trim().is_empty() {
+                system_content = format!("{}\n\n{}", description, system_content);
+            }
+        }
+    }
+
+    let definitions: Vec<ToolDefinition> = match &options.tools {
+        Some(names) => tool_definitions().into_iter().filter(|d| names.contains(&d.name)).collect(),
+        None => tool_definitions(),
+    };
+    let usage = UsageContext::new(role_id, conversation_id.clone());
+
+    // 工具描述和工具结果也占用上下文，一并计入固定部分
+    let tool_tokens = definitions.iter().map(|d| estimate_tokens(&d.to_openai().to_string())).sum::<usize>()
+        + MAX_TOOL_CONTENT_CHARS / 4;
+    let target = ContextTarget {
+        provider_id,
+        provider: &provider,
+        model_name: &model_name,
+        fixed_tokens: estimate_tokens(&system_content) + estimate_tokens(message) + tool_tokens,
+    };
+    let mut history = vec![ChatMessage::system(system_content)];
+    if let Some(conv_id) = &conversation_id {
+        for m in load_conversation_context(conv_id, &target, db_manager, &usage).await.unwrap_or_default() {
+            history.push(match m.role.as_str() {
+                "assistant" => ChatMessage::assistant(m.content),
+                "system" => ChatMessage::system(m.content),
+                _ => ChatMessage::user(m.content),
+            });
+        }
+    }
+    history.push(ChatMessage::user(message.to_string()));
+
+    let tool_loop = ToolLoop {
+        app,
+        conn: &conn,
+        db_manager,
+        provider: &provider,
+        model_name: &model_name,
+        request_id,
+        usage,
+        definitions,
+        options,
+        should_cancel,
+    };
+
+    let native = matches!(&provider, RigProvider::Custom(custom) if custom.supports_tools());
+    let mut result = if native { tool_loop.run_native(history).await? } else { tool_loop.run_text(history).await? };
+
+    // 问题和带工具调用记录的回答写入会话
+    if let Some(conv_id) = &conversation_id {
+        insert_ai_message(&conn, conv_id, "user", message, &[], &[]).await?;
+        let assistant = insert_ai_message(&conn, conv_id, "assistant", &result.reply, &[], &result.tool_calls).await?;
+        result.message_id = Some(assistant.id);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fenced_tool_call() {
+        let reply = "Let me look.\n```tool\n{\"tool\": \"search_tips\", \"arguments\": {\"query\": \"rust\"}}\n```";
+        let call = parse_text_tool_call(reply).unwrap();
+        assert_eq!(call.name, "search_tips");
+        assert_eq!(call.arguments["query"], "rust");
+    }
+
+    #[test]
+    fn parses_bare_json_tool_call() {
+        let call = parse_text_tool_call("{\"tool\": \"list_notebooks\"}").unwrap();
+        assert_eq!(call.name, "list_notebooks");
+        assert_eq!(call.arguments, json!({}));
+    }
+
+    #[test]
+    fn ignores_plain_answers() {
+        assert!(parse_text_tool_call("Your notes mention {braces} but no tool").is_none());
+        assert!(parse_text_tool_call("{\"answer\": 42}").is_none());
+        assert!(parse_text_tool_call("```tool\nnot json\n```").is_none());
+    }
+
+    #[test]
+    fn write_tools_are_marked() {
+        let writes: Vec<String> = tool_definitions().into_iter().filter(|d| d.writes).map(|d| d.name).collect();
+        assert_eq!(writes, vec!["create_tip", "add_tag"]);
+    }
+
+    #[test]
+    fn truncates_on_char_boundary() {
+        assert_eq!(truncate_chars("你好世界", 2), "你好…");
+        assert_eq!(truncate_chars("abc", 5), "abc");
+    }
+}
@@ -0,0 +1,355 @@
+use super::rig_client::{ChatMessage, StreamUsageSlot, TokenUsage};
+use crate::db::{self, AiBudget, AiUsageRecord, BudgetStatus, ModelPrice, UnifiedDbManager, UsageBucket, UsageGroupBy};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tauri::State;
+
+type TextStream = Pin<Box<dyn Stream<Item = anyhow::Result<String>> + Send>>;
+
+/// 粗略估算 token 数：ASCII 约4字符1个，其他字符（如中文）各算1个
+pub fn estimate_tokens(text: &str) -> usize {
+    let (ascii, other) = text.chars().fold((0, 0), |(a, o), c| if c.is_ascii() { (a + 1, o) } else { (a, o + 1) });
+    ascii.div_ceil(4) + other
+}
+
+/// 截取不超过 token 预算的前缀
+pub fn truncate_to_tokens(text: &str, max_tokens: usize) -> &str {
+    let (mut ascii, mut other) = (0usize, 0usize);
+    for (byte_index, c) in text.char_indices() {
+        if c.is_ascii() { ascii += 1 } else { other += 1 }
+        if ascii.div_ceil(4) + other > max_tokens {
+            return &text[..byte_index];
+        }
+    }
+    text
+}
+
+/// 估算消息列表的 token 数，每条消息额外计入角色等开销
+pub fn estimate_messages_tokens(messages: &[ChatMessage]) -> usize {
+    messages.iter().map(|m| estimate_tokens(&m.content) + 4).sum()
+}
+
+/// 调用归属的角色和会话
+#[derive(Debug, Clone, Default)]
+pub struct UsageContext {
+    pub role_id: Option<String>,
+    pub conversation_id: Option<String>,
+}
+
+impl UsageContext {
+    pub fn new(role_id: Option<String>, conversation_id: Option<String>) -> Self {
+        Self { role_id, conversation_id }
+    }
+}
+
+/// 一次进行中的调用，结束时写入用量
+pub struct UsageCall {
+    provider: String,
+    model: String,
+    operation: &'static str,
+    context: UsageContext,
+    prompt_tokens: usize, // 估算值，服务商返回用量时不使用
+    started: Instant,
+}
+
+impl UsageCall {
+    pub fn start(provider: &str, model: &str, operation: &'static str, context: &UsageContext, prompt_tokens: usize) -> Self {
+        Self {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            operation,
+            context: context.clone(),
+            prompt_tokens,
+            started: Instant::now(),
+        }
+    }
+
+    fn into_record(self, usage: Option<TokenUsage>, completion: &str, error: Option<String>) -> AiUsageRecord {
+        let (prompt_tokens, completion_tokens) = match usage {
+            Some(usage) => (usage.prompt_tokens, usage.completion_tokens),
+            None => (self.prompt_tokens as i64, estimate_tokens(completion) as i64),
+        };
+        AiUsageRecord {
+            provider: self.provider,
+            model: self.model,
+            operation: self.operation.to_string(),
+            role_id: self.context.role_id,
+            conversation_id: self.context.conversation_id,
+            prompt_tokens,
+            completion_tokens,
+            estimated: usage.is_none(),
+            latency_ms: self.started.elapsed().as_millis() as i64,
+            error,
+        }
+    }
+
+    /// 写入用量并返回该记录；记录失败只打日志，不影响调用结果
+    pub async fn finish(
+        self,
+        db_manager: &UnifiedDbManager,
+        usage: Option<TokenUsage>,
+        completion: &str,
+        error: Option<String>,
+    ) -> AiUsageRecord {
+        let record = self.into_record(usage, completion, error);
+        let result = match db_manager.get_conn().await {
+            Ok(conn) => db::record_ai_usage(&conn, &record).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to record AI usage: {}", e);
+        }
+        record
+    }
+}
+
+/// 检查本月预算：超出且设置为阻止时返回错误，接近或超出告警线时记录警告
+pub async fn enforce_budget(conn: &libsql::Connection, provider: &str) -> Result<(), String> {
+    let statuses = db::get_budget_statuses(conn, Some(provider)).await.map_err(|e| e.to_string())?;
+    for status in &statuses {
+        if status.blocks() {
+            return Err(format!(
+                "Monthly AI budget for '{}' exceeded ({:.0}% used)",
+                status.budget.scope,
+                status.ratio * 100.0
+            ));
+        }
+        if status.warning || status.exceeded {
+            tracing::warn!(
+                "Monthly AI budget for '{}' at {:.0}%",
+                status.budget.scope,
+                status.ratio * 100.0
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 包装流式响应：累计输出，流结束或被丢弃（取消）时写入用量
+pub struct TrackedStream {
+    inner: TextStream,
+    call: Option<UsageCall>,
+    db_manager: UnifiedDbManager,
+    usage_slot: Option<StreamUsageSlot>,
+    completion: String,
+    error: Option<String>,
+}
+
+impl TrackedStream {
+    pub fn new(inner: TextStream, call: UsageCall, db_manager: UnifiedDbManager, usage_slot: Option<StreamUsageSlot>) -> Self {
+        Self {
+            inner,
+            call: Some(call),
+            db_manager,
+            usage_slot,
+            completion: String::new(),
+            error: None,
+        }
+    }
+
+    fn finish(&mut self) {
+        let Some(call) = self.call.take() else {
+            return;
+        };
+        let usage = self.usage_slot.as_ref().and_then(|slot| slot.lock().ok().and_then(|usage| *usage));
+        let completion = std::mem::take(&mut self.completion);
+        let error = self.error.take();
+        let db_manager = self.db_manager.clone();
+        tauri::async_runtime::spawn(async move {
+            call.finish(&db_manager, usage, &completion, error).await;
+        });
+    }
+}
+
+impl Stream for TrackedStream {
+    type Item = anyhow::Result<String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let polled = self.inner.as_mut().poll_next(cx);
+        match &polled {
+            Poll::Ready(Some(Ok(chunk))) => self.completion.push_str(chunk),
+            Poll::Ready(Some(Err(e))) => self.error = Some(e.to_string()),
+            Poll::Ready(None) => self.finish(),
+            Poll::Pending => {}
+        }
+        polled
+    }
+}
+
+impl Drop for TrackedStream {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+// ============ 命令 ============
+
+/// 用量汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiUsageStats {
+    pub conversations: i64,
+    pub messages: i64,
+    pub tokens: UsageTokens,
+    pub requests: i64,
+    pub errors: i64,
+    pub cost: f64,
+    pub providers: HashMap<String, UsageBucket>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageTokens {
+    pub input: i64,
+    pub output: i64,
+    pub total: i64,
+}
+
+async fn count_rows(conn: &libsql::Connection, table: &str) -> Result<i64, String> {
+    let mut rows = conn
+        .query(&format!("SELECT COUNT(*) FROM {}", table), ())
+        .await
+        .map_err(|e| e.to_string())?;
+    match rows.next().await.map_err(|e| e.to_string())? {
+        Some(row) => row.get::<i64>(0).map_err(|e| e.to_string()),
+        None => Ok(0),
+    }
+}
+
+/// 时间范围内的用量汇总（毫秒），按服务商拆分
+pub async fn load_usage_stats(
+    conn: &libsql::Connection,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<AiUsageStats, String> {
+    let totals = db::get_ai_usage_totals(conn, from, to).await.map_err(|e| e.to_string())?;
+    let providers = db::query_ai_usage(conn, UsageGroupBy::Provider, from, to)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|bucket| (bucket.key.clone(), bucket))
+        .collect();
+
+    Ok(AiUsageStats {
+        conversations: count_rows(conn, "ai_conversations").await?,
+        messages: count_rows(conn, "ai_messages").await?,
+        tokens: UsageTokens {
+            input: totals.prompt_tokens,
+            output: totals.completion_tokens,
+            total: totals.total_tokens,
+        },
+        requests: totals.requests,
+        errors: totals.errors,
+        cost: totals.cost,
+        providers,
+    })
+}
+
+/// 按天、服务商、模型、角色或会话聚合用量
+#[tauri::command(rename_all = "snake_case")]
+pub async fn query_ai_usage(
+    group_by: UsageGroupBy,
+    from: Option<i64>,
+    to: Option<i64>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<Vec<UsageBucket>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::query_ai_usage(&conn, group_by, from, to).await.map_err(|e| e.to_string())
+}
+
+/// 清空用量记录
+#[tauri::command]
+pub async fn clear_ai_usage(db_manager: State<'_, UnifiedDbManager>) -> Result<(), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::clear_ai_usage(&conn).await.map_err(|e| e.to_string())
+}
+
+/// 获取模型价格表
+#[tauri::command]
+pub async fn list_ai_model_prices(db_manager: State<'_, UnifiedDbManager>) -> Result<Vec<ModelPrice>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::list_model_prices(&conn).await.map_err(|e| e.to_string())
+}
+
+/// 设置模型价格（每百万 token）
+#[tauri::command]
+pub async fn set_ai_model_price(price: ModelPrice, db_manager: State<'_, UnifiedDbManager>) -> Result<(), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::set_model_price(&conn, &price).await.map_err(|e| e.to_string())
+}
+
+/// 删除模型价格
+#[tauri::command]
+pub async fn delete_ai_model_price(
+    provider: String,
+    model: String,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<(), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::delete_model_price(&conn, &provider, &model).await.map_err(|e| e.to_string())
+}
+
+/// 获取月度预算
+#[tauri::command]
+pub async fn list_ai_budgets(db_manager: State<'_, UnifiedDbManager>) -> Result<Vec<AiBudget>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::list_ai_budgets(&conn).await.map_err(|e| e.to_string())
+}
+
+/// 新增或更新月度预算
+#[tauri::command]
+pub async fn set_ai_budget(budget: AiBudget, db_manager: State<'_, UnifiedDbManager>) -> Result<(), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::set_ai_budget(&conn, &budget).await.map_err(|e| e.to_string())
+}
+
+/// 删除月度预算
+#[tauri::command]
+pub async fn delete_ai_budget(scope: String, db_manager: State<'_, UnifiedDbManager>) -> Result<(), String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::delete_ai_budget(&conn, &scope).await.map_err(|e| e.to_string())
+}
+
+/// 本月预算使用情况，供界面提示
+#[tauri::command]
+pub async fn get_ai_budget_status(
+    provider: Option<String>,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<Vec<BudgetStatus>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::get_budget_statuses(&conn, provider.as_deref()).await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimates_tokens() {
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens("你好ab"), 3);
+        assert_eq!(estimate_tokens(""), 0);
+    }
+
+    #[test]
+    fn truncates_to_token_budget() {
+        assert_eq!(truncate_to_tokens("你好世界", 2), "你好");
+        assert_eq!(truncate_to_tokens("abc", 10), "abc");
+    }
+
+    #[test]
+    fn record_uses_reported_usage_when_available() {
+        let context = UsageContext::new(Some("role".into()), None);
+        let call = UsageCall::start("openai", "gpt-4o", "chat", &context, 100);
+        let record = call.into_record(Some(TokenUsage { prompt_tokens: 12, completion_tokens: 7 }), "ignored", None);
+        assert_eq!((record.prompt_tokens, record.completion_tokens, record.estimated), (12, 7, false));
+        assert_eq!(record.role_id.as_deref(), Some("role"));
+
+        let call = UsageCall::start("ollama", "llama3", "stream", &context, 100);
+        let record = call.into_record(None, "abcdefgh", Some("timeout".into()));
+        assert_eq!((record.prompt_tokens, record.completion_tokens, record.estimated), (100, 2, true));
+        assert_eq!(record.error.as_deref(), Some("timeout"));
+    }
+}
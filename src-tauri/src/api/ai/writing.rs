@@ -0,0 +1,382 @@
+use super::context::{history_budget, resolve_context_limit};
+use super::rig_client::{ChatMessage, RigProvider};
+use super::usage::{estimate_tokens, truncate_to_tokens, UsageContext};
+use super::{create_provider_from_config, send_message_to_provider, stream_message_from_provider, STREAM_CANCEL_MAP};
+use crate::api::tips::{save_tip, TipData, TipWithTags};
+use crate::db::{operations, Tip, TipType, UnifiedDbManager};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+
+const WRITING_SYSTEM_PROMPT: &str = "You are a writing assistant editing the user's note. \
+Reply with only the resulting text, without explanations, quotes or code fences around the whole answer.";
+
+/// 写作操作
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WritingAction {
+    Summarize,
+    FixGrammar,
+    Translate { language: String },
+    ExplainCode,
+    Continue,
+}
+
+impl WritingAction {
+    /// 结果是替换选中内容，还是插在其后
+    fn replaces_selection(&self) -> bool {
+        matches!(self, Self::FixGrammar | Self::Translate { .. })
+    }
+
+    fn instruction(&self, language: Option<&str>) -> String {
+        match self {
+            Self::Summarize => "Summarize the following text concisely in its original language, as markdown.".to_string(),
+            Self::FixGrammar => {
+                "Fix spelling, grammar and punctuation in the following text. Keep its meaning, language, tone and markdown formatting."
+                    .to_string()
+            }
+            Self::Translate { language } => format!(
+                "Translate the following text into {}. Keep markdown formatting, code and links unchanged.",
+                language
+            ),
+            Self::ExplainCode => format!(
+                "Explain what the following {}code does, step by step, in the language of the surrounding note, as markdown.",
+                language.map(|l| format!("{} ", l)).unwrap_or_default()
+            ),
+            Self::Continue => {
+                "Continue writing the note from where the text ends. Match its language, tone and formatting. Do not repeat the existing text."
+                    .to_string()
+            }
+        }
+    }
+}
+
+/// 字符偏移表示的选中范围 [start, end)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TextRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 建议的修改：用 replacement 替换 [start, end) 的字符，start == end 时为插入
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WritingPatch {
+    pub tip_id: String,
+    pub action: WritingAction,
+    pub start: usize,
+    pub end: usize,
+    pub original: String,
+    pub replacement: String,
+    pub base_updated_at: i64, // 生成建议时笔记的更新时间
+}
+
+// ============ 纯函数 ============
+
+/// 字符偏移转为字节偏移
+fn byte_offset(text: &str, chars: usize) -> usize {
+    text.char_indices().nth(chars).map(|(i, _)| i).unwrap_or(text.len())
+}
+
+fn char_slice(text: &str, start: usize, end: usize) -> &str {
+    &text[byte_offset(text, start)..byte_offset(text, end)]
+}
+
+/// 校验选中范围，未选中时为全文
+pub fn resolve_range(content: &str, range: Option<TextRange>) -> Result<TextRange, String> {
+    let len = content.chars().count();
+    let range = range.unwrap_or(TextRange { start: 0, end: len });
+    if range.start > range.end || range.end > len {
+        return Err(format!("Invalid selection {}..{} for text of {} characters", range.start, range.end, len));
+    }
+    Ok(range)
+}
+
+/// 组装请求消息；续写时提供选中位置之前的内容（超出预算时截断），其余操作提供选中内容，超出预算时报错，
+/// 避免只发送了部分内容却替换整段选区
+pub fn build_writing_messages(
+    action: &WritingAction,
+    content: &str,
+    range: TextRange,
+    language: Option<&str>,
+    max_tokens: usize,
+) -> Result<Vec<ChatMessage>, String> {
+    let text = match action {
+        WritingAction::Continue => {
+            // 保留离续写位置最近的内容
+            let before: String = char_slice(content, 0, range.end).chars().rev().collect();
+            truncate_to_tokens(&before, max_tokens).chars().rev().collect::<String>()
+        }
+        _ => {
+            let selection = char_slice(content, range.start, range.end);
+            if truncate_to_tokens(selection, max_tokens).len() < selection.len() {
+                return Err("The selection is too long for the model's context window; select less text".to_string());
+            }
+            selection.to_string()
+        }
+    };
+    Ok(vec![
+        ChatMessage::system(WRITING_SYSTEM_PROMPT.to_string()),
+        ChatMessage::user(format!("{}\n\n{}", action.instruction(language), text)),
+    ])
+}
+
+/// 根据模型输出生成修改建议
+pub fn build_patch(tip: &Tip, action: &WritingAction, range: TextRange, output: &str) -> WritingPatch {
+    let output = output.trim_matches('\n');
+    let (start, replacement) = if action.replaces_selection() {
+        (range.start, output.to_string())
+    } else if matches!(action, WritingAction::Continue) {
+        // 续写紧接在光标后，补上必要的空格
+        let before = char_slice(&tip.content, 0, range.end);
+        let needs_space = !before.is_empty() && !before.ends_with(char::is_whitespace) && !output.starts_with(char::is_whitespace);
+        (range.end, format!("{}{}", if needs_space { " " } else { "" }, output))
+    } else {
+        (range.end, format!("\n\n{}\n", output))
+    };
+    let end = if action.replaces_selection() { range.end } else { start };
+    WritingPatch {
+        tip_id: tip.id.clone(),
+        action: action.clone(),
+        start,
+        end,
+        original: char_slice(&tip.content, start, end).to_string(),
+        replacement,
+        base_updated_at: tip.updated_at,
+    }
+}
+
+/// 应用到笔记；生成建议后笔记被修改过时报错
+pub fn apply_patch_to_tip(tip: &Tip, patch: &WritingPatch) -> Result<String, String> {
+    if tip.updated_at != patch.base_updated_at {
+        return Err("The note has changed since the suggestion was made".to_string());
+    }
+    apply_patch(&tip.content, patch)
+}
+
+/// 应用修改；目标位置的内容已变化时报错
+pub fn apply_patch(content: &str, patch: &WritingPatch) -> Result<String, String> {
+    let range = resolve_range(content, Some(TextRange { start: patch.start, end: patch.end }))
+        .map_err(|_| "The note has changed since the suggestion was made".to_string())?;
+    if char_slice(content, range.start, range.end) != patch.original {
+        return Err("The note has changed since the suggestion was made".to_string());
+    }
+    let (start, end) = (byte_offset(content, range.start), byte_offset(content, range.end));
+    Ok(format!("{}{}{}", &content[..start], patch.replacement, &content[end..]))
+}
+
+// ============ 命令 ============
+
+async fn load_editable_tip(conn: &libsql::Connection, tip_id: &str) -> Result<Tip, String> {
+    let tip = operations::get_tip_by_id(conn, tip_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("Tip {} not found", tip_id))?;
+    if tip.is_encrypted.unwrap_or(false) {
+        return Err("Encrypted notes must be unlocked first".to_string());
+    }
+    Ok(tip)
+}
+
+/// 对笔记（或选中部分）执行写作操作，流式发送 ai-stream-chunk，完成后通过 ai-writing-patch 事件返回修改建议；
+/// 可用 cancel_ai_stream 取消
+#[tauri::command(rename_all = "snake_case")]
+pub async fn run_ai_writing_action(
+    app: AppHandle,
+    stream_id: String,
+    tip_id: String,
+    action: WritingAction,
+    range: Option<TextRange>,
+    provider_id: String,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<(), String> {
+    let should_cancel = Arc::new(AtomicBool::new(false));
+    STREAM_CANCEL_MAP.lock().await.insert(stream_id.clone(), should_cancel.clone());
+
+    let db_manager = db_manager.inner().clone();
+    tauri::async_runtime::spawn(async move {
+        let result = handle_writing_stream(&app, &stream_id, &tip_id, &action, range, &provider_id, &db_manager, should_cancel).await;
+        match result {
+            Ok(Some(patch)) => {
+                app.emit("ai-writing-patch", serde_json::json!({ "id": stream_id, "patch": patch })).ok();
+            }
+            Ok(None) => {}
+            Err(e) => {
+                app.emit("ai-stream-error", serde_json::json!({ "id": stream_id, "error": e })).ok();
+            }
+        }
+        app.emit("ai-stream-chunk", serde_json::json!({ "id": stream_id, "chunk": "", "done": true })).ok();
+        STREAM_CANCEL_MAP.lock().await.remove(&stream_id);
+    });
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_writing_stream(
+    app: &AppHandle,
+    stream_id: &str,
+    tip_id: &str,
+    action: &WritingAction,
+    range: Option<TextRange>,
+    provider_id: &str,
+    db_manager: &UnifiedDbManager,
+    should_cancel: Arc<AtomicBool>,
+) -> Result<Option<WritingPatch>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let tip = load_editable_tip(&conn, tip_id).await?;
+    let range = resolve_range(&tip.content, range)?;
+    if range.start == range.end && !matches!(action, WritingAction::Continue) {
+        return Err("Select some text first".to_string());
+    }
+
+    let (provider, model_name) = create_provider_from_config(provider_id, &conn).await?;
+    let limit = resolve_context_limit(&conn, provider_id, &model_name).await;
+    let max_tokens = history_budget(limit, estimate_tokens(WRITING_SYSTEM_PROMPT) + 64);
+    let language = matches!(tip.tip_type, TipType::Code).then(|| tip.language.clone()).flatten();
+    let messages = build_writing_messages(action, &tip.content, range, language.as_deref(), max_tokens)?;
+    let usage = UsageContext::default();
+
+    // 内置 SDK 的服务商不支持流式，整段返回
+    let output = match stream_message_from_provider(&provider, &model_name, messages.clone(), db_manager, &usage).await {
+        Ok(mut stream) => {
+            let mut output = String::new();
+            while let Some(chunk) = stream.next().await {
+                if should_cancel.load(Ordering::SeqCst) {
+                    return Ok(None);
+                }
+                let chunk = chunk.map_err(|e| e.to_string())?;
+                if !chunk.is_empty() {
+                    output.push_str(&chunk);
+                    app.emit("ai-stream-chunk", serde_json::json!({ "id": stream_id, "chunk": chunk, "done": false })).ok();
+                }
+            }
+            output
+        }
+        Err(_) if !matches!(provider, RigProvider::Custom(_)) => {
+            let output = send_message_to_provider(&provider, &model_name, messages, db_manager, &usage).await?;
+            app.emit("ai-stream-chunk", serde_json::json!({ "id": stream_id, "chunk": output, "done": false })).ok();
+            output
+        }
+        Err(e) => return Err(e),
+    };
+
+    if should_cancel.load(Ordering::SeqCst) || output.trim().is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(build_patch(&tip, action, range, &output)))
+}
+
+/// 接受修改建议，通过 save_tip 保存（记录修订并触发同步）
+#[tauri::command]
+pub async fn apply_ai_writing_patch(
+    patch: WritingPatch,
+    app: AppHandle,
+    db_manager: State<'_, UnifiedDbManager>,
+) -> Result<TipWithTags, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let tip = load_editable_tip(&conn, &patch.tip_id).await?;
+    let content = apply_patch_to_tip(&tip, &patch)?;
+    let tags = operations::get_tip_tags(&conn, &tip.id)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect();
+
+    save_tip(
+        TipData {
+            id: Some(tip.id),
+            title: tip.title,
+            content,
+            tip_type: tip.tip_type.into(),
+            language: tip.language,
+            category_id: tip.category_id,
+            tags,
+        },
+        app,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tip(content: &str) -> Tip {
+        Tip {
+            id: "t".to_string(),
+            title: "T".to_string(),
+            content: content.to_string(),
+            tip_type: TipType::Markdown,
+            language: None,
+            category_id: None,
+            created_at: 0,
+            updated_at: 7,
+            version: None,
+            last_synced_at: None,
+            sync_hash: None,
+            is_encrypted: None,
+            encryption_key_id: None,
+            encrypted_content: None,
+            pinned: None,
+            favorite: None,
+            archived: None,
+            sort_key: None,
+        }
+    }
+
+    #[test]
+    fn validates_ranges_in_characters() {
+        assert_eq!(resolve_range("你好", None).unwrap(), TextRange { start: 0, end: 2 });
+        assert!(resolve_range("你好", Some(TextRange { start: 1, end: 3 })).is_err());
+        assert!(resolve_range("abc", Some(TextRange { start: 2, end: 1 })).is_err());
+    }
+
+    #[test]
+    fn replaces_selection_for_grammar_fixes() {
+        let tip = tip("我爱 rust 语言");
+        let patch = build_patch(&tip, &WritingAction::FixGrammar, TextRange { start: 3, end: 7 }, "Rust\n");
+        assert_eq!(patch.original, "rust");
+        assert_eq!(apply_patch(&tip.content, &patch).unwrap(), "我爱 Rust 语言");
+    }
+
+    #[test]
+    fn inserts_after_selection_for_summaries_and_continuation() {
+        let tip = tip("Intro. Body");
+        let patch = build_patch(&tip, &WritingAction::Summarize, TextRange { start: 0, end: 6 }, "Short.");
+        assert_eq!((patch.start, patch.end), (6, 6));
+        assert_eq!(apply_patch(&tip.content, &patch).unwrap(), "Intro.\n\nShort.\n Body");
+
+        let patch = build_patch(&tip, &WritingAction::Continue, TextRange { start: 11, end: 11 }, "continues.");
+        assert_eq!(apply_patch(&tip.content, &patch).unwrap(), "Intro. Body continues.");
+    }
+
+    #[test]
+    fn rejects_stale_patches() {
+        let patch = build_patch(&tip("hello world"), &WritingAction::FixGrammar, TextRange { start: 0, end: 5 }, "Hello");
+        assert!(apply_patch("howdy world", &patch).is_err());
+        assert!(apply_patch("hi", &patch).is_err());
+
+        let mut edited = tip("hello world");
+        edited.updated_at = 8;
+        assert!(apply_patch_to_tip(&edited, &patch).is_err());
+        assert_eq!(apply_patch_to_tip(&tip("hello world"), &patch).unwrap(), "Hello world");
+    }
+
+    #[test]
+    fn continue_keeps_text_nearest_to_cursor() {
+        let messages = build_writing_messages(&WritingAction::Continue, "aaaa bbbb cccc", TextRange { start: 14, end: 14 }, None, 2).unwrap();
+        assert!(messages[1].content.ends_with("cccc"));
+        assert!(!messages[1].content.contains("aaaa"));
+    }
+
+    #[test]
+    fn rejects_selections_over_budget() {
+        let range = TextRange { start: 0, end: 14 };
+        assert!(build_writing_messages(&WritingAction::FixGrammar, "aaaa bbbb cccc", range, None, 2).is_err());
+        let messages = build_writing_messages(&WritingAction::FixGrammar, "aaaa bbbb cccc", range, None, 10).unwrap();
+        assert!(messages[1].content.ends_with("aaaa bbbb cccc"));
+    }
+}
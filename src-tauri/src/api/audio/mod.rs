@@ -126,6 +126,7 @@ pub async fn transcribe_audio(
     audio_id: String,
     language: Option<String>,
     service: String,
+    app: AppHandle,
     db_manager: State<'_, UnifiedDbManager>,
 ) -> Result<String, String> {
     let request = TranscriptionRequest {
@@ -134,7 +135,10 @@ pub async fn transcribe_audio(
         service,
     };
     
-    transcription::transcribe_audio_file(&*db_manager, request).await
+    let text = transcription::transcribe_audio_file(&*db_manager, request).await?;
+    // 转录内容参与语义检索
+    crate::api::ai::embeddings::schedule_embedding_index(&app);
+    Ok(text)
 }
 
 /// 更新音频转录
@@ -143,9 +147,12 @@ pub async fn update_audio_transcription(
     audio_id: String,
     transcription: String,
     confidence: Option<f64>,
+    app: AppHandle,
     db_manager: State<'_, UnifiedDbManager>,
 ) -> Result<(), String> {
-    storage::update_audio_transcription(&*db_manager, &audio_id, &transcription, confidence).await
+    storage::update_audio_transcription(&*db_manager, &audio_id, &transcription, confidence).await?;
+    crate::api::ai::embeddings::schedule_embedding_index(&app);
+    Ok(())
 }
 
 /// 批量转录笔记的所有音频
@@ -154,9 +161,12 @@ pub async fn batch_transcribe_tip_audio(
     tip_id: String,
     service: String,
     language: Option<String>,
+    app: AppHandle,
     db_manager: State<'_, UnifiedDbManager>,
 ) -> Result<Vec<String>, String> {
-    transcription::batch_transcribe_audio_files(&*db_manager, &tip_id, &service, language).await
+    let results = transcription::batch_transcribe_audio_files(&*db_manager, &tip_id, &service, language).await?;
+    crate::api::ai::embeddings::schedule_embedding_index(&app);
+    Ok(results)
 }
 
 /// 获取支持的语言列表
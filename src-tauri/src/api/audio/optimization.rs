@@ -252,7 +252,7 @@ async fn get_all_audio_files(
     let conn = db_manager.get_conn().await
         .map_err(|e| format!("Database connection failed: {}", e))?;
 
-    let query = "SELECT id, tip_id, audio_id, file_name, file_format, duration, transcription, created_at, updated_at FROM tip_audio_files ORDER BY created_at DESC";
+    let query = "SELECT id, tip_id, audio_id, file_name, file_format, duration, transcription, created_at, updated_at FROM tip_audio_files WHERE deleted_at IS NULL ORDER BY created_at DESC";
     
     let mut rows = conn.query(query, ()).await
         .map_err(|e| format!("Failed to execute query: {}", e))?;
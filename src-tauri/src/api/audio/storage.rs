@@ -63,7 +63,7 @@ pub async fn get_tip_audio_files(
             "SELECT id, tip_id, audio_id, file_name, file_format, 
                     file_size, duration, transcription, transcription_confidence,
                     created_at, updated_at 
-             FROM tip_audio_files WHERE tip_id = ?1 ORDER BY created_at DESC",
+             FROM tip_audio_files WHERE tip_id = ?1 AND deleted_at IS NULL ORDER BY created_at DESC",
             params![tip_id],
         )
         .await
@@ -1,4 +1,4 @@
-use crate::db::{UnifiedDbManager, models::Category, operations};
+use crate::db::{self, UnifiedDbManager, models::Category, operations, SiblingPosition};
 use tauri::{AppHandle, Manager};
 use chrono::Utc;
 use uuid::Uuid;
@@ -60,6 +60,7 @@ pub async fn create_category(
         sync_hash: None,
         is_encrypted: Some(false),
         encryption_key_id: None,
+        sort_key: None,
     };
     
     operations::create_category(&conn, &category).await.map_err(|e| e.to_string())?;
@@ -67,7 +68,9 @@ pub async fn create_category(
     // 触发后台同步（如果在嵌入式副本模式下）
     trigger_background_sync_if_needed(&app).await;
     
-    Ok(category)
+    // 重新读取以带上数据库分配的排序键
+    let created = operations::get_category_by_id(&conn, &category.id).await.map_err(|e| e.to_string())?;
+    Ok(created.unwrap_or(category))
 }
 
 // 更新分类
@@ -110,9 +113,10 @@ pub async fn delete_category(
     let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
     
     match operations::delete_category(&conn, &id).await {
-        Ok(_) => {
+        Ok(change) => {
             println!("API: Successfully deleted category with id: {}", id);
             tracing::info!("API: Successfully deleted category with id: {}", id);
+            crate::api::trash::mark_trash_change_for_sync(&conn, &change, db::SyncOperation::Delete).await;
             
             // 触发后台同步（如果在嵌入式副本模式下）
             trigger_background_sync_if_needed(&app).await;
@@ -126,3 +130,26 @@ pub async fn delete_category(
         }
     }
 }
+
+// 移动分类到新的父分类下（parent_id 为空表示根目录），position 缺省时放到最后
+#[tauri::command(rename_all = "snake_case")]
+pub async fn move_category(
+    id: String,
+    parent_id: Option<String>,
+    position: Option<SiblingPosition>,
+    app: AppHandle,
+) -> Result<Category, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    db::move_category(&conn, &id, parent_id.as_deref(), &position.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    trigger_background_sync_if_needed(&app).await;
+
+    operations::get_category_by_id(&conn, &id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Category not found".to_string())
+}
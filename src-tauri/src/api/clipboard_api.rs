@@ -292,6 +292,10 @@ pub async fn create_note_from_history(
         is_encrypted: Some(false),
         encryption_key_id: None,
         encrypted_content: None,
+        pinned: None,
+        favorite: None,
+        archived: None,
+        sort_key: None,
     };
 
     operations::create_tip(&conn, &tip).await
@@ -3,7 +3,7 @@ use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
-use tauri::{command, AppHandle, Manager, State};
+use tauri::{command, AppHandle, Emitter, Manager, State};
 use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 
 use crate::db::UnifiedDbManager;
@@ -56,7 +56,7 @@ pub async fn get_database_info(
     
     // 获取笔记数量
     let note_count: i64 = {
-        let mut rows = conn.query("SELECT COUNT(*) FROM tips", ()).await.map_err(|e| e.to_string())?;
+        let mut rows = conn.query("SELECT COUNT(*) FROM tips WHERE deleted_at IS NULL", ()).await.map_err(|e| e.to_string())?;
         if let Some(row) = rows.next().await.map_err(|e| e.to_string())? {
             row.get::<i64>(0).map_err(|e| e.to_string())?
         } else {
@@ -415,6 +415,24 @@ pub async fn set_sync_mode(
         .map_err(|e| format!("Failed to save sync config: {}", e))
 }
 
+/// 将同步中三方合并留下的文本冲突转发给前端（sync-merge-conflicts 事件）
+pub fn start_merge_conflict_forwarder(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut notices = crate::sync::subscribe_merge_conflicts();
+        loop {
+            match notices.recv().await {
+                Ok(notice) => {
+                    app_handle.emit("sync-merge-conflicts", &notice).ok();
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Dropped {} merge conflict notices", skipped);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
 /// 使用LibSQL进行安全同步
 #[command]
 pub async fn manual_sync_libsql(db_manager: State<'_, UnifiedDbManager>) -> Result<serde_json::Value, String> {
@@ -737,7 +755,7 @@ async fn create_empty_database(path: &PathBuf) -> Result<()> {
     let conn = db.connect()?;
 
     // 创建表结构
-    crate::db::operations::create_all_tables(&conn).await?;
+    crate::db::operations::create_local_tables(&conn).await?;
 
     // 插入默认分类 - 使用 NULL 而不是空字符串避免外键约束问题
     let default_categories = vec![
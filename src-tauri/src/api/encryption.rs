@@ -33,26 +33,26 @@ struct EncryptionMetadata {
 
 // 密钥派生参数
 const PBKDF2_ITERATIONS: u32 = 100_000;
-const SALT_LENGTH: usize = 32;
-const KEY_LENGTH: usize = 32;
-const NONCE_LENGTH: usize = 12;
+pub(crate) const SALT_LENGTH: usize = 32;
+pub(crate) const KEY_LENGTH: usize = 32;
+pub(crate) const NONCE_LENGTH: usize = 12;
 
 /// 生成随机盐值
-fn generate_salt() -> [u8; SALT_LENGTH] {
+pub(crate) fn generate_salt() -> [u8; SALT_LENGTH] {
     let mut salt = [0u8; SALT_LENGTH];
     OsRng.fill_bytes(&mut salt);
     salt
 }
 
 /// 生成随机nonce
-fn generate_nonce() -> [u8; NONCE_LENGTH] {
+pub(crate) fn generate_nonce() -> [u8; NONCE_LENGTH] {
     let mut nonce = [0u8; NONCE_LENGTH];
     OsRng.fill_bytes(&mut nonce);
     nonce
 }
 
 /// 从密码和盐值派生密钥
-fn derive_key(password: &str, salt: &[u8]) -> [u8; KEY_LENGTH] {
+pub(crate) fn derive_key(password: &str, salt: &[u8]) -> [u8; KEY_LENGTH] {
     let mut key = [0u8; KEY_LENGTH];
     pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, PBKDF2_ITERATIONS, &mut key);
     key
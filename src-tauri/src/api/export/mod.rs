@@ -1,4 +1,4 @@
-use crate::db::{manager::UnifiedDbManager, models::{Category, Tip}, operations};
+use crate::db::{manager::UnifiedDbManager, models::{Category, Tip}, operations, properties};
 use tauri::{AppHandle, command, Manager, State};
 use tauri_plugin_dialog::{DialogExt, FilePath};
 use std::fs;
@@ -122,8 +122,18 @@ pub async fn export_as_markdown(
             None => continue, // 如果用户取消了对话框，跳过当前笔记
         };
 
+        // 前置元数据：属性和标签
+        let tip_properties = properties::get_tip_properties(&conn, &tip.id).await.unwrap_or_default();
+        let tags: Vec<String> = operations::get_tip_tags(&conn, &tip.id)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tag| tag.name)
+            .collect();
+
         // 写入Markdown格式的内容
-        let mut content = format!("# {}\n\n", tip.title);
+        let mut content = properties::render_front_matter(&tip_properties, &tags);
+        content.push_str(&format!("# {}\n\n", tip.title));
 
         // 添加分类信息（如果有）
         if let Some(category_id) = &tip.category_id {
@@ -132,19 +142,6 @@ pub async fn export_as_markdown(
             }
         }
 
-        // 添加标签信息（暂时跳过，因为没有tag关联功能）
-        let tags: Vec<String> = Vec::new(); // TODO: 实现tag功能后替换
-        if !tags.is_empty() {
-            content.push_str("**Tags**: ");
-            for (i, tag) in tags.iter().enumerate() {
-                if i > 0 {
-                    content.push_str(", ");
-                }
-                content.push_str(tag);
-            }
-            content.push_str("\n\n");
-        }
-
         // 添加笔记内容，并将原有标题级别下移一级
         let adjusted_content = shift_markdown_headings(&tip.content, 1);
         content.push_str(&adjusted_content);
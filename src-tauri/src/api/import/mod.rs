@@ -1,4 +1,4 @@
-use crate::db::{models::{Tip, TipType, Category}, operations, manager::UnifiedDbManager};
+use crate::db::{models::{Tip, TipType, Category}, operations, properties::{self, FrontMatter}, manager::UnifiedDbManager};
 use anyhow::{anyhow, Context, Result};
 use base64::{engine::general_purpose, Engine as _};
 use chrono::Utc;
@@ -69,6 +69,8 @@ pub struct ImportOptions {
     pub include_subdirs: bool,                   // 是否包含子目录
     pub process_images: bool,                    // 是否处理图片
     pub image_compression: ImageCompressionOptions, // 图片压缩选项
+    #[serde(default)]
+    pub suggest_metadata: bool,                  // 导入后为未打标签的笔记生成标签和笔记本建议
 }
 
 // 冲突解决策略
@@ -314,6 +316,7 @@ async fn do_import_from_directory(
     
     // 每次导入开始时重置取消标志
     IMPORT_CANCELLATION_TOKEN.store(false, Ordering::SeqCst);
+    let started_at = Utc::now().timestamp_millis();
 
     let root_path = PathBuf::from(directory_path);
     
@@ -429,6 +432,28 @@ async fn do_import_from_directory(
     }
 
     final_result.success = final_result.errors.is_empty();
+
+    // 导入的笔记没有标签时，用默认对话模型（未设置时只用关键词匹配）给出建议，由前端确认后应用
+    if options.suggest_metadata && final_result.notes_imported > 0 {
+        let provider_id = match manager.get_conn().await {
+            Ok(conn) => crate::api::ai::suggest::default_chat_provider(&conn).await.unwrap_or_else(|e| {
+                tracing::warn!("Failed to load default chat provider: {}", e);
+                None
+            }),
+            Err(e) => {
+                tracing::warn!("Failed to get db connection: {}", e);
+                None
+            }
+        };
+        match crate::api::ai::suggest::suggest_for_untagged(&manager, provider_id.as_deref(), Some(started_at), final_result.notes_imported as i64).await {
+            Ok(suggestions) if !suggestions.is_empty() => {
+                let _ = app.emit("import-suggestions", suggestions);
+            }
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to suggest metadata for imported tips: {}", e),
+        }
+    }
+
     emit_progress(ImportStatus::Completed, total_files, final_processed_count, "Completed", Some(final_result.clone()));
 
     Ok(final_result)
@@ -458,6 +483,7 @@ pub fn import_markdown_file(
             max_width: 1920,
             max_height: 1920,
         },
+        suggest_metadata: false,
     };
 
     let import_handle = std::thread::spawn(move || {
@@ -478,7 +504,10 @@ pub fn import_markdown_file(
 
             let import_logic = async {
                 let content = fs::read_to_string(&path_buf).map_err(|e| anyhow!("读取文件失败: {}", e))?;
-                let title = path_buf.file_stem().and_then(|s| s.to_str()).unwrap_or("未命名").to_string();
+                let (content, front_matter) = properties::extract_front_matter(&content);
+                let title = front_matter.as_ref().and_then(|f| f.title.clone()).unwrap_or_else(|| {
+                    path_buf.file_stem().and_then(|s| s.to_str()).unwrap_or("未命名").to_string()
+                });
                 let (processed_content, image_data) = process_markdown_images(&content, &path_buf, &options.image_compression)?;
 
                 let tip_id = Uuid::new_v4().to_string();
@@ -498,10 +527,15 @@ pub fn import_markdown_file(
                     is_encrypted: Some(false),
                     encryption_key_id: None,
                     encrypted_content: None,
+                    pinned: None,
+                    favorite: None,
+                    archived: None,
+                    sort_key: None,
                 };
 
                 // 首先创建 Tip
                 operations::create_tip(&conn, &tip).await?;
+                apply_front_matter(&conn, &tip.id, front_matter.as_ref()).await?;
                 result.notes_imported += 1;
 
                 // 然后处理图片
@@ -880,6 +914,7 @@ async fn create_or_get_notebook_concurrent(
         sync_hash: None,
         is_encrypted: Some(false),
         encryption_key_id: None,
+        sort_key: None,
     };
 
     let cat_id = category.id.clone();
@@ -899,7 +934,10 @@ async fn import_single_file_to_notebook_concurrent(
     result: &Arc<Mutex<ImportResult>>,
 ) -> Result<()> {
     let content = fs::read_to_string(file_path).map_err(|e| anyhow!("Failed to read file {}: {}", file_path.display(), e))?;
-    let title = file_path.file_stem().and_then(|s| s.to_str()).unwrap_or("Untitled").to_string();
+    let (content, front_matter) = properties::extract_front_matter(&content);
+    let title = front_matter.as_ref().and_then(|f| f.title.clone()).unwrap_or_else(|| {
+        file_path.file_stem().and_then(|s| s.to_str()).unwrap_or("Untitled").to_string()
+    });
     
     let (processed_content, image_data) = if options.process_images {
         process_markdown_images(&content, file_path, &options.image_compression)?
@@ -924,9 +962,14 @@ async fn import_single_file_to_notebook_concurrent(
         is_encrypted: Some(false),
         encryption_key_id: None,
         encrypted_content: None,
+        pinned: None,
+        favorite: None,
+        archived: None,
+        sort_key: None,
     };
 
     operations::create_tip(conn, &tip).await?;
+    apply_front_matter(conn, &tip.id, front_matter.as_ref()).await?;
     result.lock().await.notes_imported += 1;
     
     let mut images_processed_count = 0;
@@ -949,6 +992,16 @@ async fn import_single_file_to_notebook_concurrent(
     Ok(())
 }
 
+// 前置元数据中的标签和属性写入新导入的笔记
+async fn apply_front_matter(conn: &crate::db::DbConnection, tip_id: &str, front_matter: Option<&FrontMatter>) -> Result<()> {
+    let Some(front_matter) = front_matter else {
+        return Ok(());
+    };
+    if !front_matter.tags.is_empty() {
+        operations::set_tip_tags(conn, tip_id, &front_matter.tags).await?;
+    }
+    properties::apply_front_matter_properties(conn, tip_id, &front_matter.properties).await
+}
 
 // 检测图片格式的辅助函数
 fn detect_image_format(file_path: &Path, file_data: &[u8]) -> Result<&'static str> {
@@ -13,6 +13,7 @@ pub mod shortcuts;
 pub mod tags;
 pub mod templates;
 pub mod tips;
+pub mod trash;
 pub mod updater;
 pub mod certificates;
 
@@ -35,5 +36,6 @@ pub use settings::*;
 pub use shortcuts::*;
 pub use tags::*;
 pub use tips::*;
+pub use trash::*;
 pub use updater::*;
 pub use templates::*;
@@ -1,7 +1,6 @@
-use crate::db::{UnifiedDbManager, models::Tag, operations};
+use crate::api::tips::TipSummary;
+use crate::db::{self, UnifiedDbManager, models::{Tag, TipSortOrder}, operations, TagWithCount};
 use tauri::State;
-use chrono::Utc;
-use uuid::Uuid;
 
 // 获取所有标签
 #[tauri::command]
@@ -10,32 +9,87 @@ pub async fn get_all_tags(db_manager: State<'_, UnifiedDbManager>) -> Result<Vec
     operations::list_tags(&conn).await.map_err(|e| e.to_string())
 }
 
-// 创建标签
+// 获取所有标签及其笔记数量（按路径排序，可直接构建标签树）
+#[tauri::command]
+pub async fn get_tags_with_counts(db_manager: State<'_, UnifiedDbManager>) -> Result<Vec<TagWithCount>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::list_tags_with_counts(&conn).await.map_err(|e| e.to_string())
+}
+
+// 创建标签，支持 lang/rust/async 形式的层级名称，上级标签自动创建
 #[tauri::command]
 pub async fn create_tag(
     db_manager: State<'_, UnifiedDbManager>,
     name: String,
 ) -> Result<Tag, String> {
     let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
-    let now = Utc::now().timestamp_millis();
-    
-    let tag = Tag {
-        id: Uuid::new_v4().to_string(),
-        name,
-        created_at: now,
-        updated_at: now,
-        version: Some(1),
-        last_synced_at: Some(0),
-        sync_hash: None,
-    };
-    
-    operations::create_tag(&conn, &tag).await.map_err(|e| e.to_string())?;
-    Ok(tag)
-}
-
-// 删除标签
+    db::create_tag_path(&conn, &name).await.map_err(|e| e.to_string())
+}
+
+// 重命名标签（下级标签一并改名，与已有标签重名时合并）
+#[tauri::command(rename_all = "snake_case")]
+pub async fn rename_tag(
+    db_manager: State<'_, UnifiedDbManager>,
+    id: String,
+    new_name: String,
+) -> Result<Tag, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::rename_tag(&conn, &id, &new_name).await.map_err(|e| e.to_string())
+}
+
+// 合并标签：源标签的笔记和下级标签转移到目标标签
+#[tauri::command(rename_all = "snake_case")]
+pub async fn merge_tags(
+    db_manager: State<'_, UnifiedDbManager>,
+    source_id: String,
+    target_id: String,
+) -> Result<Tag, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::merge_tags(&conn, &source_id, &target_id).await.map_err(|e| e.to_string())
+}
+
+// 设置标签颜色和图标（传空清除）
+#[tauri::command]
+pub async fn set_tag_appearance(
+    db_manager: State<'_, UnifiedDbManager>,
+    id: String,
+    color: Option<String>,
+    icon: Option<String>,
+) -> Result<Tag, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let color = color.filter(|c| !c.trim().is_empty());
+    let icon = icon.filter(|i| !i.trim().is_empty());
+    db::set_tag_appearance(&conn, &id, color.as_deref(), icon.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 分页获取标签下的笔记摘要，默认包含下级标签
+#[tauri::command(rename_all = "snake_case")]
+pub async fn list_tips_by_tag(
+    db_manager: State<'_, UnifiedDbManager>,
+    tag_id: String,
+    include_descendants: Option<bool>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    sort: Option<TipSortOrder>,
+) -> Result<Vec<TipSummary>, String> {
+    let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
+    db::list_tip_summaries_by_tag(
+        &conn,
+        &tag_id,
+        include_descendants.unwrap_or(true),
+        limit.unwrap_or(50),
+        offset.unwrap_or(0),
+        sort.unwrap_or_default(),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+// 删除标签（连同下级标签）
 #[tauri::command]
 pub async fn delete_tag(db_manager: State<'_, UnifiedDbManager>, id: String) -> Result<(), String> {
     let conn = db_manager.get_conn().await.map_err(|e| e.to_string())?;
-    operations::delete_tag(&conn, &id).await.map_err(|e| e.to_string())
+    db::delete_tag_subtree(&conn, &id).await.map(|_| ()).map_err(|e| e.to_string())
 }
@@ -1,9 +1,9 @@
-use crate::db::{UnifiedDbManager, models::{Tip, TipType, Tag, Category}, operations};
+use crate::db::{UnifiedDbManager, models::{Tip, TipType, Tag, Category, TipFlag, TipSortOrder, TipListOptions, SyncOperation}, operations, revisions, links, graph, search_query, properties, PropertyFilter, PropertySort, PropertyType, PropertyValue, TipProperty, PropertyDefinition, QueryParseError, SiblingPosition, SearchMatch, TipRevision, TipLink, NoteGraph};
 use anyhow::Result;
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Emitter, Manager};
 use uuid::Uuid;
 
 // 前端传递的笔记数据
@@ -39,6 +39,12 @@ pub struct TipWithTags {
     pub updated_at: i64,
     pub tags: Vec<Tag>,
     pub images: Option<HashMap<String, String>>,
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default)]
+    pub archived: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -53,6 +59,21 @@ pub struct TipSummary {
     pub tags: Vec<Tag>,
     pub is_encrypted: bool,
     pub content: Option<String>, // 添加content字段用于搜索预览
+    #[serde(default)]
+    pub pinned: bool,
+    #[serde(default)]
+    pub favorite: bool,
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_match: Option<SearchMatch>, // 全文检索命中信息（片段与高亮偏移）
+}
+
+// 结构化搜索响应：语法错误时 error 带有出错位置，tips 为空
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AdvancedSearchResponse {
+    pub tips: Vec<TipSummary>,
+    pub error: Option<QueryParseError>,
 }
 
 // 分类浏览响应数据结构
@@ -88,6 +109,10 @@ impl TryFrom<TipData> for Tip {
             is_encrypted: Some(false),
             encryption_key_id: None,
             encrypted_content: None,
+            pinned: None,
+            favorite: None,
+            archived: None,
+            sort_key: None,
         })
     }
 }
@@ -158,6 +183,9 @@ pub async fn get_all_tips(app: AppHandle) -> Result<Vec<TipWithTags>, String> {
             updated_at: tip.updated_at,
             tags,
             images,
+            pinned: tip.pinned.unwrap_or(false),
+            favorite: tip.favorite.unwrap_or(false),
+            archived: tip.archived.unwrap_or(false),
         });
     }
 
@@ -166,10 +194,16 @@ pub async fn get_all_tips(app: AppHandle) -> Result<Vec<TipWithTags>, String> {
 
 // 笔记选择器：获取最新的N条笔记摘要（带预览）
 #[tauri::command]
-pub async fn list_latest_tip_summaries(limit: i32, app: AppHandle) -> Result<Vec<TipSummary>, String> {
+pub async fn list_latest_tip_summaries(
+    limit: i32,
+    sort: Option<TipSortOrder>,
+    include_archived: Option<bool>,
+    app: AppHandle,
+) -> Result<Vec<TipSummary>, String> {
     let unified_manager = get_unified_manager(&app).await?;
     let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
-    let summaries = operations::list_latest_tip_summaries_with_preview(&conn, limit)
+    let options = TipListOptions::new(sort, include_archived);
+    let summaries = operations::list_latest_tip_summaries_with_preview(&conn, limit, &options)
         .await
         .map_err(|e| e.to_string())?;
     Ok(summaries)
@@ -221,6 +255,9 @@ pub async fn get_tip_by_title(title: String, app: AppHandle) -> Result<TipWithTa
         updated_at: tip.updated_at,
         tags: Vec::new(),
         images,
+        pinned: tip.pinned.unwrap_or(false),
+        favorite: tip.favorite.unwrap_or(false),
+        archived: tip.archived.unwrap_or(false),
     })
 }
 
@@ -248,6 +285,10 @@ pub async fn get_all_tip_summaries(app: AppHandle) -> Result<Vec<TipSummary>, St
             tags,
             is_encrypted,
             content: None, // 摘要列表不包含内容
+            pinned: tip.pinned.unwrap_or(false),
+            favorite: tip.favorite.unwrap_or(false),
+            archived: tip.archived.unwrap_or(false),
+            search_match: None,
         });
     }
 
@@ -292,6 +333,9 @@ pub async fn get_tip(id: String, app: AppHandle) -> Result<TipWithTags, String>
         updated_at: tip.updated_at,
         tags,
         images,
+        pinned: tip.pinned.unwrap_or(false),
+        favorite: tip.favorite.unwrap_or(false),
+        archived: tip.archived.unwrap_or(false),
     };
 
     Ok(result)
@@ -303,23 +347,73 @@ pub async fn save_tip(tip_data: TipData, app: AppHandle) -> Result<TipWithTags,
     let unified_manager = get_unified_manager(&app).await?;
     let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
 
+    let (result, relinked) = persist_tip_relinking(&conn, tip_data, "save").await?;
+    emit_relinked_tips(&app, &result.id, relinked);
+
+    // 触发后台同步（如果在嵌入式副本模式下）
+    trigger_background_sync_if_needed(&app).await;
+
+    // 更新语义检索向量（未配置嵌入模型时不做任何事）
+    crate::api::ai::embeddings::schedule_embedding_index(&app);
+
+    Ok(result)
+}
+
+// 通知前端重命名后被改写了链接的笔记
+fn emit_relinked_tips(app: &AppHandle, tip_id: &str, relinked: Vec<String>) {
+    if !relinked.is_empty() {
+        app.emit("tip-links-rewritten", serde_json::json!({ "tip_id": tip_id, "tip_ids": relinked })).ok();
+    }
+}
+
+// 写入笔记、标签并记录修订，revision_reason 为 'save' 或 'restore'
+pub(crate) async fn persist_tip(conn: &libsql::Connection, tip_data: TipData, revision_reason: &str) -> Result<TipWithTags, String> {
+    persist_tip_relinking(conn, tip_data, revision_reason).await.map(|(tip, _)| tip)
+}
+
+// 同 persist_tip，另外返回因重命名被改写了链接的笔记 ID（已标记待同步）
+async fn persist_tip_relinking(
+    conn: &libsql::Connection,
+    mut tip_data: TipData,
+    revision_reason: &str,
+) -> Result<(TipWithTags, Vec<String>), String> {
     let now = Utc::now().timestamp_millis();
     let tip_type = TipType::try_from(tip_data.tip_type.clone())
         .map_err(|e| format!("Invalid tip type: {}", e))?;
 
+    // Markdown 笔记的前置元数据转为标题、标签和属性，正文中不再保留；没有前置元数据时不改动属性
+    let front_matter = if matches!(tip_type, TipType::Markdown) {
+        let (body, front_matter) = properties::extract_front_matter(&tip_data.content);
+        tip_data.content = body;
+        if let Some(front_matter) = &front_matter {
+            if let Some(title) = front_matter.title.clone() {
+                tip_data.title = title;
+            }
+            for tag in &front_matter.tags {
+                if !tip_data.tags.contains(tag) {
+                    tip_data.tags.push(tag.clone());
+                }
+            }
+        }
+        front_matter
+    } else {
+        None
+    };
+
     let tip_id = tip_data.id.clone().unwrap_or_else(|| Uuid::new_v4().to_string());
     let is_new_tip = tip_data.id.is_none();
     
-    // 如果是更新操作，需要获取原始的创建时间
-    let created_at = if !is_new_tip {
-        if let Ok(Some(existing_tip)) = operations::get_tip_by_id(&conn, &tip_id).await {
-            existing_tip.created_at
-        } else {
-            now
-        }
+    // 如果是更新操作，需要获取原始的创建时间、标题和标记；回收站中的笔记需先恢复才能编辑
+    let existing_tip = if !is_new_tip {
+        let tip = operations::get_tip_by_id(conn, &tip_id).await.map_err(|e| e.to_string())?;
+        Some(tip.ok_or_else(|| format!("Tip not found or in trash: {}", tip_id))?)
     } else {
-        now
+        None
     };
+    let created_at = existing_tip.as_ref().map_or(now, |t| t.created_at);
+    let previous_title = existing_tip.as_ref().map(|t| t.title.clone());
+    let flag = |f: fn(&Tip) -> Option<bool>| existing_tip.as_ref().and_then(f).unwrap_or(false);
+    let (pinned, favorite, archived) = (flag(|t| t.pinned), flag(|t| t.favorite), flag(|t| t.archived));
 
     let tip = Tip {
         id: tip_id.clone(),
@@ -336,18 +430,51 @@ pub async fn save_tip(tip_data: TipData, app: AppHandle) -> Result<TipWithTags,
         is_encrypted: Some(false),
         encryption_key_id: None,
         encrypted_content: None,
+        pinned: Some(pinned),
+        favorite: Some(favorite),
+        archived: Some(archived),
+        sort_key: None,
     };
 
     if is_new_tip {
-        operations::create_tip(&conn, &tip).await.map_err(|e| e.to_string())?;
+        operations::create_tip(conn, &tip).await.map_err(|e| e.to_string())?;
     } else {
-        operations::update_tip(&conn, &tip).await.map_err(|e| e.to_string())?;
+        // 旧笔记首次修改前保存原始快照
+        if let Err(e) = revisions::ensure_baseline_revision(conn, &tip_id).await {
+            tracing::warn!("Failed to snapshot tip {} before update: {}", tip_id, e);
+        }
+        operations::update_tip(conn, &tip).await.map_err(|e| e.to_string())?;
     }
 
-    // 触发后台同步（如果在嵌入式副本模式下）
-    trigger_background_sync_if_needed(&app).await;
+    operations::set_tip_tags(conn, &tip_id, &tip_data.tags).await.map_err(|e| e.to_string())?;
 
-    Ok(TipWithTags {
+    if let Some(front_matter) = &front_matter {
+        properties::apply_front_matter_properties(conn, &tip_id, &front_matter.properties)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    if let Err(e) = revisions::record_tip_revision(conn, &tip_id, revision_reason).await {
+        tracing::warn!("Failed to record revision for tip {}: {}", tip_id, e);
+    }
+
+    // 更新双向链接，重命名时同步改写其他笔记中的链接文本
+    let relinked = match links::refresh_tip_links(conn, &tip_id, previous_title.as_deref()).await {
+        Ok(relinked) => relinked,
+        Err(e) => {
+            tracing::warn!("Failed to update links for tip {}: {}", tip_id, e);
+            Vec::new()
+        }
+    };
+    for id in &relinked {
+        if let Err(e) = crate::sync::mark_for_sync(conn, "tips", id, SyncOperation::Update).await {
+            tracing::warn!("Failed to mark relinked tip {} for sync: {}", id, e);
+        }
+    }
+
+    let tags = operations::get_tip_tags(conn, &tip_id).await.map_err(|e| e.to_string())?;
+
+    let saved = TipWithTags {
         id: tip_id,
         title: tip_data.title,
         content: tip_data.content,
@@ -356,9 +483,121 @@ pub async fn save_tip(tip_data: TipData, app: AppHandle) -> Result<TipWithTags,
         category_id: tip_data.category_id,
         created_at,
         updated_at: now,
-        tags: Vec::new(), // TODO: 实现标签功能
+        tags,
         images: None, // TODO: 实现图片功能
-    })
+        pinned,
+        favorite,
+        archived,
+    };
+    Ok((saved, relinked))
+}
+
+// 获取笔记的修订列表
+#[tauri::command(rename_all = "snake_case")]
+pub async fn list_tip_revisions(tip_id: String, app: AppHandle) -> Result<Vec<revisions::TipRevisionSummary>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    revisions::list_tip_revisions(&conn, &tip_id).await.map_err(|e| e.to_string())
+}
+
+// 获取单个修订的完整内容
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_tip_revision(revision_id: String, app: AppHandle) -> Result<TipRevision, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    revisions::get_tip_revision(&conn, &revision_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Revision not found".to_string())
+}
+
+// 比较两个修订（to_revision_id 为空时与当前内容比较）
+#[tauri::command(rename_all = "snake_case")]
+pub async fn diff_tip_revisions(
+    from_revision_id: String,
+    to_revision_id: Option<String>,
+    app: AppHandle,
+) -> Result<revisions::RevisionDiff, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    revisions::diff_tip_revisions(&conn, &from_revision_id, to_revision_id.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 恢复修订：以修订内容作为一次新的保存
+#[tauri::command(rename_all = "snake_case")]
+pub async fn restore_tip_revision(revision_id: String, app: AppHandle) -> Result<TipWithTags, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    let revision = revisions::get_tip_revision(&conn, &revision_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .ok_or("Revision not found")?;
+
+    let tip_data = TipData {
+        id: Some(revision.tip_id),
+        title: revision.title,
+        content: revision.content,
+        tip_type: revision.tip_type,
+        language: revision.language,
+        category_id: revision.category_id,
+        tags: revision.tags,
+    };
+    let (result, relinked) = persist_tip_relinking(&conn, tip_data, "restore").await?;
+    emit_relinked_tips(&app, &result.id, relinked);
+
+    trigger_background_sync_if_needed(&app).await;
+    crate::api::ai::embeddings::schedule_embedding_index(&app);
+
+    Ok(result)
+}
+
+// 获取指向笔记的反向链接
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_backlinks(tip_id: String, app: AppHandle) -> Result<Vec<TipLink>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    links::get_backlinks(&conn, &tip_id).await.map_err(|e| e.to_string())
+}
+
+// 获取笔记的出链
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_outgoing_links(tip_id: String, app: AppHandle) -> Result<Vec<TipLink>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    links::get_outgoing_links(&conn, &tip_id).await.map_err(|e| e.to_string())
+}
+
+// 获取悬空链接，不传 tip_id 时返回全部笔记中的悬空链接
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_dangling_links(tip_id: Option<String>, app: AppHandle) -> Result<Vec<TipLink>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    links::get_dangling_links(&conn, tip_id.as_deref()).await.map_err(|e| e.to_string())
+}
+
+// 获取笔记关系图：不传 center_tip_id 时返回整个数据库，否则返回 depth 跳以内的邻域
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_note_graph(
+    center_tip_id: Option<String>,
+    depth: Option<usize>,
+    include_tags: Option<bool>,
+    include_categories: Option<bool>,
+    app: AppHandle,
+) -> Result<NoteGraph, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    let defaults = graph::GraphOptions::default();
+    let options = graph::GraphOptions {
+        center_tip_id,
+        depth: depth.unwrap_or(defaults.depth),
+        include_tags: include_tags.unwrap_or(defaults.include_tags),
+        include_categories: include_categories.unwrap_or(defaults.include_categories),
+    };
+    graph::get_note_graph(&conn, &options).await.map_err(|e| e.to_string())
 }
 
 // 删除笔记
@@ -367,8 +606,10 @@ pub async fn delete_tip(tip_id: String, app: AppHandle) -> Result<(), String> {
     let unified_manager = get_unified_manager(&app).await?;
     let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
     
-    // 删除笔记及其所有依赖关系（包括图片、标签关联等）
+    // 移入回收站（图片、音频一并标记删除）
     operations::delete_tip(&conn, &tip_id).await.map_err(|e| e.to_string())?;
+    let change = crate::db::TrashChange { tip_ids: vec![tip_id], category_ids: Vec::new() };
+    crate::api::trash::mark_trash_change_for_sync(&conn, &change, SyncOperation::Delete).await;
     
     // 触发后台同步（如果在嵌入式副本模式下）
     trigger_background_sync_if_needed(&app).await;
@@ -376,6 +617,157 @@ pub async fn delete_tip(tip_id: String, app: AppHandle) -> Result<(), String> {
     Ok(())
 }
 
+// 移动笔记到指定笔记本中的位置（category_id 为空时在当前笔记本内调整顺序）
+#[tauri::command(rename_all = "snake_case")]
+pub async fn move_tip(
+    tip_id: String,
+    category_id: Option<String>,
+    position: Option<SiblingPosition>,
+    app: AppHandle,
+) -> Result<f64, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    let sort_key = crate::db::move_tip(&conn, &tip_id, category_id.as_deref(), &position.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())?;
+
+    trigger_background_sync_if_needed(&app).await;
+
+    Ok(sort_key)
+}
+
+// 按标记列出笔记摘要
+async fn list_tips_by_flag(
+    app: &AppHandle,
+    flag: TipFlag,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    sort: Option<TipSortOrder>,
+) -> Result<Vec<TipSummary>, String> {
+    let unified_manager = get_unified_manager(app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    operations::list_tip_summaries_by_flag(&conn, flag, limit.unwrap_or(50), offset.unwrap_or(0), sort.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// 设置笔记标记并触发同步
+async fn update_tip_flag(app: &AppHandle, tip_id: &str, flag: TipFlag, value: bool) -> Result<(), String> {
+    let unified_manager = get_unified_manager(app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    operations::set_tip_flag(&conn, tip_id, flag, value)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    trigger_background_sync_if_needed(app).await;
+
+    Ok(())
+}
+
+// 获取置顶笔记
+#[tauri::command]
+pub async fn list_pinned_tips(
+    limit: Option<i32>,
+    offset: Option<i32>,
+    sort: Option<TipSortOrder>,
+    app: AppHandle,
+) -> Result<Vec<TipSummary>, String> {
+    list_tips_by_flag(&app, TipFlag::Pinned, limit, offset, sort).await
+}
+
+// 获取收藏笔记
+#[tauri::command]
+pub async fn list_favorite_tips(
+    limit: Option<i32>,
+    offset: Option<i32>,
+    sort: Option<TipSortOrder>,
+    app: AppHandle,
+) -> Result<Vec<TipSummary>, String> {
+    list_tips_by_flag(&app, TipFlag::Favorite, limit, offset, sort).await
+}
+
+// 获取已归档笔记
+#[tauri::command]
+pub async fn list_archived_tips(
+    limit: Option<i32>,
+    offset: Option<i32>,
+    sort: Option<TipSortOrder>,
+    app: AppHandle,
+) -> Result<Vec<TipSummary>, String> {
+    list_tips_by_flag(&app, TipFlag::Archived, limit, offset, sort).await
+}
+
+// 置顶/取消置顶笔记
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_tip_pinned(tip_id: String, value: bool, app: AppHandle) -> Result<(), String> {
+    update_tip_flag(&app, &tip_id, TipFlag::Pinned, value).await
+}
+
+// 收藏/取消收藏笔记
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_tip_favorite(tip_id: String, value: bool, app: AppHandle) -> Result<(), String> {
+    update_tip_flag(&app, &tip_id, TipFlag::Favorite, value).await
+}
+
+// 归档/取消归档笔记
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_tip_archived(tip_id: String, value: bool, app: AppHandle) -> Result<(), String> {
+    update_tip_flag(&app, &tip_id, TipFlag::Archived, value).await
+}
+
+// 获取笔记属性
+#[tauri::command(rename_all = "snake_case")]
+pub async fn get_tip_properties(tip_id: String, app: AppHandle) -> Result<Vec<TipProperty>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    properties::get_tip_properties(&conn, &tip_id).await.map_err(|e| e.to_string())
+}
+
+// 设置笔记属性（值形如 {"type": "date", "value": "2025-01-31"}）
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_tip_property(tip_id: String, key: String, value: PropertyValue, app: AppHandle) -> Result<Vec<TipProperty>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    properties::set_tip_property(&conn, &tip_id, &key, &value).await.map_err(|e| e.to_string())?;
+    properties::get_tip_properties(&conn, &tip_id).await.map_err(|e| e.to_string())
+}
+
+// 删除笔记属性
+#[tauri::command(rename_all = "snake_case")]
+pub async fn delete_tip_property(tip_id: String, key: String, app: AppHandle) -> Result<Vec<TipProperty>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    properties::delete_tip_property(&conn, &tip_id, &key).await.map_err(|e| e.to_string())?;
+    properties::get_tip_properties(&conn, &tip_id).await.map_err(|e| e.to_string())
+}
+
+// 列出所有属性（已声明类型的和笔记中用到的）
+#[tauri::command]
+pub async fn list_property_definitions(app: AppHandle) -> Result<Vec<PropertyDefinition>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    properties::list_property_definitions(&conn).await.map_err(|e| e.to_string())
+}
+
+// 声明属性类型，select 类型可指定可选值
+#[tauri::command(rename_all = "snake_case")]
+pub async fn set_property_definition(
+    key: String,
+    value_type: PropertyType,
+    options: Option<Vec<String>>,
+    app: AppHandle,
+) -> Result<Vec<PropertyDefinition>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    properties::set_property_definition(&conn, &key, value_type, &options.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())?;
+    properties::list_property_definitions(&conn).await.map_err(|e| e.to_string())
+}
+
 // 搜索笔记
 #[tauri::command]
 pub async fn search_tips(query: String, app: AppHandle) -> Result<Vec<TipWithTags>, String> {
@@ -419,6 +811,9 @@ pub async fn search_tips(query: String, app: AppHandle) -> Result<Vec<TipWithTag
             updated_at: tip.updated_at,
             tags,
             images,
+            pinned: tip.pinned.unwrap_or(false),
+            favorite: tip.favorite.unwrap_or(false),
+            archived: tip.archived.unwrap_or(false),
         });
     }
 
@@ -441,6 +836,34 @@ pub async fn search_tips_summary(query: String, app: AppHandle) -> Result<Vec<Ti
     Ok(summaries)
 }
 
+// 结构化搜索（支持 tag:/type:/lang:/in:/created:/before:/after:/prop.<键>:、-排除、引号短语和 OR）
+#[tauri::command]
+pub async fn search_tips_advanced(
+    query: String,
+    limit: Option<i32>,
+    offset: Option<i32>,
+    sort_property: Option<PropertySort>,
+    app: AppHandle,
+) -> Result<AdvancedSearchResponse, String> {
+    if query.trim().is_empty() {
+        return Ok(AdvancedSearchResponse { tips: Vec::new(), error: None });
+    }
+
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    let compiled = match search_query::prepare_search_query(&conn, &query).await.map_err(|e| e.to_string())? {
+        Ok(compiled) => compiled,
+        Err(error) => return Ok(AdvancedSearchResponse { tips: Vec::new(), error: Some(error) }),
+    };
+
+    let tips = search_query::search_tips_summary_structured(&conn, &compiled, sort_property.as_ref(), limit.unwrap_or(50), offset.unwrap_or(0))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(AdvancedSearchResponse { tips, error: None })
+}
+
 // 按分类获取笔记
 #[tauri::command]
 pub async fn get_tips_by_category(category_id: String, app: AppHandle) -> Result<Vec<TipWithTags>, String> {
@@ -480,16 +903,47 @@ pub async fn get_tips_by_category(category_id: String, app: AppHandle) -> Result
             updated_at: tip.updated_at,
             tags,
             images,
+            pinned: tip.pinned.unwrap_or(false),
+            favorite: tip.favorite.unwrap_or(false),
+            archived: tip.archived.unwrap_or(false),
         });
     }
 
     Ok(result)
 }
 
-// 按标签获取笔记 - 临时返回空结果
+// 按标签获取笔记，默认包含下级标签
 #[tauri::command]
-pub async fn get_tips_by_tag(_tag_id: String, _app: AppHandle) -> Result<Vec<TipWithTags>, String> {
-    Ok(Vec::new()) // TODO: 实现标签查询功能
+pub async fn get_tips_by_tag(tag_id: String, include_descendants: Option<bool>, app: AppHandle) -> Result<Vec<TipWithTags>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let tips = crate::db::get_tips_by_tag(&conn, &tag_id, include_descendants.unwrap_or(true))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for tip in tips {
+        let tags = operations::get_tip_tags(&conn, &tip.id).await.map_err(|e| e.to_string())?;
+        let tip_type_str: String = tip.tip_type.into();
+
+        result.push(TipWithTags {
+            id: tip.id,
+            title: tip.title,
+            content: tip.content,
+            tip_type: tip_type_str,
+            language: tip.language,
+            category_id: tip.category_id,
+            created_at: tip.created_at,
+            updated_at: tip.updated_at,
+            tags,
+            images: None,
+            pinned: tip.pinned.unwrap_or(false),
+            favorite: tip.favorite.unwrap_or(false),
+            archived: tip.archived.unwrap_or(false),
+        });
+    }
+
+    Ok(result)
 }
 
 // 图片相关API
@@ -598,6 +1052,9 @@ pub async fn get_tips_by_category_recursive(category_id: String, app: AppHandle)
             updated_at: tip.updated_at,
             tags,
             images,
+            pinned: tip.pinned.unwrap_or(false),
+            favorite: tip.favorite.unwrap_or(false),
+            archived: tip.archived.unwrap_or(false),
         });
     }
 
@@ -606,9 +1063,17 @@ pub async fn get_tips_by_category_recursive(category_id: String, app: AppHandle)
 
 // 新的分类浏览API - 优化性能
 #[tauri::command]
-pub async fn browse_category(category_id: String, app: AppHandle) -> Result<CategoryBrowseResponse, String> {
+pub async fn browse_category(
+    category_id: String,
+    sort: Option<TipSortOrder>,
+    include_archived: Option<bool>,
+    property_filters: Option<Vec<PropertyFilter>>,
+    sort_property: Option<PropertySort>,
+    app: AppHandle,
+) -> Result<CategoryBrowseResponse, String> {
     let unified_manager = get_unified_manager(&app).await?;
     let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let options = TipListOptions::new(sort, include_archived).with_properties(property_filters, sort_property);
     
     // 获取当前分类信息
     let current_category = if category_id.is_empty() {
@@ -633,22 +1098,22 @@ pub async fn browse_category(category_id: String, app: AppHandle) -> Result<Cate
         // 根目录：计算所有顶级分类及其子分类的笔记总数
         let mut total = 0i64;
         for subcategory in &subcategories {
-            total += operations::count_tips_by_category_recursive(&conn, &subcategory.id).await.map_err(|e| e.to_string())?;
+            total += operations::count_tips_by_category_recursive(&conn, &subcategory.id, &options).await.map_err(|e| e.to_string())?;
         }
         total
     } else {
         // 特定分类：使用递归计数，包括所有子分类
-        operations::count_tips_by_category_recursive(&conn, &category_id).await.map_err(|e| e.to_string())?
+        operations::count_tips_by_category_recursive(&conn, &category_id, &options).await.map_err(|e| e.to_string())?
     };
     
     // 获取第一条笔记的完整内容（如果有的话）
     let featured_tip = if total_tips_count > 0 {
         let tips = if category_id.is_empty() {
             // 根目录：从所有子分类中递归获取笔记
-            operations::get_tips_by_category_recursive_paged(&conn, &subcategories.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), 1, 0).await.map_err(|e| e.to_string())?
+            operations::get_tips_by_category_recursive_paged(&conn, &subcategories.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), 1, 0, &options).await.map_err(|e| e.to_string())?
         } else {
             // 特定分类：递归获取该分类及其子分类的笔记
-            operations::get_tips_by_category_recursive_paged_single(&conn, &category_id, 1, 0).await.map_err(|e| e.to_string())?
+            operations::get_tips_by_category_recursive_paged_single(&conn, &category_id, 1, 0, &options).await.map_err(|e| e.to_string())?
         };
         if let Some(tip) = tips.first() {
             let tags: Vec<Tag> = Vec::new(); // TODO: 实现标签功能
@@ -681,6 +1146,9 @@ pub async fn browse_category(category_id: String, app: AppHandle) -> Result<Cate
                 updated_at: tip.updated_at,
                 tags,
                 images,
+                pinned: tip.pinned.unwrap_or(false),
+                favorite: tip.favorite.unwrap_or(false),
+                archived: tip.archived.unwrap_or(false),
             })
         } else {
             None
@@ -693,10 +1161,10 @@ pub async fn browse_category(category_id: String, app: AppHandle) -> Result<Cate
     let tip_summaries = if total_tips_count > 1 {
         let tips = if category_id.is_empty() {
             // 根目录：从所有子分类中递归获取笔记
-            operations::get_tips_by_category_recursive_paged(&conn, &subcategories.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), 20, 1).await.map_err(|e| e.to_string())?
+            operations::get_tips_by_category_recursive_paged(&conn, &subcategories.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), 20, 1, &options).await.map_err(|e| e.to_string())?
         } else {
             // 特定分类：递归获取该分类及其子分类的笔记
-            operations::get_tips_by_category_recursive_paged_single(&conn, &category_id, 20, 1).await.map_err(|e| e.to_string())?
+            operations::get_tips_by_category_recursive_paged_single(&conn, &category_id, 20, 1, &options).await.map_err(|e| e.to_string())?
         }; // 最多20条摘要，跳过第一条
         let mut summaries = Vec::new();
         for tip in tips {
@@ -715,6 +1183,10 @@ pub async fn browse_category(category_id: String, app: AppHandle) -> Result<Cate
                 tags,
                 is_encrypted,
                 content: None, // 摘要不包含内容
+                pinned: tip.pinned.unwrap_or(false),
+                favorite: tip.favorite.unwrap_or(false),
+                archived: tip.archived.unwrap_or(false),
+                search_match: None,
             });
         }
         summaries
@@ -737,10 +1209,15 @@ pub async fn load_more_tips_by_category(
     category_id: String, 
     offset: i32, 
     limit: i32, 
+    sort: Option<TipSortOrder>,
+    include_archived: Option<bool>,
+    property_filters: Option<Vec<PropertyFilter>>,
+    sort_property: Option<PropertySort>,
     app: AppHandle
 ) -> Result<Vec<TipSummary>, String> {
     let unified_manager = get_unified_manager(&app).await?;
     let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+    let options = TipListOptions::new(sort, include_archived).with_properties(property_filters, sort_property);
     
     let tips = if category_id.is_empty() {
         // 根目录：从所有顶级分类中递归获取笔记
@@ -748,10 +1225,10 @@ pub async fn load_more_tips_by_category(
             .into_iter()
             .filter(|cat| cat.parent_id.is_none())
             .collect::<Vec<_>>();
-        operations::get_tips_by_category_recursive_paged(&conn, &subcategories.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), limit, offset).await.map_err(|e| e.to_string())?
+        operations::get_tips_by_category_recursive_paged(&conn, &subcategories.iter().map(|c| c.id.as_str()).collect::<Vec<_>>(), limit, offset, &options).await.map_err(|e| e.to_string())?
     } else {
         // 特定分类：递归获取该分类及其子分类的笔记
-        operations::get_tips_by_category_recursive_paged_single(&conn, &category_id, limit, offset).await.map_err(|e| e.to_string())?
+        operations::get_tips_by_category_recursive_paged_single(&conn, &category_id, limit, offset, &options).await.map_err(|e| e.to_string())?
     };
 
     let mut summaries = Vec::new();
@@ -771,6 +1248,10 @@ pub async fn load_more_tips_by_category(
             tags,
             is_encrypted,
             content: None, // 摘要不包含内容
+            pinned: tip.pinned.unwrap_or(false),
+            favorite: tip.favorite.unwrap_or(false),
+            archived: tip.archived.unwrap_or(false),
+            search_match: None,
         });
     }
 
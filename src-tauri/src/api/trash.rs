@@ -0,0 +1,153 @@
+use crate::db::{self, DbConnection, SyncOperation, TrashChange, TrashItem, TrashItemType, UnifiedDbManager};
+use tauri::{AppHandle, Manager};
+
+// 自动清理检查间隔
+const TRASH_PURGE_INTERVAL_SECS: u64 = 6 * 60 * 60;
+
+// 辅助函数：获取统一数据库管理器
+async fn get_unified_manager(app: &AppHandle) -> Result<UnifiedDbManager, String> {
+    if let Some(manager) = app.try_state::<UnifiedDbManager>() {
+        Ok((*manager.inner()).clone())
+    } else {
+        // 如果没有找到，尝试创建
+        UnifiedDbManager::new(app.clone()).await
+            .map_err(|e| format!("Failed to create unified manager: {}", e))
+    }
+}
+
+// 辅助函数：在需要时触发后台同步
+async fn trigger_background_sync_if_needed(app: &AppHandle) {
+    if let Ok(unified_manager) = get_unified_manager(app).await {
+        let current_mode = unified_manager.get_current_mode().await;
+        if current_mode.supports_sync() {
+            tokio::spawn(async move {
+                if let Err(e) = unified_manager.sync().await {
+                    tracing::warn!("Background sync failed after database operation: {}", e);
+                } else {
+                    tracing::info!("Background sync completed after database operation");
+                }
+            });
+        }
+    }
+}
+
+/// 将移入或移出回收站的记录标记为待同步，删除时作为墓碑同步到远端
+pub(crate) async fn mark_trash_change_for_sync(conn: &DbConnection, change: &TrashChange, operation: SyncOperation) {
+    let records = change.category_ids.iter().map(|id| ("categories", id))
+        .chain(change.tip_ids.iter().map(|id| ("tips", id)));
+    for (table_name, id) in records {
+        if let Err(e) = crate::sync::mark_for_sync(conn, table_name, id, operation.clone()).await {
+            tracing::warn!("Failed to mark {} {} for sync: {}", table_name, id, e);
+        }
+    }
+}
+
+// 获取回收站列表
+#[tauri::command]
+pub async fn list_trash(app: AppHandle) -> Result<Vec<TrashItem>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    db::list_trash(&conn).await.map_err(|e| e.to_string())
+}
+
+// 从回收站恢复笔记，返回恢复后所在的笔记本（原笔记本不可用时为 None）
+#[tauri::command(rename_all = "snake_case")]
+pub async fn restore_tip(tip_id: String, app: AppHandle) -> Result<Option<String>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    let category_id = db::restore_tip(&conn, &tip_id).await.map_err(|e| e.to_string())?;
+    let change = TrashChange { tip_ids: vec![tip_id], category_ids: Vec::new() };
+    mark_trash_change_for_sync(&conn, &change, SyncOperation::Update).await;
+
+    trigger_background_sync_if_needed(&app).await;
+
+    Ok(category_id)
+}
+
+// 从回收站恢复笔记本（连同一起删除的子笔记本和笔记），返回恢复后的父笔记本
+#[tauri::command]
+pub async fn restore_category(id: String, app: AppHandle) -> Result<Option<String>, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    let (parent_id, change) = db::restore_category(&conn, &id).await.map_err(|e| e.to_string())?;
+    mark_trash_change_for_sync(&conn, &change, SyncOperation::Update).await;
+
+    trigger_background_sync_if_needed(&app).await;
+
+    Ok(parent_id)
+}
+
+// 彻底删除回收站中的条目
+#[tauri::command(rename_all = "snake_case")]
+pub async fn purge_trash_item(item_type: TrashItemType, id: String, app: AppHandle) -> Result<(), String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    db::purge_trash_item(&conn, item_type, &id).await.map_err(|e| e.to_string())
+}
+
+// 清空回收站，返回删除的条目数
+#[tauri::command]
+pub async fn empty_trash(app: AppHandle) -> Result<usize, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    db::empty_trash(&conn).await.map_err(|e| e.to_string())
+}
+
+// 获取回收站保留天数
+#[tauri::command]
+pub async fn get_trash_retention_days(app: AppHandle) -> Result<i64, String> {
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    db::get_trash_retention_days(&conn).await.map_err(|e| e.to_string())
+}
+
+// 设置回收站保留天数（0 表示不自动清理）
+#[tauri::command]
+pub async fn set_trash_retention_days(days: i64, app: AppHandle) -> Result<(), String> {
+    if days < 0 {
+        return Err("Retention days must not be negative".to_string());
+    }
+
+    let unified_manager = get_unified_manager(&app).await?;
+    let conn = unified_manager.get_conn().await.map_err(|e| e.to_string())?;
+
+    db::save_setting(&conn, db::TRASH_RETENTION_DAYS_KEY, &days.to_string())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 启动回收站定时清理任务
+pub fn start_trash_purge_task(app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(TRASH_PURGE_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            let unified_manager = match get_unified_manager(&app_handle).await {
+                Ok(manager) => manager,
+                Err(e) => {
+                    tracing::warn!("Trash purge skipped: {}", e);
+                    continue;
+                }
+            };
+            let conn = match unified_manager.get_conn().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    tracing::warn!("Trash purge skipped: {}", e);
+                    continue;
+                }
+            };
+
+            if let Err(e) = db::purge_expired_trash(&conn).await {
+                tracing::warn!("Failed to purge expired trash items: {}", e);
+            }
+        }
+    });
+}
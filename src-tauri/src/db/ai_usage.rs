@@ -0,0 +1,489 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Local, TimeZone, Utc};
+use libsql::{params, Value};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::operations::DbConnection;
+
+/// 预算作用于所有服务商时的 scope
+pub const ALL_PROVIDERS_SCOPE: &str = "*";
+
+/// 一次 AI 调用的用量
+#[derive(Debug, Clone, Default)]
+pub struct AiUsageRecord {
+    pub provider: String,
+    pub model: String,
+    pub operation: String, // chat / stream / embedding
+    pub role_id: Option<String>,
+    pub conversation_id: Option<String>,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub estimated: bool, // 服务商未返回用量，按字符数估算
+    pub latency_ms: i64,
+    pub error: Option<String>,
+}
+
+/// 聚合维度
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageGroupBy {
+    Day,
+    Provider,
+    Model,
+    Role,
+    Conversation,
+    Operation,
+}
+
+impl UsageGroupBy {
+    fn key_sql(&self) -> &'static str {
+        match self {
+            UsageGroupBy::Day => "strftime('%Y-%m-%d', created_at / 1000, 'unixepoch', 'localtime')",
+            UsageGroupBy::Provider => "provider",
+            UsageGroupBy::Model => "provider || '/' || model",
+            UsageGroupBy::Role => "COALESCE(role_id, '')",
+            UsageGroupBy::Conversation => "COALESCE(conversation_id, '')",
+            UsageGroupBy::Operation => "operation",
+        }
+    }
+}
+
+/// 聚合结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageBucket {
+    pub key: String,
+    pub requests: i64,
+    pub errors: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub total_tokens: i64,
+    pub estimated_requests: i64,
+    pub cost: f64,
+    pub avg_latency_ms: f64,
+}
+
+/// 模型价格（每百万 token）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub provider: String,
+    pub model: String, // * 表示该服务商的所有模型
+    pub input_price: f64,
+    pub output_price: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, prompt_tokens: i64, completion_tokens: i64) -> f64 {
+        (prompt_tokens as f64 * self.input_price + completion_tokens as f64 * self.output_price) / 1_000_000.0
+    }
+}
+
+/// 超出预算时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetAction {
+    #[default]
+    Warn,
+    Block,
+}
+
+impl BudgetAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            BudgetAction::Warn => "warn",
+            BudgetAction::Block => "block",
+        }
+    }
+}
+
+/// 月度预算，token 和费用上限可只设其一
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AiBudget {
+    pub scope: String, // 服务商ID，* 表示全部
+    pub token_limit: Option<i64>,
+    pub cost_limit: Option<f64>,
+    #[serde(default)]
+    pub action: BudgetAction,
+    #[serde(default = "default_warn_ratio")]
+    pub warn_ratio: f64,
+}
+
+fn default_warn_ratio() -> f64 {
+    0.8
+}
+
+/// 本月预算使用情况
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetStatus {
+    pub budget: AiBudget,
+    pub used_tokens: i64,
+    pub used_cost: f64,
+    pub ratio: f64, // 已用比例，取 token 和费用中较高者
+    pub warning: bool,
+    pub exceeded: bool,
+}
+
+impl BudgetStatus {
+    pub fn evaluate(budget: AiBudget, used_tokens: i64, used_cost: f64) -> Self {
+        let token_ratio = budget
+            .token_limit
+            .filter(|limit| *limit > 0)
+            .map(|limit| used_tokens as f64 / limit as f64);
+        let cost_ratio = budget
+            .cost_limit
+            .filter(|limit| *limit > 0.0)
+            .map(|limit| used_cost / limit);
+        let ratio = token_ratio.into_iter().chain(cost_ratio).fold(0.0, f64::max);
+        let exceeded = ratio >= 1.0;
+        BudgetStatus {
+            warning: !exceeded && ratio >= budget.warn_ratio,
+            exceeded,
+            ratio,
+            used_tokens,
+            used_cost,
+            budget,
+        }
+    }
+
+    /// 是否应拒绝新的请求
+    pub fn blocks(&self) -> bool {
+        self.exceeded && self.budget.action == BudgetAction::Block
+    }
+}
+
+/// 本地时区当月1日零点（毫秒）
+pub fn month_start_millis(now: DateTime<Local>) -> i64 {
+    Local
+        .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .earliest()
+        .map(|start| start.timestamp_millis())
+        .unwrap_or_else(|| now.timestamp_millis())
+}
+
+// ============ 用量记录 ============
+
+/// 写入一次调用的用量，按价格表估算费用
+pub async fn record_ai_usage(conn: &DbConnection, record: &AiUsageRecord) -> Result<()> {
+    let cost = get_model_price(conn, &record.provider, &record.model)
+        .await?
+        .map(|price| price.cost(record.prompt_tokens, record.completion_tokens));
+
+    conn.execute(
+        "INSERT INTO ai_usage (id, provider, model, operation, role_id, conversation_id, prompt_tokens,
+                               completion_tokens, estimated, cost, latency_ms, error, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            Uuid::new_v4().to_string(),
+            record.provider.clone(),
+            record.model.clone(),
+            record.operation.clone(),
+            record.role_id.clone(),
+            record.conversation_id.clone(),
+            record.prompt_tokens,
+            record.completion_tokens,
+            record.estimated as i64,
+            cost,
+            record.latency_ms,
+            record.error.clone(),
+            Utc::now().timestamp_millis()
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// 按维度聚合时间范围内的用量（毫秒，左闭右开）
+pub async fn query_ai_usage(
+    conn: &DbConnection,
+    group_by: UsageGroupBy,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<Vec<UsageBucket>> {
+    let order = if group_by == UsageGroupBy::Day { "bucket ASC" } else { "total_tokens DESC, bucket ASC" };
+    let sql = format!(
+        "SELECT {} AS bucket, COUNT(*), SUM(CASE WHEN error IS NULL THEN 0 ELSE 1 END),
+                COALESCE(SUM(prompt_tokens), 0), COALESCE(SUM(completion_tokens), 0),
+                COALESCE(SUM(prompt_tokens + completion_tokens), 0) AS total_tokens,
+                COALESCE(SUM(estimated), 0), COALESCE(SUM(cost), 0.0), COALESCE(AVG(latency_ms), 0.0)
+         FROM ai_usage
+         WHERE created_at >= ?1 AND created_at < ?2
+         GROUP BY bucket
+         ORDER BY {}",
+        group_by.key_sql(),
+        order
+    );
+
+    let mut rows = conn
+        .query(&sql, params![from.unwrap_or(0), to.unwrap_or(i64::MAX)])
+        .await?;
+    let mut buckets = Vec::new();
+    while let Some(row) = rows.next().await? {
+        buckets.push(UsageBucket {
+            key: row.get(0)?,
+            requests: row.get(1)?,
+            errors: row.get(2)?,
+            prompt_tokens: row.get(3)?,
+            completion_tokens: row.get(4)?,
+            total_tokens: row.get(5)?,
+            estimated_requests: row.get(6)?,
+            cost: row.get(7)?,
+            avg_latency_ms: row.get(8)?,
+        });
+    }
+    Ok(buckets)
+}
+
+/// 时间范围内的总用量
+pub async fn get_ai_usage_totals(conn: &DbConnection, from: Option<i64>, to: Option<i64>) -> Result<UsageBucket> {
+    let mut rows = conn
+        .query(
+            "SELECT COUNT(*), SUM(CASE WHEN error IS NULL THEN 0 ELSE 1 END),
+                    COALESCE(SUM(prompt_tokens), 0), COALESCE(SUM(completion_tokens), 0),
+                    COALESCE(SUM(estimated), 0), COALESCE(SUM(cost), 0.0), COALESCE(AVG(latency_ms), 0.0)
+             FROM ai_usage
+             WHERE created_at >= ?1 AND created_at < ?2",
+            params![from.unwrap_or(0), to.unwrap_or(i64::MAX)],
+        )
+        .await?;
+    let row = rows.next().await?.ok_or_else(|| anyhow!("Failed to aggregate AI usage"))?;
+    let prompt_tokens: i64 = row.get(2)?;
+    let completion_tokens: i64 = row.get(3)?;
+    Ok(UsageBucket {
+        key: String::new(),
+        requests: row.get(0)?,
+        errors: row.get::<Option<i64>>(1)?.unwrap_or(0),
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+        estimated_requests: row.get(4)?,
+        cost: row.get(5)?,
+        avg_latency_ms: row.get(6)?,
+    })
+}
+
+/// 清空用量记录
+pub async fn clear_ai_usage(conn: &DbConnection) -> Result<()> {
+    conn.execute("DELETE FROM ai_usage", ()).await?;
+    Ok(())
+}
+
+// ============ 价格表 ============
+
+fn row_to_price(row: &libsql::Row) -> Result<ModelPrice> {
+    Ok(ModelPrice {
+        provider: row.get(0)?,
+        model: row.get(1)?,
+        input_price: row.get(2)?,
+        output_price: row.get(3)?,
+    })
+}
+
+pub async fn list_model_prices(conn: &DbConnection) -> Result<Vec<ModelPrice>> {
+    let mut rows = conn
+        .query(
+            "SELECT provider, model, input_price, output_price FROM ai_model_prices ORDER BY provider, model",
+            (),
+        )
+        .await?;
+    let mut prices = Vec::new();
+    while let Some(row) = rows.next().await? {
+        prices.push(row_to_price(&row)?);
+    }
+    Ok(prices)
+}
+
+/// 查找模型价格，精确匹配优先，其次服务商通配
+pub async fn get_model_price(conn: &DbConnection, provider: &str, model: &str) -> Result<Option<ModelPrice>> {
+    let mut rows = conn
+        .query(
+            "SELECT provider, model, input_price, output_price FROM ai_model_prices
+             WHERE provider = ?1 AND (model = ?2 OR model = '*')
+             ORDER BY CASE WHEN model = '*' THEN 1 ELSE 0 END
+             LIMIT 1",
+            params![provider, model],
+        )
+        .await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(row_to_price(&row)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn set_model_price(conn: &DbConnection, price: &ModelPrice) -> Result<()> {
+    if price.input_price < 0.0 || price.output_price < 0.0 {
+        return Err(anyhow!("Prices must not be negative"));
+    }
+    conn.execute(
+        "INSERT INTO ai_model_prices (provider, model, input_price, output_price, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(provider, model) DO UPDATE SET
+            input_price = excluded.input_price,
+            output_price = excluded.output_price,
+            updated_at = excluded.updated_at",
+        params![
+            price.provider.clone(),
+            price.model.clone(),
+            price.input_price,
+            price.output_price,
+            Utc::now().timestamp_millis()
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_model_price(conn: &DbConnection, provider: &str, model: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM ai_model_prices WHERE provider = ?1 AND model = ?2",
+        params![provider, model],
+    )
+    .await?;
+    Ok(())
+}
+
+// ============ 月度预算 ============
+
+fn row_to_budget(row: &libsql::Row) -> Result<AiBudget> {
+    let action: String = row.get(3)?;
+    Ok(AiBudget {
+        scope: row.get(0)?,
+        token_limit: row.get(1)?,
+        cost_limit: row.get(2)?,
+        action: if action == "block" { BudgetAction::Block } else { BudgetAction::Warn },
+        warn_ratio: row.get(4)?,
+    })
+}
+
+pub async fn list_ai_budgets(conn: &DbConnection) -> Result<Vec<AiBudget>> {
+    let mut rows = conn
+        .query(
+            "SELECT scope, token_limit, cost_limit, action, warn_ratio FROM ai_budgets ORDER BY scope",
+            (),
+        )
+        .await?;
+    let mut budgets = Vec::new();
+    while let Some(row) = rows.next().await? {
+        budgets.push(row_to_budget(&row)?);
+    }
+    Ok(budgets)
+}
+
+pub async fn set_ai_budget(conn: &DbConnection, budget: &AiBudget) -> Result<()> {
+    if budget.scope.trim().is_empty() {
+        return Err(anyhow!("Budget scope must not be empty"));
+    }
+    if budget.token_limit.is_none() && budget.cost_limit.is_none() {
+        return Err(anyhow!("Budget needs a token or cost limit"));
+    }
+    if !(0.0..=1.0).contains(&budget.warn_ratio) {
+        return Err(anyhow!("Warn ratio must be between 0 and 1"));
+    }
+    conn.execute(
+        "INSERT INTO ai_budgets (scope, token_limit, cost_limit, action, warn_ratio, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(scope) DO UPDATE SET
+            token_limit = excluded.token_limit,
+            cost_limit = excluded.cost_limit,
+            action = excluded.action,
+            warn_ratio = excluded.warn_ratio,
+            updated_at = excluded.updated_at",
+        params![
+            budget.scope.trim().to_string(),
+            budget.token_limit,
+            budget.cost_limit,
+            budget.action.as_str(),
+            budget.warn_ratio,
+            Utc::now().timestamp_millis()
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+pub async fn delete_ai_budget(conn: &DbConnection, scope: &str) -> Result<()> {
+    conn.execute("DELETE FROM ai_budgets WHERE scope = ?1", params![scope]).await?;
+    Ok(())
+}
+
+/// 本月各预算的使用情况；指定服务商时只返回对其生效的预算
+pub async fn get_budget_statuses(conn: &DbConnection, provider: Option<&str>) -> Result<Vec<BudgetStatus>> {
+    let since = month_start_millis(Local::now());
+    let mut statuses = Vec::new();
+    for budget in list_ai_budgets(conn).await? {
+        if let Some(provider) = provider {
+            if budget.scope != ALL_PROVIDERS_SCOPE && budget.scope != provider {
+                continue;
+            }
+        }
+
+        let (filter, filter_params): (&str, Vec<Value>) = if budget.scope == ALL_PROVIDERS_SCOPE {
+            ("", vec![])
+        } else {
+            (" AND provider = ?2", vec![Value::Text(budget.scope.clone())])
+        };
+        let sql = format!(
+            "SELECT COALESCE(SUM(prompt_tokens + completion_tokens), 0), COALESCE(SUM(cost), 0.0)
+             FROM ai_usage WHERE created_at >= ?1{}",
+            filter
+        );
+        let mut query_params = vec![Value::Integer(since)];
+        query_params.extend(filter_params);
+
+        let mut rows = conn.query(&sql, libsql::params_from_iter(query_params)).await?;
+        let (used_tokens, used_cost) = match rows.next().await? {
+            Some(row) => (row.get::<i64>(0)?, row.get::<f64>(1)?),
+            None => (0, 0.0),
+        };
+        statuses.push(BudgetStatus::evaluate(budget, used_tokens, used_cost));
+    }
+    Ok(statuses)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn budget(token_limit: Option<i64>, cost_limit: Option<f64>, action: BudgetAction) -> AiBudget {
+        AiBudget {
+            scope: ALL_PROVIDERS_SCOPE.to_string(),
+            token_limit,
+            cost_limit,
+            action,
+            warn_ratio: 0.8,
+        }
+    }
+
+    #[test]
+    fn test_budget_status() {
+        let status = BudgetStatus::evaluate(budget(Some(1000), None, BudgetAction::Block), 500, 0.0);
+        assert!(!status.warning && !status.exceeded && !status.blocks());
+
+        let status = BudgetStatus::evaluate(budget(Some(1000), Some(2.0), BudgetAction::Block), 100, 1.7);
+        assert!(status.warning);
+        assert!((status.ratio - 0.85).abs() < 1e-9);
+
+        let status = BudgetStatus::evaluate(budget(Some(1000), None, BudgetAction::Block), 1000, 0.0);
+        assert!(status.exceeded && status.blocks());
+
+        let status = BudgetStatus::evaluate(budget(Some(1000), None, BudgetAction::Warn), 5000, 0.0);
+        assert!(status.exceeded && !status.blocks());
+    }
+
+    #[test]
+    fn test_model_price_cost() {
+        let price = ModelPrice {
+            provider: "openai".into(),
+            model: "*".into(),
+            input_price: 2.5,
+            output_price: 10.0,
+        };
+        assert!((price.cost(1_000_000, 500_000) - 7.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_month_start() {
+        let now = Local.with_ymd_and_hms(2024, 3, 15, 12, 30, 0).unwrap();
+        let start = Local.timestamp_millis_opt(month_start_millis(now)).unwrap();
+        assert_eq!((start.year(), start.month(), start.day()), (2024, 3, 1));
+    }
+}
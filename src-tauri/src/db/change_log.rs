@@ -0,0 +1,359 @@
+use anyhow::Result;
+use libsql::params;
+use serde::{Deserialize, Serialize};
+
+use super::operations::{get_setting, save_setting, DbConnection};
+use crate::sync::table_descriptors::{table_columns, TableSyncDescriptor, SYNC_TABLES};
+
+/// 已确认同步的变更日志序号
+const CHANGE_LOG_CURSOR_SETTING: &str = "change_log_cursor";
+
+/// 同一记录连续同步失败达到该次数后不再重试，留待全量同步处理
+pub const MAX_CHANGE_LOG_ATTEMPTS: i64 = 5;
+
+/// 当前时间（毫秒），供触发器使用
+const NOW_MS_SQL: &str = "CAST((julianday('now') - 2440587.5) * 86400000 AS INTEGER)";
+
+/// 变更日志条目
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeLogEntry {
+    pub seq: i64,
+    pub table_name: String,
+    /// 记录键，编码方式与同步描述一致
+    pub record_key: String,
+    /// INSERT / UPDATE / DELETE
+    pub operation: String,
+    pub changed_at: i64,
+}
+
+/// 创建变更日志表，并按同步描述为每张表重建触发器
+pub async fn create_change_log_schema(conn: &DbConnection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS change_log (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            table_name TEXT NOT NULL,
+            record_key TEXT NOT NULL,
+            operation TEXT NOT NULL,
+            changed_at INTEGER NOT NULL
+        )",
+        (),
+    ).await?;
+    // 同步失败的记录：游标照常推进，失败记录在这里重试，超过次数后保留作为死信
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS change_log_failures (
+            table_name TEXT NOT NULL,
+            record_key TEXT NOT NULL,
+            operation TEXT NOT NULL,
+            changed_at INTEGER NOT NULL,
+            attempts INTEGER NOT NULL,
+            last_error TEXT,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (table_name, record_key)
+        )",
+        (),
+    ).await?;
+    // 同步回写标记：只在回写事务内存在，触发器据此跳过同步自身的写入
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sync_applying (id INTEGER PRIMARY KEY CHECK (id = 1))",
+        (),
+    ).await?;
+
+    for descriptor in SYNC_TABLES {
+        let columns = table_columns(conn, descriptor.table).await?;
+        if columns.is_empty() {
+            continue;
+        }
+        for sql in change_log_trigger_sql(descriptor, &columns) {
+            conn.execute(&sql, ()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 记录键表达式：单列主键取原值，复合主键编码为 JSON 数组
+fn record_key_expr(descriptor: &TableSyncDescriptor, alias: &str) -> String {
+    if descriptor.key_columns.len() == 1 {
+        format!("CAST({}.\"{}\" AS TEXT)", alias, descriptor.key_columns[0])
+    } else {
+        let parts = descriptor.key_columns
+            .iter()
+            .map(|c| format!("CAST({}.\"{}\" AS TEXT)", alias, c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("json_array({})", parts)
+    }
+}
+
+/// 同步回写期间不记录变更
+const NOT_SYNC_APPLYING_SQL: &str = "NOT EXISTS (SELECT 1 FROM sync_applying)";
+
+/// 触发条件：跳过同步回写；有行过滤时触发器中无法直接引用列名，按 rowid 回查本行
+fn when_clause(descriptor: &TableSyncDescriptor, alias: &str) -> String {
+    match descriptor.row_filter {
+        Some(filter) => format!(
+            " WHEN EXISTS (SELECT 1 FROM {} WHERE rowid = {}.rowid AND {}) AND {}",
+            descriptor.table, alias, filter, NOT_SYNC_APPLYING_SQL
+        ),
+        None => format!(" WHEN {}", NOT_SYNC_APPLYING_SQL),
+    }
+}
+
+fn log_insert_sql(descriptor: &TableSyncDescriptor, alias: &str, operation: &str) -> String {
+    format!(
+        "INSERT INTO change_log (table_name, record_key, operation, changed_at) VALUES ('{}', {}, '{}', {});",
+        descriptor.table,
+        record_key_expr(descriptor, alias),
+        operation,
+        NOW_MS_SQL
+    )
+}
+
+/// 生成单表的变更日志触发器；更新只监听同步列，删除在有行过滤时改为 BEFORE 以便回查
+pub fn change_log_trigger_sql(descriptor: &TableSyncDescriptor, columns: &[String]) -> Vec<String> {
+    let table = descriptor.table;
+    let synced_columns = columns
+        .iter()
+        .filter(|c| descriptor.is_synced_column(c))
+        .map(|c| format!("\"{}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let key_changed = descriptor.key_columns
+        .iter()
+        .map(|c| format!("OLD.\"{0}\" IS NOT NEW.\"{0}\"", c))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    let delete_timing = if descriptor.row_filter.is_some() { "BEFORE" } else { "AFTER" };
+
+    vec![
+        format!("DROP TRIGGER IF EXISTS trg_change_log_{}_insert", table),
+        format!("DROP TRIGGER IF EXISTS trg_change_log_{}_update", table),
+        format!("DROP TRIGGER IF EXISTS trg_change_log_{}_delete", table),
+        format!(
+            "CREATE TRIGGER trg_change_log_{0}_insert AFTER INSERT ON {0}{1} BEGIN {2} END",
+            table,
+            when_clause(descriptor, "NEW"),
+            log_insert_sql(descriptor, "NEW", "INSERT")
+        ),
+        format!(
+            "CREATE TRIGGER trg_change_log_{0}_update AFTER UPDATE OF {1} ON {0}{2} BEGIN {3} \
+             INSERT INTO change_log (table_name, record_key, operation, changed_at) \
+             SELECT '{0}', {4}, 'DELETE', {5} WHERE {6}; END",
+            table,
+            synced_columns,
+            when_clause(descriptor, "NEW"),
+            log_insert_sql(descriptor, "NEW", "UPDATE"),
+            record_key_expr(descriptor, "OLD"),
+            NOW_MS_SQL,
+            key_changed
+        ),
+        format!(
+            "CREATE TRIGGER trg_change_log_{0}_delete {1} DELETE ON {0}{2} BEGIN {3} END",
+            table,
+            delete_timing,
+            when_clause(descriptor, "OLD"),
+            log_insert_sql(descriptor, "OLD", "DELETE")
+        ),
+    ]
+}
+
+/// 开始同步回写事务，事务内对同步表的写入不进入变更日志
+pub async fn begin_sync_apply(conn: &DbConnection) -> Result<()> {
+    conn.execute("BEGIN IMMEDIATE", ()).await?;
+    if let Err(e) = conn.execute("INSERT OR IGNORE INTO sync_applying (id) VALUES (1)", ()).await {
+        let _ = conn.execute("ROLLBACK", ()).await;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// 结束同步回写事务：写入成功时清除标记并提交，失败时回滚
+pub async fn finish_sync_apply<T>(conn: &DbConnection, result: Result<T>) -> Result<T> {
+    let value = match result {
+        Ok(value) => value,
+        Err(e) => {
+            let _ = conn.execute("ROLLBACK", ()).await;
+            return Err(e);
+        }
+    };
+    if let Err(e) = conn.execute("DELETE FROM sync_applying", ()).await {
+        let _ = conn.execute("ROLLBACK", ()).await;
+        return Err(e.into());
+    }
+    conn.execute("COMMIT", ()).await?;
+    Ok(value)
+}
+
+/// 读取序号大于 after_seq 的变更
+pub async fn read_change_log(conn: &DbConnection, after_seq: i64, limit: i64) -> Result<Vec<ChangeLogEntry>> {
+    let mut rows = conn.query(
+        "SELECT seq, table_name, record_key, operation, changed_at FROM change_log
+         WHERE seq > ?1 ORDER BY seq LIMIT ?2",
+        params![after_seq, limit],
+    ).await?;
+
+    let mut entries = Vec::new();
+    while let Some(row) = rows.next().await? {
+        entries.push(ChangeLogEntry {
+            seq: row.get(0)?,
+            table_name: row.get(1)?,
+            record_key: row.get(2)?,
+            operation: row.get(3)?,
+            changed_at: row.get(4)?,
+        });
+    }
+    Ok(entries)
+}
+
+/// 当前最大序号
+pub async fn max_change_seq(conn: &DbConnection) -> Result<i64> {
+    let mut rows = conn.query("SELECT COALESCE(MAX(seq), 0) FROM change_log", ()).await?;
+    match rows.next().await? {
+        Some(row) => Ok(row.get(0)?),
+        None => Ok(0),
+    }
+}
+
+/// 读取已确认的游标；从未同步过时返回 None
+pub async fn get_change_log_cursor(conn: &DbConnection) -> Result<Option<i64>> {
+    Ok(get_setting(conn, CHANGE_LOG_CURSOR_SETTING).await?.and_then(|v| v.parse().ok()))
+}
+
+/// 保存已确认的游标
+pub async fn save_change_log_cursor(conn: &DbConnection, seq: i64) -> Result<()> {
+    save_setting(conn, CHANGE_LOG_CURSOR_SETTING, &seq.to_string()).await
+}
+
+/// 压缩变更日志：删除已确认的条目，返回删除数量
+pub async fn compact_change_log(conn: &DbConnection, acknowledged_seq: i64) -> Result<u64> {
+    let deleted = conn.execute(
+        "DELETE FROM change_log WHERE seq <= ?1",
+        params![acknowledged_seq],
+    ).await?;
+    Ok(deleted)
+}
+
+/// 记录一次同步失败，返回该记录累计的失败次数
+pub async fn record_change_log_failure(
+    conn: &DbConnection,
+    table_name: &str,
+    record_key: &str,
+    operation: &str,
+    changed_at: i64,
+    error: &str,
+) -> Result<i64> {
+    conn.execute(
+        &format!(
+            "INSERT INTO change_log_failures (table_name, record_key, operation, changed_at, attempts, last_error, updated_at)
+             VALUES (?1, ?2, ?3, ?4, 1, ?5, {})
+             ON CONFLICT(table_name, record_key) DO UPDATE SET
+                operation = excluded.operation, changed_at = excluded.changed_at, attempts = attempts + 1,
+                last_error = excluded.last_error, updated_at = excluded.updated_at",
+            NOW_MS_SQL
+        ),
+        params![table_name, record_key, operation, changed_at, error],
+    ).await?;
+
+    let mut rows = conn.query(
+        "SELECT attempts FROM change_log_failures WHERE table_name = ?1 AND record_key = ?2",
+        params![table_name, record_key],
+    ).await?;
+    match rows.next().await? {
+        Some(row) => Ok(row.get(0)?),
+        None => Ok(0),
+    }
+}
+
+/// 记录同步成功后清除其失败记录
+pub async fn clear_change_log_failure(conn: &DbConnection, table_name: &str, record_key: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM change_log_failures WHERE table_name = ?1 AND record_key = ?2",
+        params![table_name, record_key],
+    ).await?;
+    Ok(())
+}
+
+/// 待重试的失败记录（未达到重试上限），按变更日志条目返回，序号为 0
+pub async fn pending_change_log_failures(conn: &DbConnection) -> Result<Vec<ChangeLogEntry>> {
+    let mut rows = conn.query(
+        "SELECT table_name, record_key, operation, changed_at FROM change_log_failures
+         WHERE attempts < ?1 ORDER BY changed_at",
+        params![MAX_CHANGE_LOG_ATTEMPTS],
+    ).await?;
+
+    let mut entries = Vec::new();
+    while let Some(row) = rows.next().await? {
+        entries.push(ChangeLogEntry {
+            seq: 0,
+            table_name: row.get(0)?,
+            record_key: row.get(1)?,
+            operation: row.get(2)?,
+            changed_at: row.get(3)?,
+        });
+    }
+    Ok(entries)
+}
+
+/// 全量同步完成后清除全部失败记录（包括死信）
+pub async fn clear_change_log_failures(conn: &DbConnection) -> Result<u64> {
+    Ok(conn.execute("DELETE FROM change_log_failures", ()).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::table_descriptors::descriptor_for;
+
+    fn columns(names: &[&str]) -> Vec<String> {
+        names.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn update_trigger_ignores_excluded_columns() {
+        let tips = descriptor_for("tips").unwrap();
+        let sql = change_log_trigger_sql(tips, &columns(&["id", "title", "sync_hash", "last_synced_at"]));
+        let update = sql.iter().find(|s| s.contains("_update AFTER UPDATE")).unwrap();
+        assert!(update.contains("UPDATE OF \"id\", \"title\" ON tips"));
+        assert!(!update.contains("sync_hash"));
+    }
+
+    #[test]
+    fn composite_keys_encode_as_json_array() {
+        let tip_tags = descriptor_for("tip_tags").unwrap();
+        assert_eq!(
+            record_key_expr(tip_tags, "NEW"),
+            "json_array(CAST(NEW.\"tip_id\" AS TEXT), CAST(NEW.\"tag_id\" AS TEXT))"
+        );
+    }
+
+    #[test]
+    fn filtered_tables_log_deletes_before_row_is_gone() {
+        let settings = descriptor_for("app_settings").unwrap();
+        let sql = change_log_trigger_sql(settings, &columns(&["key", "value", "created_at", "updated_at"]));
+        let delete = sql.iter().find(|s| s.contains("_delete ") && s.starts_with("CREATE")).unwrap();
+        assert!(delete.contains("BEFORE DELETE ON app_settings WHEN EXISTS"));
+        assert!(delete.contains("rowid = OLD.rowid"));
+        assert!(delete.contains("NOT EXISTS (SELECT 1 FROM sync_applying)"));
+    }
+
+    #[tokio::test]
+    async fn sync_apply_writes_are_not_logged() {
+        let db = libsql::Builder::new_local(":memory:").build().await.unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute(
+            "CREATE TABLE tags (id TEXT PRIMARY KEY, name TEXT NOT NULL, created_at INTEGER, updated_at INTEGER)",
+            (),
+        ).await.unwrap();
+        create_change_log_schema(&conn).await.unwrap();
+
+        conn.execute("INSERT INTO tags (id, name) VALUES ('a', 'local')", ()).await.unwrap();
+        begin_sync_apply(&conn).await.unwrap();
+        let applied = conn.execute("INSERT INTO tags (id, name) VALUES ('b', 'remote')", ()).await.map_err(Into::into);
+        finish_sync_apply(&conn, applied).await.unwrap();
+        conn.execute("UPDATE tags SET name = 'edited' WHERE id = 'b'", ()).await.unwrap();
+
+        let entries = read_change_log(&conn, 0, 10).await.unwrap();
+        let logged: Vec<(&str, &str)> = entries.iter().map(|e| (e.record_key.as_str(), e.operation.as_str())).collect();
+        assert_eq!(logged, vec![("a", "INSERT"), ("b", "UPDATE")]);
+    }
+}
@@ -0,0 +1,409 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use libsql::{params, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::models::TipSortOrder;
+use super::operations::{query_tip_summaries, DbConnection};
+use crate::api::tips::TipSummary;
+
+// 单个分块的最大字符数（按段落切分，超长段落再硬切）
+pub const EMBEDDING_CHUNK_CHARS: usize = 1200;
+
+// 倒数排名融合常数
+const RRF_K: f64 = 60.0;
+
+/// 待建立向量索引的笔记
+#[derive(Debug, Clone)]
+pub struct EmbeddingCandidate {
+    pub tip_id: String,
+    pub title: String,
+    pub content: String,
+    pub transcriptions: Vec<String>,
+}
+
+impl EmbeddingCandidate {
+    /// 内容哈希，包含模型名：换模型后需要重建
+    pub fn content_hash(&self, model: &str) -> String {
+        let mut hasher = blake3::Hasher::new();
+        for part in [model, &self.title, &self.content] {
+            hasher.update(part.as_bytes());
+            hasher.update(&[0]);
+        }
+        for transcription in &self.transcriptions {
+            hasher.update(transcription.as_bytes());
+            hasher.update(&[0]);
+        }
+        hasher.finalize().to_hex().to_string()
+    }
+
+    /// 切分为待嵌入的分块，每块带上标题作为上下文
+    pub fn chunks(&self) -> Vec<EmbeddingChunk> {
+        let mut chunks = Vec::new();
+        let mut push = |source: &str, text: String| {
+            let text = if self.title.trim().is_empty() { text } else { format!("{}\n{}", self.title.trim(), text) };
+            chunks.push(EmbeddingChunk { source: source.to_string(), text });
+        };
+
+        let body = chunk_text(&self.content, EMBEDDING_CHUNK_CHARS);
+        if body.is_empty() && !self.title.trim().is_empty() {
+            push("tip", String::new());
+        }
+        for text in body {
+            push("tip", text);
+        }
+        for transcription in &self.transcriptions {
+            for text in chunk_text(transcription, EMBEDDING_CHUNK_CHARS) {
+                push("audio", text);
+            }
+        }
+        chunks
+    }
+}
+
+/// 向量分块
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddingChunk {
+    pub source: String, // 'tip' 或 'audio'
+    pub text: String,
+}
+
+/// 向量索引状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingIndexStats {
+    pub model: Option<String>,
+    pub indexed_tips: i64,
+    pub total_chunks: i64,
+    pub total_tips: i64,
+}
+
+/// 语义检索命中
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticHit {
+    pub tip_id: String,
+    pub score: f32,            // 最相近分块的余弦相似度
+    pub chunk_text: String,    // 最相近的分块
+    pub source: String,        // 'tip' 或 'audio'
+}
+
+/// 混合检索结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticSearchResult {
+    pub tip: TipSummary,
+    pub score: f64,                     // 排序得分（纯语义时为相似度，混合时为融合得分）
+    pub semantic_score: Option<f32>,
+    pub keyword_rank: Option<usize>,    // 关键词结果中的名次（从 1 开始）
+    pub matched_chunk: Option<String>,
+}
+
+/// 按段落切分文本，段落超长时按字符硬切
+pub fn chunk_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_len = 0;
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let paragraph_len = paragraph.chars().count();
+        if current_len > 0 && current_len + paragraph_len + 2 > max_chars {
+            chunks.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        if paragraph_len > max_chars {
+            let chars: Vec<char> = paragraph.chars().collect();
+            for piece in chars.chunks(max_chars) {
+                chunks.push(piece.iter().collect());
+            }
+            continue;
+        }
+        if current_len > 0 {
+            current.push_str("\n\n");
+            current_len += 2;
+        }
+        current.push_str(paragraph);
+        current_len += paragraph_len;
+    }
+    if current_len > 0 {
+        chunks.push(current);
+    }
+    chunks
+}
+
+pub fn encode_vector(vector: &[f32]) -> Vec<u8> {
+    vector.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+pub fn decode_vector(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0f32, 0.0f32, 0.0f32);
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a.sqrt() * norm_b.sqrt())
+    }
+}
+
+/// 倒数排名融合：返回 (笔记ID, 得分)，按得分降序
+pub fn reciprocal_rank_fusion(rankings: &[Vec<String>]) -> Vec<(String, f64)> {
+    let mut scores: HashMap<&str, f64> = HashMap::new();
+    let mut order: Vec<&str> = Vec::new();
+    for ranking in rankings {
+        for (rank, id) in ranking.iter().enumerate() {
+            let score = scores.entry(id.as_str()).or_insert_with(|| {
+                order.push(id.as_str());
+                0.0
+            });
+            *score += 1.0 / (RRF_K + rank as f64 + 1.0);
+        }
+    }
+    let mut merged: Vec<(String, f64)> = order.into_iter().map(|id| (id.to_string(), scores[id])).collect();
+    merged.sort_by(|a, b| b.1.total_cmp(&a.1));
+    merged
+}
+
+/// 列出内容可能已变化、需要重新嵌入的笔记（已加密和空白笔记不建索引）
+pub async fn list_embedding_candidates(conn: &DbConnection, model: &str, limit: i32) -> Result<Vec<EmbeddingCandidate>> {
+    let mut rows = conn.query(
+        "SELECT t.id, t.title, t.content,
+                (SELECT group_concat(a.transcription, char(10) || char(10)) FROM tip_audio_files a
+                 WHERE a.tip_id = t.id AND a.deleted_at IS NULL AND COALESCE(a.transcription, '') != '')
+         FROM tips t
+         WHERE t.deleted_at IS NULL AND COALESCE(t.is_encrypted, 0) = 0
+           AND (TRIM(COALESCE(t.title, '')) != '' OR TRIM(COALESCE(t.content, '')) != '')
+           AND NOT EXISTS (
+               SELECT 1 FROM tip_embeddings e
+               WHERE e.tip_id = t.id AND e.model = ?1
+                 AND e.indexed_at >= t.updated_at
+                 AND e.indexed_at >= COALESCE((SELECT MAX(a.updated_at) FROM tip_audio_files a WHERE a.tip_id = t.id), 0)
+           )
+         ORDER BY t.updated_at DESC
+         LIMIT ?2",
+        params![model, limit],
+    ).await?;
+
+    let mut candidates = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let transcriptions: Option<String> = row.get(3)?;
+        candidates.push(EmbeddingCandidate {
+            tip_id: row.get(0)?,
+            title: row.get(1)?,
+            content: row.get(2)?,
+            transcriptions: transcriptions.into_iter().collect(),
+        });
+    }
+    Ok(candidates)
+}
+
+/// 已存储的内容哈希
+pub async fn get_embedding_hash(conn: &DbConnection, tip_id: &str, model: &str) -> Result<Option<String>> {
+    let mut rows = conn.query(
+        "SELECT sync_hash FROM tip_embeddings WHERE tip_id = ?1 AND model = ?2 LIMIT 1",
+        params![tip_id, model],
+    ).await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+/// 内容未变化时只刷新索引时间
+pub async fn touch_tip_embeddings(conn: &DbConnection, tip_id: &str, model: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE tip_embeddings SET indexed_at = ?1 WHERE tip_id = ?2 AND model = ?3",
+        params![Utc::now().timestamp_millis(), tip_id, model],
+    ).await?;
+    Ok(())
+}
+
+/// 替换笔记的全部向量
+pub async fn replace_tip_embeddings(
+    conn: &DbConnection,
+    tip_id: &str,
+    model: &str,
+    content_hash: &str,
+    chunks: &[EmbeddingChunk],
+    vectors: &[Vec<f32>],
+) -> Result<()> {
+    if chunks.len() != vectors.len() {
+        return Err(anyhow!("Expected {} embeddings, got {}", chunks.len(), vectors.len()));
+    }
+
+    let now = Utc::now().timestamp_millis();
+    conn.execute("BEGIN TRANSACTION", ()).await?;
+    let result = async {
+        conn.execute("DELETE FROM tip_embeddings WHERE tip_id = ?1", params![tip_id]).await?;
+        for (index, (chunk, vector)) in chunks.iter().zip(vectors).enumerate() {
+            conn.execute(
+                "INSERT INTO tip_embeddings (tip_id, chunk_index, source, chunk_text, model, sync_hash, dimensions, vector, indexed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    tip_id,
+                    index as i64,
+                    chunk.source.as_str(),
+                    chunk.text.as_str(),
+                    model,
+                    content_hash,
+                    vector.len() as i64,
+                    Value::Blob(encode_vector(vector)),
+                    now
+                ],
+            ).await?;
+        }
+        Ok::<(), anyhow::Error>(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            conn.execute("COMMIT", ()).await?;
+            Ok(())
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", ()).await?;
+            Err(e)
+        }
+    }
+}
+
+/// 清除其他模型和已删除笔记的向量
+pub async fn prune_tip_embeddings(conn: &DbConnection, model: &str) -> Result<u64> {
+    let deleted = conn.execute(
+        "DELETE FROM tip_embeddings WHERE model != ?1
+            OR tip_id NOT IN (SELECT id FROM tips WHERE deleted_at IS NULL AND COALESCE(is_encrypted, 0) = 0)",
+        params![model],
+    ).await?;
+    Ok(deleted)
+}
+
+/// 清空向量索引（强制重建）
+pub async fn clear_tip_embeddings(conn: &DbConnection) -> Result<()> {
+    conn.execute("DELETE FROM tip_embeddings", ()).await?;
+    Ok(())
+}
+
+/// 向量索引统计
+pub async fn get_embedding_index_stats(conn: &DbConnection, model: Option<&str>) -> Result<EmbeddingIndexStats> {
+    let row = conn.query(
+        "SELECT
+            (SELECT COUNT(DISTINCT tip_id) FROM tip_embeddings WHERE model = ?1),
+            (SELECT COUNT(*) FROM tip_embeddings WHERE model = ?1),
+            (SELECT COUNT(*) FROM tips WHERE deleted_at IS NULL AND COALESCE(is_encrypted, 0) = 0)",
+        params![model.unwrap_or_default()],
+    ).await?
+    .next().await?
+    .ok_or_else(|| anyhow!("Failed to read embedding stats"))?;
+
+    Ok(EmbeddingIndexStats {
+        model: model.map(str::to_string),
+        indexed_tips: row.get(0)?,
+        total_chunks: row.get(1)?,
+        total_tips: row.get(2)?,
+    })
+}
+
+/// 暴力计算余弦相似度，每篇笔记取最相近的分块，返回前 top_k 个
+pub async fn semantic_search(conn: &DbConnection, model: &str, query: &[f32], top_k: usize) -> Result<Vec<SemanticHit>> {
+    let mut rows = conn.query(
+        "SELECT e.tip_id, e.chunk_text, e.source, e.vector
+         FROM tip_embeddings e
+         JOIN tips t ON t.id = e.tip_id
+         WHERE e.model = ?1 AND e.dimensions = ?2 AND t.deleted_at IS NULL",
+        params![model, query.len() as i64],
+    ).await?;
+
+    let mut best: HashMap<String, SemanticHit> = HashMap::new();
+    while let Some(row) = rows.next().await? {
+        let tip_id: String = row.get(0)?;
+        let vector: Vec<u8> = row.get(3)?;
+        let score = cosine_similarity(query, &decode_vector(&vector));
+        if best.get(&tip_id).is_some_and(|hit| hit.score >= score) {
+            continue;
+        }
+        best.insert(tip_id.clone(), SemanticHit {
+            tip_id,
+            score,
+            chunk_text: row.get(1)?,
+            source: row.get(2)?,
+        });
+    }
+
+    let mut hits: Vec<SemanticHit> = best.into_values().collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(top_k);
+    Ok(hits)
+}
+
+/// 按ID批量获取笔记摘要，保持传入顺序
+pub async fn get_tip_summaries_by_ids(conn: &DbConnection, ids: &[String]) -> Result<Vec<TipSummary>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders: Vec<String> = (0..ids.len()).map(|i| format!("?{}", i + 3)).collect();
+    let filter = format!("id IN ({}) AND deleted_at IS NULL", placeholders.join(", "));
+    let params = ids.iter().map(|id| Value::Text(id.clone())).collect();
+    let mut summaries = query_tip_summaries(conn, &filter, params, TipSortOrder::default(), ids.len() as i32, 0).await?;
+
+    let position: HashMap<&str, usize> = ids.iter().enumerate().map(|(i, id)| (id.as_str(), i)).collect();
+    summaries.sort_by_key(|s| position.get(s.id.as_str()).copied().unwrap_or(usize::MAX));
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_by_paragraph() {
+        let text = "aaaa\n\nbbbb\n\ncccccccccc";
+        assert_eq!(chunk_text(text, 10), vec!["aaaa\n\nbbbb", "cccccccccc"]);
+        assert_eq!(chunk_text("abcdefg", 3), vec!["abc", "def", "g"]);
+        assert!(chunk_text("  \n\n ", 10).is_empty());
+    }
+
+    #[test]
+    fn vector_round_trip_and_cosine() {
+        let v = vec![1.0f32, -2.5, 0.25];
+        assert_eq!(decode_vector(&encode_vector(&v)), v);
+        assert!((cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn fuses_rankings() {
+        let semantic = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let keyword = vec!["c".to_string(), "b".to_string()];
+        let merged = reciprocal_rank_fusion(&[semantic, keyword]);
+        let ids: Vec<&str> = merged.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn hash_changes_with_model_and_transcription() {
+        let mut candidate = EmbeddingCandidate {
+            tip_id: "t".into(),
+            title: "Title".into(),
+            content: "Body".into(),
+            transcriptions: Vec::new(),
+        };
+        let hash = candidate.content_hash("m1");
+        assert_ne!(hash, candidate.content_hash("m2"));
+        candidate.transcriptions.push("spoken".into());
+        assert_ne!(hash, candidate.content_hash("m1"));
+        assert_eq!(candidate.chunks().last().unwrap().source, "audio");
+    }
+}
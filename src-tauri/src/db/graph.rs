@@ -0,0 +1,408 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use super::operations::DbConnection;
+
+/// 图节点类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphNodeKind {
+    Tip,
+    Tag,
+    Category,
+}
+
+/// 图边类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphEdgeKind {
+    Link,   // 笔记 -> 笔记（wiki 链接）
+    Tagged, // 笔记 -> 标签
+    Parent, // 笔记 -> 所在笔记本，子笔记本 -> 父笔记本
+}
+
+/// 图节点，id 形如 `tip:<id>` / `tag:<id>` / `category:<id>`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub id: String,
+    pub kind: GraphNodeKind,
+    pub ref_id: String,
+    pub label: String,
+    pub degree: usize,
+    pub component: usize, // 连通分量编号，0 为最大分量
+}
+
+/// 图边（有向，统计度数和连通性时按无向处理）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub source: String,
+    pub target: String,
+    pub kind: GraphEdgeKind,
+    pub weight: i64, // 同一对笔记之间的链接数
+}
+
+/// 图统计
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GraphStats {
+    pub node_count: usize,
+    pub edge_count: usize,
+    pub component_count: usize,
+    pub largest_component_size: usize,
+    pub max_degree: usize,
+    pub average_degree: f64,
+    pub isolated_node_count: usize,
+    pub orphan_tip_ids: Vec<String>, // 不属于任何笔记本且没有其他笔记链接到的笔记
+}
+
+/// 笔记关系图
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteGraph {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+    pub stats: GraphStats,
+}
+
+/// 图查询选项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphOptions {
+    pub center_tip_id: Option<String>, // 为空时返回整个数据库的图
+    pub depth: usize,                  // 以 center_tip_id 为中心的跳数
+    pub include_tags: bool,
+    pub include_categories: bool,
+}
+
+impl Default for GraphOptions {
+    fn default() -> Self {
+        Self {
+            center_tip_id: None,
+            depth: 1,
+            include_tags: true,
+            include_categories: true,
+        }
+    }
+}
+
+/// 构图所需的原始数据（已排除回收站中的条目）
+#[derive(Debug, Clone, Default)]
+pub struct GraphData {
+    pub tips: Vec<(String, String, Option<String>)>,       // id, title, category_id
+    pub categories: Vec<(String, String, Option<String>)>, // id, name, parent_id
+    pub tags: Vec<(String, String)>,                       // id, name
+    pub tip_tags: Vec<(String, String)>,                   // tip_id, tag_id
+    pub links: Vec<(String, String, i64)>,                 // source_tip_id, target_tip_id, count
+}
+
+fn node_key(kind: GraphNodeKind, id: &str) -> String {
+    match kind {
+        GraphNodeKind::Tip => format!("tip:{}", id),
+        GraphNodeKind::Tag => format!("tag:{}", id),
+        GraphNodeKind::Category => format!("category:{}", id),
+    }
+}
+
+/// 从数据库读取构图数据
+pub async fn load_graph_data(conn: &DbConnection) -> Result<GraphData> {
+    let mut data = GraphData::default();
+
+    let mut rows = conn.query(
+        "SELECT id, title, category_id FROM tips WHERE deleted_at IS NULL",
+        (),
+    ).await?;
+    while let Some(row) = rows.next().await? {
+        data.tips.push((row.get(0)?, row.get(1)?, row.get(2)?));
+    }
+
+    let mut rows = conn.query(
+        "SELECT id, name, parent_id FROM categories WHERE deleted_at IS NULL",
+        (),
+    ).await?;
+    while let Some(row) = rows.next().await? {
+        data.categories.push((row.get(0)?, row.get(1)?, row.get(2)?));
+    }
+
+    let mut rows = conn.query("SELECT id, name FROM tags", ()).await?;
+    while let Some(row) = rows.next().await? {
+        data.tags.push((row.get(0)?, row.get(1)?));
+    }
+
+    let mut rows = conn.query(
+        "SELECT tt.tip_id, tt.tag_id FROM tip_tags tt
+         JOIN tips t ON t.id = tt.tip_id
+         WHERE t.deleted_at IS NULL",
+        (),
+    ).await?;
+    while let Some(row) = rows.next().await? {
+        data.tip_tags.push((row.get(0)?, row.get(1)?));
+    }
+
+    let mut rows = conn.query(
+        "SELECT l.source_tip_id, l.target_tip_id, COUNT(*) FROM tip_links l
+         JOIN tips s ON s.id = l.source_tip_id
+         JOIN tips t ON t.id = l.target_tip_id
+         WHERE s.deleted_at IS NULL AND t.deleted_at IS NULL
+         GROUP BY l.source_tip_id, l.target_tip_id",
+        (),
+    ).await?;
+    while let Some(row) = rows.next().await? {
+        data.links.push((row.get(0)?, row.get(1)?, row.get(2)?));
+    }
+
+    Ok(data)
+}
+
+/// 根据原始数据构建关系图并计算统计信息
+pub fn build_graph(data: &GraphData, options: &GraphOptions) -> Result<NoteGraph> {
+    let mut labels: BTreeMap<String, (GraphNodeKind, String, String)> = BTreeMap::new();
+    let mut edges: Vec<GraphEdge> = Vec::new();
+
+    let category_ids: HashSet<&str> = data.categories.iter().map(|(id, _, _)| id.as_str()).collect();
+    let tip_ids: HashSet<&str> = data.tips.iter().map(|(id, _, _)| id.as_str()).collect();
+    let tag_ids: HashSet<&str> = data.tags.iter().map(|(id, _)| id.as_str()).collect();
+
+    for (id, title, category_id) in &data.tips {
+        labels.insert(node_key(GraphNodeKind::Tip, id), (GraphNodeKind::Tip, id.clone(), title.clone()));
+        if options.include_categories {
+            if let Some(category_id) = category_id.as_deref().filter(|c| category_ids.contains(c)) {
+                edges.push(GraphEdge {
+                    source: node_key(GraphNodeKind::Tip, id),
+                    target: node_key(GraphNodeKind::Category, category_id),
+                    kind: GraphEdgeKind::Parent,
+                    weight: 1,
+                });
+            }
+        }
+    }
+
+    if options.include_categories {
+        for (id, name, parent_id) in &data.categories {
+            labels.insert(node_key(GraphNodeKind::Category, id), (GraphNodeKind::Category, id.clone(), name.clone()));
+            if let Some(parent_id) = parent_id.as_deref().filter(|p| category_ids.contains(p)) {
+                edges.push(GraphEdge {
+                    source: node_key(GraphNodeKind::Category, id),
+                    target: node_key(GraphNodeKind::Category, parent_id),
+                    kind: GraphEdgeKind::Parent,
+                    weight: 1,
+                });
+            }
+        }
+    }
+
+    if options.include_tags {
+        let tag_names: HashMap<&str, &str> = data.tags.iter().map(|(id, name)| (id.as_str(), name.as_str())).collect();
+        for (tip_id, tag_id) in &data.tip_tags {
+            if !tip_ids.contains(tip_id.as_str()) || !tag_ids.contains(tag_id.as_str()) {
+                continue;
+            }
+            // 只收录被使用的标签
+            labels.entry(node_key(GraphNodeKind::Tag, tag_id)).or_insert_with(|| {
+                (GraphNodeKind::Tag, tag_id.clone(), tag_names[tag_id.as_str()].to_string())
+            });
+            edges.push(GraphEdge {
+                source: node_key(GraphNodeKind::Tip, tip_id),
+                target: node_key(GraphNodeKind::Tag, tag_id),
+                kind: GraphEdgeKind::Tagged,
+                weight: 1,
+            });
+        }
+    }
+
+    for (source, target, count) in &data.links {
+        if source == target || !tip_ids.contains(source.as_str()) || !tip_ids.contains(target.as_str()) {
+            continue;
+        }
+        edges.push(GraphEdge {
+            source: node_key(GraphNodeKind::Tip, source),
+            target: node_key(GraphNodeKind::Tip, target),
+            kind: GraphEdgeKind::Link,
+            weight: *count,
+        });
+    }
+
+    // 孤立笔记：基于完整数据判断，与是否展示笔记本无关
+    let linked_targets: HashSet<&str> = data.links.iter()
+        .filter(|(source, target, _)| source != target)
+        .map(|(_, target, _)| target.as_str())
+        .collect();
+    let orphan_tips: HashSet<String> = data.tips.iter()
+        .filter(|(id, _, category_id)| {
+            !category_id.as_deref().is_some_and(|c| category_ids.contains(c))
+                && !linked_targets.contains(id.as_str())
+        })
+        .map(|(id, _, _)| node_key(GraphNodeKind::Tip, id))
+        .collect();
+
+    // 邻域查询：按无向图做 BFS，只保留范围内的节点和边
+    if let Some(center) = &options.center_tip_id {
+        let center_key = node_key(GraphNodeKind::Tip, center);
+        if !labels.contains_key(&center_key) {
+            return Err(anyhow!("Tip not found: {}", center));
+        }
+
+        let adjacency = adjacency_of(&edges);
+        let mut visited: HashSet<String> = HashSet::from([center_key.clone()]);
+        let mut queue = VecDeque::from([(center_key, 0usize)]);
+        while let Some((node, distance)) = queue.pop_front() {
+            if distance >= options.depth {
+                continue;
+            }
+            for next in adjacency.get(node.as_str()).into_iter().flatten() {
+                if visited.insert(next.to_string()) {
+                    queue.push_back((next.to_string(), distance + 1));
+                }
+            }
+        }
+
+        labels.retain(|key, _| visited.contains(key));
+        edges.retain(|edge| visited.contains(&edge.source) && visited.contains(&edge.target));
+    }
+
+    // 度数与连通分量
+    let adjacency = adjacency_of(&edges);
+    let mut component_of: HashMap<&str, usize> = HashMap::new();
+    let mut component_sizes: Vec<usize> = Vec::new();
+    for key in labels.keys() {
+        if component_of.contains_key(key.as_str()) {
+            continue;
+        }
+        let component = component_sizes.len();
+        let mut size = 0;
+        let mut stack = vec![key.as_str()];
+        component_of.insert(key.as_str(), component);
+        while let Some(node) = stack.pop() {
+            size += 1;
+            for &next in adjacency.get(node).into_iter().flatten() {
+                if !component_of.contains_key(next) {
+                    component_of.insert(next, component);
+                    stack.push(next);
+                }
+            }
+        }
+        component_sizes.push(size);
+    }
+
+    // 按分量大小重新编号，0 为最大分量
+    let mut order: Vec<usize> = (0..component_sizes.len()).collect();
+    order.sort_by(|a, b| component_sizes[*b].cmp(&component_sizes[*a]).then(a.cmp(b)));
+    let mut renumber = vec![0; order.len()];
+    for (new_index, old_index) in order.iter().enumerate() {
+        renumber[*old_index] = new_index;
+    }
+
+    let mut degrees: HashMap<&str, usize> = HashMap::new();
+    for edge in &edges {
+        *degrees.entry(edge.source.as_str()).or_default() += 1;
+        *degrees.entry(edge.target.as_str()).or_default() += 1;
+    }
+
+    let mut nodes: Vec<GraphNode> = labels.iter()
+        .map(|(key, (kind, ref_id, label))| GraphNode {
+            id: key.clone(),
+            kind: *kind,
+            ref_id: ref_id.clone(),
+            label: label.clone(),
+            degree: degrees.get(key.as_str()).copied().unwrap_or(0),
+            component: renumber[component_of[key.as_str()]],
+        })
+        .collect();
+    nodes.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.label.cmp(&b.label)).then_with(|| a.id.cmp(&b.id)));
+
+    let mut orphan_tip_ids: Vec<String> = nodes.iter()
+        .filter(|node| orphan_tips.contains(&node.id))
+        .map(|node| node.ref_id.clone())
+        .collect();
+    orphan_tip_ids.sort();
+
+    let stats = GraphStats {
+        node_count: nodes.len(),
+        edge_count: edges.len(),
+        component_count: component_sizes.len(),
+        largest_component_size: component_sizes.iter().copied().max().unwrap_or(0),
+        max_degree: nodes.iter().map(|node| node.degree).max().unwrap_or(0),
+        average_degree: if nodes.is_empty() {
+            0.0
+        } else {
+            (2 * edges.len()) as f64 / nodes.len() as f64
+        },
+        isolated_node_count: nodes.iter().filter(|node| node.degree == 0).count(),
+        orphan_tip_ids,
+    };
+
+    Ok(NoteGraph { nodes, edges, stats })
+}
+
+/// 无向邻接表
+fn adjacency_of(edges: &[GraphEdge]) -> HashMap<&str, Vec<&str>> {
+    let mut adjacency: HashMap<&str, Vec<&str>> = HashMap::new();
+    for edge in edges {
+        adjacency.entry(edge.source.as_str()).or_default().push(edge.target.as_str());
+        adjacency.entry(edge.target.as_str()).or_default().push(edge.source.as_str());
+    }
+    adjacency
+}
+
+/// 获取笔记关系图
+pub async fn get_note_graph(conn: &DbConnection, options: &GraphOptions) -> Result<NoteGraph> {
+    let data = load_graph_data(conn).await?;
+    build_graph(&data, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> GraphData {
+        GraphData {
+            tips: vec![
+                ("a".into(), "A".into(), Some("c1".into())),
+                ("b".into(), "B".into(), None),
+                ("c".into(), "C".into(), None),
+                ("d".into(), "D".into(), None),
+            ],
+            categories: vec![("c1".into(), "Work".into(), None)],
+            tags: vec![("t1".into(), "rust".into()), ("t2".into(), "unused".into())],
+            tip_tags: vec![("c".into(), "t1".into())],
+            links: vec![("a".into(), "b".into(), 2)],
+        }
+    }
+
+    #[test]
+    fn builds_whole_graph_with_stats() {
+        let graph = build_graph(&sample(), &GraphOptions::default()).unwrap();
+        // 4 笔记 + 1 笔记本 + 1 使用中的标签
+        assert_eq!(graph.stats.node_count, 6);
+        assert_eq!(graph.stats.edge_count, 3);
+        // {a, b, c1}, {c, t1}, {d}
+        assert_eq!(graph.stats.component_count, 3);
+        assert_eq!(graph.stats.largest_component_size, 3);
+        assert_eq!(graph.stats.isolated_node_count, 1);
+        // c 和 d 既不在笔记本中也没有入链
+        assert_eq!(graph.stats.orphan_tip_ids, vec!["c".to_string(), "d".to_string()]);
+
+        let a = graph.nodes.iter().find(|n| n.id == "tip:a").unwrap();
+        assert_eq!(a.degree, 2);
+        assert_eq!(a.component, 0);
+    }
+
+    #[test]
+    fn neighborhood_respects_depth() {
+        let mut data = sample();
+        data.links.push(("b".into(), "d".into(), 1));
+
+        let options = GraphOptions {
+            center_tip_id: Some("a".into()),
+            depth: 1,
+            include_tags: false,
+            include_categories: false,
+        };
+        let graph = build_graph(&data, &options).unwrap();
+        let ids: Vec<&str> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["tip:a", "tip:b"]);
+
+        let graph = build_graph(&data, &GraphOptions { depth: 2, ..options.clone() }).unwrap();
+        assert_eq!(graph.stats.node_count, 3);
+        assert_eq!(graph.stats.component_count, 1);
+
+        assert!(build_graph(&data, &GraphOptions { center_tip_id: Some("x".into()), ..options }).is_err());
+    }
+}
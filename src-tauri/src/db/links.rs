@@ -0,0 +1,402 @@
+use anyhow::Result;
+use chrono::Utc;
+use libsql::{params, Connection};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::operations::{get_setting, get_tip_by_id, save_setting, DbConnection};
+use super::revisions::record_tip_revision;
+
+// 链接索引版本，解析规则变化时递增以触发重建
+const TIP_LINKS_VERSION: &str = "1";
+const TIP_LINKS_VERSION_KEY: &str = "tip_links_version";
+
+// 反向链接上下文前后保留的字符数
+const CONTEXT_CHARS: i64 = 60;
+
+/// 正文中的 `[[目标]]` / `[[目标|别名]]` 链接，偏移为字节偏移（含方括号）
+#[derive(Debug, Clone, PartialEq)]
+pub struct WikiLink {
+    pub target: String,
+    pub alias: Option<String>,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 链接状态
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkStatus {
+    Resolved, // 指向存在的笔记
+    Dangling, // 找不到目标笔记
+    Trashed,  // 目标笔记在回收站中
+}
+
+/// 笔记链接
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TipLink {
+    pub source_tip_id: String,
+    pub source_title: String,
+    pub target_tip_id: Option<String>,
+    pub target_title: Option<String>,
+    pub target_text: String,   // 链接中书写的目标（标题或ID）
+    pub alias: Option<String>,
+    pub position: i64,         // 链接在源笔记正文中的字符偏移
+    pub context: String,       // 链接附近的正文片段
+    pub status: LinkStatus,
+}
+
+/// 解析正文中的 wiki 链接，忽略代码块和行内代码中的内容
+pub fn parse_wiki_links(content: &str) -> Vec<WikiLink> {
+    let mut links = Vec::new();
+    let mut in_fence = false;
+    let mut line_start = 0;
+
+    for line in content.split_inclusive('\n') {
+        let offset = line_start;
+        line_start += line.len();
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        let bytes = line.as_bytes();
+        let mut in_code = false;
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == b'`' {
+                in_code = !in_code;
+                i += 1;
+                continue;
+            }
+            if in_code || bytes[i] != b'[' || bytes.get(i + 1) != Some(&b'[') {
+                i += 1;
+                continue;
+            }
+
+            let inner_start = i + 2;
+            let close = match line[inner_start..].find("]]") {
+                Some(pos) => inner_start + pos,
+                None => break,
+            };
+            let inner = &line[inner_start..close];
+            if inner.contains('[') || inner.contains('\n') {
+                i += 1;
+                continue;
+            }
+
+            let (target, alias) = match inner.split_once('|') {
+                Some((target, alias)) => (target.trim(), Some(alias.trim())),
+                None => (inner.trim(), None),
+            };
+            if !target.is_empty() {
+                links.push(WikiLink {
+                    target: target.to_string(),
+                    alias: alias.filter(|a| !a.is_empty()).map(|a| a.to_string()),
+                    start: offset + i,
+                    end: offset + close + 2,
+                });
+            }
+            i = close + 2;
+        }
+    }
+
+    links
+}
+
+/// 标题能否直接写入链接而不破坏语法
+fn is_link_safe_title(title: &str) -> bool {
+    !title.trim().is_empty() && !title.contains(['[', ']', '|', '\n'])
+}
+
+/// 将指向旧标题的链接改写为新标题，保留别名；新标题无法写入链接时改用笔记ID
+pub fn rewrite_wiki_links(content: &str, old_title: &str, new_title: &str, tip_id: &str) -> Option<String> {
+    let old_title = old_title.trim();
+    let mut result = String::with_capacity(content.len());
+    let mut last = 0;
+
+    for link in parse_wiki_links(content) {
+        if !link.target.eq_ignore_ascii_case(old_title) {
+            continue;
+        }
+
+        let replacement = if is_link_safe_title(new_title) {
+            match &link.alias {
+                Some(alias) => format!("[[{}|{}]]", new_title.trim(), alias),
+                None => format!("[[{}]]", new_title.trim()),
+            }
+        } else {
+            let display = link.alias.clone().unwrap_or_else(|| link.target.clone());
+            format!("[[{}|{}]]", tip_id, display)
+        };
+
+        result.push_str(&content[last..link.start]);
+        result.push_str(&replacement);
+        last = link.end;
+    }
+
+    if last == 0 {
+        return None;
+    }
+    result.push_str(&content[last..]);
+    Some(result)
+}
+
+/// 解析链接目标：优先按笔记ID，其次按标题（不区分大小写，取最近更新的未删除笔记）
+async fn resolve_link_target(conn: &DbConnection, target: &str) -> Result<Option<String>> {
+    let mut rows = conn.query("SELECT id FROM tips WHERE id = ?1", params![target]).await?;
+    if let Some(row) = rows.next().await? {
+        return Ok(Some(row.get(0)?));
+    }
+
+    let mut rows = conn.query(
+        "SELECT id FROM tips WHERE title = ?1 COLLATE NOCASE AND deleted_at IS NULL
+         ORDER BY updated_at DESC LIMIT 1",
+        params![target],
+    ).await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+/// 重新解析笔记正文并写入链接表，返回链接数
+pub async fn update_tip_links(conn: &DbConnection, tip_id: &str, content: &str) -> Result<usize> {
+    conn.execute("DELETE FROM tip_links WHERE source_tip_id = ?1", params![tip_id]).await?;
+
+    let links = parse_wiki_links(content);
+    let now = Utc::now().timestamp_millis();
+    for link in &links {
+        let target_tip_id = resolve_link_target(conn, &link.target).await?;
+        let position = content[..link.start].chars().count() as i64;
+        let length = content[link.start..link.end].chars().count() as i64;
+        conn.execute(
+            "INSERT INTO tip_links (id, source_tip_id, target_tip_id, target_text, alias, position, length, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                Uuid::new_v4().to_string(),
+                tip_id,
+                target_tip_id,
+                link.target.as_str(),
+                link.alias.as_deref(),
+                position,
+                length,
+                now
+            ],
+        ).await?;
+    }
+
+    Ok(links.len())
+}
+
+/// 笔记保存后维护链接：解析出链、处理重命名、解析指向新标题的悬空链接
+///
+/// 返回因重命名而被改写正文的其他笔记ID。
+pub async fn refresh_tip_links(conn: &DbConnection, tip_id: &str, previous_title: Option<&str>) -> Result<Vec<String>> {
+    let tip = match get_tip_by_id(conn, tip_id).await? {
+        Some(tip) => tip,
+        None => return Ok(Vec::new()),
+    };
+
+    let content = if tip.is_encrypted.unwrap_or(false) { "" } else { tip.content.as_str() };
+    update_tip_links(conn, tip_id, content).await?;
+
+    let mut rewritten = Vec::new();
+    if let Some(old_title) = previous_title {
+        if old_title.trim() != tip.title.trim() {
+            rewritten = rewrite_links_after_rename(conn, tip_id, old_title, &tip.title).await?;
+        }
+    }
+
+    conn.execute(
+        "UPDATE tip_links SET target_tip_id = ?1 WHERE target_tip_id IS NULL AND target_text = ?2 COLLATE NOCASE",
+        params![tip_id, tip.title.trim()],
+    ).await?;
+
+    Ok(rewritten)
+}
+
+/// 改写其他笔记中按旧标题指向该笔记的链接，在一个事务中完成，返回被改写的笔记 ID
+async fn rewrite_links_after_rename(conn: &DbConnection, tip_id: &str, old_title: &str, new_title: &str) -> Result<Vec<String>> {
+    conn.execute("BEGIN IMMEDIATE", ()).await?;
+    match rewrite_linking_tips(conn, tip_id, old_title, new_title).await {
+        Ok(rewritten) => {
+            conn.execute("COMMIT", ()).await?;
+            if !rewritten.is_empty() {
+                tracing::info!("Rewrote links to renamed tip {} in {} tips", tip_id, rewritten.len());
+            }
+            Ok(rewritten)
+        }
+        Err(e) => {
+            let _ = conn.execute("ROLLBACK", ()).await;
+            Err(e)
+        }
+    }
+}
+
+async fn rewrite_linking_tips(conn: &DbConnection, tip_id: &str, old_title: &str, new_title: &str) -> Result<Vec<String>> {
+    let mut rows = conn.query(
+        "SELECT DISTINCT source_tip_id FROM tip_links
+         WHERE target_tip_id = ?1 AND target_text = ?2 COLLATE NOCASE AND source_tip_id != ?1",
+        params![tip_id, old_title.trim()],
+    ).await?;
+    let mut source_ids: Vec<String> = Vec::new();
+    while let Some(row) = rows.next().await? {
+        source_ids.push(row.get(0)?);
+    }
+
+    let mut rewritten = Vec::new();
+    for source_id in source_ids {
+        let source = match get_tip_by_id(conn, &source_id).await? {
+            Some(source) if !source.is_encrypted.unwrap_or(false) => source,
+            _ => continue,
+        };
+        let content = match rewrite_wiki_links(&source.content, old_title, new_title, tip_id) {
+            Some(content) => content,
+            None => continue,
+        };
+
+        conn.execute(
+            "UPDATE tips SET content = ?1, updated_at = ?2 WHERE id = ?3",
+            params![content.as_str(), Utc::now().timestamp_millis(), source_id.as_str()],
+        ).await?;
+        update_tip_links(conn, &source_id, &content).await?;
+        if let Err(e) = record_tip_revision(conn, &source_id, "link_rename").await {
+            tracing::warn!("Failed to record revision for tip {}: {}", source_id, e);
+        }
+        rewritten.push(source_id);
+    }
+    Ok(rewritten)
+}
+
+/// 链接查询的公共部分
+const LINK_SELECT: &str =
+    "SELECT l.source_tip_id, s.title, l.target_tip_id, t.title, t.deleted_at, l.target_text, l.alias, l.position,
+            substr(s.content, MAX(1, l.position + 1 - ?1), l.length + 2 * ?1)
+     FROM tip_links l
+     JOIN tips s ON s.id = l.source_tip_id
+     LEFT JOIN tips t ON t.id = l.target_tip_id";
+
+async fn query_links(conn: &DbConnection, filter: &str, params: Vec<libsql::Value>) -> Result<Vec<TipLink>> {
+    let sql = format!("{} WHERE s.deleted_at IS NULL AND ({}) ORDER BY s.updated_at DESC, l.position", LINK_SELECT, filter);
+    let mut all_params = vec![libsql::Value::Integer(CONTEXT_CHARS)];
+    all_params.extend(params);
+
+    let mut rows = conn.query(&sql, libsql::params_from_iter(all_params)).await?;
+    let mut links = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let target_tip_id: Option<String> = row.get(2)?;
+        let target_title: Option<String> = row.get(3)?;
+        let target_deleted_at: Option<i64> = row.get(4)?;
+        let status = match (&target_title, target_deleted_at) {
+            (None, _) => LinkStatus::Dangling,
+            (Some(_), Some(_)) => LinkStatus::Trashed,
+            (Some(_), None) => LinkStatus::Resolved,
+        };
+        links.push(TipLink {
+            source_tip_id: row.get(0)?,
+            source_title: row.get(1)?,
+            target_tip_id,
+            target_title,
+            target_text: row.get(5)?,
+            alias: row.get(6)?,
+            position: row.get(7)?,
+            context: row.get::<Option<String>>(8)?.unwrap_or_default(),
+            status,
+        });
+    }
+    Ok(links)
+}
+
+/// 获取指向该笔记的反向链接
+pub async fn get_backlinks(conn: &DbConnection, tip_id: &str) -> Result<Vec<TipLink>> {
+    query_links(conn, "l.target_tip_id = ?2", vec![libsql::Value::Text(tip_id.to_string())]).await
+}
+
+/// 获取该笔记的出链
+pub async fn get_outgoing_links(conn: &DbConnection, tip_id: &str) -> Result<Vec<TipLink>> {
+    query_links(conn, "l.source_tip_id = ?2", vec![libsql::Value::Text(tip_id.to_string())]).await
+}
+
+/// 获取悬空链接（目标不存在或在回收站中），可按源笔记过滤
+pub async fn get_dangling_links(conn: &DbConnection, tip_id: Option<&str>) -> Result<Vec<TipLink>> {
+    let dangling = "(t.id IS NULL OR t.deleted_at IS NOT NULL)";
+    match tip_id {
+        Some(tip_id) => {
+            query_links(conn, &format!("{} AND l.source_tip_id = ?2", dangling), vec![libsql::Value::Text(tip_id.to_string())]).await
+        }
+        None => query_links(conn, dangling, Vec::new()).await,
+    }
+}
+
+/// 链接表为空或解析规则变化时重建全部链接
+pub async fn ensure_tip_links_backfilled(conn: &Connection) -> Result<()> {
+    if get_setting(conn, TIP_LINKS_VERSION_KEY).await?.as_deref() == Some(TIP_LINKS_VERSION) {
+        return Ok(());
+    }
+
+    let mut rows = conn.query(
+        "SELECT id, CASE WHEN COALESCE(is_encrypted, 0) THEN '' ELSE content END FROM tips",
+        (),
+    ).await?;
+    let mut tips: Vec<(String, String)> = Vec::new();
+    while let Some(row) = rows.next().await? {
+        tips.push((row.get(0)?, row.get(1)?));
+    }
+
+    conn.execute("DELETE FROM tip_links", ()).await?;
+    let mut total = 0;
+    for (tip_id, content) in &tips {
+        if content.contains("[[") {
+            total += update_tip_links(conn, tip_id, content).await?;
+        }
+    }
+
+    save_setting(conn, TIP_LINKS_VERSION_KEY, TIP_LINKS_VERSION).await?;
+    tracing::info!("Indexed {} links from {} tips", total, tips.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_titles_and_aliases() {
+        let content = "见 [[Rust 笔记]] 和 [[abc-123|别名]]。";
+        let links = parse_wiki_links(content);
+        assert_eq!(links.len(), 2);
+        assert_eq!(links[0].target, "Rust 笔记");
+        assert_eq!(links[0].alias, None);
+        assert_eq!(&content[links[0].start..links[0].end], "[[Rust 笔记]]");
+        assert_eq!(links[1].target, "abc-123");
+        assert_eq!(links[1].alias.as_deref(), Some("别名"));
+    }
+
+    #[test]
+    fn skips_code_and_malformed_links() {
+        let content = "```\n[[in fence]]\n```\n`[[inline]]` [[ ]] [[open\n[[ok]]";
+        let links = parse_wiki_links(content);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].target, "ok");
+    }
+
+    #[test]
+    fn rewrites_links_keeping_alias() {
+        let content = "[[old title]] and [[Old Title|see]] but not [[other]]";
+        let rewritten = rewrite_wiki_links(content, "Old Title", "New", "id1").unwrap();
+        assert_eq!(rewritten, "[[New]] and [[New|see]] but not [[other]]");
+        assert_eq!(rewrite_wiki_links("[[other]]", "Old Title", "New", "id1"), None);
+    }
+
+    #[test]
+    fn rewrites_to_id_when_title_is_unsafe() {
+        let rewritten = rewrite_wiki_links("[[Old]]", "Old", "a|b", "id1").unwrap();
+        assert_eq!(rewritten, "[[id1|Old]]");
+    }
+}
@@ -10,7 +10,7 @@ use tracing::{info, warn, error, debug};
 use uuid::Uuid;
 
 use super::models::*;
-use super::operations::{create_all_tables, init_default_data};
+use super::operations::{create_all_tables, create_local_tables, init_default_data};
 
 /// 数据库模式
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -1172,6 +1172,7 @@ impl UnifiedDbManager {
         // 应用PRAGMA设置（跳过嵌入式副本模式不支持的设置）
         let config = self.config.read().await;
         let is_embedded_replica = matches!(config.mode, DatabaseMode::EmbeddedReplica { .. });
+        let is_local = matches!(config.mode, DatabaseMode::Local { .. });
         
         for (key, value) in &config.pragma_settings {
             // 嵌入式副本模式跳过某些不支持的PRAGMA设置
@@ -1193,8 +1194,13 @@ impl UnifiedDbManager {
                 .map_err(|e| anyhow!("Failed to enable foreign keys: {}", e))?;
         }
 
-        // 创建表结构 - 增强错误处理
-        match create_all_tables(&conn).await {
+        // 创建表结构 - 增强错误处理；变更日志只建在本地数据库
+        let created = if is_local {
+            create_local_tables(&conn).await
+        } else {
+            create_all_tables(&conn).await
+        };
+        match created {
             Ok(_) => {
                 info!("Database tables created successfully");
             },
@@ -1737,7 +1743,7 @@ impl UnifiedDbManager {
         };
 
         // 获取笔记数量
-        let note_count = match conn.query("SELECT COUNT(*) FROM tips", ()).await {
+        let note_count = match conn.query("SELECT COUNT(*) FROM tips WHERE deleted_at IS NULL", ()).await {
             Ok(mut rows) => {
                 if let Some(row) = rows.next().await? {
                     row.get::<i64>(0).unwrap_or(0) as u64
@@ -0,0 +1,35 @@
+use anyhow::Result;
+use chrono::Utc;
+use libsql::params;
+
+use super::operations::DbConnection;
+
+/// 保存记录在同步时的内容，作为下次三方合并的共同祖先
+pub async fn save_merge_base(conn: &DbConnection, table_name: &str, record_id: &str, content: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO sync_merge_bases (table_name, record_id, content, synced_at) VALUES (?1, ?2, ?3, ?4)",
+        params![table_name, record_id, content, Utc::now().timestamp_millis()],
+    ).await?;
+    Ok(())
+}
+
+/// 读取记录的共同祖先
+pub async fn get_merge_base(conn: &DbConnection, table_name: &str, record_id: &str) -> Result<Option<String>> {
+    let mut rows = conn.query(
+        "SELECT content FROM sync_merge_bases WHERE table_name = ?1 AND record_id = ?2",
+        params![table_name, record_id],
+    ).await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+/// 记录删除后清理共同祖先
+pub async fn delete_merge_base(conn: &DbConnection, table_name: &str, record_id: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM sync_merge_bases WHERE table_name = ?1 AND record_id = ?2",
+        params![table_name, record_id],
+    ).await?;
+    Ok(())
+}
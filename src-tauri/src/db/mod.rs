@@ -2,8 +2,38 @@
 pub mod models;
 pub mod operations;
 pub mod manager;
+pub mod search;
+pub mod search_query;
+pub mod revisions;
+pub mod trash;
+pub mod links;
+pub mod graph;
+pub mod ordering;
+pub mod tags;
+pub mod properties;
+pub mod embeddings;
+pub mod ai_usage;
+pub mod prompt_runs;
+pub mod suggestions;
+pub mod change_log;
+pub mod merge_bases;
 
 // 重新导出常用类型和函数
 pub use models::*;
 pub use operations::*;
-pub use manager::*; 
\ No newline at end of file
+pub use manager::*;
+pub use search::*;
+pub use search_query::*;
+pub use revisions::*;
+pub use trash::*;
+pub use links::*;
+pub use graph::*;
+pub use ordering::*;
+pub use tags::*;
+pub use properties::*;
+pub use embeddings::*;
+pub use ai_usage::*;
+pub use prompt_runs::*;
+pub use suggestions::*;
+pub use change_log::*;
+pub use merge_bases::*;
@@ -1,6 +1,8 @@
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 
+use super::properties::{property_filter_sql, property_order_sql, PropertyFilter, PropertySort};
+
 // 笔记类型枚举
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub enum TipType {
@@ -56,6 +58,118 @@ pub struct Tip {
     pub is_encrypted: Option<bool>,
     pub encryption_key_id: Option<String>,
     pub encrypted_content: Option<String>,
+    // v4 新增字段
+    pub pinned: Option<bool>,   // 置顶
+    pub favorite: Option<bool>, // 收藏
+    pub archived: Option<bool>, // 归档，默认不出现在列表中
+    // v5 新增字段
+    pub sort_key: Option<f64>, // 笔记本内的手动排序键
+}
+
+// 笔记状态标记
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TipFlag {
+    Pinned,
+    Favorite,
+    Archived,
+}
+
+impl TipFlag {
+    // 对应的 tips 表列名
+    pub fn column(&self) -> &'static str {
+        match self {
+            TipFlag::Pinned => "pinned",
+            TipFlag::Favorite => "favorite",
+            TipFlag::Archived => "archived",
+        }
+    }
+}
+
+// 笔记列表排序方式（置顶笔记始终在前）
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TipSortOrder {
+    #[default]
+    UpdatedDesc,
+    UpdatedAsc,
+    CreatedDesc,
+    CreatedAsc,
+    TitleAsc,
+    TitleDesc,
+    Manual, // 按用户拖拽的顺序
+}
+
+impl TipSortOrder {
+    // ORDER BY 子句（不含 ORDER BY 关键字），列名不带表前缀
+    pub fn order_clause(&self) -> &'static str {
+        match self {
+            TipSortOrder::UpdatedDesc => "pinned DESC, updated_at DESC",
+            TipSortOrder::UpdatedAsc => "pinned DESC, updated_at ASC",
+            TipSortOrder::CreatedDesc => "pinned DESC, created_at DESC",
+            TipSortOrder::CreatedAsc => "pinned DESC, created_at ASC",
+            TipSortOrder::TitleAsc => "pinned DESC, title COLLATE NOCASE ASC",
+            TipSortOrder::TitleDesc => "pinned DESC, title COLLATE NOCASE DESC",
+            TipSortOrder::Manual => "pinned DESC, sort_key IS NULL, sort_key ASC, updated_at DESC",
+        }
+    }
+}
+
+// 分页列表查询选项
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TipListOptions {
+    #[serde(default)]
+    pub sort: TipSortOrder,
+    #[serde(default)]
+    pub include_archived: bool, // 默认不包含已归档笔记
+    #[serde(default)]
+    pub property_filters: Vec<PropertyFilter>,
+    #[serde(default)]
+    pub sort_property: Option<PropertySort>, // 优先于 sort，其余按 sort 排序
+}
+
+impl TipListOptions {
+    pub fn new(sort: Option<TipSortOrder>, include_archived: Option<bool>) -> Self {
+        Self {
+            sort: sort.unwrap_or_default(),
+            include_archived: include_archived.unwrap_or(false),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_properties(mut self, filters: Option<Vec<PropertyFilter>>, sort_property: Option<PropertySort>) -> Self {
+        self.property_filters = filters.unwrap_or_default();
+        self.sort_property = sort_property;
+        self
+    }
+
+    // 归档过滤条件，可直接拼接在 WHERE 子句中
+    pub fn archived_filter(&self) -> &'static str {
+        if self.include_archived {
+            "1 = 1"
+        } else {
+            "COALESCE(archived, 0) = 0"
+        }
+    }
+
+    /// 生成 WHERE 条件和 ORDER BY 子句，参数编号从 `first_param` 开始；`tip_column` 为笔记 ID 列
+    pub fn to_sql(&self, tip_column: &str, first_param: usize) -> Result<(String, String, Vec<libsql::Value>)> {
+        let mut params = Vec::new();
+        let mut bind = |value: libsql::Value| {
+            params.push(value);
+            format!("?{}", first_param + params.len() - 1)
+        };
+
+        let mut conditions = vec![self.archived_filter().to_string()];
+        for filter in &self.property_filters {
+            conditions.push(property_filter_sql(filter, tip_column, &mut bind)?);
+        }
+        let order = match &self.sort_property {
+            Some(sort) => format!("{}, {}", property_order_sql(sort, tip_column, &mut bind), self.sort.order_clause()),
+            None => self.sort.order_clause().to_string(),
+        };
+        Ok((conditions.join(" AND "), order, params))
+    }
 }
 
 // 分类模型
@@ -73,19 +187,24 @@ pub struct Category {
     // v2 新增字段
     pub is_encrypted: Option<bool>,
     pub encryption_key_id: Option<String>,
+    // v5 新增字段
+    pub sort_key: Option<f64>, // 同级笔记本间的手动排序键
 }
 
 // 标签模型
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Tag {
     pub id: String,
-    pub name: String,
+    pub name: String, // 完整路径，层级以 / 分隔，如 lang/rust/async
     pub created_at: i64,
     pub updated_at: i64,
     // v3 新增字段
     pub version: Option<i32>,
     pub last_synced_at: Option<i64>,
     pub sync_hash: Option<String>,
+    // v5 新增字段
+    pub color: Option<String>,
+    pub icon: Option<String>,
 }
 
 // 笔记标签关联
@@ -95,6 +214,23 @@ pub struct TipTag {
     pub tag_id: String,
 }
 
+// 笔记修订模型
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TipRevision {
+    pub id: String,
+    pub tip_id: String,
+    pub revision_number: i64,
+    pub title: String,
+    pub content: String,
+    pub tip_type: String,
+    pub language: Option<String>,
+    pub category_id: Option<String>,
+    pub tags: Vec<String>,
+    pub reason: String, // 'initial', 'save', 'restore'
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
 // AI角色模型
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AIRole {
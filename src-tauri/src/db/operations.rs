@@ -100,6 +100,51 @@ pub async fn create_all_tables(conn: &Connection) -> Result<()> {
         (),
     ).await?;
 
+    // 创建笔记属性表（前置元数据），日期和数字同时写入 value_number 以便比较
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tip_properties (
+            tip_id TEXT NOT NULL,
+            key TEXT NOT NULL COLLATE NOCASE,
+            value_type TEXT NOT NULL,
+            value_text TEXT NOT NULL,
+            value_number REAL,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (tip_id, key),
+            FOREIGN KEY (tip_id) REFERENCES tips (id) ON DELETE CASCADE
+        )",
+        (),
+    ).await?;
+
+    // 创建属性定义表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tip_property_definitions (
+            key TEXT PRIMARY KEY COLLATE NOCASE,
+            value_type TEXT NOT NULL,
+            options TEXT,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        (),
+    ).await?;
+
+    // 创建笔记向量表（语义检索），sync_hash 为内容哈希，未变化的笔记不重复嵌入
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tip_embeddings (
+            tip_id TEXT NOT NULL,
+            chunk_index INTEGER NOT NULL,
+            source TEXT NOT NULL,
+            chunk_text TEXT NOT NULL,
+            model TEXT NOT NULL,
+            sync_hash TEXT NOT NULL,
+            dimensions INTEGER NOT NULL,
+            vector BLOB NOT NULL,
+            indexed_at INTEGER NOT NULL,
+            PRIMARY KEY (tip_id, chunk_index),
+            FOREIGN KEY (tip_id) REFERENCES tips (id) ON DELETE CASCADE
+        )",
+        (),
+    ).await?;
+
     // 创建图片表
     conn.execute(
         "CREATE TABLE IF NOT EXISTS tip_images (
@@ -139,6 +184,42 @@ pub async fn create_all_tables(conn: &Connection) -> Result<()> {
         (),
     ).await?;
 
+    // 创建笔记修订历史表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tip_revisions (
+            id TEXT PRIMARY KEY,
+            tip_id TEXT NOT NULL,
+            revision_number INTEGER NOT NULL,
+            title TEXT NOT NULL,
+            content TEXT NOT NULL,
+            tip_type TEXT NOT NULL,
+            language TEXT,
+            category_id TEXT,
+            tags TEXT NOT NULL DEFAULT '[]',
+            reason TEXT NOT NULL DEFAULT 'save',
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (tip_id) REFERENCES tips (id) ON DELETE CASCADE
+        )",
+        (),
+    ).await?;
+
+    // 创建笔记双向链接表，target_tip_id 为空表示悬空链接
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tip_links (
+            id TEXT PRIMARY KEY,
+            source_tip_id TEXT NOT NULL,
+            target_tip_id TEXT,
+            target_text TEXT NOT NULL,
+            alias TEXT,
+            position INTEGER NOT NULL,
+            length INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (source_tip_id) REFERENCES tips (id) ON DELETE CASCADE
+        )",
+        (),
+    ).await?;
+
     // 创建AI角色表
     conn.execute(
         "CREATE TABLE IF NOT EXISTS ai_roles (
@@ -183,6 +264,74 @@ pub async fn create_all_tables(conn: &Connection) -> Result<()> {
         (),
     ).await?;
 
+    // 创建AI用量表，每次调用一行；服务商未返回用量时 estimated 为 1
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ai_usage (
+            id TEXT PRIMARY KEY,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            operation TEXT NOT NULL,
+            role_id TEXT,
+            conversation_id TEXT,
+            prompt_tokens INTEGER NOT NULL DEFAULT 0,
+            completion_tokens INTEGER NOT NULL DEFAULT 0,
+            estimated INTEGER NOT NULL DEFAULT 0,
+            cost REAL,
+            latency_ms INTEGER NOT NULL DEFAULT 0,
+            error TEXT,
+            created_at INTEGER NOT NULL
+        )",
+        (),
+    ).await?;
+
+    // 创建模型价格表（每百万 token），model 为 * 时作用于该服务商的所有模型
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ai_model_prices (
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            input_price REAL NOT NULL,
+            output_price REAL NOT NULL,
+            updated_at INTEGER NOT NULL,
+            PRIMARY KEY (provider, model)
+        )",
+        (),
+    ).await?;
+
+    // 创建月度预算表，scope 为 * 时统计所有服务商
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ai_budgets (
+            scope TEXT PRIMARY KEY,
+            token_limit INTEGER,
+            cost_limit REAL,
+            action TEXT NOT NULL DEFAULT 'warn',
+            warn_ratio REAL NOT NULL DEFAULT 0.8,
+            updated_at INTEGER NOT NULL
+        )",
+        (),
+    ).await?;
+
+    // 创建提示词运行记录表
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS ai_prompt_runs (
+            id TEXT PRIMARY KEY,
+            tip_id TEXT NOT NULL,
+            batch_id TEXT NOT NULL,
+            provider_id TEXT NOT NULL,
+            model TEXT NOT NULL DEFAULT '',
+            variables TEXT NOT NULL DEFAULT '{}',
+            rendered_prompt TEXT NOT NULL,
+            output TEXT,
+            error TEXT,
+            latency_ms INTEGER NOT NULL DEFAULT 0,
+            prompt_tokens INTEGER NOT NULL DEFAULT 0,
+            completion_tokens INTEGER NOT NULL DEFAULT 0,
+            estimated INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (tip_id) REFERENCES tips (id) ON DELETE CASCADE
+        )",
+        (),
+    ).await?;
+
     // 创建模板表
     conn.execute(
         "CREATE TABLE IF NOT EXISTS tip_templates (
@@ -310,6 +459,18 @@ pub async fn create_all_tables(conn: &Connection) -> Result<()> {
         (),
     ).await?;
 
+    // 创建三方合并祖先表，保存记录上次同步时的内容（仅本地）
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sync_merge_bases (
+            table_name TEXT NOT NULL,
+            record_id TEXT NOT NULL,
+            content TEXT NOT NULL,
+            synced_at INTEGER NOT NULL,
+            PRIMARY KEY (table_name, record_id)
+        )",
+        (),
+    ).await?;
+
     // 创建同步统计表
     conn.execute(
         "CREATE TABLE IF NOT EXISTS sync_statistics (
@@ -327,13 +488,96 @@ pub async fn create_all_tables(conn: &Connection) -> Result<()> {
         (),
     ).await?;
 
+    // 旧库补充新增列
+    migrate_columns(conn).await?;
+
     // 创建所有索引
     create_all_indexes(conn).await?;
 
+    // 创建全文检索索引（含旧数据回填）
+    super::search::create_fts_schema(conn).await?;
+
+    // 解析旧笔记中的链接
+    super::links::ensure_tip_links_backfilled(conn).await?;
+
+    // 为旧数据补齐手动排序键
+    super::ordering::ensure_sort_keys_backfilled(conn).await?;
+
     tracing::info!("All database tables and indexes created successfully");
     Ok(())
 }
 
+/// 创建本地数据库的表结构：在共享表结构之外创建变更日志及触发器。
+/// 远程数据库只调用 create_all_tables，否则同步写入远程的每一行都会在远程留下无人消费的日志
+pub async fn create_local_tables(conn: &Connection) -> Result<()> {
+    create_all_tables(conn).await?;
+    // 放在回填之后，避免记录启动时的回填改动
+    super::change_log::create_change_log_schema(conn).await
+}
+
+/// 若列不存在则通过 ALTER TABLE 补充（无迁移框架，需保持幂等）
+pub async fn ensure_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<bool> {
+    let mut rows = conn.query(&format!("PRAGMA table_info({})", table), ()).await?;
+    while let Some(row) = rows.next().await? {
+        let name: String = row.get(1)?;
+        if name.eq_ignore_ascii_case(column) {
+            return Ok(false);
+        }
+    }
+
+    conn.execute(
+        &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+        (),
+    ).await?;
+    tracing::info!("Added column {}.{}", table, column);
+    Ok(true)
+}
+
+/// 为旧数据库补充后续版本新增的列
+async fn migrate_columns(conn: &Connection) -> Result<()> {
+    // 回收站：软删除时间戳
+    for table in ["tips", "categories", "tip_images", "tip_audio_files"] {
+        ensure_column(conn, table, "deleted_at", "INTEGER").await?;
+    }
+
+    // 置顶、收藏、归档标记
+    for flag in ["pinned", "favorite", "archived"] {
+        ensure_column(conn, "tips", flag, "INTEGER NOT NULL DEFAULT 0").await?;
+    }
+
+    // 手动排序键
+    for table in ["tips", "categories"] {
+        ensure_column(conn, table, "sort_key", "REAL").await?;
+    }
+
+    // 标签颜色和图标
+    for column in ["color", "icon"] {
+        ensure_column(conn, "tags", column, "TEXT").await?;
+    }
+
+    // AI 回答引用的笔记（JSON）
+    ensure_column(conn, "ai_messages", "citations", "TEXT").await?;
+    ensure_column(conn, "ai_messages", "tool_calls", "TEXT").await?;
+
+    // 消息分支：旧消息按时间顺序串成一条链
+    if ensure_column(conn, "ai_messages", "parent_id", "TEXT").await? {
+        conn.execute(
+            "UPDATE ai_messages SET parent_id = (
+                SELECT p.id FROM ai_messages p
+                WHERE p.conversation_id = ai_messages.conversation_id
+                  AND (p.created_at < ai_messages.created_at OR (p.created_at = ai_messages.created_at AND p.rowid < ai_messages.rowid))
+                ORDER BY p.created_at DESC, p.rowid DESC LIMIT 1
+            )",
+            (),
+        ).await?;
+    }
+    ensure_column(conn, "ai_conversations", "active_message_id", "TEXT").await?;
+    // 长对话的滚动摘要
+    ensure_column(conn, "ai_messages", "summary_until", "TEXT").await?;
+
+    Ok(())
+}
+
 /// 创建所有索引
 async fn create_all_indexes(conn: &Connection) -> Result<()> {
     // 基础索引
@@ -342,9 +586,42 @@ async fn create_all_indexes(conn: &Connection) -> Result<()> {
     conn.execute("CREATE INDEX IF NOT EXISTS idx_tips_updated_at ON tips (updated_at)", ()).await?;
     conn.execute("CREATE INDEX IF NOT EXISTS idx_categories_parent_id ON categories (parent_id)", ()).await?;
     conn.execute("CREATE INDEX IF NOT EXISTS idx_ai_messages_conversation_id ON ai_messages (conversation_id)", ()).await?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_ai_messages_parent_id ON ai_messages (parent_id)", ()).await?;
     conn.execute("CREATE INDEX IF NOT EXISTS idx_ai_conversations_role_id ON ai_conversations (role_id)", ()).await?;
     conn.execute("CREATE INDEX IF NOT EXISTS idx_clipboard_history_created_at ON clipboard_history (created_at)", ()).await?;
 
+    // 回收站索引
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tips_deleted_at ON tips (deleted_at)", ()).await?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_categories_deleted_at ON categories (deleted_at)", ()).await?;
+
+    // 笔记标记索引
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tips_flags ON tips (archived, pinned, updated_at)", ()).await?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tips_favorite ON tips (favorite)", ()).await?;
+
+    // 手动排序索引
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tips_category_sort_key ON tips (category_id, sort_key)", ()).await?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_categories_parent_sort_key ON categories (parent_id, sort_key)", ()).await?;
+
+    // 笔记属性索引
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tip_properties_key_number ON tip_properties (key, value_number)", ()).await?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tip_properties_key_text ON tip_properties (key, value_text)", ()).await?;
+
+    // AI 用量索引
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_ai_usage_created_at ON ai_usage (created_at)", ()).await?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_ai_usage_provider ON ai_usage (provider, created_at)", ()).await?;
+
+    // 向量索引
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tip_embeddings_model ON tip_embeddings (model, dimensions)", ()).await?;
+
+    // 修订历史索引
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tip_revisions_tip_id ON tip_revisions (tip_id, revision_number)", ()).await?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_ai_prompt_runs_tip_id ON ai_prompt_runs (tip_id, created_at)", ()).await?;
+
+    // 笔记链接索引
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tip_links_source ON tip_links (source_tip_id)", ()).await?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tip_links_target ON tip_links (target_tip_id)", ()).await?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_tip_links_target_text ON tip_links (target_text COLLATE NOCASE)", ()).await?;
+
     // 图片相关索引
     conn.execute("CREATE INDEX IF NOT EXISTS idx_tip_images_tip_id ON tip_images (tip_id)", ()).await?;
     conn.execute("CREATE INDEX IF NOT EXISTS idx_tip_images_image_id ON tip_images (image_id)", ()).await?;
@@ -465,8 +742,9 @@ async fn init_default_roles(conn: &Connection) -> Result<()> {
 /// 创建笔记
 pub async fn create_tip(conn: &DbConnection, tip: &Tip) -> Result<()> {
     conn.execute(
-        "INSERT INTO tips (id, title, content, tip_type, language, category_id, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        "INSERT INTO tips (id, title, content, tip_type, language, category_id, created_at, updated_at, sort_key)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8,
+                 COALESCE(?9, (SELECT MIN(sort_key) FROM tips WHERE category_id IS ?6) - ?10, ?10))",
         params![
             tip.id.as_str(),
             tip.title.as_str(),
@@ -475,7 +753,9 @@ pub async fn create_tip(conn: &DbConnection, tip: &Tip) -> Result<()> {
             tip.language.as_deref(),
             tip.category_id.as_deref(),
             tip.created_at,
-            tip.updated_at
+            tip.updated_at,
+            tip.sort_key,
+            super::ordering::SORT_KEY_STEP
         ]
     ).await?;
     Ok(())
@@ -485,8 +765,8 @@ pub async fn create_tip(conn: &DbConnection, tip: &Tip) -> Result<()> {
 pub async fn list_tips(conn: &DbConnection) -> Result<Vec<Tip>> {
     let mut rows = conn.query(
         "SELECT id, title, content, tip_type, language, category_id, created_at, updated_at,
-                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content 
-         FROM tips ORDER BY updated_at DESC",
+                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content, pinned, favorite, archived, sort_key 
+         FROM tips WHERE deleted_at IS NULL ORDER BY updated_at DESC",
         ()
     ).await?;
 
@@ -508,18 +788,22 @@ pub async fn list_tips(conn: &DbConnection) -> Result<Vec<Tip>> {
             is_encrypted: row.get(11)?,
             encryption_key_id: row.get(12)?,
             encrypted_content: row.get(13)?,
+            pinned: row.get(14)?,
+            favorite: row.get(15)?,
+            archived: row.get(16)?,
+            sort_key: row.get(17)?,
         };
         tips.push(tip);
     }
     Ok(tips)
 }
 
-/// 根据ID获取笔记
+/// 根据ID获取笔记（不含回收站中的笔记）
 pub async fn get_tip_by_id(conn: &DbConnection, tip_id: &str) -> Result<Option<Tip>> {
     let mut rows = conn.query(
         "SELECT id, title, content, tip_type, language, category_id, created_at, updated_at,
-                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content 
-         FROM tips WHERE id = ?1",
+                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content, pinned, favorite, archived, sort_key 
+         FROM tips WHERE id = ?1 AND deleted_at IS NULL",
         params![tip_id]
     ).await?;
 
@@ -540,6 +824,10 @@ pub async fn get_tip_by_id(conn: &DbConnection, tip_id: &str) -> Result<Option<T
             is_encrypted: row.get(11)?,
             encryption_key_id: row.get(12)?,
             encrypted_content: row.get(13)?,
+            pinned: row.get(14)?,
+            favorite: row.get(15)?,
+            archived: row.get(16)?,
+            sort_key: row.get(17)?,
         };
         Ok(Some(tip))
     } else {
@@ -549,8 +837,9 @@ pub async fn get_tip_by_id(conn: &DbConnection, tip_id: &str) -> Result<Option<T
 
 /// 更新笔记
 pub async fn update_tip(conn: &DbConnection, tip: &Tip) -> Result<()> {
-    conn.execute(
-        "UPDATE tips SET title = ?1, content = ?2, tip_type = ?3, language = ?4, category_id = ?5, updated_at = ?6 WHERE id = ?7",
+    let rows_affected = conn.execute(
+        "UPDATE tips SET title = ?1, content = ?2, tip_type = ?3, language = ?4, category_id = ?5, updated_at = ?6
+         WHERE id = ?7 AND deleted_at IS NULL",
         params![
             tip.title.as_str(),
             tip.content.as_str(),
@@ -561,15 +850,20 @@ pub async fn update_tip(conn: &DbConnection, tip: &Tip) -> Result<()> {
             tip.id.as_str()
         ]
     ).await?;
+
+    if rows_affected == 0 {
+        return Err(anyhow!("Tip not found or in trash: {}", tip.id));
+    }
     Ok(())
 }
 
-/// 删除笔记
+/// 删除笔记（移入回收站）
 pub async fn delete_tip(conn: &DbConnection, tip_id: &str) -> Result<()> {
     // 启动事务
     conn.execute("BEGIN TRANSACTION", ()).await?;
     
-    match delete_tip_with_dependencies(conn, tip_id).await {
+    let deleted_at = Utc::now().timestamp_millis();
+    match super::trash::soft_delete_tip(conn, tip_id, deleted_at).await {
         Ok(_) => {
             conn.execute("COMMIT", ()).await?;
             Ok(())
@@ -581,8 +875,8 @@ pub async fn delete_tip(conn: &DbConnection, tip_id: &str) -> Result<()> {
     }
 }
 
-/// 删除笔记及其依赖关系的内部函数
-async fn delete_tip_with_dependencies(conn: &DbConnection, tip_id: &str) -> Result<()> {
+/// 彻底删除笔记及其依赖关系（回收站清理使用）
+pub(super) async fn delete_tip_with_dependencies(conn: &DbConnection, tip_id: &str) -> Result<()> {
     // 1. 先查询并记录要删除的图片数量
     let image_count: i64 = conn.query(
         "SELECT COUNT(*) FROM tip_images WHERE tip_id = ?1",
@@ -612,7 +906,18 @@ async fn delete_tip_with_dependencies(conn: &DbConnection, tip_id: &str) -> Resu
     
     tracing::info!("Deleted {} tag associations for tip {}", deleted_tags, tip_id);
     
-    // 4. 删除笔记本身
+    // 4. 删除音频、修订历史、属性、向量和提示词运行记录
+    conn.execute("DELETE FROM tip_audio_files WHERE tip_id = ?1", params![tip_id]).await?;
+    conn.execute("DELETE FROM tip_revisions WHERE tip_id = ?1", params![tip_id]).await?;
+    conn.execute("DELETE FROM tip_properties WHERE tip_id = ?1", params![tip_id]).await?;
+    conn.execute("DELETE FROM tip_embeddings WHERE tip_id = ?1", params![tip_id]).await?;
+    conn.execute("DELETE FROM ai_prompt_runs WHERE tip_id = ?1", params![tip_id]).await?;
+    
+    // 出链随笔记删除，入链变为悬空链接
+    conn.execute("DELETE FROM tip_links WHERE source_tip_id = ?1", params![tip_id]).await?;
+    conn.execute("UPDATE tip_links SET target_tip_id = NULL WHERE target_tip_id = ?1", params![tip_id]).await?;
+    
+    // 5. 删除笔记本身
     let rows_affected = conn.execute(
         "DELETE FROM tips WHERE id = ?1", 
         params![tip_id]
@@ -635,14 +940,17 @@ async fn delete_tip_with_dependencies(conn: &DbConnection, tip_id: &str) -> Resu
 /// 创建分类
 pub async fn create_category(conn: &DbConnection, category: &Category) -> Result<()> {
     conn.execute(
-        "INSERT INTO categories (id, name, parent_id, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5)",
+        "INSERT INTO categories (id, name, parent_id, created_at, updated_at, sort_key)
+         VALUES (?1, ?2, ?3, ?4, ?5,
+                 COALESCE(?6, (SELECT MAX(sort_key) FROM categories WHERE parent_id IS ?3) + ?7, ?7))",
         params![
             category.id.as_str(),
             category.name.as_str(),
             category.parent_id.as_deref(),
             category.created_at,
-            category.updated_at
+            category.updated_at,
+            category.sort_key,
+            super::ordering::SORT_KEY_STEP
         ]
     ).await?;
     Ok(())
@@ -651,8 +959,8 @@ pub async fn create_category(conn: &DbConnection, category: &Category) -> Result
 /// 获取所有分类
 pub async fn list_categories(conn: &DbConnection) -> Result<Vec<Category>> {
     let mut rows = conn.query(
-        "SELECT id, name, parent_id, created_at, updated_at, version, last_synced_at, sync_hash, is_encrypted, encryption_key_id 
-         FROM categories ORDER BY created_at ASC",
+        "SELECT id, name, parent_id, created_at, updated_at, version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, sort_key 
+         FROM categories WHERE deleted_at IS NULL ORDER BY sort_key IS NULL, sort_key ASC, created_at ASC",
         ()
     ).await?;
 
@@ -669,6 +977,7 @@ pub async fn list_categories(conn: &DbConnection) -> Result<Vec<Category>> {
             sync_hash: row.get(7)?,
             is_encrypted: row.get(8)?,
             encryption_key_id: row.get(9)?,
+            sort_key: row.get(10)?,
         };
         categories.push(category);
     }
@@ -678,7 +987,7 @@ pub async fn list_categories(conn: &DbConnection) -> Result<Vec<Category>> {
 /// 根据ID获取分类
 pub async fn get_category_by_id(conn: &DbConnection, category_id: &str) -> Result<Option<Category>> {
     let mut rows = conn.query(
-        "SELECT id, name, parent_id, created_at, updated_at, version, last_synced_at, sync_hash, is_encrypted, encryption_key_id 
+        "SELECT id, name, parent_id, created_at, updated_at, version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, sort_key 
          FROM categories WHERE id = ?1",
         params![category_id]
     ).await?;
@@ -695,6 +1004,7 @@ pub async fn get_category_by_id(conn: &DbConnection, category_id: &str) -> Resul
             sync_hash: row.get(7)?,
             is_encrypted: row.get(8)?,
             encryption_key_id: row.get(9)?,
+            sort_key: row.get(10)?,
         };
         Ok(Some(category))
     } else {
@@ -716,15 +1026,16 @@ pub async fn update_category(conn: &DbConnection, category: &Category) -> Result
     Ok(())
 }
 
-/// 删除分类
-pub async fn delete_category(conn: &DbConnection, category_id: &str) -> Result<()> {
+/// 删除分类（连同子分类和其中的笔记一起移入回收站），返回移入回收站的记录
+pub async fn delete_category(conn: &DbConnection, category_id: &str) -> Result<super::trash::TrashChange> {
     // 启动事务
     conn.execute("BEGIN TRANSACTION", ()).await?;
     
-    match delete_category_with_dependencies(conn, category_id).await {
-        Ok(_) => {
+    let deleted_at = Utc::now().timestamp_millis();
+    match super::trash::soft_delete_category(conn, category_id, deleted_at).await {
+        Ok(change) => {
             conn.execute("COMMIT", ()).await?;
-            Ok(())
+            Ok(change)
         }
         Err(e) => {
             conn.execute("ROLLBACK", ()).await?;
@@ -733,71 +1044,6 @@ pub async fn delete_category(conn: &DbConnection, category_id: &str) -> Result<(
     }
 }
 
-/// 删除分类及其依赖关系的内部函数
-async fn delete_category_with_dependencies(conn: &DbConnection, category_id: &str) -> Result<()> {
-    println!("Starting to delete category {} and all its dependencies", category_id);
-    tracing::info!("Starting to delete category {} and all its dependencies", category_id);
-    
-    // 1. 递归查找所有子分类ID（包括自身）
-    let mut all_category_ids = vec![category_id.to_string()];
-    let mut stack = vec![category_id.to_string()];
-    while let Some(current_id) = stack.pop() {
-        let mut rows = conn.query(
-            "SELECT id FROM categories WHERE parent_id = ?1",
-            params![current_id.as_str()]
-        ).await?;
-        while let Some(row) = rows.next().await? {
-            let child_id: String = row.get(0)?;
-            all_category_ids.push(child_id.clone());
-            stack.push(child_id);
-        }
-    }
-    
-    println!("Found {} categories to delete: {:?}", all_category_ids.len(), all_category_ids);
-    tracing::info!("Found {} categories to delete: {:?}", all_category_ids.len(), all_category_ids);
-
-    // 2. 删除所有相关分类下的笔记及其依赖数据
-    for cat_id in &all_category_ids {
-        // 查找该分类下所有笔记ID
-        let mut tip_rows = conn.query(
-            "SELECT id FROM tips WHERE category_id = ?1",
-            params![cat_id.as_str()]
-        ).await?;
-        let mut tip_ids: Vec<String> = Vec::new();
-        while let Some(row) = tip_rows.next().await? {
-            tip_ids.push(row.get(0)?);
-        }
-        
-        println!("Category {} has {} tips to delete", cat_id, tip_ids.len());
-        tracing::info!("Category {} has {} tips to delete", cat_id, tip_ids.len());
-        
-        // 删除所有笔记（及其图片、标签关联等）
-        for tip_id in &tip_ids {
-            delete_tip_with_dependencies(conn, tip_id).await?;
-        }
-    }
-
-    // 3. 解除所有子分类的parent_id依赖，避免外键约束失败
-    for cat_id in &all_category_ids {
-        conn.execute(
-            "UPDATE categories SET parent_id = NULL WHERE parent_id = ?1",
-            params![cat_id.as_str()]
-        ).await?;
-    }
-
-    // 4. 删除所有子分类（包括自身）
-    for cat_id in &all_category_ids {
-        conn.execute(
-            "DELETE FROM categories WHERE id = ?1",
-            params![cat_id.as_str()]
-        ).await?;
-        tracing::info!("Deleted category {}", cat_id);
-    }
-
-    tracing::info!("Successfully deleted category {} and all its dependencies", category_id);
-    Ok(())
-}
-
 /// 确保"未分类"分类存在
 async fn ensure_uncategorized_exists(conn: &DbConnection) -> Result<()> {
     // 检查是否存在
@@ -822,8 +1068,8 @@ async fn ensure_uncategorized_exists(conn: &DbConnection) -> Result<()> {
 /// 获取指定分类的直接子分类
 pub async fn get_subcategories(conn: &DbConnection, parent_id: &str) -> Result<Vec<Category>> {
     let mut rows = conn.query(
-        "SELECT id, name, parent_id, created_at, updated_at, version, last_synced_at, sync_hash, is_encrypted, encryption_key_id 
-         FROM categories WHERE parent_id = ?1 ORDER BY name",
+        "SELECT id, name, parent_id, created_at, updated_at, version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, sort_key 
+         FROM categories WHERE parent_id = ?1 AND deleted_at IS NULL ORDER BY sort_key IS NULL, sort_key ASC, name",
         params![parent_id]
     ).await?;
 
@@ -840,6 +1086,7 @@ pub async fn get_subcategories(conn: &DbConnection, parent_id: &str) -> Result<V
             sync_hash: row.get(7)?,
             is_encrypted: row.get(8)?,
             encryption_key_id: row.get(9)?,
+            sort_key: row.get(10)?,
         };
         categories.push(category);
     }
@@ -866,8 +1113,8 @@ pub async fn get_category_ids_recursive(conn: &DbConnection, category_id: &str)
 pub async fn get_tips_by_category(conn: &DbConnection, category_id: &str) -> Result<Vec<Tip>> {
     let mut rows = conn.query(
         "SELECT id, title, content, tip_type, language, category_id, created_at, updated_at,
-                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content 
-         FROM tips WHERE category_id = ?1 ORDER BY updated_at DESC",
+                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content, pinned, favorite, archived, sort_key 
+         FROM tips WHERE category_id = ?1 AND deleted_at IS NULL ORDER BY updated_at DESC",
         params![category_id]
     ).await?;
 
@@ -889,6 +1136,10 @@ pub async fn get_tips_by_category(conn: &DbConnection, category_id: &str) -> Res
             is_encrypted: row.get(11)?,
             encryption_key_id: row.get(12)?,
             encrypted_content: row.get(13)?,
+            pinned: row.get(14)?,
+            favorite: row.get(15)?,
+            archived: row.get(16)?,
+            sort_key: row.get(17)?,
         };
         tips.push(tip);
     }
@@ -918,13 +1169,69 @@ pub async fn get_tips_by_category_recursive(conn: &DbConnection, category_id: &s
 }
 
 /// 分页查询分类下的笔记
-pub async fn get_tips_by_category_paged(conn: &DbConnection, category_id: &str, limit: i32, offset: i32) -> Result<Vec<Tip>> {
-    let mut rows = conn.query(
+pub async fn get_tips_by_category_paged(conn: &DbConnection, category_id: &str, limit: i32, offset: i32, options: &TipListOptions) -> Result<Vec<Tip>> {
+    query_tips_in_categories(conn, &[category_id.to_string()], limit, offset, options).await
+}
+
+/// 计算分类下的笔记总数
+pub async fn count_tips_by_category(conn: &DbConnection, category_id: &str, options: &TipListOptions) -> Result<i64> {
+    count_tips_in_categories(conn, &[category_id.to_string()], options).await
+}
+
+/// 递归计算分类及其子分类下的笔记总数
+pub async fn count_tips_by_category_recursive(conn: &DbConnection, category_id: &str, options: &TipListOptions) -> Result<i64> {
+    let category_ids = get_category_ids_recursive(conn, category_id).await?;
+    count_tips_in_categories(conn, &category_ids, options).await
+}
+
+/// 递归分页查询单个分类及其子分类下的笔记
+pub async fn get_tips_by_category_recursive_paged_single(conn: &DbConnection, category_id: &str, limit: i32, offset: i32, options: &TipListOptions) -> Result<Vec<Tip>> {
+    // 获取所有相关的分类ID（包括自己和所有子分类）
+    let category_ids = get_category_ids_recursive(conn, category_id).await?;
+    query_tips_in_categories(conn, &category_ids, limit, offset, options).await
+}
+
+/// 递归分页查询多个分类下的笔记（用于根目录）
+pub async fn get_tips_by_category_recursive_paged(conn: &DbConnection, category_ids: &[&str], limit: i32, offset: i32, options: &TipListOptions) -> Result<Vec<Tip>> {
+    // 收集所有指定分类及其子分类，去重后一次查询
+    let mut all_ids: Vec<String> = Vec::new();
+    for &category_id in category_ids {
+        for id in get_category_ids_recursive(conn, category_id).await? {
+            if !all_ids.contains(&id) {
+                all_ids.push(id);
+            }
+        }
+    }
+    query_tips_in_categories(conn, &all_ids, limit, offset, options).await
+}
+
+// 分类 IN 条件，参数从 ?{first_param} 开始编号
+fn category_in_clause(category_ids: &[String], first_param: usize) -> (String, Vec<libsql::Value>) {
+    let placeholders: Vec<String> = (0..category_ids.len()).map(|i| format!("?{}", first_param + i)).collect();
+    let values = category_ids.iter().map(|id| libsql::Value::Text(id.clone())).collect();
+    (format!("category_id IN ({})", placeholders.join(", ")), values)
+}
+
+/// 在指定分类中按列表选项分页查询笔记（过滤、排序和分页都在 SQL 中完成）
+async fn query_tips_in_categories(conn: &DbConnection, category_ids: &[String], limit: i32, offset: i32, options: &TipListOptions) -> Result<Vec<Tip>> {
+    if category_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (category_filter, mut query_params) = category_in_clause(category_ids, 3);
+    let (filter, order, option_params) = options.to_sql("tips.id", 3 + category_ids.len())?;
+    let sql = format!(
         "SELECT id, title, content, tip_type, language, category_id, created_at, updated_at,
-                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content 
-         FROM tips WHERE category_id = ?1 ORDER BY updated_at DESC LIMIT ?2 OFFSET ?3",
-        params![category_id, limit, offset]
-    ).await?;
+                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content, pinned, favorite, archived, sort_key 
+         FROM tips WHERE {} AND deleted_at IS NULL AND {} ORDER BY {} LIMIT ?1 OFFSET ?2",
+        category_filter,
+        filter,
+        order
+    );
+    query_params.insert(0, libsql::Value::Integer(offset as i64));
+    query_params.insert(0, libsql::Value::Integer(limit as i64));
+    query_params.extend(option_params);
+    let mut rows = conn.query(&sql, libsql::params_from_iter(query_params)).await?;
 
     let mut tips = Vec::new();
     while let Some(row) = rows.next().await? {
@@ -944,98 +1251,34 @@ pub async fn get_tips_by_category_paged(conn: &DbConnection, category_id: &str,
             is_encrypted: row.get(11)?,
             encryption_key_id: row.get(12)?,
             encrypted_content: row.get(13)?,
+            pinned: row.get(14)?,
+            favorite: row.get(15)?,
+            archived: row.get(16)?,
+            sort_key: row.get(17)?,
         };
         tips.push(tip);
     }
     Ok(tips)
 }
 
-/// 计算分类下的笔记总数
-pub async fn count_tips_by_category(conn: &DbConnection, category_id: &str) -> Result<i64> {
+/// 统计指定分类中符合列表选项的笔记数量
+async fn count_tips_in_categories(conn: &DbConnection, category_ids: &[String], options: &TipListOptions) -> Result<i64> {
+    if category_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let (category_filter, mut query_params) = category_in_clause(category_ids, 1);
+    let (filter, _, option_params) = options.to_sql("tips.id", 1 + category_ids.len())?;
+    query_params.extend(option_params);
     let count: i64 = conn.query(
-        "SELECT COUNT(*) FROM tips WHERE category_id = ?1",
-        params![category_id]
+        &format!("SELECT COUNT(*) FROM tips WHERE {} AND deleted_at IS NULL AND {}", category_filter, filter),
+        libsql::params_from_iter(query_params)
     ).await?
     .next().await?
     .unwrap()
     .get(0)?;
-    
-    Ok(count)
-}
 
-/// 递归计算分类及其子分类下的笔记总数
-pub async fn count_tips_by_category_recursive(conn: &DbConnection, category_id: &str) -> Result<i64> {
-    let category_ids = get_category_ids_recursive(conn, category_id).await?;
-    
-    if category_ids.is_empty() {
-        return Ok(0);
-    }
-
-    let mut total_count = 0;
-    for id in category_ids {
-        let count = count_tips_by_category(conn, &id).await?;
-        total_count += count;
-    }
-    
-    Ok(total_count)
-}
-
-/// 递归分页查询单个分类及其子分类下的笔记
-pub async fn get_tips_by_category_recursive_paged_single(conn: &DbConnection, category_id: &str, limit: i32, offset: i32) -> Result<Vec<Tip>> {
-    // 获取所有相关的分类ID（包括自己和所有子分类）
-    let category_ids = get_category_ids_recursive(conn, category_id).await?;
-    
-    if category_ids.is_empty() {
-        return Ok(Vec::new());
-    }
-
-    // 收集所有分类的笔记并按更新时间排序
-    let mut all_tips = Vec::new();
-    for id in category_ids {
-        let tips = get_tips_by_category(conn, &id).await?;
-        all_tips.extend(tips);
-    }
-
-    // 按更新时间排序
-    all_tips.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
-    
-    // 应用分页
-    let start = offset as usize;
-    let end = std::cmp::min(start + limit as usize, all_tips.len());
-    
-    if start >= all_tips.len() {
-        return Ok(Vec::new());
-    }
-    
-    Ok(all_tips[start..end].to_vec())
-}
-
-/// 递归分页查询多个分类下的笔记（用于根目录）
-pub async fn get_tips_by_category_recursive_paged(conn: &DbConnection, category_ids: &[&str], limit: i32, offset: i32) -> Result<Vec<Tip>> {
-    if category_ids.is_empty() {
-        return Ok(Vec::new());
-    }
-
-    // 收集所有指定分类及其子分类的笔记
-    let mut all_tips = Vec::new();
-    for &category_id in category_ids {
-        let tips = get_tips_by_category_recursive(conn, category_id).await?;
-        all_tips.extend(tips);
-    }
-
-    // 按更新时间排序并去重（可能同一笔记在多个分类路径中）
-    all_tips.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
-    all_tips.dedup_by(|a, b| a.id == b.id);
-    
-    // 应用分页
-    let start = offset as usize;
-    let end = std::cmp::min(start + limit as usize, all_tips.len());
-    
-    if start >= all_tips.len() {
-        return Ok(Vec::new());
-    }
-    
-    Ok(all_tips[start..end].to_vec())
+    Ok(count)
 }
 
 // ===============================================
@@ -1045,9 +1288,9 @@ pub async fn get_tips_by_category_recursive_paged(conn: &DbConnection, category_
 /// 创建标签
 pub async fn create_tag(conn: &DbConnection, tag: &Tag) -> Result<()> {
     conn.execute(
-        "INSERT INTO tags (id, name, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4)",
-        params![tag.id.as_str(), tag.name.as_str(), tag.created_at, tag.updated_at]
+        "INSERT INTO tags (id, name, created_at, updated_at, color, icon)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![tag.id.as_str(), tag.name.as_str(), tag.created_at, tag.updated_at, tag.color.as_deref(), tag.icon.as_deref()]
     ).await?;
     Ok(())
 }
@@ -1055,7 +1298,7 @@ pub async fn create_tag(conn: &DbConnection, tag: &Tag) -> Result<()> {
 /// 获取所有标签
 pub async fn list_tags(conn: &DbConnection) -> Result<Vec<Tag>> {
     let mut rows = conn.query(
-        "SELECT id, name, created_at, updated_at, version, last_synced_at, sync_hash 
+        "SELECT id, name, created_at, updated_at, version, last_synced_at, sync_hash, color, icon 
          FROM tags ORDER BY name",
         ()
     ).await?;
@@ -1070,6 +1313,8 @@ pub async fn list_tags(conn: &DbConnection) -> Result<Vec<Tag>> {
             version: row.get(4)?,
             last_synced_at: row.get(5)?,
             sync_hash: row.get(6)?,
+            color: row.get(7)?,
+            icon: row.get(8)?,
         };
         tags.push(tag);
     }
@@ -1079,8 +1324,8 @@ pub async fn list_tags(conn: &DbConnection) -> Result<Vec<Tag>> {
 /// 更新标签
 pub async fn update_tag(conn: &DbConnection, tag: &Tag) -> Result<()> {
     conn.execute(
-        "UPDATE tags SET name = ?1, updated_at = ?2 WHERE id = ?3",
-        params![tag.name.as_str(), tag.updated_at, tag.id.as_str()]
+        "UPDATE tags SET name = ?1, updated_at = ?2, color = ?3, icon = ?4 WHERE id = ?5",
+        params![tag.name.as_str(), tag.updated_at, tag.color.as_deref(), tag.icon.as_deref(), tag.id.as_str()]
     ).await?;
     Ok(())
 }
@@ -1091,6 +1336,49 @@ pub async fn delete_tag(conn: &DbConnection, tag_id: &str) -> Result<()> {
     Ok(())
 }
 
+/// 获取笔记的标签
+pub async fn get_tip_tags(conn: &DbConnection, tip_id: &str) -> Result<Vec<Tag>> {
+    let mut rows = conn.query(
+        "SELECT t.id, t.name, t.created_at, t.updated_at, t.version, t.last_synced_at, t.sync_hash, t.color, t.icon
+         FROM tags t JOIN tip_tags tt ON tt.tag_id = t.id
+         WHERE tt.tip_id = ?1 ORDER BY t.name",
+        params![tip_id]
+    ).await?;
+
+    let mut tags = Vec::new();
+    while let Some(row) = rows.next().await? {
+        tags.push(Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            created_at: row.get(2)?,
+            updated_at: row.get(3)?,
+            version: row.get(4)?,
+            last_synced_at: row.get(5)?,
+            sync_hash: row.get(6)?,
+            color: row.get(7)?,
+            icon: row.get(8)?,
+        });
+    }
+    Ok(tags)
+}
+
+/// 设置笔记的标签：可传标签 ID 或标签名（不存在的标签及其上级标签会自动创建）
+pub async fn set_tip_tags(conn: &DbConnection, tip_id: &str, tags: &[String]) -> Result<()> {
+    let now = Utc::now().timestamp_millis();
+    conn.execute("DELETE FROM tip_tags WHERE tip_id = ?1", params![tip_id]).await?;
+
+    for tag in tags {
+        let Some(tag_id) = super::tags::resolve_tag_ref(conn, tag, now).await? else {
+            continue;
+        };
+        conn.execute(
+            "INSERT OR IGNORE INTO tip_tags (tip_id, tag_id) VALUES (?1, ?2)",
+            params![tip_id, tag_id]
+        ).await?;
+    }
+    Ok(())
+}
+
 // ===============================================
 // 图片相关数据库操作函数
 // ===============================================
@@ -1131,7 +1419,7 @@ pub async fn save_tip_image(
 /// 获取笔记的所有图片
 pub async fn get_tip_images(conn: &DbConnection, tip_id: &str) -> Result<Vec<(String, String)>> {
     let mut rows = conn.query(
-        "SELECT image_id, image_data FROM tip_images WHERE tip_id = ?1 ORDER BY created_at",
+        "SELECT image_id, image_data FROM tip_images WHERE tip_id = ?1 AND deleted_at IS NULL ORDER BY created_at",
         params![tip_id]
     ).await?;
 
@@ -1152,7 +1440,7 @@ pub async fn get_tip_images_paginated(
     offset: i32
 ) -> Result<Vec<(String, String)>> {
     let mut rows = conn.query(
-        "SELECT image_id, image_data FROM tip_images WHERE tip_id = ?1 
+        "SELECT image_id, image_data FROM tip_images WHERE tip_id = ?1 AND deleted_at IS NULL
          ORDER BY created_at LIMIT ?2 OFFSET ?3",
         params![tip_id, limit, offset]
     ).await?;
@@ -1169,7 +1457,7 @@ pub async fn get_tip_images_paginated(
 /// 获取笔记图片总数
 pub async fn get_tip_images_count(conn: &DbConnection, tip_id: &str) -> Result<i64> {
     let count: i64 = conn.query(
-        "SELECT COUNT(*) FROM tip_images WHERE tip_id = ?1",
+        "SELECT COUNT(*) FROM tip_images WHERE tip_id = ?1 AND deleted_at IS NULL",
         params![tip_id]
     ).await?
     .next().await?
@@ -1396,8 +1684,23 @@ pub async fn search_tips(conn: &DbConnection, query: &str) -> Result<Vec<Tip>> {
     search_tips_with_limit(conn, query, 50).await
 }
 
-/// 搜索笔记（带限制）- 性能优化版本
+/// 搜索笔记（带限制）- 优先使用全文索引，短词或索引不可用时回退到 LIKE
 pub async fn search_tips_with_limit(conn: &DbConnection, query: &str, limit: i32) -> Result<Vec<Tip>> {
+    // 含 tag:/type:/in: 等运算符的结构化查询
+    if let Some(compiled) = super::search_query::compile_if_structured(conn, query).await? {
+        return super::search_query::search_tips_structured(conn, &compiled, limit, 0).await;
+    }
+    if let Some(match_query) = super::search::build_fts_match_query(query) {
+        match super::search::search_tips_fts(conn, &match_query, limit).await {
+            Ok(tips) => return Ok(tips),
+            Err(e) => tracing::warn!("Full-text search failed, falling back to LIKE: {}", e),
+        }
+    }
+    search_tips_like(conn, query, limit).await
+}
+
+/// LIKE 模糊搜索笔记
+async fn search_tips_like(conn: &DbConnection, query: &str, limit: i32) -> Result<Vec<Tip>> {
     let search_pattern = format!("%{}%", query);
     
     // 优化的查询：
@@ -1406,14 +1709,14 @@ pub async fn search_tips_with_limit(conn: &DbConnection, query: &str, limit: i32
     // 3. 使用CASE WHEN进行相关性排序
     let mut rows = conn.query(
         "SELECT id, title, content, tip_type, language, category_id, created_at, updated_at,
-                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content,
+                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content, pinned, favorite, archived, sort_key,
                 CASE 
                     WHEN title LIKE ?1 THEN 1 
                     WHEN content LIKE ?1 THEN 2 
                     ELSE 3 
                 END as relevance_score
          FROM tips 
-         WHERE title LIKE ?1 OR content LIKE ?1 
+         WHERE (title LIKE ?1 OR content LIKE ?1) AND deleted_at IS NULL
          ORDER BY relevance_score ASC, updated_at DESC
          LIMIT ?2",
         params![search_pattern, limit]
@@ -1437,14 +1740,32 @@ pub async fn search_tips_with_limit(conn: &DbConnection, query: &str, limit: i32
             is_encrypted: row.get(11)?,
             encryption_key_id: row.get(12)?,
             encrypted_content: row.get(13)?,
+            pinned: row.get(14)?,
+            favorite: row.get(15)?,
+            archived: row.get(16)?,
+            sort_key: row.get(17)?,
         };
         tips.push(tip);
     }
     Ok(tips)
 }
 
-/// 快速搜索笔记摘要 - 只返回必要字段
+/// 快速搜索笔记摘要 - 只返回必要字段，全文检索时附带片段和高亮
 pub async fn search_tips_summary_fast(conn: &DbConnection, query: &str, limit: i32) -> Result<Vec<TipSummary>> {
+    if let Some(compiled) = super::search_query::compile_if_structured(conn, query).await? {
+        return super::search_query::search_tips_summary_structured(conn, &compiled, None, limit, 0).await;
+    }
+    if let Some(match_query) = super::search::build_fts_match_query(query) {
+        match super::search::search_tips_summary_fts(conn, &match_query, limit).await {
+            Ok(summaries) => return Ok(summaries),
+            Err(e) => tracing::warn!("Full-text summary search failed, falling back to LIKE: {}", e),
+        }
+    }
+    search_tips_summary_like(conn, query, limit).await
+}
+
+/// LIKE 模糊搜索笔记摘要
+async fn search_tips_summary_like(conn: &DbConnection, query: &str, limit: i32) -> Result<Vec<TipSummary>> {
     let search_pattern = format!("%{}%", query);
     
     // 只查询摘要需要的字段，大幅减少数据传输
@@ -1455,9 +1776,10 @@ pub async fn search_tips_summary_fast(conn: &DbConnection, query: &str, limit: i
                     WHEN content LIKE ?1 THEN 2 
                     ELSE 3 
                 END as relevance_score,
-                substr(content, 1, 200) as content_preview
+                substr(content, 1, 200) as content_preview,
+                pinned, favorite, archived
          FROM tips 
-         WHERE title LIKE ?1 OR content LIKE ?1 
+         WHERE (title LIKE ?1 OR content LIKE ?1) AND deleted_at IS NULL
          ORDER BY relevance_score ASC, updated_at DESC
          LIMIT ?2",
         params![search_pattern, limit]
@@ -1477,6 +1799,10 @@ pub async fn search_tips_summary_fast(conn: &DbConnection, query: &str, limit: i
             tags: Vec::new(), // 搜索时不加载标签，提高速度
             is_encrypted: row.get(7)?,
             content: Some(row.get(9)?), // 只返回前200字符作为预览
+            pinned: row.get(10)?,
+            favorite: row.get(11)?,
+            archived: row.get(12)?,
+            search_match: None,
         };
         summaries.push(summary);
     }
@@ -1484,15 +1810,69 @@ pub async fn search_tips_summary_fast(conn: &DbConnection, query: &str, limit: i
 }
 
 /// 获取最新的笔记摘要（带预览，限制数量）
-pub async fn list_latest_tip_summaries_with_preview(conn: &DbConnection, limit: i32) -> Result<Vec<TipSummary>> {
-    let mut rows = conn.query(
+pub async fn list_latest_tip_summaries_with_preview(conn: &DbConnection, limit: i32, options: &TipListOptions) -> Result<Vec<TipSummary>> {
+    query_tip_summaries(
+        conn,
+        &format!("deleted_at IS NULL AND {}", options.archived_filter()),
+        Vec::new(),
+        options.sort,
+        limit,
+        0,
+    ).await
+}
+
+/// 按标记列出笔记摘要（置顶/收藏/归档）
+pub async fn list_tip_summaries_by_flag(conn: &DbConnection, flag: TipFlag, limit: i32, offset: i32, sort: TipSortOrder) -> Result<Vec<TipSummary>> {
+    // 置顶和收藏列表不包含已归档笔记
+    let archived_filter = if flag == TipFlag::Archived { "" } else { " AND COALESCE(archived, 0) = 0" };
+    query_tip_summaries(
+        conn,
+        &format!("deleted_at IS NULL AND COALESCE({}, 0) = 1{}", flag.column(), archived_filter),
+        Vec::new(),
+        sort,
+        limit,
+        offset,
+    ).await
+}
+
+/// 设置笔记标记，同时更新 updated_at 以便同步时按较新的一端合并
+pub async fn set_tip_flag(conn: &DbConnection, tip_id: &str, flag: TipFlag, value: bool) -> Result<()> {
+    let rows_affected = conn.execute(
+        &format!("UPDATE tips SET {} = ?1, updated_at = ?2 WHERE id = ?3 AND deleted_at IS NULL", flag.column()),
+        params![value, Utc::now().timestamp_millis(), tip_id]
+    ).await?;
+
+    if rows_affected == 0 {
+        return Err(anyhow!("Tip not found: {}", tip_id));
+    }
+
+    crate::sync::mark_for_sync(conn, "tips", tip_id, SyncOperation::Update).await?;
+    Ok(())
+}
+
+/// 按条件查询笔记摘要，`filter` 中的额外参数从 ?3 开始编号
+pub(super) async fn query_tip_summaries(
+    conn: &DbConnection,
+    filter: &str,
+    filter_params: Vec<libsql::Value>,
+    sort: TipSortOrder,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<TipSummary>> {
+    let sql = format!(
         "SELECT id, title, tip_type, language, category_id, created_at, updated_at, is_encrypted,
-                substr(content, 1, 200) as content_preview
+                substr(content, 1, 200) as content_preview,
+                pinned, favorite, archived
          FROM tips 
-         ORDER BY updated_at DESC
-         LIMIT ?1",
-        params![limit]
-    ).await?;
+         WHERE {}
+         ORDER BY {}
+         LIMIT ?1 OFFSET ?2",
+        filter,
+        sort.order_clause()
+    );
+    let mut query_params = vec![libsql::Value::Integer(limit as i64), libsql::Value::Integer(offset as i64)];
+    query_params.extend(filter_params);
+    let mut rows = conn.query(&sql, libsql::params_from_iter(query_params)).await?;
 
     let mut summaries = Vec::new();
     while let Some(row) = rows.next().await? {
@@ -1508,6 +1888,10 @@ pub async fn list_latest_tip_summaries_with_preview(conn: &DbConnection, limit:
             tags: Vec::new(),
             is_encrypted: row.get(7)?,
             content: Some(row.get(8)?),
+            pinned: row.get(9)?,
+            favorite: row.get(10)?,
+            archived: row.get(11)?,
+            search_match: None,
         };
         summaries.push(summary);
     }
@@ -1518,8 +1902,8 @@ pub async fn list_latest_tip_summaries_with_preview(conn: &DbConnection, limit:
 pub async fn get_tip_by_title(conn: &DbConnection, title: &str) -> Result<Option<Tip>> {
     let mut rows = conn.query(
         "SELECT id, title, content, tip_type, language, category_id, created_at, updated_at,
-                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content 
-         FROM tips WHERE title = ?1 LIMIT 1",
+                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content, pinned, favorite, archived, sort_key 
+         FROM tips WHERE title = ?1 AND deleted_at IS NULL LIMIT 1",
         params![title]
     ).await?;
 
@@ -1540,6 +1924,10 @@ pub async fn get_tip_by_title(conn: &DbConnection, title: &str) -> Result<Option
             is_encrypted: row.get(11)?,
             encryption_key_id: row.get(12)?,
             encrypted_content: row.get(13)?,
+            pinned: row.get(14)?,
+            favorite: row.get(15)?,
+            archived: row.get(16)?,
+            sort_key: row.get(17)?,
         };
         Ok(Some(tip))
     } else {
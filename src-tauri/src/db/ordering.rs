@@ -0,0 +1,352 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use libsql::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use super::models::SyncOperation;
+use super::operations::{get_category_ids_recursive, DbConnection};
+use crate::sync::mark_for_sync;
+
+// 相邻排序键的初始间隔，插入时取中点，间隔耗尽后重排整组
+pub const SORT_KEY_STEP: f64 = 1024.0;
+
+/// 在同级中的目标位置
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "position", content = "sibling_id", rename_all = "snake_case")]
+pub enum SiblingPosition {
+    First,
+    #[default]
+    Last,
+    Before(String),
+    After(String),
+}
+
+/// 参与排序的表：笔记在笔记本内排序，笔记本在父笔记本内排序
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OrderedTable {
+    Tips,
+    Categories,
+}
+
+impl OrderedTable {
+    fn name(&self) -> &'static str {
+        match self {
+            OrderedTable::Tips => "tips",
+            OrderedTable::Categories => "categories",
+        }
+    }
+
+    fn group_column(&self) -> &'static str {
+        match self {
+            OrderedTable::Tips => "category_id",
+            OrderedTable::Categories => "parent_id",
+        }
+    }
+
+    // 没有排序键时的回退顺序，与引入手动排序前的默认展示一致
+    fn fallback_order(&self) -> &'static str {
+        match self {
+            OrderedTable::Tips => "updated_at DESC",
+            OrderedTable::Categories => "CASE WHEN parent_id IS NULL THEN created_at ELSE 0 END ASC, name ASC",
+        }
+    }
+}
+
+/// 计算插入到 `index` 处的排序键，`keys` 为其余同级按顺序排列的排序键；
+/// 前后两个键之间已无可用间隔时返回 None，需要先重排
+pub fn sort_key_at(keys: &[f64], index: usize) -> Option<f64> {
+    let prev = index.checked_sub(1).and_then(|i| keys.get(i)).copied();
+    let next = keys.get(index).copied();
+
+    match (prev, next) {
+        (None, None) => Some(SORT_KEY_STEP),
+        (None, Some(next)) => Some(next - SORT_KEY_STEP),
+        (Some(prev), None) => Some(prev + SORT_KEY_STEP),
+        (Some(prev), Some(next)) => {
+            let mid = prev + (next - prev) / 2.0;
+            (prev < mid && mid < next).then_some(mid)
+        }
+    }
+}
+
+/// 根据目标位置确定在同级列表中的插入下标
+fn insertion_index(sibling_ids: &[String], position: &SiblingPosition) -> Result<usize> {
+    let find = |id: &str| {
+        sibling_ids
+            .iter()
+            .position(|s| s == id)
+            .ok_or_else(|| anyhow!("Sibling not found in target: {}", id))
+    };
+
+    match position {
+        SiblingPosition::First => Ok(0),
+        SiblingPosition::Last => Ok(sibling_ids.len()),
+        SiblingPosition::Before(id) => find(id),
+        SiblingPosition::After(id) => find(id).map(|i| i + 1),
+    }
+}
+
+/// 按当前顺序读取同级条目（不含被移动的条目本身）
+async fn load_siblings(
+    conn: &DbConnection,
+    table: OrderedTable,
+    group: Option<&str>,
+    exclude_id: &str,
+) -> Result<Vec<(String, Option<f64>)>> {
+    let sql = format!(
+        "SELECT id, sort_key FROM {} WHERE {} IS ?1 AND deleted_at IS NULL AND id != ?2
+         ORDER BY sort_key IS NULL, sort_key ASC, {}",
+        table.name(),
+        table.group_column(),
+        table.fallback_order()
+    );
+    let mut rows = conn.query(&sql, params![group, exclude_id]).await?;
+
+    let mut siblings = Vec::new();
+    while let Some(row) = rows.next().await? {
+        siblings.push((row.get(0)?, row.get(1)?));
+    }
+    Ok(siblings)
+}
+
+/// 按给定顺序以固定间隔重写整组排序键
+async fn renumber(conn: &DbConnection, table: OrderedTable, ids: &[String], sync: bool) -> Result<Vec<f64>> {
+    let sql = format!("UPDATE {} SET sort_key = ?1 WHERE id = ?2", table.name());
+    let mut keys = Vec::with_capacity(ids.len());
+    for (i, id) in ids.iter().enumerate() {
+        let key = (i + 1) as f64 * SORT_KEY_STEP;
+        conn.execute(&sql, params![key, id.as_str()]).await?;
+        if sync {
+            mark_for_sync(conn, table.name(), id, SyncOperation::Update).await?;
+        }
+        keys.push(key);
+    }
+    Ok(keys)
+}
+
+/// 计算移动到目标位置后的排序键，必要时重排同级
+async fn place_in_group(
+    conn: &DbConnection,
+    table: OrderedTable,
+    group: Option<&str>,
+    id: &str,
+    position: &SiblingPosition,
+) -> Result<f64> {
+    let siblings = load_siblings(conn, table, group, id).await?;
+    let ids: Vec<String> = siblings.iter().map(|(id, _)| id.clone()).collect();
+    let index = insertion_index(&ids, position)?;
+
+    // 从其他设备同步来的旧条目可能没有排序键
+    let mut keys: Vec<f64> = match siblings.iter().map(|(_, key)| *key).collect::<Option<Vec<_>>>() {
+        Some(keys) => keys,
+        None => renumber(conn, table, &ids, true).await?,
+    };
+
+    if let Some(key) = sort_key_at(&keys, index) {
+        return Ok(key);
+    }
+
+    keys = renumber(conn, table, &ids, true).await?;
+    sort_key_at(&keys, index).ok_or_else(|| anyhow!("Failed to allocate sort key"))
+}
+
+/// 分类是否存在且不在回收站中
+async fn is_live_category(conn: &DbConnection, category_id: &str) -> Result<bool> {
+    let mut rows = conn.query(
+        "SELECT 1 FROM categories WHERE id = ?1 AND deleted_at IS NULL",
+        params![category_id],
+    ).await?;
+    Ok(rows.next().await?.is_some())
+}
+
+async fn run_in_transaction<T>(conn: &DbConnection, result: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+    conn.execute("BEGIN TRANSACTION", ()).await?;
+    match result.await {
+        Ok(value) => {
+            conn.execute("COMMIT", ()).await?;
+            Ok(value)
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", ()).await?;
+            Err(e)
+        }
+    }
+}
+
+async fn move_tip_inner(
+    conn: &DbConnection,
+    tip_id: &str,
+    category_id: Option<&str>,
+    position: &SiblingPosition,
+) -> Result<f64> {
+    let mut rows = conn.query(
+        "SELECT category_id FROM tips WHERE id = ?1 AND deleted_at IS NULL",
+        params![tip_id],
+    ).await?;
+    let current: Option<String> = match rows.next().await? {
+        Some(row) => row.get(0)?,
+        None => return Err(anyhow!("Tip not found: {}", tip_id)),
+    };
+
+    let target = match category_id {
+        Some(id) => {
+            if !is_live_category(conn, id).await? {
+                return Err(anyhow!("Category not found: {}", id));
+            }
+            Some(id.to_string())
+        }
+        None => current.clone(),
+    };
+
+    let key = place_in_group(conn, OrderedTable::Tips, target.as_deref(), tip_id, position).await?;
+
+    // 仅调整顺序不视为内容修改，跨笔记本移动才更新时间
+    if target == current {
+        conn.execute(
+            "UPDATE tips SET sort_key = ?1 WHERE id = ?2",
+            params![key, tip_id],
+        ).await?;
+    } else {
+        conn.execute(
+            "UPDATE tips SET sort_key = ?1, category_id = ?2, updated_at = ?3 WHERE id = ?4",
+            params![key, target.as_deref(), Utc::now().timestamp_millis(), tip_id],
+        ).await?;
+    }
+    mark_for_sync(conn, "tips", tip_id, SyncOperation::Update).await?;
+
+    Ok(key)
+}
+
+/// 移动笔记：`category_id` 为 None 时在当前笔记本内调整顺序，返回新的排序键
+pub async fn move_tip(
+    conn: &DbConnection,
+    tip_id: &str,
+    category_id: Option<&str>,
+    position: &SiblingPosition,
+) -> Result<f64> {
+    let key = run_in_transaction(conn, move_tip_inner(conn, tip_id, category_id, position)).await?;
+    tracing::info!("Moved tip {} ({:?})", tip_id, position);
+    Ok(key)
+}
+
+async fn move_category_inner(
+    conn: &DbConnection,
+    category_id: &str,
+    parent_id: Option<&str>,
+    position: &SiblingPosition,
+) -> Result<f64> {
+    if !is_live_category(conn, category_id).await? {
+        return Err(anyhow!("Category not found: {}", category_id));
+    }
+
+    if let Some(parent_id) = parent_id {
+        if !is_live_category(conn, parent_id).await? {
+            return Err(anyhow!("Parent category not found: {}", parent_id));
+        }
+        // get_category_ids_recursive 包含分类自身
+        let subtree = get_category_ids_recursive(conn, category_id).await?;
+        if subtree.iter().any(|id| id == parent_id) {
+            return Err(anyhow!("Cannot move a category into itself or one of its subcategories"));
+        }
+    }
+
+    let key = place_in_group(conn, OrderedTable::Categories, parent_id, category_id, position).await?;
+
+    conn.execute(
+        "UPDATE categories SET parent_id = ?1, sort_key = ?2, updated_at = ?3 WHERE id = ?4",
+        params![parent_id, key, Utc::now().timestamp_millis(), category_id],
+    ).await?;
+    mark_for_sync(conn, "categories", category_id, SyncOperation::Update).await?;
+
+    Ok(key)
+}
+
+/// 移动笔记本到新的父笔记本（None 为根）下的指定位置，返回新的排序键
+pub async fn move_category(
+    conn: &DbConnection,
+    category_id: &str,
+    parent_id: Option<&str>,
+    position: &SiblingPosition,
+) -> Result<f64> {
+    let key = run_in_transaction(conn, move_category_inner(conn, category_id, parent_id, position)).await?;
+    tracing::info!("Moved category {} under {:?} ({:?})", category_id, parent_id, position);
+    Ok(key)
+}
+
+/// 为缺少排序键的分组补齐排序键，保持原有展示顺序
+///
+/// 只在本地补齐，不加入同步队列：各设备按相同规则计算，手动移动后才同步。
+pub async fn ensure_sort_keys_backfilled(conn: &Connection) -> Result<()> {
+    for table in [OrderedTable::Categories, OrderedTable::Tips] {
+        let mut rows = conn.query(
+            &format!(
+                "SELECT DISTINCT {} FROM {} WHERE sort_key IS NULL",
+                table.group_column(),
+                table.name()
+            ),
+            (),
+        ).await?;
+        let mut groups: Vec<Option<String>> = Vec::new();
+        while let Some(row) = rows.next().await? {
+            groups.push(row.get(0)?);
+        }
+        if groups.is_empty() {
+            continue;
+        }
+
+        conn.execute("BEGIN TRANSACTION", ()).await?;
+        for group in &groups {
+            // 回收站中的条目也一并补齐，恢复后无需再处理
+            let sql = format!(
+                "SELECT id FROM {} WHERE {} IS ?1
+                 ORDER BY sort_key IS NULL, sort_key ASC, {}",
+                table.name(),
+                table.group_column(),
+                table.fallback_order()
+            );
+            let mut rows = conn.query(&sql, params![group.as_deref()]).await?;
+            let mut ids = Vec::new();
+            while let Some(row) = rows.next().await? {
+                ids.push(row.get::<String>(0)?);
+            }
+            if let Err(e) = renumber(conn, table, &ids, false).await {
+                conn.execute("ROLLBACK", ()).await?;
+                return Err(e);
+            }
+        }
+        conn.execute("COMMIT", ()).await?;
+        tracing::info!("Backfilled sort keys for {} groups in {}", groups.len(), table.name());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocates_keys_around_siblings() {
+        assert_eq!(sort_key_at(&[], 0), Some(SORT_KEY_STEP));
+        assert_eq!(sort_key_at(&[1024.0, 2048.0], 0), Some(0.0));
+        assert_eq!(sort_key_at(&[1024.0, 2048.0], 1), Some(1536.0));
+        assert_eq!(sort_key_at(&[1024.0, 2048.0], 2), Some(3072.0));
+    }
+
+    #[test]
+    fn reports_exhausted_gap() {
+        let a = 1.0_f64;
+        let b = f64::from_bits(a.to_bits() + 1);
+        assert_eq!(sort_key_at(&[a, b], 1), None);
+        assert_eq!(sort_key_at(&[a, a], 1), None);
+    }
+
+    #[test]
+    fn resolves_sibling_positions() {
+        let ids = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        assert_eq!(insertion_index(&ids, &SiblingPosition::First).unwrap(), 0);
+        assert_eq!(insertion_index(&ids, &SiblingPosition::Last).unwrap(), 3);
+        assert_eq!(insertion_index(&ids, &SiblingPosition::Before("b".into())).unwrap(), 1);
+        assert_eq!(insertion_index(&ids, &SiblingPosition::After("b".into())).unwrap(), 2);
+        assert!(insertion_index(&ids, &SiblingPosition::After("x".into())).is_err());
+    }
+}
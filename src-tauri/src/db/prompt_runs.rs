@@ -0,0 +1,186 @@
+use anyhow::Result;
+use libsql::params;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+use super::operations::DbConnection;
+
+/// 提示词笔记在某个服务商上的一次运行结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptRun {
+    pub id: String,
+    pub tip_id: String,
+    pub batch_id: String, // 同一次提交到多个服务商的运行共用
+    pub provider_id: String,
+    pub model: String,
+    pub variables: BTreeMap<String, String>,
+    pub rendered_prompt: String,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub latency_ms: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub estimated: bool, // 服务商未返回用量，按字符数估算
+    pub created_at: i64,
+}
+
+/// 同一批次的运行，用于并排对比
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptRunBatch {
+    pub batch_id: String,
+    pub tip_id: String,
+    pub variables: BTreeMap<String, String>,
+    pub rendered_prompt: String,
+    pub created_at: i64,
+    pub runs: Vec<PromptRun>,
+}
+
+impl PromptRun {
+    pub fn new(
+        tip_id: &str,
+        batch_id: &str,
+        provider_id: &str,
+        variables: &BTreeMap<String, String>,
+        rendered_prompt: &str,
+        created_at: i64,
+    ) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            tip_id: tip_id.to_string(),
+            batch_id: batch_id.to_string(),
+            provider_id: provider_id.to_string(),
+            variables: variables.clone(),
+            rendered_prompt: rendered_prompt.to_string(),
+            created_at,
+            ..Default::default()
+        }
+    }
+}
+
+/// 保存一次运行
+pub async fn insert_prompt_run(conn: &DbConnection, run: &PromptRun) -> Result<()> {
+    conn.execute(
+        "INSERT INTO ai_prompt_runs (id, tip_id, batch_id, provider_id, model, variables, rendered_prompt, output, error,
+            latency_ms, prompt_tokens, completion_tokens, estimated, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        params![
+            run.id.clone(),
+            run.tip_id.clone(),
+            run.batch_id.clone(),
+            run.provider_id.clone(),
+            run.model.clone(),
+            serde_json::to_string(&run.variables)?,
+            run.rendered_prompt.clone(),
+            run.output.clone(),
+            run.error.clone(),
+            run.latency_ms,
+            run.prompt_tokens,
+            run.completion_tokens,
+            run.estimated as i64,
+            run.created_at
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// 笔记最近的运行批次（新的在前），每批按服务商顺序排列
+pub async fn list_prompt_runs(conn: &DbConnection, tip_id: &str, batch_limit: i64) -> Result<Vec<PromptRunBatch>> {
+    let mut rows = conn
+        .query(
+            "SELECT id, tip_id, batch_id, provider_id, model, variables, rendered_prompt, output, error,
+                latency_ms, prompt_tokens, completion_tokens, estimated, created_at
+             FROM ai_prompt_runs
+             WHERE tip_id = ?1 AND batch_id IN (
+                SELECT batch_id FROM ai_prompt_runs WHERE tip_id = ?1
+                GROUP BY batch_id ORDER BY MAX(created_at) DESC LIMIT ?2
+             )
+             ORDER BY created_at DESC, batch_id, rowid ASC",
+            params![tip_id, batch_limit],
+        )
+        .await?;
+
+    let mut runs = Vec::new();
+    while let Some(row) = rows.next().await? {
+        runs.push(PromptRun {
+            id: row.get(0)?,
+            tip_id: row.get(1)?,
+            batch_id: row.get(2)?,
+            provider_id: row.get(3)?,
+            model: row.get(4)?,
+            variables: serde_json::from_str(&row.get::<String>(5)?).unwrap_or_default(),
+            rendered_prompt: row.get(6)?,
+            output: row.get(7)?,
+            error: row.get(8)?,
+            latency_ms: row.get(9)?,
+            prompt_tokens: row.get(10)?,
+            completion_tokens: row.get(11)?,
+            estimated: row.get::<i64>(12)? != 0,
+            created_at: row.get(13)?,
+        });
+    }
+    Ok(group_prompt_runs(runs))
+}
+
+/// 按批次分组，保持输入中批次首次出现的顺序
+pub fn group_prompt_runs(runs: Vec<PromptRun>) -> Vec<PromptRunBatch> {
+    let mut batches: Vec<PromptRunBatch> = Vec::new();
+    for run in runs {
+        match batches.iter_mut().find(|b| b.batch_id == run.batch_id) {
+            Some(batch) => {
+                batch.created_at = batch.created_at.min(run.created_at);
+                batch.runs.push(run);
+            }
+            None => batches.push(PromptRunBatch {
+                batch_id: run.batch_id.clone(),
+                tip_id: run.tip_id.clone(),
+                variables: run.variables.clone(),
+                rendered_prompt: run.rendered_prompt.clone(),
+                created_at: run.created_at,
+                runs: vec![run],
+            }),
+        }
+    }
+    batches
+}
+
+/// 删除笔记的运行记录；指定 batch_id 时只删除该批次
+pub async fn delete_prompt_runs(conn: &DbConnection, tip_id: &str, batch_id: Option<&str>) -> Result<u64> {
+    let deleted = match batch_id {
+        Some(batch_id) => {
+            conn.execute(
+                "DELETE FROM ai_prompt_runs WHERE tip_id = ?1 AND batch_id = ?2",
+                params![tip_id, batch_id],
+            )
+            .await?
+        }
+        None => conn.execute("DELETE FROM ai_prompt_runs WHERE tip_id = ?1", params![tip_id]).await?,
+    };
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(batch_id: &str, provider_id: &str, created_at: i64) -> PromptRun {
+        PromptRun {
+            batch_id: batch_id.to_string(),
+            provider_id: provider_id.to_string(),
+            created_at,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn groups_runs_by_batch() {
+        let batches = group_prompt_runs(vec![run("b2", "openai", 20), run("b2", "ollama", 21), run("b1", "openai", 10)]);
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].batch_id, "b2");
+        assert_eq!(batches[0].created_at, 20);
+        let providers: Vec<&str> = batches[0].runs.iter().map(|r| r.provider_id.as_str()).collect();
+        assert_eq!(providers, vec!["openai", "ollama"]);
+        assert_eq!(batches[1].runs.len(), 1);
+    }
+}
@@ -0,0 +1,770 @@
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, Utc};
+use libsql::{params, Value};
+use serde::{Deserialize, Serialize};
+
+use super::operations::DbConnection;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// 属性值类型
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PropertyType {
+    Text,
+    Number,
+    Date,
+    Url,
+    Select,
+}
+
+impl PropertyType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PropertyType::Text => "text",
+            PropertyType::Number => "number",
+            PropertyType::Date => "date",
+            PropertyType::Url => "url",
+            PropertyType::Select => "select",
+        }
+    }
+}
+
+impl TryFrom<&str> for PropertyType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "text" => Ok(PropertyType::Text),
+            "number" => Ok(PropertyType::Number),
+            "date" => Ok(PropertyType::Date),
+            "url" => Ok(PropertyType::Url),
+            "select" => Ok(PropertyType::Select),
+            other => Err(anyhow!("Unknown property type: {}", other)),
+        }
+    }
+}
+
+/// 带类型的属性值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum PropertyValue {
+    Text(String),
+    Number(f64),
+    Date(NaiveDate),
+    Url(String),
+    Select(String),
+}
+
+fn parse_date(raw: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(raw.trim(), DATE_FORMAT).ok()
+}
+
+fn parse_number(raw: &str) -> Option<f64> {
+    raw.trim().parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+fn is_url(raw: &str) -> bool {
+    let raw = raw.trim();
+    (raw.starts_with("http://") || raw.starts_with("https://")) && !raw.contains(char::is_whitespace)
+}
+
+// 日期按 UTC 零点存为毫秒时间戳，便于与数字一起比较和排序
+fn date_millis(date: NaiveDate) -> f64 {
+    date.and_hms_opt(0, 0, 0)
+        .map(|dt| dt.and_utc().timestamp_millis() as f64)
+        .unwrap_or(0.0)
+}
+
+impl PropertyValue {
+    pub fn value_type(&self) -> PropertyType {
+        match self {
+            PropertyValue::Text(_) => PropertyType::Text,
+            PropertyValue::Number(_) => PropertyType::Number,
+            PropertyValue::Date(_) => PropertyType::Date,
+            PropertyValue::Url(_) => PropertyType::Url,
+            PropertyValue::Select(_) => PropertyType::Select,
+        }
+    }
+
+    /// 规范化的文本形式（也用于写回前置元数据）
+    pub fn to_text(&self) -> String {
+        match self {
+            PropertyValue::Text(s) | PropertyValue::Url(s) | PropertyValue::Select(s) => s.clone(),
+            PropertyValue::Number(n) => n.to_string(),
+            PropertyValue::Date(d) => d.format(DATE_FORMAT).to_string(),
+        }
+    }
+
+    /// 可比较的数值（数字或日期）
+    pub fn to_number(&self) -> Option<f64> {
+        match self {
+            PropertyValue::Number(n) => Some(*n),
+            PropertyValue::Date(d) => Some(date_millis(*d)),
+            _ => None,
+        }
+    }
+
+    /// 按指定类型解析，select 类型在给出选项时必须是其中之一
+    pub fn parse(value_type: PropertyType, raw: &str, options: &[String]) -> Result<Self> {
+        let trimmed = raw.trim();
+        match value_type {
+            PropertyType::Text => Ok(PropertyValue::Text(trimmed.to_string())),
+            PropertyType::Number => parse_number(trimmed)
+                .map(PropertyValue::Number)
+                .ok_or_else(|| anyhow!("'{}' is not a number", trimmed)),
+            PropertyType::Date => parse_date(trimmed)
+                .map(PropertyValue::Date)
+                .ok_or_else(|| anyhow!("'{}' is not a date (YYYY-MM-DD)", trimmed)),
+            PropertyType::Url if is_url(trimmed) => Ok(PropertyValue::Url(trimmed.to_string())),
+            PropertyType::Url => Err(anyhow!("'{}' is not an http(s) URL", trimmed)),
+            PropertyType::Select => {
+                if options.is_empty() || options.iter().any(|o| o == trimmed) {
+                    Ok(PropertyValue::Select(trimmed.to_string()))
+                } else {
+                    Err(anyhow!("'{}' is not one of the allowed options", trimmed))
+                }
+            }
+        }
+    }
+
+    /// 未定义类型的属性按值推断：数字、日期、链接，否则为文本
+    pub fn infer(raw: &str) -> Self {
+        let trimmed = raw.trim();
+        if let Some(n) = parse_number(trimmed) {
+            PropertyValue::Number(n)
+        } else if let Some(d) = parse_date(trimmed) {
+            PropertyValue::Date(d)
+        } else if is_url(trimmed) {
+            PropertyValue::Url(trimmed.to_string())
+        } else {
+            PropertyValue::Text(trimmed.to_string())
+        }
+    }
+
+    fn from_columns(value_type: &str, text: String, number: Option<f64>) -> Result<Self> {
+        Ok(match PropertyType::try_from(value_type)? {
+            PropertyType::Text => PropertyValue::Text(text),
+            PropertyType::Number => PropertyValue::Number(number.unwrap_or_default()),
+            PropertyType::Date => PropertyValue::Date(
+                parse_date(&text).ok_or_else(|| anyhow!("Invalid stored date: {}", text))?,
+            ),
+            PropertyType::Url => PropertyValue::Url(text),
+            PropertyType::Select => PropertyValue::Select(text),
+        })
+    }
+}
+
+/// 笔记属性
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TipProperty {
+    pub key: String,
+    #[serde(flatten)]
+    pub value: PropertyValue,
+}
+
+/// 属性定义：已声明类型的属性，以及笔记中使用过的属性
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropertyDefinition {
+    pub key: String,
+    pub value_type: PropertyType,
+    pub options: Vec<String>, // select 类型的可选值
+    pub declared: bool,       // false 表示未声明，类型取自已有的值
+    pub usage_count: i64,
+}
+
+/// 属性过滤运算符
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PropertyFilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Contains,
+    Exists,
+}
+
+/// 属性过滤条件，值为数字或 YYYY-MM-DD 时按数值比较，否则按文本比较（不区分大小写）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PropertyFilter {
+    pub key: String,
+    pub op: PropertyFilterOp,
+    #[serde(default)]
+    pub value: Option<String>,
+}
+
+/// 按属性排序，缺少该属性的笔记排在最后
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PropertySort {
+    pub key: String,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+// ===============================================
+// SQL 片段
+// ===============================================
+
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// 生成属性过滤条件；`bind` 记录参数并返回占位符，以适配编号或匿名参数
+pub fn property_filter_sql(
+    filter: &PropertyFilter,
+    tip_column: &str,
+    bind: &mut dyn FnMut(Value) -> String,
+) -> Result<String> {
+    let key = bind(Value::Text(filter.key.clone()));
+    let exists = |condition: &str| {
+        format!(
+            "EXISTS (SELECT 1 FROM tip_properties tp WHERE tp.tip_id = {} AND tp.key = {} COLLATE NOCASE AND {})",
+            tip_column, key, condition
+        )
+    };
+
+    if filter.op == PropertyFilterOp::Exists {
+        return Ok(exists("1"));
+    }
+
+    let raw = filter
+        .value
+        .as_deref()
+        .ok_or_else(|| anyhow!("Property filter on '{}' requires a value", filter.key))?;
+
+    if filter.op == PropertyFilterOp::Contains {
+        let pattern = bind(Value::Text(format!("%{}%", escape_like(raw))));
+        return Ok(exists(&format!("tp.value_text LIKE {} ESCAPE '\\'", pattern)));
+    }
+
+    let (column, operand) = match PropertyValue::infer(raw).to_number() {
+        Some(n) => ("tp.value_number", bind(Value::Real(n))),
+        None => ("tp.value_text", format!("{} COLLATE NOCASE", bind(Value::Text(raw.trim().to_string())))),
+    };
+    let op = match filter.op {
+        PropertyFilterOp::Eq | PropertyFilterOp::Ne => "=",
+        PropertyFilterOp::Lt => "<",
+        PropertyFilterOp::Le => "<=",
+        PropertyFilterOp::Gt => ">",
+        PropertyFilterOp::Ge => ">=",
+        PropertyFilterOp::Contains | PropertyFilterOp::Exists => unreachable!(),
+    };
+    let condition = exists(&format!("{} {} {}", column, op, operand));
+
+    // 不等于包含没有该属性的笔记
+    Ok(if filter.op == PropertyFilterOp::Ne {
+        format!("NOT {}", condition)
+    } else {
+        condition
+    })
+}
+
+/// 生成按属性排序的 ORDER BY 片段（不含 ORDER BY 关键字）
+pub fn property_order_sql(sort: &PropertySort, tip_column: &str, bind: &mut dyn FnMut(Value) -> String) -> String {
+    let mut lookup = |column: &str| {
+        format!(
+            "(SELECT tp.{} FROM tip_properties tp WHERE tp.tip_id = {} AND tp.key = {} COLLATE NOCASE)",
+            column,
+            tip_column,
+            bind(Value::Text(sort.key.clone()))
+        )
+    };
+    let direction = if sort.descending { "DESC" } else { "ASC" };
+    format!(
+        "{} IS NULL, {} {dir}, {} COLLATE NOCASE {dir}",
+        lookup("value_text"),
+        lookup("value_number"),
+        lookup("value_text"),
+        dir = direction
+    )
+}
+
+// ===============================================
+// 前置元数据（YAML front matter 的常用子集）
+// ===============================================
+
+/// 前置元数据中的值
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrontMatterValue {
+    Scalar(String),
+    List(Vec<String>),
+}
+
+impl FrontMatterValue {
+    /// 列表合并为逗号分隔的文本
+    pub fn to_text(&self) -> String {
+        match self {
+            FrontMatterValue::Scalar(s) => s.clone(),
+            FrontMatterValue::List(items) => items.join(", "),
+        }
+    }
+
+    /// 标签等列表字段：标量按逗号拆分
+    pub fn to_list(&self) -> Vec<String> {
+        match self {
+            FrontMatterValue::Scalar(s) => s
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+            FrontMatterValue::List(items) => items.clone(),
+        }
+    }
+}
+
+/// 从 Markdown 中拆出的前置元数据
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrontMatter {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub properties: Vec<(String, String)>,
+}
+
+impl FrontMatter {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.tags.is_empty() && self.properties.is_empty()
+    }
+}
+
+fn unquote(raw: &str) -> String {
+    let raw = raw.trim();
+    if raw.len() >= 2 && raw.starts_with('"') && raw.ends_with('"') {
+        raw[1..raw.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\")
+    } else if raw.len() >= 2 && raw.starts_with('\'') && raw.ends_with('\'') {
+        raw[1..raw.len() - 1].replace("''", "'")
+    } else {
+        raw.to_string()
+    }
+}
+
+fn parse_inline_list(raw: &str) -> Option<Vec<String>> {
+    let inner = raw.trim().strip_prefix('[')?.strip_suffix(']')?;
+    Some(
+        inner
+            .split(',')
+            .map(unquote)
+            .filter(|s| !s.is_empty())
+            .collect(),
+    )
+}
+
+/// 解析正文开头的前置元数据，返回各字段和剩余正文；格式不符时返回 None，正文保持原样
+pub fn parse_front_matter(content: &str) -> Option<(Vec<(String, FrontMatterValue)>, &str)> {
+    let rest = content
+        .strip_prefix("\u{feff}")
+        .unwrap_or(content)
+        .strip_prefix("---")?;
+    let rest = rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n'))?;
+
+    let mut entries: Vec<(String, FrontMatterValue)> = Vec::new();
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        offset += line.len();
+        let line = line.trim_end_matches(['\r', '\n']);
+        let trimmed = line.trim();
+
+        if trimmed == "---" || trimmed == "..." {
+            let body = &rest[offset..];
+            let body = body.strip_prefix("\r\n").or_else(|| body.strip_prefix('\n')).unwrap_or(body);
+            return Some((entries, body));
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        // 块列表项属于上一个键
+        if let Some(item) = trimmed.strip_prefix("- ").or_else(|| (trimmed == "-").then_some("")) {
+            let (_, value) = entries.last_mut()?;
+            match value {
+                FrontMatterValue::List(items) => items.push(unquote(item)),
+                FrontMatterValue::Scalar(s) if s.is_empty() => {
+                    *value = FrontMatterValue::List(vec![unquote(item)]);
+                }
+                FrontMatterValue::Scalar(_) => return None,
+            }
+            continue;
+        }
+
+        let (key, value) = line.split_once(':')?;
+        let key = key.trim();
+        if key.is_empty() || line.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let value = match parse_inline_list(value) {
+            Some(items) => FrontMatterValue::List(items),
+            None => FrontMatterValue::Scalar(unquote(value)),
+        };
+        entries.push((key.to_string(), value));
+    }
+
+    // 没有结束分隔符
+    None
+}
+
+/// 拆出前置元数据：title、tags 单独返回，其余作为属性；没有前置元数据时原样返回正文和 None
+pub fn extract_front_matter(content: &str) -> (String, Option<FrontMatter>) {
+    let Some((entries, body)) = parse_front_matter(content) else {
+        return (content.to_string(), None);
+    };
+
+    let mut front_matter = FrontMatter::default();
+    for (key, value) in entries {
+        match key.to_lowercase().as_str() {
+            "title" => front_matter.title = Some(value.to_text()).filter(|t| !t.is_empty()),
+            "tags" => front_matter.tags = value.to_list(),
+            _ => {
+                let text = value.to_text();
+                if !text.is_empty() {
+                    front_matter.properties.push((key, text));
+                }
+            }
+        }
+    }
+    (body.to_string(), Some(front_matter))
+}
+
+fn needs_quotes(value: &str) -> bool {
+    value.is_empty()
+        || value != value.trim()
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.starts_with(['"', '\'', '[', '{', '-', '#', '&', '*', '!', '|', '>', '%', '@', '`'])
+}
+
+fn quote_scalar(value: &str) -> String {
+    if needs_quotes(value) {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// 生成前置元数据（含首尾分隔符和结尾换行），没有内容时返回空字符串
+pub fn render_front_matter(properties: &[TipProperty], tags: &[String]) -> String {
+    if properties.is_empty() && tags.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::from("---\n");
+    for property in properties {
+        out.push_str(&format!("{}: {}\n", property.key, quote_scalar(&property.value.to_text())));
+    }
+    if !tags.is_empty() {
+        let items: Vec<String> = tags.iter().map(|t| quote_scalar(t)).collect();
+        out.push_str(&format!("tags: [{}]\n", items.join(", ")));
+    }
+    out.push_str("---\n\n");
+    out
+}
+
+// ===============================================
+// 数据库操作
+// ===============================================
+
+/// 获取笔记的所有属性（按键名排序）
+pub async fn get_tip_properties(conn: &DbConnection, tip_id: &str) -> Result<Vec<TipProperty>> {
+    let mut rows = conn.query(
+        "SELECT key, value_type, value_text, value_number FROM tip_properties
+         WHERE tip_id = ?1 ORDER BY key COLLATE NOCASE",
+        params![tip_id],
+    ).await?;
+
+    let mut properties = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let value_type: String = row.get(1)?;
+        properties.push(TipProperty {
+            key: row.get(0)?,
+            value: PropertyValue::from_columns(&value_type, row.get(2)?, row.get(3)?)?,
+        });
+    }
+    Ok(properties)
+}
+
+async fn get_declared_type(conn: &DbConnection, key: &str) -> Result<Option<(PropertyType, Vec<String>)>> {
+    let mut rows = conn.query(
+        "SELECT value_type, options FROM tip_property_definitions WHERE key = ?1",
+        params![key],
+    ).await?;
+    match rows.next().await? {
+        Some(row) => {
+            let value_type: String = row.get(0)?;
+            let options: Option<String> = row.get(1)?;
+            let options = options
+                .map(|o| serde_json::from_str::<Vec<String>>(&o))
+                .transpose()?
+                .unwrap_or_default();
+            Ok(Some((PropertyType::try_from(value_type.as_str())?, options)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// 写入属性：value_text 保存原始文本（如 "007"），解析出的数值只写入 value_number
+async fn write_property(
+    conn: &DbConnection,
+    tip_id: &str,
+    key: &str,
+    value: &PropertyValue,
+    text: &str,
+    now: i64,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO tip_properties (tip_id, key, value_type, value_text, value_number, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(tip_id, key) DO UPDATE SET
+            key = excluded.key, value_type = excluded.value_type, value_text = excluded.value_text,
+            value_number = excluded.value_number, updated_at = excluded.updated_at",
+        params![tip_id, key, value.value_type().as_str(), text, value.to_number(), now],
+    ).await?;
+    Ok(())
+}
+
+/// 设置笔记属性，已声明类型的属性必须匹配声明
+pub async fn set_tip_property(conn: &DbConnection, tip_id: &str, key: &str, value: &PropertyValue) -> Result<()> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(anyhow!("Property key must not be empty"));
+    }
+    if let Some((declared, options)) = get_declared_type(conn, key).await? {
+        if declared != value.value_type() {
+            return Err(anyhow!("Property '{}' expects a {} value", key, declared.as_str()));
+        }
+        // 按声明重新校验（如 select 选项）
+        PropertyValue::parse(declared, &value.to_text(), &options)?;
+    }
+    write_property(conn, tip_id, key, value, &value.to_text(), Utc::now().timestamp_millis()).await
+}
+
+/// 删除笔记属性
+pub async fn delete_tip_property(conn: &DbConnection, tip_id: &str, key: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM tip_properties WHERE tip_id = ?1 AND key = ?2",
+        params![tip_id, key],
+    ).await?;
+    Ok(())
+}
+
+/// 以前置元数据为准写入属性：覆盖同名属性，删除前置元数据中已不存在的属性；值不符合声明类型时按文本保存。
+/// 前置元数据中没有属性时保留现有属性
+pub async fn apply_front_matter_properties(conn: &DbConnection, tip_id: &str, properties: &[(String, String)]) -> Result<()> {
+    if properties.is_empty() {
+        return Ok(());
+    }
+
+    let now = Utc::now().timestamp_millis();
+    let placeholders = (0..properties.len()).map(|i| format!("?{}", i + 2)).collect::<Vec<_>>().join(", ");
+    let mut values = vec![Value::Text(tip_id.to_string())];
+    values.extend(properties.iter().map(|(key, _)| Value::Text(key.clone())));
+    conn.execute(
+        &format!("DELETE FROM tip_properties WHERE tip_id = ?1 AND key NOT IN ({})", placeholders),
+        libsql::params_from_iter(values),
+    ).await?;
+
+    for (key, raw) in properties {
+        let value = match get_declared_type(conn, key).await? {
+            Some((declared, options)) => PropertyValue::parse(declared, raw, &options).unwrap_or_else(|e| {
+                tracing::warn!("Property '{}' on tip {} stored as text: {}", key, tip_id, e);
+                PropertyValue::Text(raw.trim().to_string())
+            }),
+            None => PropertyValue::infer(raw),
+        };
+        write_property(conn, tip_id, key, &value, raw.trim(), now).await?;
+    }
+    Ok(())
+}
+
+/// 声明属性类型（select 可附带选项）
+pub async fn set_property_definition(
+    conn: &DbConnection,
+    key: &str,
+    value_type: PropertyType,
+    options: &[String],
+) -> Result<()> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(anyhow!("Property key must not be empty"));
+    }
+    let options = if value_type == PropertyType::Select && !options.is_empty() {
+        Some(serde_json::to_string(options)?)
+    } else {
+        None
+    };
+    let now = Utc::now().timestamp_millis();
+    conn.execute(
+        "INSERT INTO tip_property_definitions (key, value_type, options, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?4)
+         ON CONFLICT(key) DO UPDATE SET
+            value_type = excluded.value_type, options = excluded.options, updated_at = excluded.updated_at",
+        params![key, value_type.as_str(), options, now],
+    ).await?;
+    Ok(())
+}
+
+/// 列出已声明和已使用的属性
+pub async fn list_property_definitions(conn: &DbConnection) -> Result<Vec<PropertyDefinition>> {
+    let mut rows = conn.query(
+        "SELECT key, value_type, options, 1, 0 FROM tip_property_definitions
+         UNION ALL
+         SELECT key, MAX(value_type), NULL, 0, COUNT(*) FROM tip_properties
+         GROUP BY key COLLATE NOCASE",
+        (),
+    ).await?;
+
+    let mut definitions: Vec<PropertyDefinition> = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let key: String = row.get(0)?;
+        let value_type: String = row.get(1)?;
+        let options: Option<String> = row.get(2)?;
+        let declared: i64 = row.get(3)?;
+        let usage_count: i64 = row.get(4)?;
+
+        match definitions.iter_mut().find(|d| d.key.eq_ignore_ascii_case(&key)) {
+            Some(existing) => existing.usage_count += usage_count,
+            None => definitions.push(PropertyDefinition {
+                key,
+                value_type: PropertyType::try_from(value_type.as_str())?,
+                options: options
+                    .map(|o| serde_json::from_str(&o))
+                    .transpose()?
+                    .unwrap_or_default(),
+                declared: declared == 1,
+                usage_count,
+            }),
+        }
+    }
+    definitions.sort_by_key(|d| d.key.to_lowercase());
+    Ok(definitions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_front_matter() {
+        let content = "---\ntitle: \"Hello: world\"\nauthor: Alice\ntags:\n  - rust\n  - 'async io'\ndue: 2025-03-01\n---\n# Body\n";
+        let (body, fm) = extract_front_matter(content);
+        let fm = fm.unwrap();
+        assert_eq!(body, "# Body\n");
+        assert_eq!(fm.title.as_deref(), Some("Hello: world"));
+        assert_eq!(fm.tags, vec!["rust", "async io"]);
+        assert_eq!(
+            fm.properties,
+            vec![("author".to_string(), "Alice".to_string()), ("due".to_string(), "2025-03-01".to_string())]
+        );
+    }
+
+    #[test]
+    fn leaves_content_without_front_matter() {
+        for content in ["# Title\n", "---\nno closing fence\n", "---\n  indented: x\n---\n", "text\n---\na: b\n---\n"] {
+            let (body, fm) = extract_front_matter(content);
+            assert_eq!(body, content);
+            assert!(fm.is_none());
+        }
+    }
+
+    #[test]
+    fn infers_and_validates_values() {
+        assert_eq!(PropertyValue::infer("42"), PropertyValue::Number(42.0));
+        assert_eq!(
+            PropertyValue::infer("2025-01-31"),
+            PropertyValue::Date(NaiveDate::from_ymd_opt(2025, 1, 31).unwrap())
+        );
+        assert_eq!(PropertyValue::infer("https://example.com"), PropertyValue::Url("https://example.com".into()));
+        assert_eq!(PropertyValue::infer("draft"), PropertyValue::Text("draft".into()));
+
+        let options = vec!["todo".to_string(), "done".to_string()];
+        assert!(PropertyValue::parse(PropertyType::Select, "done", &options).is_ok());
+        assert!(PropertyValue::parse(PropertyType::Select, "later", &options).is_err());
+        assert!(PropertyValue::parse(PropertyType::Number, "abc", &[]).is_err());
+    }
+
+    #[test]
+    fn renders_round_trip() {
+        let properties = vec![
+            TipProperty { key: "author".into(), value: PropertyValue::Text("Bob: Jr".into()) },
+            TipProperty { key: "score".into(), value: PropertyValue::Number(4.5) },
+        ];
+        let rendered = render_front_matter(&properties, &["a".to_string(), "b c".to_string()]);
+        assert_eq!(rendered, "---\nauthor: \"Bob: Jr\"\nscore: 4.5\ntags: [a, b c]\n---\n\n");
+
+        let (body, fm) = extract_front_matter(&format!("{}body", rendered));
+        let fm = fm.unwrap();
+        assert_eq!(body, "body");
+        assert_eq!(fm.tags, vec!["a", "b c"]);
+        assert_eq!(fm.properties[0], ("author".to_string(), "Bob: Jr".to_string()));
+    }
+
+    #[test]
+    fn builds_filter_sql() {
+        let mut params = Vec::new();
+        let mut bind = |v: Value| {
+            params.push(v);
+            format!("?{}", params.len() + 2)
+        };
+        let filter = PropertyFilter { key: "due".into(), op: PropertyFilterOp::Lt, value: Some("2025-01-01".into()) };
+        let sql = property_filter_sql(&filter, "tips.id", &mut bind).unwrap();
+        assert_eq!(
+            sql,
+            "EXISTS (SELECT 1 FROM tip_properties tp WHERE tp.tip_id = tips.id AND tp.key = ?3 COLLATE NOCASE AND tp.value_number < ?4)"
+        );
+        assert_eq!(params.len(), 2);
+    }
+
+    async fn property_test_db() -> (libsql::Database, DbConnection) {
+        let db = libsql::Builder::new_local(":memory:").build().await.unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE tip_properties (
+                 tip_id TEXT NOT NULL, key TEXT NOT NULL COLLATE NOCASE, value_type TEXT NOT NULL,
+                 value_text TEXT NOT NULL, value_number REAL, updated_at INTEGER NOT NULL,
+                 PRIMARY KEY (tip_id, key)
+             );
+             CREATE TABLE tip_property_definitions (
+                 key TEXT PRIMARY KEY COLLATE NOCASE, value_type TEXT NOT NULL, options TEXT,
+                 created_at INTEGER NOT NULL, updated_at INTEGER NOT NULL
+             );",
+        ).await.unwrap();
+        (db, conn)
+    }
+
+    #[tokio::test]
+    async fn front_matter_replaces_properties_and_keeps_raw_text() {
+        let (_db, conn) = property_test_db().await;
+        let first = vec![("code".to_string(), "007".to_string()), ("author".to_string(), "Alice".to_string())];
+        apply_front_matter_properties(&conn, "t", &first).await.unwrap();
+        apply_front_matter_properties(&conn, "t", &first[..1]).await.unwrap();
+
+        let mut rows = conn.query("SELECT key, value_text, value_number FROM tip_properties WHERE tip_id = 't'", ()).await.unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get::<String>(0).unwrap(), "code");
+        assert_eq!(row.get::<String>(1).unwrap(), "007");
+        assert_eq!(row.get::<f64>(2).unwrap(), 7.0);
+        assert!(rows.next().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn saving_without_front_matter_keeps_properties() {
+        let (_db, conn) = property_test_db().await;
+        let written = vec![("author".to_string(), "Alice".to_string())];
+        apply_front_matter_properties(&conn, "t", &written).await.unwrap();
+        set_tip_property(&conn, "t", "score", &PropertyValue::Number(3.0)).await.unwrap();
+
+        // 上次保存已移除前置元数据，再次保存的正文中没有前置元数据
+        let (_, front_matter) = extract_front_matter("# Body\n");
+        assert!(front_matter.is_none());
+        let (_, front_matter) = extract_front_matter("---\ntitle: Only a title\n---\n# Body\n");
+        apply_front_matter_properties(&conn, "t", &front_matter.unwrap().properties).await.unwrap();
+
+        let mut rows = conn.query("SELECT key FROM tip_properties WHERE tip_id = 't' ORDER BY key", ()).await.unwrap();
+        let mut keys = Vec::new();
+        while let Some(row) = rows.next().await.unwrap() {
+            keys.push(row.get::<String>(0).unwrap());
+        }
+        assert_eq!(keys, vec!["author", "score"]);
+    }
+}
@@ -0,0 +1,579 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use libsql::params;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::models::TipRevision;
+use super::operations::{get_setting, get_tip_by_id, get_tip_tags, DbConnection};
+
+// 修订历史相关设置（app_settings）
+pub const REVISION_MAX_PER_TIP_KEY: &str = "revision_max_per_tip";
+pub const REVISION_COALESCE_SECONDS_KEY: &str = "revision_coalesce_seconds";
+pub const REVISION_RETENTION_DAYS_KEY: &str = "revision_retention_days";
+
+/// 修订历史配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevisionSettings {
+    pub max_per_tip: i64,      // 每条笔记最多保留的修订数，0 表示不限制
+    pub coalesce_seconds: i64, // 该时间窗口内的连续保存合并为一个修订
+    pub retention_days: i64,   // 超过天数的修订被清理（始终保留最新一条），0 表示不限制
+}
+
+impl Default for RevisionSettings {
+    fn default() -> Self {
+        Self {
+            max_per_tip: 50,
+            coalesce_seconds: 60,
+            retention_days: 0,
+        }
+    }
+}
+
+/// 修订列表项（不含正文）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TipRevisionSummary {
+    pub id: String,
+    pub tip_id: String,
+    pub revision_number: i64,
+    pub title: String,
+    pub reason: String,
+    pub content_length: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// 差异行类型
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// 差异行，行号从1开始
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub text: String,
+}
+
+/// 两个修订之间的差异
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevisionDiff {
+    pub from_revision_id: String,
+    pub to_revision_id: Option<String>, // None 表示与当前内容比较
+    pub title_changed: bool,
+    pub lines: Vec<DiffLine>,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// 序列差异操作（索引从0开始）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffOp {
+    Equal { old: usize, new: usize },
+    Delete { old: usize },
+    Insert { new: usize },
+}
+
+// ===============================================
+// 差异算法
+// ===============================================
+
+/// Myers 差异算法（线性空间），先裁掉公共前后缀以减少计算量
+pub fn diff_sequences<T: PartialEq>(a: &[T], b: &[T]) -> Vec<DiffOp> {
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+
+    let mid_a = &a[prefix..a.len() - suffix];
+    let mid_b = &b[prefix..b.len() - suffix];
+
+    let mut ops: Vec<DiffOp> = (0..prefix).map(|i| DiffOp::Equal { old: i, new: i }).collect();
+    for op in myers(mid_a, mid_b) {
+        ops.push(match op {
+            DiffOp::Equal { old, new } => DiffOp::Equal { old: old + prefix, new: new + prefix },
+            DiffOp::Delete { old } => DiffOp::Delete { old: old + prefix },
+            DiffOp::Insert { new } => DiffOp::Insert { new: new + prefix },
+        });
+    }
+    for i in 0..suffix {
+        ops.push(DiffOp::Equal {
+            old: a.len() - suffix + i,
+            new: b.len() - suffix + i,
+        });
+    }
+    ops
+}
+
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<DiffOp> {
+    let mut ops = Vec::with_capacity(a.len() + b.len());
+    diff_range(a, b, (0, a.len()), (0, b.len()), &mut ops);
+    ops
+}
+
+/// 线性空间的 Myers：以中间蛇为界分治，内存只与序列长度成正比
+fn diff_range<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    (mut a_lo, a_hi): (usize, usize),
+    (mut b_lo, b_hi): (usize, usize),
+    ops: &mut Vec<DiffOp>,
+) {
+    while a_lo < a_hi && b_lo < b_hi && a[a_lo] == b[b_lo] {
+        ops.push(DiffOp::Equal { old: a_lo, new: b_lo });
+        a_lo += 1;
+        b_lo += 1;
+    }
+    let mut suffix = 0;
+    while a_lo + suffix < a_hi && b_lo + suffix < b_hi && a[a_hi - 1 - suffix] == b[b_hi - 1 - suffix] {
+        suffix += 1;
+    }
+    let (a_end, b_end) = (a_hi - suffix, b_hi - suffix);
+
+    if a_lo == a_end {
+        ops.extend((b_lo..b_end).map(|new| DiffOp::Insert { new }));
+    } else if b_lo == b_end {
+        ops.extend((a_lo..a_end).map(|old| DiffOp::Delete { old }));
+    } else {
+        let (x0, y0, x1, y1) = middle_snake(&a[a_lo..a_end], &b[b_lo..b_end]);
+        diff_range(a, b, (a_lo, a_lo + x0), (b_lo, b_lo + y0), ops);
+        ops.extend((0..x1 - x0).map(|i| DiffOp::Equal { old: a_lo + x0 + i, new: b_lo + y0 + i }));
+        diff_range(a, b, (a_lo + x1, a_end), (b_lo + y1, b_end), ops);
+    }
+
+    ops.extend((0..suffix).map(|i| DiffOp::Equal { old: a_end + i, new: b_end + i }));
+}
+
+/// 从两端同时搜索，返回最短编辑路径中间的一段公共片段 (x0, y0) -> (x1, y1)
+fn middle_snake<T: PartialEq>(a: &[T], b: &[T]) -> (usize, usize, usize, usize) {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    let mut forward = vec![0isize; (2 * offset + 1) as usize];
+    let mut backward = vec![0isize; (2 * offset + 1) as usize];
+    let at = |k: isize| (k + offset) as usize;
+
+    for d in 0..=max {
+        let mut k = -d;
+        while k <= d {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+            let reverse_k = delta - k;
+            if odd && (-(d - 1)..=d - 1).contains(&reverse_k) && x + backward[at(reverse_k)] >= n {
+                return (x0 as usize, y0 as usize, x as usize, y as usize);
+            }
+            k += 2;
+        }
+
+        // 反向搜索：x、y 为距离序列末尾的步数
+        let mut k = -d;
+        while k <= d {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (x0, y0) = (x, y);
+            while x < n && y < m && a[(n - 1 - x) as usize] == b[(m - 1 - y) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+            let forward_k = delta - k;
+            if !odd && (-d..=d).contains(&forward_k) && x + forward[at(forward_k)] >= n {
+                return ((n - x) as usize, (m - y) as usize, (n - x0) as usize, (m - y0) as usize);
+            }
+            k += 2;
+        }
+    }
+
+    unreachable!("Myers search always meets within (n + m + 1) / 2 steps")
+}
+
+/// 按行比较两段文本
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    diff_sequences(&old_lines, &new_lines)
+        .into_iter()
+        .map(|op| match op {
+            DiffOp::Equal { old, new } => DiffLine {
+                kind: DiffKind::Equal,
+                old_line: Some(old + 1),
+                new_line: Some(new + 1),
+                text: old_lines[old].to_string(),
+            },
+            DiffOp::Delete { old } => DiffLine {
+                kind: DiffKind::Delete,
+                old_line: Some(old + 1),
+                new_line: None,
+                text: old_lines[old].to_string(),
+            },
+            DiffOp::Insert { new } => DiffLine {
+                kind: DiffKind::Insert,
+                old_line: None,
+                new_line: Some(new + 1),
+                text: new_lines[new].to_string(),
+            },
+        })
+        .collect()
+}
+
+// ===============================================
+// 修订历史数据库操作
+// ===============================================
+
+async fn get_i64_setting(conn: &DbConnection, key: &str, default: i64) -> Result<i64> {
+    Ok(get_setting(conn, key)
+        .await?
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .unwrap_or(default))
+}
+
+/// 读取修订历史配置
+pub async fn get_revision_settings(conn: &DbConnection) -> Result<RevisionSettings> {
+    let defaults = RevisionSettings::default();
+    Ok(RevisionSettings {
+        max_per_tip: get_i64_setting(conn, REVISION_MAX_PER_TIP_KEY, defaults.max_per_tip).await?,
+        coalesce_seconds: get_i64_setting(conn, REVISION_COALESCE_SECONDS_KEY, defaults.coalesce_seconds).await?,
+        retention_days: get_i64_setting(conn, REVISION_RETENTION_DAYS_KEY, defaults.retention_days).await?,
+    })
+}
+
+fn row_to_revision(row: &libsql::Row) -> Result<TipRevision> {
+    let tags_json: String = row.get(8)?;
+    Ok(TipRevision {
+        id: row.get(0)?,
+        tip_id: row.get(1)?,
+        revision_number: row.get(2)?,
+        title: row.get(3)?,
+        content: row.get(4)?,
+        tip_type: row.get(5)?,
+        language: row.get(6)?,
+        category_id: row.get(7)?,
+        tags: serde_json::from_str(&tags_json).unwrap_or_default(),
+        reason: row.get(9)?,
+        created_at: row.get(10)?,
+        updated_at: row.get(11)?,
+    })
+}
+
+const REVISION_COLUMNS: &str = "id, tip_id, revision_number, title, content, tip_type, language, category_id, tags, reason, created_at, updated_at";
+
+/// 获取单个修订
+pub async fn get_tip_revision(conn: &DbConnection, revision_id: &str) -> Result<Option<TipRevision>> {
+    let mut rows = conn.query(
+        &format!("SELECT {} FROM tip_revisions WHERE id = ?1", REVISION_COLUMNS),
+        params![revision_id],
+    ).await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(row_to_revision(&row)?)),
+        None => Ok(None),
+    }
+}
+
+async fn get_latest_revision(conn: &DbConnection, tip_id: &str) -> Result<Option<TipRevision>> {
+    let mut rows = conn.query(
+        &format!(
+            "SELECT {} FROM tip_revisions WHERE tip_id = ?1 ORDER BY revision_number DESC LIMIT 1",
+            REVISION_COLUMNS
+        ),
+        params![tip_id],
+    ).await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(row_to_revision(&row)?)),
+        None => Ok(None),
+    }
+}
+
+/// 列出笔记的修订（最新在前）
+pub async fn list_tip_revisions(conn: &DbConnection, tip_id: &str) -> Result<Vec<TipRevisionSummary>> {
+    let mut rows = conn.query(
+        "SELECT id, tip_id, revision_number, title, reason, length(content), created_at, updated_at
+         FROM tip_revisions WHERE tip_id = ?1 ORDER BY revision_number DESC",
+        params![tip_id],
+    ).await?;
+
+    let mut revisions = Vec::new();
+    while let Some(row) = rows.next().await? {
+        revisions.push(TipRevisionSummary {
+            id: row.get(0)?,
+            tip_id: row.get(1)?,
+            revision_number: row.get(2)?,
+            title: row.get(3)?,
+            reason: row.get(4)?,
+            content_length: row.get(5)?,
+            created_at: row.get(6)?,
+            updated_at: row.get(7)?,
+        });
+    }
+    Ok(revisions)
+}
+
+/// 读取笔记当前状态作为快照（不含 id/编号）
+async fn snapshot_tip(conn: &DbConnection, tip_id: &str) -> Result<Option<TipRevision>> {
+    let tip = match get_tip_by_id(conn, tip_id).await? {
+        Some(tip) => tip,
+        None => return Ok(None),
+    };
+    let tags = get_tip_tags(conn, tip_id).await?.into_iter().map(|t| t.name).collect();
+
+    Ok(Some(TipRevision {
+        id: String::new(),
+        tip_id: tip.id,
+        revision_number: 0,
+        title: tip.title,
+        content: tip.content,
+        tip_type: tip.tip_type.into(),
+        language: tip.language,
+        category_id: tip.category_id,
+        tags,
+        reason: String::new(),
+        created_at: tip.updated_at,
+        updated_at: tip.updated_at,
+    }))
+}
+
+fn same_snapshot(a: &TipRevision, b: &TipRevision) -> bool {
+    a.title == b.title
+        && a.content == b.content
+        && a.tip_type == b.tip_type
+        && a.language == b.language
+        && a.category_id == b.category_id
+        && a.tags == b.tags
+}
+
+async fn insert_revision(conn: &DbConnection, revision: &TipRevision) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT INTO tip_revisions ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            REVISION_COLUMNS
+        ),
+        params![
+            revision.id.as_str(),
+            revision.tip_id.as_str(),
+            revision.revision_number,
+            revision.title.as_str(),
+            revision.content.as_str(),
+            revision.tip_type.as_str(),
+            revision.language.as_deref(),
+            revision.category_id.as_deref(),
+            serde_json::to_string(&revision.tags)?,
+            revision.reason.as_str(),
+            revision.created_at,
+            revision.updated_at
+        ],
+    ).await?;
+    Ok(())
+}
+
+/// 更新前调用：没有任何修订的旧笔记先保存一份原始快照，避免首次覆盖丢失
+pub async fn ensure_baseline_revision(conn: &DbConnection, tip_id: &str) -> Result<()> {
+    if get_latest_revision(conn, tip_id).await?.is_some() {
+        return Ok(());
+    }
+    if let Some(mut snapshot) = snapshot_tip(conn, tip_id).await? {
+        snapshot.id = Uuid::new_v4().to_string();
+        snapshot.revision_number = 1;
+        snapshot.reason = "initial".to_string();
+        insert_revision(conn, &snapshot).await?;
+    }
+    Ok(())
+}
+
+/// 保存后调用：记录当前状态为新修订
+///
+/// 内容未变化时不记录；距上一个普通保存修订的创建时间在合并窗口内时，直接覆盖该修订。
+pub async fn record_tip_revision(conn: &DbConnection, tip_id: &str, reason: &str) -> Result<Option<TipRevision>> {
+    let mut snapshot = match snapshot_tip(conn, tip_id).await? {
+        Some(snapshot) => snapshot,
+        None => return Ok(None),
+    };
+    let settings = get_revision_settings(conn).await?;
+    let now = Utc::now().timestamp_millis();
+    let latest = get_latest_revision(conn, tip_id).await?;
+
+    if let Some(latest) = &latest {
+        if same_snapshot(latest, &snapshot) {
+            return Ok(None);
+        }
+
+        let within_window = now - latest.created_at < settings.coalesce_seconds * 1000;
+        if reason == "save" && latest.reason == "save" && within_window {
+            conn.execute(
+                "UPDATE tip_revisions SET title = ?1, content = ?2, tip_type = ?3, language = ?4,
+                        category_id = ?5, tags = ?6, updated_at = ?7
+                 WHERE id = ?8",
+                params![
+                    snapshot.title.as_str(),
+                    snapshot.content.as_str(),
+                    snapshot.tip_type.as_str(),
+                    snapshot.language.as_deref(),
+                    snapshot.category_id.as_deref(),
+                    serde_json::to_string(&snapshot.tags)?,
+                    now,
+                    latest.id.as_str()
+                ],
+            ).await?;
+            return get_tip_revision(conn, &latest.id).await;
+        }
+    }
+
+    snapshot.id = Uuid::new_v4().to_string();
+    snapshot.revision_number = latest.map(|r| r.revision_number + 1).unwrap_or(1);
+    snapshot.reason = reason.to_string();
+    snapshot.created_at = now;
+    snapshot.updated_at = now;
+    insert_revision(conn, &snapshot).await?;
+
+    prune_tip_revisions(conn, tip_id, &settings).await?;
+    Ok(Some(snapshot))
+}
+
+/// 按配置清理旧修订，始终保留最新一条
+pub async fn prune_tip_revisions(conn: &DbConnection, tip_id: &str, settings: &RevisionSettings) -> Result<()> {
+    if settings.max_per_tip > 0 {
+        conn.execute(
+            "DELETE FROM tip_revisions WHERE tip_id = ?1 AND id NOT IN (
+                SELECT id FROM tip_revisions WHERE tip_id = ?1 ORDER BY revision_number DESC LIMIT ?2
+             )",
+            params![tip_id, settings.max_per_tip],
+        ).await?;
+    }
+
+    if settings.retention_days > 0 {
+        let cutoff = Utc::now().timestamp_millis() - settings.retention_days * 24 * 60 * 60 * 1000;
+        conn.execute(
+            "DELETE FROM tip_revisions WHERE tip_id = ?1 AND updated_at < ?2 AND id NOT IN (
+                SELECT id FROM tip_revisions WHERE tip_id = ?1 ORDER BY revision_number DESC LIMIT 1
+             )",
+            params![tip_id, cutoff],
+        ).await?;
+    }
+    Ok(())
+}
+
+/// 比较两个修订；to_revision_id 为空时与笔记当前内容比较
+pub async fn diff_tip_revisions(
+    conn: &DbConnection,
+    from_revision_id: &str,
+    to_revision_id: Option<&str>,
+) -> Result<RevisionDiff> {
+    let from = get_tip_revision(conn, from_revision_id)
+        .await?
+        .ok_or_else(|| anyhow!("Revision not found: {}", from_revision_id))?;
+
+    let to = match to_revision_id {
+        Some(id) => get_tip_revision(conn, id)
+            .await?
+            .ok_or_else(|| anyhow!("Revision not found: {}", id))?,
+        None => snapshot_tip(conn, &from.tip_id)
+            .await?
+            .ok_or_else(|| anyhow!("Tip not found: {}", from.tip_id))?,
+    };
+
+    let lines = diff_lines(&from.content, &to.content);
+    let added = lines.iter().filter(|l| l.kind == DiffKind::Insert).count();
+    let removed = lines.iter().filter(|l| l.kind == DiffKind::Delete).count();
+
+    Ok(RevisionDiff {
+        from_revision_id: from.id,
+        to_revision_id: to_revision_id.map(|s| s.to_string()),
+        title_changed: from.title != to.title,
+        lines,
+        added,
+        removed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(a: &[&str], b: &[&str], ops: &[DiffOp]) -> Vec<String> {
+        // 根据差异操作重建新序列，用于校验正确性
+        let mut out = Vec::new();
+        for op in ops {
+            match op {
+                DiffOp::Equal { old, new } => {
+                    assert_eq!(a[*old], b[*new]);
+                    out.push(a[*old].to_string());
+                }
+                DiffOp::Insert { new } => out.push(b[*new].to_string()),
+                DiffOp::Delete { .. } => {}
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_diff_sequences_roundtrip() {
+        let cases: Vec<(Vec<&str>, Vec<&str>)> = vec![
+            (vec![], vec![]),
+            (vec![], vec!["a"]),
+            (vec!["a"], vec![]),
+            (vec!["a", "b", "c"], vec!["a", "b", "c"]),
+            (vec!["a", "b", "c", "a", "b", "b", "a"], vec!["c", "b", "a", "b", "a", "c"]),
+            (vec!["x", "a", "y"], vec!["a", "z"]),
+        ];
+        for (a, b) in cases {
+            let ops = diff_sequences(&a, &b);
+            assert_eq!(apply(&a, &b, &ops), b.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+            let deletes = ops.iter().filter(|o| matches!(o, DiffOp::Delete { .. })).count();
+            let equals = ops.iter().filter(|o| matches!(o, DiffOp::Equal { .. })).count();
+            assert_eq!(deletes + equals, a.len());
+        }
+    }
+
+    #[test]
+    fn test_diff_sequences_is_minimal() {
+        // 经典示例：ABCABBA -> CBABAC 的最短编辑距离为5
+        let a: Vec<char> = "ABCABBA".chars().collect();
+        let b: Vec<char> = "CBABAC".chars().collect();
+        let edits = diff_sequences(&a, &b)
+            .iter()
+            .filter(|o| !matches!(o, DiffOp::Equal { .. }))
+            .count();
+        assert_eq!(edits, 5);
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let lines = diff_lines("one\ntwo\nthree", "one\n2\nthree\nfour");
+        let summary: Vec<(DiffKind, Option<usize>, Option<usize>, &str)> = lines
+            .iter()
+            .map(|l| (l.kind, l.old_line, l.new_line, l.text.as_str()))
+            .collect();
+        assert_eq!(summary, vec![
+            (DiffKind::Equal, Some(1), Some(1), "one"),
+            (DiffKind::Delete, Some(2), None, "two"),
+            (DiffKind::Insert, None, Some(2), "2"),
+            (DiffKind::Equal, Some(3), Some(3), "three"),
+            (DiffKind::Insert, None, Some(4), "four"),
+        ]);
+    }
+}
@@ -0,0 +1,391 @@
+use anyhow::Result;
+use libsql::{params, Connection};
+use serde::{Deserialize, Serialize};
+
+use super::models::Tip;
+use super::operations::{get_setting, save_setting, DbConnection};
+use crate::api::tips::TipSummary;
+
+// FTS 索引结构版本，结构变化时递增以触发重建
+const TIPS_FTS_VERSION: &str = "1";
+const TIPS_FTS_VERSION_KEY: &str = "tips_fts_version";
+
+// highlight()/snippet() 使用的标记字符，返回前会被剥离并转换为偏移量
+const MARK_START: char = '\u{2}';
+const MARK_END: char = '\u{3}';
+
+// trigram 分词器要求每个词至少3个字符
+const MIN_TERM_CHARS: usize = 3;
+
+/// 高亮区间（字符偏移，左闭右开）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighlightRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// 全文检索命中信息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchMatch {
+    pub rank: f64,                           // bm25 得分，越小越相关
+    pub title_highlights: Vec<HighlightRange>, // 标题中的命中区间
+    pub snippet: String,                     // 命中片段（已去除标记）
+    pub snippet_highlights: Vec<HighlightRange>, // 片段中的命中区间
+}
+
+/// 标签名拼接表达式
+fn tags_expr(tip_id: &str) -> String {
+    format!(
+        "COALESCE((SELECT group_concat(tg.name, ' ') FROM tip_tags tt JOIN tags tg ON tg.id = tt.tag_id WHERE tt.tip_id = {}), '')",
+        tip_id
+    )
+}
+
+/// 分类路径表达式
+fn category_path_expr(category_id: &str) -> String {
+    format!(
+        "COALESCE((SELECT path FROM category_paths WHERE category_id = {}), '')",
+        category_id
+    )
+}
+
+/// 加密笔记不进入索引
+fn content_expr(prefix: &str) -> String {
+    format!(
+        "CASE WHEN COALESCE({p}.is_encrypted, 0) THEN '' ELSE COALESCE({p}.content, '') END",
+        p = prefix
+    )
+}
+
+/// 创建全文检索相关的视图、虚拟表和触发器
+pub async fn create_fts_schema(conn: &Connection) -> Result<()> {
+    // 分类完整路径视图（如 "工作/项目A"），id_path 用于判断子树
+    conn.execute(
+        "CREATE VIEW IF NOT EXISTS category_paths AS
+         WITH RECURSIVE paths(category_id, path, id_path) AS (
+             SELECT id, name, '/' || id || '/' FROM categories WHERE parent_id IS NULL
+             UNION ALL
+             SELECT c.id, p.path || '/' || c.name, p.id_path || c.id || '/'
+             FROM categories c JOIN paths p ON c.parent_id = p.category_id
+         )
+         SELECT category_id, path, id_path FROM paths",
+        (),
+    ).await?;
+
+    // 使用 trigram 分词，兼顾中文和子串匹配
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS tips_fts USING fts5(
+            tip_id UNINDEXED,
+            title,
+            content,
+            tags,
+            category_path,
+            tokenize = 'trigram'
+        )",
+        (),
+    ).await?;
+
+    // 插入前先删除同 id 的旧行，兼容 INSERT OR REPLACE（REPLACE 不会触发删除触发器）
+    let insert_row = format!(
+        "DELETE FROM tips_fts WHERE tip_id = new.id;
+         INSERT INTO tips_fts (tip_id, title, content, tags, category_path)
+         VALUES (new.id, new.title, {}, {}, {});",
+        content_expr("new"),
+        tags_expr("new.id"),
+        category_path_expr("new.category_id"),
+    );
+
+    conn.execute(
+        &format!(
+            "CREATE TRIGGER IF NOT EXISTS tips_fts_ai AFTER INSERT ON tips BEGIN {} END",
+            insert_row
+        ),
+        (),
+    ).await?;
+
+    conn.execute(
+        &format!(
+            "CREATE TRIGGER IF NOT EXISTS tips_fts_au AFTER UPDATE OF title, content, category_id, is_encrypted ON tips BEGIN
+                DELETE FROM tips_fts WHERE tip_id = old.id;
+                {}
+             END",
+            insert_row
+        ),
+        (),
+    ).await?;
+
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS tips_fts_ad AFTER DELETE ON tips BEGIN
+            DELETE FROM tips_fts WHERE tip_id = old.id;
+         END",
+        (),
+    ).await?;
+
+    // 标签关联变化时刷新 tags 列
+    conn.execute(
+        &format!(
+            "CREATE TRIGGER IF NOT EXISTS tip_tags_fts_ai AFTER INSERT ON tip_tags BEGIN
+                UPDATE tips_fts SET tags = {} WHERE tip_id = new.tip_id;
+             END",
+            tags_expr("new.tip_id")
+        ),
+        (),
+    ).await?;
+
+    conn.execute(
+        &format!(
+            "CREATE TRIGGER IF NOT EXISTS tip_tags_fts_ad AFTER DELETE ON tip_tags BEGIN
+                UPDATE tips_fts SET tags = {} WHERE tip_id = old.tip_id;
+             END",
+            tags_expr("old.tip_id")
+        ),
+        (),
+    ).await?;
+
+    conn.execute(
+        &format!(
+            "CREATE TRIGGER IF NOT EXISTS tags_fts_au AFTER UPDATE OF name ON tags BEGIN
+                UPDATE tips_fts SET tags = {}
+                WHERE tip_id IN (SELECT tip_id FROM tip_tags WHERE tag_id = new.id);
+             END",
+            tags_expr("tips_fts.tip_id")
+        ),
+        (),
+    ).await?;
+
+    // 分类改名或移动时刷新整个子树的路径
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS categories_fts_au AFTER UPDATE OF name, parent_id ON categories BEGIN
+            UPDATE tips_fts SET category_path = COALESCE((
+                SELECT cp.path FROM tips t JOIN category_paths cp ON cp.category_id = t.category_id
+                WHERE t.id = tips_fts.tip_id
+            ), '')
+            WHERE tip_id IN (
+                SELECT t.id FROM tips t JOIN category_paths cp ON cp.category_id = t.category_id
+                WHERE cp.id_path LIKE '%/' || new.id || '/%'
+            );
+         END",
+        (),
+    ).await?;
+
+    ensure_tips_fts_backfilled(conn).await?;
+
+    Ok(())
+}
+
+/// 检查索引是否需要回填（旧数据库首次升级或索引版本变化）
+async fn ensure_tips_fts_backfilled(conn: &Connection) -> Result<()> {
+    let version = get_setting(conn, TIPS_FTS_VERSION_KEY).await?;
+
+    let mut rows = conn.query(
+        "SELECT (SELECT COUNT(*) FROM tips), (SELECT COUNT(*) FROM tips_fts)",
+        (),
+    ).await?;
+    let (tips_count, fts_count): (i64, i64) = match rows.next().await? {
+        Some(row) => (row.get(0)?, row.get(1)?),
+        None => (0, 0),
+    };
+
+    if version.as_deref() != Some(TIPS_FTS_VERSION) || tips_count != fts_count {
+        tracing::info!(
+            "Rebuilding tips full-text index ({} tips, {} indexed)",
+            tips_count, fts_count
+        );
+        rebuild_tips_fts(conn).await?;
+        save_setting(conn, TIPS_FTS_VERSION_KEY, TIPS_FTS_VERSION).await?;
+    }
+
+    Ok(())
+}
+
+/// 重建笔记全文索引
+pub async fn rebuild_tips_fts(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM tips_fts", ()).await?;
+    conn.execute(
+        &format!(
+            "INSERT INTO tips_fts (tip_id, title, content, tags, category_path)
+             SELECT t.id, t.title, {}, {}, {} FROM tips t",
+            content_expr("t"),
+            tags_expr("t.id"),
+            category_path_expr("t.category_id"),
+        ),
+        (),
+    ).await?;
+    Ok(())
+}
+
+/// 将用户输入转换为 FTS5 MATCH 表达式
+///
+/// 每个词都作为短语加引号处理，多个词之间为 AND。
+/// 任一词少于3个字符时 trigram 无法匹配，返回 None 由调用方回退到 LIKE。
+pub fn build_fts_match_query(query: &str) -> Option<String> {
+    let terms: Vec<&str> = query.split_whitespace().collect();
+    if terms.is_empty() || terms.iter().any(|t| t.chars().count() < MIN_TERM_CHARS) {
+        return None;
+    }
+
+    Some(
+        terms
+            .iter()
+            .map(|t| format!("\"{}\"", t.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+/// 去除高亮标记并返回命中区间
+pub fn extract_highlights(marked: &str) -> (String, Vec<HighlightRange>) {
+    let mut text = String::with_capacity(marked.len());
+    let mut ranges = Vec::new();
+    let mut pos = 0usize;
+    let mut open: Option<usize> = None;
+
+    for ch in marked.chars() {
+        match ch {
+            MARK_START => open = Some(pos),
+            MARK_END => {
+                if let Some(start) = open.take() {
+                    if pos > start {
+                        ranges.push(HighlightRange { start, end: pos });
+                    }
+                }
+            }
+            _ => {
+                text.push(ch);
+                pos += 1;
+            }
+        }
+    }
+
+    (text, ranges)
+}
+
+// bm25 列权重：tip_id, title, content, tags, category_path
+const RANK_EXPR: &str = "bm25(tips_fts, 0.0, 10.0, 1.0, 5.0, 2.0)";
+
+/// 全文检索笔记（完整内容），按 bm25 排序
+pub async fn search_tips_fts(conn: &DbConnection, match_query: &str, limit: i32) -> Result<Vec<Tip>> {
+    let mut rows = conn.query(
+        &format!(
+            "SELECT t.id, t.title, t.content, t.tip_type, t.language, t.category_id, t.created_at, t.updated_at,
+                    t.version, t.last_synced_at, t.sync_hash, t.is_encrypted, t.encryption_key_id, t.encrypted_content, t.pinned, t.favorite, t.archived, t.sort_key
+             FROM tips_fts
+             JOIN tips t ON t.id = tips_fts.tip_id
+             WHERE tips_fts MATCH ?1 AND t.deleted_at IS NULL
+             ORDER BY {}, t.updated_at DESC
+             LIMIT ?2",
+            RANK_EXPR
+        ),
+        params![match_query, limit],
+    ).await?;
+
+    let mut tips = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let tip_type_str: String = row.get(3)?;
+        tips.push(Tip {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            content: row.get(2)?,
+            tip_type: tip_type_str.try_into()?,
+            language: row.get(4)?,
+            category_id: row.get(5)?,
+            created_at: row.get(6)?,
+            updated_at: row.get(7)?,
+            version: row.get(8)?,
+            last_synced_at: row.get(9)?,
+            sync_hash: row.get(10)?,
+            is_encrypted: row.get(11)?,
+            encryption_key_id: row.get(12)?,
+            encrypted_content: row.get(13)?,
+            pinned: row.get(14)?,
+            favorite: row.get(15)?,
+            archived: row.get(16)?,
+            sort_key: row.get(17)?,
+        });
+    }
+    Ok(tips)
+}
+
+/// 全文检索笔记摘要，附带片段和高亮偏移
+pub async fn search_tips_summary_fts(conn: &DbConnection, match_query: &str, limit: i32) -> Result<Vec<TipSummary>> {
+    let mut rows = conn.query(
+        &format!(
+            "SELECT t.id, t.tip_type, t.language, t.category_id, t.created_at, t.updated_at, t.is_encrypted,
+                    {rank} AS rank,
+                    highlight(tips_fts, 1, char(2), char(3)),
+                    snippet(tips_fts, -1, char(2), char(3), '…', 24),
+                    t.pinned, t.favorite, t.archived
+             FROM tips_fts
+             JOIN tips t ON t.id = tips_fts.tip_id
+             WHERE tips_fts MATCH ?1 AND t.deleted_at IS NULL
+             ORDER BY rank, t.updated_at DESC
+             LIMIT ?2",
+            rank = RANK_EXPR
+        ),
+        params![match_query, limit],
+    ).await?;
+
+    let mut summaries = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let tip_type_str: String = row.get(1)?;
+        let marked_title: String = row.get(8)?;
+        let marked_snippet: String = row.get(9)?;
+        let (title, title_highlights) = extract_highlights(&marked_title);
+        let (snippet, snippet_highlights) = extract_highlights(&marked_snippet);
+
+        summaries.push(TipSummary {
+            id: row.get(0)?,
+            title,
+            tip_type: tip_type_str,
+            language: row.get(2)?,
+            category_id: row.get(3)?,
+            created_at: row.get(4)?,
+            updated_at: row.get(5)?,
+            tags: Vec::new(), // 搜索时不加载标签，提高速度
+            is_encrypted: row.get(6)?,
+            content: Some(snippet.clone()),
+            pinned: row.get(10)?,
+            favorite: row.get(11)?,
+            archived: row.get(12)?,
+            search_match: Some(SearchMatch {
+                rank: row.get(7)?,
+                title_highlights,
+                snippet,
+                snippet_highlights,
+            }),
+        });
+    }
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_fts_match_query() {
+        assert_eq!(build_fts_match_query("rust async"), Some("\"rust\" \"async\"".to_string()));
+        assert_eq!(build_fts_match_query("say \"hi\"!"), Some("\"say\" \"\"\"hi\"\"!\"".to_string()));
+        assert_eq!(build_fts_match_query("数据库"), Some("\"数据库\"".to_string()));
+        // 过短的词交给 LIKE 处理
+        assert_eq!(build_fts_match_query("go lang"), None);
+        assert_eq!(build_fts_match_query("   "), None);
+    }
+
+    #[test]
+    fn test_extract_highlights() {
+        let (text, ranges) = extract_highlights("use \u{2}tokio\u{3} and \u{2}异步\u{3}");
+        assert_eq!(text, "use tokio and 异步");
+        assert_eq!(
+            ranges,
+            vec![
+                HighlightRange { start: 4, end: 9 },
+                HighlightRange { start: 14, end: 16 },
+            ]
+        );
+
+        let (text, ranges) = extract_highlights("no marks");
+        assert_eq!(text, "no marks");
+        assert!(ranges.is_empty());
+    }
+}
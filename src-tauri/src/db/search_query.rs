@@ -0,0 +1,736 @@
+use anyhow::Result;
+use chrono::{Duration, NaiveDate};
+use libsql::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::models::{Tip, TipType};
+use super::properties::{property_filter_sql, property_order_sql, PropertyFilter, PropertyFilterOp, PropertySort};
+use super::operations::{get_category_ids_recursive, list_categories, DbConnection};
+use crate::api::tips::TipSummary;
+
+/// 查询语法错误，位置为字符偏移（左闭右开），便于前端标注
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueryParseError {
+    pub message: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl std::fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (at {}..{})", self.message, self.start, self.end)
+    }
+}
+
+impl std::error::Error for QueryParseError {}
+
+/// 比较运算符（用于日期和属性过滤）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CmpOp {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// 日期字段
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateField {
+    Created,
+    Updated,
+}
+
+impl DateField {
+    fn column(&self) -> &'static str {
+        match self {
+            DateField::Created => "t.created_at",
+            DateField::Updated => "t.updated_at",
+        }
+    }
+}
+
+/// 查询表达式
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryExpr {
+    And(Vec<QueryExpr>),
+    Or(Vec<QueryExpr>),
+    Not(Box<QueryExpr>),
+    Text(String),
+    Tag(String),
+    Type(String),
+    Lang(String),
+    // 分类路径及其在查询中的位置（解析路径失败时用于报错）
+    In { path: String, start: usize, end: usize },
+    Date { field: DateField, op: CmpOp, date: NaiveDate },
+    // prop.<键>:值，值为 * 时只要求属性存在
+    Property(PropertyFilter),
+}
+
+impl QueryExpr {
+    /// 是否只包含普通关键词（可直接走全文检索并返回高亮）
+    pub fn is_plain_text(&self) -> bool {
+        match self {
+            QueryExpr::Text(_) => true,
+            QueryExpr::And(items) => items.iter().all(|e| matches!(e, QueryExpr::Text(_))),
+            _ => false,
+        }
+    }
+
+    /// 收集所有 in: 路径
+    fn collect_paths<'a>(&'a self, out: &mut Vec<&'a QueryExpr>) {
+        match self {
+            QueryExpr::And(items) | QueryExpr::Or(items) => {
+                items.iter().for_each(|e| e.collect_paths(out))
+            }
+            QueryExpr::Not(inner) => inner.collect_paths(out),
+            QueryExpr::In { .. } => out.push(self),
+            _ => {}
+        }
+    }
+}
+
+// ===============================================
+// 词法分析
+// ===============================================
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Term {
+        negated: bool,
+        field: Option<String>,
+        op: Option<CmpOp>,
+        value: String,
+        start: usize,
+        end: usize,
+    },
+    Or { start: usize, end: usize },
+}
+
+fn err(message: impl Into<String>, start: usize, end: usize) -> QueryParseError {
+    QueryParseError { message: message.into(), start, end }
+}
+
+/// 读取引号包裹的短语，pos 指向起始引号
+fn read_quoted(chars: &[char], pos: &mut usize) -> Result<String, QueryParseError> {
+    let open = *pos;
+    *pos += 1;
+    let mut value = String::new();
+    while *pos < chars.len() {
+        if chars[*pos] == '"' {
+            *pos += 1;
+            return Ok(value);
+        }
+        value.push(chars[*pos]);
+        *pos += 1;
+    }
+    Err(err("Unterminated quote", open, chars.len()))
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, QueryParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        if chars[pos].is_whitespace() {
+            pos += 1;
+            continue;
+        }
+
+        let start = pos;
+        let negated = chars[pos] == '-' && pos + 1 < chars.len() && !chars[pos + 1].is_whitespace();
+        if negated {
+            pos += 1;
+        }
+
+        // 引号短语
+        if chars[pos] == '"' {
+            let value = read_quoted(&chars, &mut pos)?;
+            tokens.push(Token::Term { negated, field: None, op: None, value, start, end: pos });
+            continue;
+        }
+
+        // 普通词或 field:value
+        let word_start = pos;
+        while pos < chars.len() && !chars[pos].is_whitespace() && chars[pos] != ':' && chars[pos] != '"' {
+            pos += 1;
+        }
+        let word: String = chars[word_start..pos].iter().collect();
+
+        if pos < chars.len() && chars[pos] == ':' && !word.is_empty() {
+            pos += 1;
+            let field = word.to_lowercase();
+
+            let op = if chars.get(pos) == Some(&'>') || chars.get(pos) == Some(&'<') {
+                let is_gt = chars[pos] == '>';
+                pos += 1;
+                let or_equal = chars.get(pos) == Some(&'=');
+                if or_equal {
+                    pos += 1;
+                }
+                Some(match (is_gt, or_equal) {
+                    (true, false) => CmpOp::Gt,
+                    (true, true) => CmpOp::Ge,
+                    (false, false) => CmpOp::Lt,
+                    (false, true) => CmpOp::Le,
+                })
+            } else if chars.get(pos) == Some(&'=') {
+                pos += 1;
+                Some(CmpOp::Eq)
+            } else {
+                None
+            };
+
+            let value = if chars.get(pos) == Some(&'"') {
+                read_quoted(&chars, &mut pos)?
+            } else {
+                let value_start = pos;
+                while pos < chars.len() && !chars[pos].is_whitespace() {
+                    pos += 1;
+                }
+                chars[value_start..pos].iter().collect::<String>()
+            };
+
+            if value.trim().is_empty() {
+                return Err(err(format!("Missing value for '{}:'", field), start, pos));
+            }
+            tokens.push(Token::Term { negated, field: Some(field), op, value, start, end: pos });
+            continue;
+        }
+
+        // 词中出现的引号或冒号按普通字符处理
+        while pos < chars.len() && !chars[pos].is_whitespace() {
+            pos += 1;
+        }
+        let value: String = chars[word_start..pos].iter().collect();
+
+        if value == "OR" && !negated {
+            tokens.push(Token::Or { start, end: pos });
+        } else {
+            tokens.push(Token::Term { negated, field: None, op: None, value, start, end: pos });
+        }
+    }
+
+    Ok(tokens)
+}
+
+// ===============================================
+// 语法分析
+// ===============================================
+
+fn parse_date(value: &str, start: usize, end: usize) -> Result<NaiveDate, QueryParseError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| err(format!("Invalid date '{}', expected YYYY-MM-DD", value), start, end))
+}
+
+fn term_to_expr(token: &Token) -> Result<QueryExpr, QueryParseError> {
+    let Token::Term { negated, field, op, value, start, end, .. } = token else {
+        unreachable!("OR tokens are handled by the parser");
+    };
+    let (start, end) = (*start, *end);
+
+    let expr = match field.as_deref() {
+        None => QueryExpr::Text(value.clone()),
+        Some(name) => {
+            let is_date_field = matches!(name, "created" | "updated");
+            if let Some(key) = name.strip_prefix("prop.").filter(|k| !k.is_empty()) {
+                let op = match op.unwrap_or(CmpOp::Eq) {
+                    CmpOp::Eq if value == "*" => PropertyFilterOp::Exists,
+                    CmpOp::Eq => PropertyFilterOp::Eq,
+                    CmpOp::Gt => PropertyFilterOp::Gt,
+                    CmpOp::Ge => PropertyFilterOp::Ge,
+                    CmpOp::Lt => PropertyFilterOp::Lt,
+                    CmpOp::Le => PropertyFilterOp::Le,
+                };
+                let expr = QueryExpr::Property(PropertyFilter {
+                    key: key.to_string(),
+                    op,
+                    value: Some(value.clone()).filter(|_| op != PropertyFilterOp::Exists),
+                });
+                return Ok(if *negated { QueryExpr::Not(Box::new(expr)) } else { expr });
+            }
+            if op.is_some() && !is_date_field {
+                return Err(err(format!("Operator '{}:' does not support comparisons", name), start, end));
+            }
+            match name {
+                "tag" => QueryExpr::Tag(value.clone()),
+                "type" => {
+                    let tip_type = TipType::try_from(value.to_lowercase())
+                        .map_err(|_| err(format!("Unknown tip type '{}'", value), start, end))?;
+                    QueryExpr::Type(tip_type.into())
+                }
+                "lang" | "language" => QueryExpr::Lang(value.clone()),
+                "in" => QueryExpr::In {
+                    path: value.trim_matches('/').to_string(),
+                    start,
+                    end,
+                },
+                "created" | "updated" => QueryExpr::Date {
+                    field: if name == "created" { DateField::Created } else { DateField::Updated },
+                    op: op.unwrap_or(CmpOp::Eq),
+                    date: parse_date(value, start, end)?,
+                },
+                "before" => QueryExpr::Date {
+                    field: DateField::Created,
+                    op: CmpOp::Lt,
+                    date: parse_date(value, start, end)?,
+                },
+                "after" => QueryExpr::Date {
+                    field: DateField::Created,
+                    op: CmpOp::Gt,
+                    date: parse_date(value, start, end)?,
+                },
+                other => return Err(err(format!("Unknown operator '{}:'", other), start, end)),
+            }
+        }
+    };
+
+    Ok(if *negated { QueryExpr::Not(Box::new(expr)) } else { expr })
+}
+
+/// 解析搜索查询
+///
+/// 词与词之间为 AND，`OR` 优先级高于隐式 AND：`a b OR c` 等价于 `a AND (b OR c)`。
+pub fn parse_search_query(input: &str) -> Result<QueryExpr, QueryParseError> {
+    let tokens = tokenize(input)?;
+    let mut groups: Vec<Vec<QueryExpr>> = Vec::new();
+    let mut pending_or: Option<(usize, usize)> = None;
+
+    for token in &tokens {
+        match token {
+            Token::Or { start, end } => {
+                if groups.is_empty() || pending_or.is_some() {
+                    return Err(err("OR must appear between two terms", *start, *end));
+                }
+                pending_or = Some((*start, *end));
+            }
+            Token::Term { .. } => {
+                let expr = term_to_expr(token)?;
+                match (pending_or.take(), groups.last_mut()) {
+                    (Some(_), Some(group)) => group.push(expr),
+                    _ => groups.push(vec![expr]),
+                }
+            }
+        }
+    }
+
+    if let Some((start, end)) = pending_or {
+        return Err(err("OR must appear between two terms", start, end));
+    }
+    if groups.is_empty() {
+        return Err(err("Empty query", 0, input.chars().count()));
+    }
+
+    let mut items: Vec<QueryExpr> = groups
+        .into_iter()
+        .map(|mut group| if group.len() == 1 { group.remove(0) } else { QueryExpr::Or(group) })
+        .collect();
+
+    Ok(if items.len() == 1 { items.remove(0) } else { QueryExpr::And(items) })
+}
+
+// ===============================================
+// SQL 编译
+// ===============================================
+
+/// 编译后的查询条件
+#[derive(Debug, Clone)]
+pub struct CompiledQuery {
+    pub where_sql: String,
+    pub params: Vec<Value>,
+}
+
+fn day_start_millis(date: NaiveDate) -> i64 {
+    date.and_hms_opt(0, 0, 0)
+        .map(|dt| dt.and_utc().timestamp_millis())
+        .unwrap_or(0)
+}
+
+const MIN_FTS_CHARS: usize = 3;
+
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+fn compile_expr(
+    expr: &QueryExpr,
+    categories: &HashMap<String, Vec<String>>,
+    params: &mut Vec<Value>,
+) -> String {
+    match expr {
+        QueryExpr::And(items) => items
+            .iter()
+            .map(|e| compile_expr(e, categories, params))
+            .collect::<Vec<_>>()
+            .join(" AND "),
+        QueryExpr::Or(items) => format!(
+            "({})",
+            items
+                .iter()
+                .map(|e| compile_expr(e, categories, params))
+                .collect::<Vec<_>>()
+                .join(" OR ")
+        ),
+        // COALESCE 避免 NULL 列（如 language）在取反时被整体排除
+        QueryExpr::Not(inner) => format!("NOT COALESCE(({}), 0)", compile_expr(inner, categories, params)),
+        // trigram 分词要求至少3个字符，更短的词回退到 LIKE
+        QueryExpr::Text(text) if text.chars().count() >= MIN_FTS_CHARS => {
+            params.push(Value::Text(format!("\"{}\"", text.replace('"', "\"\""))));
+            "t.id IN (SELECT tip_id FROM tips_fts WHERE tips_fts MATCH ?)".to_string()
+        }
+        QueryExpr::Text(text) => {
+            let pattern = format!("%{}%", escape_like(text));
+            params.push(Value::Text(pattern.clone()));
+            params.push(Value::Text(pattern));
+            "(t.title LIKE ? ESCAPE '\\' OR t.content LIKE ? ESCAPE '\\')".to_string()
+        }
+        QueryExpr::Tag(name) => {
+            params.push(Value::Text(name.clone()));
+            "EXISTS (SELECT 1 FROM tip_tags tt JOIN tags tg ON tg.id = tt.tag_id WHERE tt.tip_id = t.id AND tg.name = ? COLLATE NOCASE)".to_string()
+        }
+        QueryExpr::Type(tip_type) => {
+            params.push(Value::Text(tip_type.clone()));
+            "t.tip_type = ?".to_string()
+        }
+        QueryExpr::Lang(lang) => {
+            params.push(Value::Text(lang.clone()));
+            "t.language = ? COLLATE NOCASE".to_string()
+        }
+        QueryExpr::In { path, .. } => {
+            let ids = categories.get(path).cloned().unwrap_or_default();
+            if ids.is_empty() {
+                return "0".to_string();
+            }
+            let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
+            params.extend(ids.into_iter().map(Value::Text));
+            format!("t.category_id IN ({})", placeholders)
+        }
+        QueryExpr::Date { field, op, date } => {
+            let column = field.column();
+            let day_start = day_start_millis(*date);
+            let next_day = day_start_millis(*date + Duration::days(1));
+            match op {
+                CmpOp::Eq => {
+                    params.push(Value::Integer(day_start));
+                    params.push(Value::Integer(next_day));
+                    format!("({c} >= ? AND {c} < ?)", c = column)
+                }
+                CmpOp::Gt => {
+                    params.push(Value::Integer(next_day));
+                    format!("{} >= ?", column)
+                }
+                CmpOp::Ge => {
+                    params.push(Value::Integer(day_start));
+                    format!("{} >= ?", column)
+                }
+                CmpOp::Lt => {
+                    params.push(Value::Integer(day_start));
+                    format!("{} < ?", column)
+                }
+                CmpOp::Le => {
+                    params.push(Value::Integer(next_day));
+                    format!("{} < ?", column)
+                }
+            }
+        }
+        QueryExpr::Property(filter) => {
+            let mut bind = |value: Value| {
+                params.push(value);
+                "?".to_string()
+            };
+            // 解析时已保证比较运算带有值
+            property_filter_sql(filter, "t.id", &mut bind).unwrap_or_else(|_| "0".to_string())
+        }
+    }
+}
+
+/// 将表达式编译为参数化 WHERE 子句（日期按 UTC 自然日计算）
+///
+/// `categories` 为 in: 路径到递归分类ID列表的映射。
+pub fn compile_search_query(expr: &QueryExpr, categories: &HashMap<String, Vec<String>>) -> CompiledQuery {
+    let mut params = Vec::new();
+    let where_sql = compile_expr(expr, categories, &mut params);
+    CompiledQuery { where_sql, params }
+}
+
+/// 按 "笔记本/子笔记本" 路径查找分类ID（名称不区分大小写）
+pub async fn resolve_category_path(conn: &DbConnection, path: &str) -> Result<Option<String>> {
+    let categories = list_categories(conn).await?;
+    let mut parent: Option<String> = None;
+
+    for segment in path.split('/').map(str::trim).filter(|s| !s.is_empty()) {
+        let found = categories.iter().find(|c| {
+            c.parent_id == parent && c.name.to_lowercase() == segment.to_lowercase()
+        });
+        match found {
+            Some(category) => parent = Some(category.id.clone()),
+            None => return Ok(None),
+        }
+    }
+
+    Ok(parent)
+}
+
+/// 解析 in: 路径为递归分类ID列表，路径不存在时返回带位置的错误
+async fn resolve_query_categories(
+    conn: &DbConnection,
+    expr: &QueryExpr,
+) -> Result<std::result::Result<HashMap<String, Vec<String>>, QueryParseError>> {
+    let mut paths = Vec::new();
+    expr.collect_paths(&mut paths);
+
+    let mut resolved = HashMap::new();
+    for node in paths {
+        if let QueryExpr::In { path, start, end } = node {
+            if resolved.contains_key(path) {
+                continue;
+            }
+            match resolve_category_path(conn, path).await? {
+                Some(category_id) => {
+                    let ids = get_category_ids_recursive(conn, &category_id).await?;
+                    resolved.insert(path.clone(), ids);
+                }
+                None => {
+                    return Ok(Err(err(format!("Notebook '{}' not found", path), *start, *end)));
+                }
+            }
+        }
+    }
+
+    Ok(Ok(resolved))
+}
+
+/// 编译结构化查询；外层错误为数据库错误，内层错误为查询语法/路径错误
+pub async fn prepare_search_query(
+    conn: &DbConnection,
+    query: &str,
+) -> Result<std::result::Result<CompiledQuery, QueryParseError>> {
+    let expr = match parse_search_query(query) {
+        Ok(expr) => expr,
+        Err(e) => return Ok(Err(e)),
+    };
+    let categories = match resolve_query_categories(conn, &expr).await? {
+        Ok(categories) => categories,
+        Err(e) => return Ok(Err(e)),
+    };
+    Ok(Ok(compile_search_query(&expr, &categories)))
+}
+
+/// 查询包含运算符时编译为结构化查询；纯关键词或语法错误时返回 None，由调用方按普通文本搜索
+pub async fn compile_if_structured(conn: &DbConnection, query: &str) -> Result<Option<CompiledQuery>> {
+    match parse_search_query(query) {
+        Ok(expr) if !expr.is_plain_text() => {
+            Ok(prepare_search_query(conn, query).await?.ok())
+        }
+        _ => Ok(None),
+    }
+}
+
+/// 执行结构化查询，返回完整笔记
+pub async fn search_tips_structured(conn: &DbConnection, compiled: &CompiledQuery, limit: i32, offset: i32) -> Result<Vec<Tip>> {
+    let sql = format!(
+        "SELECT t.id, t.title, t.content, t.tip_type, t.language, t.category_id, t.created_at, t.updated_at,
+                t.version, t.last_synced_at, t.sync_hash, t.is_encrypted, t.encryption_key_id, t.encrypted_content, t.pinned, t.favorite, t.archived, t.sort_key
+         FROM tips t
+         WHERE t.deleted_at IS NULL AND ({})
+         ORDER BY t.updated_at DESC
+         LIMIT ? OFFSET ?",
+        compiled.where_sql
+    );
+    let mut params = compiled.params.clone();
+    params.push(Value::Integer(limit as i64));
+    params.push(Value::Integer(offset as i64));
+
+    let mut rows = conn.query(&sql, libsql::params_from_iter(params)).await?;
+    let mut tips = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let tip_type_str: String = row.get(3)?;
+        tips.push(Tip {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            content: row.get(2)?,
+            tip_type: tip_type_str.try_into()?,
+            language: row.get(4)?,
+            category_id: row.get(5)?,
+            created_at: row.get(6)?,
+            updated_at: row.get(7)?,
+            version: row.get(8)?,
+            last_synced_at: row.get(9)?,
+            sync_hash: row.get(10)?,
+            is_encrypted: row.get(11)?,
+            encryption_key_id: row.get(12)?,
+            encrypted_content: row.get(13)?,
+            pinned: row.get(14)?,
+            favorite: row.get(15)?,
+            archived: row.get(16)?,
+            sort_key: row.get(17)?,
+        });
+    }
+    Ok(tips)
+}
+
+/// 执行结构化查询，返回笔记摘要
+///
+/// 指定 `sort_property` 时按该属性排序，其余按更新时间排序。
+pub async fn search_tips_summary_structured(
+    conn: &DbConnection,
+    compiled: &CompiledQuery,
+    sort_property: Option<&PropertySort>,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<TipSummary>> {
+    let mut params = compiled.params.clone();
+    let order = match sort_property {
+        Some(sort) => {
+            let mut bind = |value: Value| {
+                params.push(value);
+                "?".to_string()
+            };
+            format!("{}, t.updated_at DESC", property_order_sql(sort, "t.id", &mut bind))
+        }
+        None => "t.updated_at DESC".to_string(),
+    };
+    let sql = format!(
+        "SELECT t.id, t.title, t.tip_type, t.language, t.category_id, t.created_at, t.updated_at, t.is_encrypted,
+                substr(t.content, 1, 200) as content_preview,
+                t.pinned, t.favorite, t.archived
+         FROM tips t
+         WHERE t.deleted_at IS NULL AND ({})
+         ORDER BY {}
+         LIMIT ? OFFSET ?",
+        compiled.where_sql,
+        order
+    );
+    params.push(Value::Integer(limit as i64));
+    params.push(Value::Integer(offset as i64));
+
+    let mut rows = conn.query(&sql, libsql::params_from_iter(params)).await?;
+    let mut summaries = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let tip_type_str: String = row.get(2)?;
+        summaries.push(TipSummary {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            tip_type: tip_type_str,
+            language: row.get(3)?,
+            category_id: row.get(4)?,
+            created_at: row.get(5)?,
+            updated_at: row.get(6)?,
+            tags: Vec::new(),
+            is_encrypted: row.get(7)?,
+            content: Some(row.get(8)?),
+            pinned: row.get(9)?,
+            favorite: row.get(10)?,
+            archived: row.get(11)?,
+            search_match: None,
+        });
+    }
+    Ok(summaries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plain_terms() {
+        let expr = parse_search_query("rust async").unwrap();
+        assert_eq!(expr, QueryExpr::And(vec![
+            QueryExpr::Text("rust".into()),
+            QueryExpr::Text("async".into()),
+        ]));
+        assert!(expr.is_plain_text());
+    }
+
+    #[test]
+    fn test_parse_operators() {
+        let expr = parse_search_query(r#"tag:rust type:code -lang:python in:"Work/Project A" "exact phrase""#).unwrap();
+        assert_eq!(expr, QueryExpr::And(vec![
+            QueryExpr::Tag("rust".into()),
+            QueryExpr::Type("code".into()),
+            QueryExpr::Not(Box::new(QueryExpr::Lang("python".into()))),
+            QueryExpr::In { path: "Work/Project A".into(), start: 32, end: 51 },
+            QueryExpr::Text("exact phrase".into()),
+        ]));
+        assert!(!expr.is_plain_text());
+    }
+
+    #[test]
+    fn test_parse_or_and_dates() {
+        let expr = parse_search_query("created:>2025-01-01 tag:a OR tag:b").unwrap();
+        assert_eq!(expr, QueryExpr::And(vec![
+            QueryExpr::Date {
+                field: DateField::Created,
+                op: CmpOp::Gt,
+                date: NaiveDate::from_ymd_opt(2025, 1, 1).unwrap(),
+            },
+            QueryExpr::Or(vec![QueryExpr::Tag("a".into()), QueryExpr::Tag("b".into())]),
+        ]));
+    }
+
+    #[test]
+    fn test_parse_errors_have_positions() {
+        assert_eq!(
+            parse_search_query("foo \"bar").unwrap_err(),
+            QueryParseError { message: "Unterminated quote".into(), start: 4, end: 8 }
+        );
+        let e = parse_search_query("foo OR").unwrap_err();
+        assert_eq!((e.start, e.end), (4, 6));
+        let e = parse_search_query("x created:2025-13-01").unwrap_err();
+        assert_eq!((e.start, e.end), (2, 20));
+        let e = parse_search_query("color:red").unwrap_err();
+        assert_eq!(e.message, "Unknown operator 'color:'");
+        assert!(parse_search_query("type:video").is_err());
+        assert!(parse_search_query("tag:>x").is_err());
+        assert!(parse_search_query("tag:").is_err());
+    }
+
+    #[test]
+    fn test_parse_property_filters() {
+        let expr = parse_search_query("prop.due:<2025-01-01 -prop.status:done prop.owner:*").unwrap();
+        let QueryExpr::And(items) = &expr else { panic!("expected AND") };
+        assert_eq!(
+            items[0],
+            QueryExpr::Property(PropertyFilter { key: "due".into(), op: PropertyFilterOp::Lt, value: Some("2025-01-01".into()) })
+        );
+        assert!(matches!(&items[1], QueryExpr::Not(inner) if matches!(**inner, QueryExpr::Property(_))));
+        assert_eq!(
+            items[2],
+            QueryExpr::Property(PropertyFilter { key: "owner".into(), op: PropertyFilterOp::Exists, value: None })
+        );
+
+        let compiled = compile_search_query(&expr, &HashMap::new());
+        assert_eq!(compiled.params.len(), 5);
+        assert!(compiled.where_sql.contains("tp.value_number < ?"));
+    }
+
+    #[test]
+    fn test_compile_query() {
+        let expr = parse_search_query("tag:rust -go created:<=2025-01-01 in:Work").unwrap();
+        let mut categories = HashMap::new();
+        categories.insert("Work".to_string(), vec!["c1".to_string(), "c2".to_string()]);
+        let compiled = compile_search_query(&expr, &categories);
+
+        assert_eq!(
+            compiled.where_sql,
+            "EXISTS (SELECT 1 FROM tip_tags tt JOIN tags tg ON tg.id = tt.tag_id WHERE tt.tip_id = t.id AND tg.name = ? COLLATE NOCASE) \
+             AND NOT COALESCE(((t.title LIKE ? ESCAPE '\\' OR t.content LIKE ? ESCAPE '\\')), 0) \
+             AND t.created_at < ? \
+             AND t.category_id IN (?, ?)"
+        );
+        assert_eq!(compiled.params.len(), 6);
+        assert_eq!(compiled.params[1], Value::Text("%go%".into()));
+        // <= 某天 表示早于次日零点
+        assert_eq!(compiled.params[3], Value::Integer(1_735_776_000_000));
+    }
+
+    #[test]
+    fn test_compile_text_uses_fts_for_long_terms() {
+        let expr = parse_search_query("\"tokio runtime\"").unwrap();
+        let compiled = compile_search_query(&expr, &HashMap::new());
+        assert_eq!(compiled.where_sql, "t.id IN (SELECT tip_id FROM tips_fts WHERE tips_fts MATCH ?)");
+        assert_eq!(compiled.params, vec![Value::Text("\"tokio runtime\"".into())]);
+    }
+}
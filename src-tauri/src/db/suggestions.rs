@@ -0,0 +1,82 @@
+use anyhow::Result;
+use libsql::params;
+use std::collections::HashMap;
+
+use super::operations::DbConnection;
+
+// 每条笔记参与统计的最大字符数
+const CORPUS_CONTENT_CHARS: i64 = 2000;
+
+/// 标签及打了该标签的笔记文本（标题 + 正文开头）
+pub async fn load_tag_corpus(conn: &DbConnection) -> Result<HashMap<String, String>> {
+    let mut rows = conn
+        .query(
+            "SELECT tg.name, t.title, substr(t.content, 1, ?1)
+             FROM tags tg
+             LEFT JOIN tip_tags tt ON tt.tag_id = tg.id
+             LEFT JOIN tips t ON t.id = tt.tip_id AND t.deleted_at IS NULL AND COALESCE(t.is_encrypted, 0) = 0",
+            params![CORPUS_CONTENT_CHARS],
+        )
+        .await?;
+
+    let mut corpus: HashMap<String, String> = HashMap::new();
+    while let Some(row) = rows.next().await? {
+        let name: String = row.get(0)?;
+        let entry = corpus.entry(name).or_default();
+        if let (Some(title), Some(content)) = (row.get::<Option<String>>(1)?, row.get::<Option<String>>(2)?) {
+            entry.push_str(&title);
+            entry.push('\n');
+            entry.push_str(&content);
+            entry.push('\n');
+        }
+    }
+    Ok(corpus)
+}
+
+/// 笔记本 ID -> (名称, 笔记本内笔记的文本)
+pub async fn load_category_corpus(conn: &DbConnection) -> Result<HashMap<String, (String, String)>> {
+    let mut rows = conn
+        .query(
+            "SELECT c.id, c.name, t.title, substr(t.content, 1, ?1)
+             FROM categories c
+             LEFT JOIN tips t ON t.category_id = c.id AND t.deleted_at IS NULL AND COALESCE(t.is_encrypted, 0) = 0
+             WHERE c.deleted_at IS NULL",
+            params![CORPUS_CONTENT_CHARS],
+        )
+        .await?;
+
+    let mut corpus: HashMap<String, (String, String)> = HashMap::new();
+    while let Some(row) = rows.next().await? {
+        let id: String = row.get(0)?;
+        let name: String = row.get(1)?;
+        let entry = corpus.entry(id).or_insert_with(|| (name, String::new()));
+        if let (Some(title), Some(content)) = (row.get::<Option<String>>(2)?, row.get::<Option<String>>(3)?) {
+            entry.1.push_str(&title);
+            entry.1.push('\n');
+            entry.1.push_str(&content);
+            entry.1.push('\n');
+        }
+    }
+    Ok(corpus)
+}
+
+/// 没有任何标签的笔记（不含回收站和加密笔记），最近更新的在前；`since` 限定创建时间
+pub async fn list_untagged_tip_ids(conn: &DbConnection, since: Option<i64>, limit: i64) -> Result<Vec<String>> {
+    let mut rows = conn
+        .query(
+            "SELECT t.id FROM tips t
+             WHERE t.deleted_at IS NULL AND COALESCE(t.is_encrypted, 0) = 0
+               AND t.created_at >= ?1
+               AND NOT EXISTS (SELECT 1 FROM tip_tags tt WHERE tt.tip_id = t.id)
+             ORDER BY t.updated_at DESC
+             LIMIT ?2",
+            params![since.unwrap_or(0), limit],
+        )
+        .await?;
+
+    let mut ids = Vec::new();
+    while let Some(row) = rows.next().await? {
+        ids.push(row.get(0)?);
+    }
+    Ok(ids)
+}
@@ -0,0 +1,460 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use libsql::params;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::models::{SyncOperation, Tag, Tip, TipSortOrder};
+use super::operations::{query_tip_summaries, DbConnection};
+use crate::api::tips::TipSummary;
+use crate::sync::mark_for_sync;
+
+// 标签层级分隔符
+pub const TAG_PATH_SEPARATOR: char = '/';
+
+// 匹配标签自身及其所有下级标签，`param` 为标签完整路径
+fn subtree_condition(column: &str, param: &str) -> String {
+    format!(
+        "({col} = {p} OR substr({col}, 1, length({p}) + 1) = {p} || '/')",
+        col = column,
+        p = param
+    )
+}
+
+/// 带笔记数量的标签
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagWithCount {
+    #[serde(flatten)]
+    pub tag: Tag,
+    pub parent_name: Option<String>, // 上级标签完整路径
+    pub label: String,               // 最后一级名称
+    pub depth: usize,                // 顶级标签为 0
+    pub tip_count: i64,              // 直接打了该标签的笔记数
+    pub total_tip_count: i64,        // 含下级标签的笔记数（去重）
+}
+
+/// 规范化标签路径：去掉每级首尾空白和空层级，全部为空时返回 None
+pub fn normalize_tag_name(name: &str) -> Option<String> {
+    let segments: Vec<&str> = name
+        .split(TAG_PATH_SEPARATOR)
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    if segments.is_empty() {
+        None
+    } else {
+        Some(segments.join("/"))
+    }
+}
+
+/// 上级标签路径
+pub fn parent_tag_name(name: &str) -> Option<&str> {
+    name.rfind(TAG_PATH_SEPARATOR).map(|i| &name[..i])
+}
+
+/// 所有上级标签路径，由近到远
+pub fn ancestor_tag_names(name: &str) -> Vec<&str> {
+    let mut ancestors = Vec::new();
+    let mut current = name;
+    while let Some(parent) = parent_tag_name(current) {
+        ancestors.push(parent);
+        current = parent;
+    }
+    ancestors
+}
+
+/// 将子树中的标签路径从 `old` 前缀替换为 `new` 前缀
+fn replace_tag_prefix(name: &str, old: &str, new: &str) -> String {
+    format!("{}{}", new, &name[old.len()..])
+}
+
+fn tag_depth(name: &str) -> usize {
+    name.matches(TAG_PATH_SEPARATOR).count()
+}
+
+fn tag_from_row(row: &libsql::Row) -> Result<Tag> {
+    Ok(Tag {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+        version: row.get(4)?,
+        last_synced_at: row.get(5)?,
+        sync_hash: row.get(6)?,
+        color: row.get(7)?,
+        icon: row.get(8)?,
+    })
+}
+
+/// 根据ID获取标签
+pub async fn get_tag_by_id(conn: &DbConnection, tag_id: &str) -> Result<Option<Tag>> {
+    let mut rows = conn.query(
+        "SELECT id, name, created_at, updated_at, version, last_synced_at, sync_hash, color, icon
+         FROM tags WHERE id = ?1",
+        params![tag_id],
+    ).await?;
+    match rows.next().await? {
+        Some(row) => Ok(Some(tag_from_row(&row)?)),
+        None => Ok(None),
+    }
+}
+
+/// 确保标签及其所有上级标签存在，返回该标签ID
+pub async fn ensure_tag_path(conn: &DbConnection, name: &str, now: i64) -> Result<String> {
+    let mut names = ancestor_tag_names(name);
+    names.reverse();
+    names.push(name);
+
+    for path in names {
+        conn.execute(
+            "INSERT OR IGNORE INTO tags (id, name, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+            params![Uuid::new_v4().to_string(), path, now],
+        ).await?;
+    }
+
+    let mut rows = conn.query("SELECT id FROM tags WHERE name = ?1", params![name]).await?;
+    match rows.next().await? {
+        Some(row) => Ok(row.get(0)?),
+        None => Err(anyhow!("Failed to create tag: {}", name)),
+    }
+}
+
+/// 解析标签引用：优先匹配已有标签 ID，否则按标签路径查找或创建
+pub async fn resolve_tag_ref(conn: &DbConnection, value: &str, now: i64) -> Result<Option<String>> {
+    let mut rows = conn.query("SELECT id FROM tags WHERE id = ?1", params![value]).await?;
+    if let Some(row) = rows.next().await? {
+        return Ok(Some(row.get(0)?));
+    }
+    match normalize_tag_name(value) {
+        Some(name) => Ok(Some(ensure_tag_path(conn, &name, now).await?)),
+        None => Ok(None),
+    }
+}
+
+/// 创建标签（自动补齐上级标签），已存在时返回已有标签
+pub async fn create_tag_path(conn: &DbConnection, name: &str) -> Result<Tag> {
+    let name = normalize_tag_name(name).ok_or_else(|| anyhow!("Tag name must not be empty"))?;
+    let tag_id = ensure_tag_path(conn, &name, Utc::now().timestamp_millis()).await?;
+    get_tag_by_id(conn, &tag_id).await?.ok_or_else(|| anyhow!("Tag not found: {}", tag_id))
+}
+
+/// 获取所有标签及笔记数量（单次查询，不统计回收站中的笔记）
+pub async fn list_tags_with_counts(conn: &DbConnection) -> Result<Vec<TagWithCount>> {
+    let sql = format!(
+        "SELECT t.id, t.name, t.created_at, t.updated_at, t.version, t.last_synced_at, t.sync_hash, t.color, t.icon,
+                COUNT(DISTINCT CASE WHEN d.id = t.id THEN p.id END) AS tip_count,
+                COUNT(DISTINCT p.id) AS total_tip_count
+         FROM tags t
+         JOIN tags d ON {}
+         LEFT JOIN tip_tags tt ON tt.tag_id = d.id
+         LEFT JOIN tips p ON p.id = tt.tip_id AND p.deleted_at IS NULL
+         GROUP BY t.id
+         ORDER BY t.name",
+        subtree_condition("d.name", "t.name")
+    );
+    let mut rows = conn.query(&sql, ()).await?;
+
+    let mut tags = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let tag = tag_from_row(&row)?;
+        let parent_name = parent_tag_name(&tag.name).map(str::to_string);
+        let label = tag.name.rsplit(TAG_PATH_SEPARATOR).next().unwrap_or_default().to_string();
+        tags.push(TagWithCount {
+            depth: tag_depth(&tag.name),
+            parent_name,
+            label,
+            tip_count: row.get(9)?,
+            total_tip_count: row.get(10)?,
+            tag,
+        });
+    }
+    Ok(tags)
+}
+
+/// 将标签子树整体改到新路径下，目标路径已存在的标签会被合并，返回原标签最终对应的标签ID；需在事务中调用
+async fn relocate_tag_subtree(conn: &DbConnection, tag_id: &str, new_name: &str) -> Result<String> {
+    let tag = get_tag_by_id(conn, tag_id).await?.ok_or_else(|| anyhow!("Tag not found: {}", tag_id))?;
+    let old_name = tag.name;
+    if old_name == new_name {
+        return Ok(tag.id);
+    }
+    if new_name.starts_with(&format!("{}/", old_name)) {
+        return Err(anyhow!("Cannot move tag '{}' under itself", old_name));
+    }
+
+    let now = Utc::now().timestamp_millis();
+    for ancestor in ancestor_tag_names(new_name) {
+        ensure_tag_path(conn, ancestor, now).await?;
+    }
+
+    let mut rows = conn.query(
+        &format!("SELECT id, name FROM tags WHERE {}", subtree_condition("name", "?1")),
+        params![old_name.as_str()],
+    ).await?;
+    let mut members: Vec<(String, String)> = Vec::new();
+    while let Some(row) = rows.next().await? {
+        members.push((row.get(0)?, row.get(1)?));
+    }
+    // 先处理最深的标签，合并进来的关联会随上级一起移动
+    members.sort_by_key(|(_, name)| std::cmp::Reverse(tag_depth(name)));
+
+    let mut result_id = tag.id.clone();
+    for (id, name) in &members {
+        let target_name = replace_tag_prefix(name, &old_name, new_name);
+        let mut rows = conn.query(
+            "SELECT id FROM tags WHERE name = ?1 AND id != ?2",
+            params![target_name.as_str(), id.as_str()],
+        ).await?;
+        let existing: Option<String> = match rows.next().await? {
+            Some(row) => Some(row.get(0)?),
+            None => None,
+        };
+
+        match existing {
+            Some(target_id) => {
+                conn.execute(
+                    "INSERT OR IGNORE INTO tip_tags (tip_id, tag_id) SELECT tip_id, ?1 FROM tip_tags WHERE tag_id = ?2",
+                    params![target_id.as_str(), id.as_str()],
+                ).await?;
+                conn.execute("DELETE FROM tip_tags WHERE tag_id = ?1", params![id.as_str()]).await?;
+                conn.execute("DELETE FROM tags WHERE id = ?1", params![id.as_str()]).await?;
+                conn.execute(
+                    "UPDATE tags SET updated_at = ?1 WHERE id = ?2",
+                    params![now, target_id.as_str()],
+                ).await?;
+                mark_for_sync(conn, "tags", id, SyncOperation::Delete).await?;
+                mark_for_sync(conn, "tags", &target_id, SyncOperation::Update).await?;
+                if *id == tag.id {
+                    result_id = target_id;
+                }
+            }
+            None => {
+                conn.execute(
+                    "UPDATE tags SET name = ?1, updated_at = ?2 WHERE id = ?3",
+                    params![target_name.as_str(), now, id.as_str()],
+                ).await?;
+                mark_for_sync(conn, "tags", id, SyncOperation::Update).await?;
+            }
+        }
+    }
+
+    Ok(result_id)
+}
+
+async fn relocate_in_transaction(conn: &DbConnection, tag_id: &str, new_name: &str) -> Result<Tag> {
+    conn.execute("BEGIN TRANSACTION", ()).await?;
+    let result_id = match relocate_tag_subtree(conn, tag_id, new_name).await {
+        Ok(id) => {
+            conn.execute("COMMIT", ()).await?;
+            id
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", ()).await?;
+            return Err(e);
+        }
+    };
+    get_tag_by_id(conn, &result_id).await?.ok_or_else(|| anyhow!("Tag not found: {}", result_id))
+}
+
+/// 重命名标签，下级标签随之改名；新名称已存在时合并到已有标签
+pub async fn rename_tag(conn: &DbConnection, tag_id: &str, new_name: &str) -> Result<Tag> {
+    let new_name = normalize_tag_name(new_name).ok_or_else(|| anyhow!("Tag name must not be empty"))?;
+    let tag = relocate_in_transaction(conn, tag_id, &new_name).await?;
+    tracing::info!("Renamed tag {} to '{}'", tag_id, tag.name);
+    Ok(tag)
+}
+
+/// 将源标签合并到目标标签：笔记关联转移到目标，下级标签移到目标下，然后删除源标签
+pub async fn merge_tags(conn: &DbConnection, source_id: &str, target_id: &str) -> Result<Tag> {
+    if source_id == target_id {
+        return Err(anyhow!("Cannot merge a tag into itself"));
+    }
+    let target = get_tag_by_id(conn, target_id).await?.ok_or_else(|| anyhow!("Tag not found: {}", target_id))?;
+    let tag = relocate_in_transaction(conn, source_id, &target.name).await?;
+    tracing::info!("Merged tag {} into {}", source_id, target_id);
+    Ok(tag)
+}
+
+/// 设置标签颜色和图标
+pub async fn set_tag_appearance(
+    conn: &DbConnection,
+    tag_id: &str,
+    color: Option<&str>,
+    icon: Option<&str>,
+) -> Result<Tag> {
+    let rows_affected = conn.execute(
+        "UPDATE tags SET color = ?1, icon = ?2, updated_at = ?3 WHERE id = ?4",
+        params![color, icon, Utc::now().timestamp_millis(), tag_id],
+    ).await?;
+    if rows_affected == 0 {
+        return Err(anyhow!("Tag not found: {}", tag_id));
+    }
+    mark_for_sync(conn, "tags", tag_id, SyncOperation::Update).await?;
+    get_tag_by_id(conn, tag_id).await?.ok_or_else(|| anyhow!("Tag not found: {}", tag_id))
+}
+
+/// 删除标签及其所有下级标签
+pub async fn delete_tag_subtree(conn: &DbConnection, tag_id: &str) -> Result<usize> {
+    let tag = get_tag_by_id(conn, tag_id).await?.ok_or_else(|| anyhow!("Tag not found: {}", tag_id))?;
+
+    let mut rows = conn.query(
+        &format!("SELECT id FROM tags WHERE {}", subtree_condition("name", "?1")),
+        params![tag.name.as_str()],
+    ).await?;
+    let mut ids: Vec<String> = Vec::new();
+    while let Some(row) = rows.next().await? {
+        ids.push(row.get(0)?);
+    }
+
+    conn.execute("BEGIN TRANSACTION", ()).await?;
+    for id in &ids {
+        let result = async {
+            conn.execute("DELETE FROM tip_tags WHERE tag_id = ?1", params![id.as_str()]).await?;
+            conn.execute("DELETE FROM tags WHERE id = ?1", params![id.as_str()]).await?;
+            mark_for_sync(conn, "tags", id, SyncOperation::Delete).await
+        }.await;
+        if let Err(e) = result {
+            conn.execute("ROLLBACK", ()).await?;
+            return Err(e);
+        }
+    }
+    conn.execute("COMMIT", ()).await?;
+    Ok(ids.len())
+}
+
+// 按标签筛选笔记的条件，`param` 为标签路径参数
+fn tag_filter_sql(param: &str, include_descendants: bool) -> String {
+    let condition = if include_descendants {
+        subtree_condition("tg.name", param)
+    } else {
+        format!("tg.name = {}", param)
+    };
+    format!(
+        "id IN (SELECT tt.tip_id FROM tip_tags tt JOIN tags tg ON tg.id = tt.tag_id WHERE {})",
+        condition
+    )
+}
+
+/// 分页获取标签（默认包含下级标签）下的笔记摘要，不含已归档笔记
+pub async fn list_tip_summaries_by_tag(
+    conn: &DbConnection,
+    tag_id: &str,
+    include_descendants: bool,
+    limit: i32,
+    offset: i32,
+    sort: TipSortOrder,
+) -> Result<Vec<TipSummary>> {
+    let tag = get_tag_by_id(conn, tag_id).await?.ok_or_else(|| anyhow!("Tag not found: {}", tag_id))?;
+    query_tip_summaries(
+        conn,
+        &format!(
+            "deleted_at IS NULL AND COALESCE(archived, 0) = 0 AND {}",
+            tag_filter_sql("?3", include_descendants)
+        ),
+        vec![libsql::Value::Text(tag.name)],
+        sort,
+        limit,
+        offset,
+    ).await
+}
+
+/// 获取标签下的全部笔记（完整内容），按更新时间倒序
+pub async fn get_tips_by_tag(conn: &DbConnection, tag_id: &str, include_descendants: bool) -> Result<Vec<Tip>> {
+    let tag = get_tag_by_id(conn, tag_id).await?.ok_or_else(|| anyhow!("Tag not found: {}", tag_id))?;
+    let sql = format!(
+        "SELECT id, title, content, tip_type, language, category_id, created_at, updated_at,
+                version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content, pinned, favorite, archived, sort_key
+         FROM tips
+         WHERE deleted_at IS NULL AND COALESCE(archived, 0) = 0 AND {}
+         ORDER BY pinned DESC, updated_at DESC",
+        tag_filter_sql("?1", include_descendants)
+    );
+    let mut rows = conn.query(&sql, params![tag.name.as_str()]).await?;
+
+    let mut tips = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let tip_type_str: String = row.get(3)?;
+        tips.push(Tip {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            content: row.get(2)?,
+            tip_type: tip_type_str.try_into()?,
+            language: row.get(4)?,
+            category_id: row.get(5)?,
+            created_at: row.get(6)?,
+            updated_at: row.get(7)?,
+            version: row.get(8)?,
+            last_synced_at: row.get(9)?,
+            sync_hash: row.get(10)?,
+            is_encrypted: row.get(11)?,
+            encryption_key_id: row.get(12)?,
+            encrypted_content: row.get(13)?,
+            pinned: row.get(14)?,
+            favorite: row.get(15)?,
+            archived: row.get(16)?,
+            sort_key: row.get(17)?,
+        });
+    }
+    Ok(tips)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_tag_paths() {
+        assert_eq!(normalize_tag_name(" lang / rust//async/ ").as_deref(), Some("lang/rust/async"));
+        assert_eq!(normalize_tag_name("rust").as_deref(), Some("rust"));
+        assert_eq!(normalize_tag_name(" / "), None);
+    }
+
+    #[test]
+    fn walks_ancestors() {
+        assert_eq!(ancestor_tag_names("lang/rust/async"), vec!["lang/rust", "lang"]);
+        assert!(ancestor_tag_names("lang").is_empty());
+        assert_eq!(parent_tag_name("lang/rust"), Some("lang"));
+        assert_eq!(tag_depth("lang/rust/async"), 2);
+    }
+
+    #[test]
+    fn replaces_subtree_prefix() {
+        assert_eq!(replace_tag_prefix("lang/rust/async", "lang/rust", "rust"), "rust/async");
+        assert_eq!(replace_tag_prefix("lang/rust", "lang/rust", "code/rust"), "code/rust");
+    }
+
+    #[tokio::test]
+    async fn set_tip_tags_accepts_tag_ids() {
+        let db = libsql::Builder::new_local(":memory:").build().await.unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE tags (id TEXT PRIMARY KEY, name TEXT NOT NULL UNIQUE, created_at INTEGER, updated_at INTEGER);
+             CREATE TABLE tip_tags (tip_id TEXT NOT NULL, tag_id TEXT NOT NULL, PRIMARY KEY (tip_id, tag_id));
+             INSERT INTO tags (id, name, created_at, updated_at) VALUES ('tag-1', 'lang/rust', 0, 0);",
+        ).await.unwrap();
+
+        let tags = vec!["tag-1".to_string(), "reading".to_string()];
+        crate::db::set_tip_tags(&conn, "tip-1", &tags).await.unwrap();
+        // 再次以 ID 保存，不应生成以 ID 命名的新标签
+        let mut rows = conn.query(
+            "SELECT t.id FROM tags t JOIN tip_tags tt ON tt.tag_id = t.id WHERE tt.tip_id = 'tip-1' ORDER BY t.name",
+            (),
+        ).await.unwrap();
+        let mut ids = Vec::new();
+        while let Some(row) = rows.next().await.unwrap() {
+            ids.push(row.get::<String>(0).unwrap());
+        }
+        crate::db::set_tip_tags(&conn, "tip-1", &ids).await.unwrap();
+
+        let mut rows = conn.query("SELECT name FROM tags ORDER BY name", ()).await.unwrap();
+        let mut names = Vec::new();
+        while let Some(row) = rows.next().await.unwrap() {
+            names.push(row.get::<String>(0).unwrap());
+        }
+        assert_eq!(names, vec!["lang", "lang/rust", "reading"]);
+
+        let mut rows = conn.query("SELECT COUNT(*) FROM tip_tags WHERE tip_id = 'tip-1'", ()).await.unwrap();
+        let count: i64 = rows.next().await.unwrap().unwrap().get(0).unwrap();
+        assert_eq!(count, 2);
+    }
+}
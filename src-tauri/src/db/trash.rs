@@ -0,0 +1,540 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use libsql::params;
+use serde::{Deserialize, Serialize};
+
+use super::operations::{delete_tip_with_dependencies, get_category_ids_recursive, get_setting, DbConnection};
+
+// 回收站保留天数（app_settings），0 表示不自动清理
+pub const TRASH_RETENTION_DAYS_KEY: &str = "trash_retention_days";
+pub const DEFAULT_TRASH_RETENTION_DAYS: i64 = 30;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// 回收站条目类型
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrashItemType {
+    Tip,
+    Category,
+}
+
+/// 回收站条目
+///
+/// 只列出被直接删除的条目：随笔记本一起删除的子笔记本和笔记归属于该笔记本，
+/// 恢复或彻底删除时一并处理。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashItem {
+    pub item_type: TrashItemType,
+    pub id: String,
+    pub title: String,
+    pub original_parent_id: Option<String>,   // 笔记所在笔记本 / 笔记本的父笔记本
+    pub original_parent_name: Option<String>,
+    pub restores_to_root: bool,                // 原笔记本已不存在或仍在回收站时恢复到根
+    pub tip_count: i64,                        // 笔记本条目：随之删除的笔记数
+    pub deleted_at: i64,
+    pub purge_at: Option<i64>,                 // 预计自动清理时间
+}
+
+/// 一次移入或移出回收站涉及的记录，由调用方标记同步
+#[derive(Debug, Clone, Default)]
+pub struct TrashChange {
+    pub tip_ids: Vec<String>,
+    pub category_ids: Vec<String>,
+}
+
+/// 读取回收站保留天数
+pub async fn get_trash_retention_days(conn: &DbConnection) -> Result<i64> {
+    Ok(get_setting(conn, TRASH_RETENTION_DAYS_KEY)
+        .await?
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .filter(|v| *v >= 0)
+        .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS))
+}
+
+/// 计算清理截止时间，早于该时间删除的条目应被清理
+fn purge_cutoff(now: i64, retention_days: i64) -> Option<i64> {
+    if retention_days <= 0 {
+        None
+    } else {
+        Some(now - retention_days * DAY_MS)
+    }
+}
+
+/// 查询记录的删除时间：None 表示记录不存在，Some(None) 表示未删除
+pub async fn get_deleted_at(conn: &DbConnection, table_name: &str, record_id: &str) -> Result<Option<Option<i64>>> {
+    if !matches!(table_name, "tips" | "categories") {
+        return Err(anyhow!("Table does not support soft delete: {}", table_name));
+    }
+
+    let mut rows = conn.query(
+        &format!("SELECT deleted_at FROM {} WHERE id = ?1", table_name),
+        params![record_id],
+    ).await?;
+
+    match rows.next().await? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+/// 软删除单条笔记（含图片和音频），需在事务中调用
+pub async fn soft_delete_tip(conn: &DbConnection, tip_id: &str, deleted_at: i64) -> Result<()> {
+    let rows_affected = conn.execute(
+        "UPDATE tips SET deleted_at = ?1, updated_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+        params![deleted_at, tip_id],
+    ).await?;
+
+    if rows_affected == 0 {
+        return Err(anyhow!("Tip not found or already deleted: {}", tip_id));
+    }
+
+    conn.execute(
+        "UPDATE tip_images SET deleted_at = ?1 WHERE tip_id = ?2 AND deleted_at IS NULL",
+        params![deleted_at, tip_id],
+    ).await?;
+    conn.execute(
+        "UPDATE tip_audio_files SET deleted_at = ?1 WHERE tip_id = ?2 AND deleted_at IS NULL",
+        params![deleted_at, tip_id],
+    ).await?;
+
+    tracing::info!("Moved tip {} to trash", tip_id);
+    Ok(())
+}
+
+/// 软删除笔记本及其全部子笔记本和笔记，使用同一删除时间作为批次标记，需在事务中调用
+pub async fn soft_delete_category(conn: &DbConnection, category_id: &str, deleted_at: i64) -> Result<TrashChange> {
+    if get_deleted_at(conn, "categories", category_id).await? != Some(None) {
+        return Err(anyhow!("Category not found or already deleted: {}", category_id));
+    }
+
+    let category_ids = get_category_ids_recursive(conn, category_id).await?;
+    let mut change = TrashChange::default();
+
+    for cat_id in &category_ids {
+        let mut rows = conn.query(
+            "SELECT id FROM tips WHERE category_id = ?1 AND deleted_at IS NULL",
+            params![cat_id.as_str()],
+        ).await?;
+        let mut tip_ids: Vec<String> = Vec::new();
+        while let Some(row) = rows.next().await? {
+            tip_ids.push(row.get(0)?);
+        }
+
+        for tip_id in &tip_ids {
+            soft_delete_tip(conn, tip_id, deleted_at).await?;
+        }
+        change.tip_ids.extend(tip_ids);
+
+        conn.execute(
+            "UPDATE categories SET deleted_at = ?1, updated_at = ?1 WHERE id = ?2",
+            params![deleted_at, cat_id.as_str()],
+        ).await?;
+    }
+
+    tracing::info!(
+        "Moved category {} to trash with {} subcategories and {} tips",
+        category_id,
+        category_ids.len() - 1,
+        change.tip_ids.len()
+    );
+    change.category_ids = category_ids;
+    Ok(change)
+}
+
+/// 查找与指定笔记本同一批次删除的笔记本（包括自身）
+async fn collect_deleted_batch(conn: &DbConnection, category_id: &str, deleted_at: i64) -> Result<Vec<String>> {
+    let mut category_ids = vec![category_id.to_string()];
+    let mut stack = vec![category_id.to_string()];
+
+    while let Some(current_id) = stack.pop() {
+        let mut rows = conn.query(
+            "SELECT id FROM categories WHERE parent_id = ?1 AND deleted_at = ?2",
+            params![current_id.as_str(), deleted_at],
+        ).await?;
+        while let Some(row) = rows.next().await? {
+            let child_id: String = row.get(0)?;
+            category_ids.push(child_id.clone());
+            stack.push(child_id);
+        }
+    }
+
+    Ok(category_ids)
+}
+
+/// 判断笔记本是否存在且未被删除
+async fn is_live_category(conn: &DbConnection, category_id: Option<&str>) -> Result<bool> {
+    match category_id {
+        Some(id) => Ok(get_deleted_at(conn, "categories", id).await? == Some(None)),
+        None => Ok(false),
+    }
+}
+
+/// 恢复笔记（含同批次删除的图片和音频），原笔记本不可用时恢复到根，需在事务中调用
+async fn restore_tip_inner(conn: &DbConnection, tip_id: &str, now: i64) -> Result<Option<String>> {
+    let mut rows = conn.query(
+        "SELECT category_id, deleted_at FROM tips WHERE id = ?1",
+        params![tip_id],
+    ).await?;
+    let (category_id, deleted_at): (Option<String>, Option<i64>) = match rows.next().await? {
+        Some(row) => (row.get(0)?, row.get(1)?),
+        None => return Err(anyhow!("Tip not found: {}", tip_id)),
+    };
+    let deleted_at = deleted_at.ok_or_else(|| anyhow!("Tip is not in trash: {}", tip_id))?;
+
+    let target_category = if is_live_category(conn, category_id.as_deref()).await? {
+        category_id
+    } else {
+        None
+    };
+
+    conn.execute(
+        "UPDATE tips SET deleted_at = NULL, category_id = ?1, updated_at = ?2 WHERE id = ?3",
+        params![target_category.as_deref(), now, tip_id],
+    ).await?;
+    conn.execute(
+        "UPDATE tip_images SET deleted_at = NULL WHERE tip_id = ?1 AND deleted_at = ?2",
+        params![tip_id, deleted_at],
+    ).await?;
+    conn.execute(
+        "UPDATE tip_audio_files SET deleted_at = NULL WHERE tip_id = ?1 AND deleted_at = ?2",
+        params![tip_id, deleted_at],
+    ).await?;
+
+    Ok(target_category)
+}
+
+/// 从回收站恢复笔记，返回恢复后所在的笔记本
+pub async fn restore_tip(conn: &DbConnection, tip_id: &str) -> Result<Option<String>> {
+    conn.execute("BEGIN TRANSACTION", ()).await?;
+
+    match restore_tip_inner(conn, tip_id, Utc::now().timestamp_millis()).await {
+        Ok(category_id) => {
+            conn.execute("COMMIT", ()).await?;
+            tracing::info!("Restored tip {} from trash", tip_id);
+            Ok(category_id)
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", ()).await?;
+            Err(e)
+        }
+    }
+}
+
+/// 恢复笔记本及同批次删除的子笔记本和笔记，需在事务中调用
+async fn restore_category_inner(conn: &DbConnection, category_id: &str, now: i64) -> Result<(Option<String>, TrashChange)> {
+    let mut rows = conn.query(
+        "SELECT parent_id, deleted_at FROM categories WHERE id = ?1",
+        params![category_id],
+    ).await?;
+    let (parent_id, deleted_at): (Option<String>, Option<i64>) = match rows.next().await? {
+        Some(row) => (row.get(0)?, row.get(1)?),
+        None => return Err(anyhow!("Category not found: {}", category_id)),
+    };
+    let deleted_at = deleted_at.ok_or_else(|| anyhow!("Category is not in trash: {}", category_id))?;
+
+    let target_parent = if is_live_category(conn, parent_id.as_deref()).await? {
+        parent_id
+    } else {
+        None
+    };
+
+    let category_ids = collect_deleted_batch(conn, category_id, deleted_at).await?;
+    for cat_id in &category_ids {
+        conn.execute(
+            "UPDATE categories SET deleted_at = NULL, updated_at = ?1 WHERE id = ?2",
+            params![now, cat_id.as_str()],
+        ).await?;
+    }
+    conn.execute(
+        "UPDATE categories SET parent_id = ?1 WHERE id = ?2",
+        params![target_parent.as_deref(), category_id],
+    ).await?;
+
+    // 笔记本已先行恢复，笔记会回到原位置
+    let mut tip_ids_restored = Vec::new();
+    for cat_id in &category_ids {
+        let mut rows = conn.query(
+            "SELECT id FROM tips WHERE category_id = ?1 AND deleted_at = ?2",
+            params![cat_id.as_str(), deleted_at],
+        ).await?;
+        let mut tip_ids: Vec<String> = Vec::new();
+        while let Some(row) = rows.next().await? {
+            tip_ids.push(row.get(0)?);
+        }
+        for tip_id in &tip_ids {
+            restore_tip_inner(conn, tip_id, now).await?;
+        }
+        tip_ids_restored.extend(tip_ids);
+    }
+
+    Ok((target_parent, TrashChange { tip_ids: tip_ids_restored, category_ids }))
+}
+
+/// 从回收站恢复笔记本，返回恢复后的父笔记本和恢复的记录
+pub async fn restore_category(conn: &DbConnection, category_id: &str) -> Result<(Option<String>, TrashChange)> {
+    conn.execute("BEGIN TRANSACTION", ()).await?;
+
+    match restore_category_inner(conn, category_id, Utc::now().timestamp_millis()).await {
+        Ok(restored) => {
+            conn.execute("COMMIT", ()).await?;
+            tracing::info!("Restored category {} from trash", category_id);
+            Ok(restored)
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", ()).await?;
+            Err(e)
+        }
+    }
+}
+
+/// 列出回收站条目，按删除时间倒序
+pub async fn list_trash(conn: &DbConnection) -> Result<Vec<TrashItem>> {
+    let retention_days = get_trash_retention_days(conn).await?;
+    let purge_at = |deleted_at: i64| {
+        if retention_days > 0 {
+            Some(deleted_at + retention_days * DAY_MS)
+        } else {
+            None
+        }
+    };
+
+    let mut items = Vec::new();
+
+    // 直接删除的笔记（排除随笔记本一起删除的）
+    let mut rows = conn.query(
+        "SELECT t.id, t.title, t.category_id, c.name, c.deleted_at, t.deleted_at
+         FROM tips t
+         LEFT JOIN categories c ON c.id = t.category_id
+         WHERE t.deleted_at IS NOT NULL
+           AND (c.deleted_at IS NULL OR c.deleted_at != t.deleted_at)",
+        (),
+    ).await?;
+    while let Some(row) = rows.next().await? {
+        let parent_name: Option<String> = row.get(3)?;
+        let parent_deleted_at: Option<i64> = row.get(4)?;
+        let deleted_at: i64 = row.get(5)?;
+        items.push(TrashItem {
+            item_type: TrashItemType::Tip,
+            id: row.get(0)?,
+            title: row.get(1)?,
+            original_parent_id: row.get(2)?,
+            restores_to_root: parent_name.is_none() || parent_deleted_at.is_some(),
+            original_parent_name: parent_name,
+            tip_count: 0,
+            deleted_at,
+            purge_at: purge_at(deleted_at),
+        });
+    }
+
+    // 直接删除的笔记本（排除随父笔记本一起删除的）
+    let mut rows = conn.query(
+        "SELECT c.id, c.name, c.parent_id, p.name, p.deleted_at, c.deleted_at
+         FROM categories c
+         LEFT JOIN categories p ON p.id = c.parent_id
+         WHERE c.deleted_at IS NOT NULL
+           AND (p.deleted_at IS NULL OR p.deleted_at != c.deleted_at)",
+        (),
+    ).await?;
+    let mut category_rows = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let id: String = row.get(0)?;
+        let name: String = row.get(1)?;
+        let parent_id: Option<String> = row.get(2)?;
+        let parent_name: Option<String> = row.get(3)?;
+        let parent_deleted_at: Option<i64> = row.get(4)?;
+        let deleted_at: i64 = row.get(5)?;
+        category_rows.push((id, name, parent_id, parent_name, parent_deleted_at, deleted_at));
+    }
+
+    for (id, name, parent_id, parent_name, parent_deleted_at, deleted_at) in category_rows {
+        let mut tip_count = 0;
+        for cat_id in collect_deleted_batch(conn, &id, deleted_at).await? {
+            let count: i64 = conn.query(
+                "SELECT COUNT(*) FROM tips WHERE category_id = ?1 AND deleted_at = ?2",
+                params![cat_id.as_str(), deleted_at],
+            ).await?
+            .next().await?
+            .map(|row| row.get::<i64>(0))
+            .transpose()?
+            .unwrap_or(0);
+            tip_count += count;
+        }
+
+        items.push(TrashItem {
+            item_type: TrashItemType::Category,
+            id,
+            title: name,
+            restores_to_root: parent_id.is_none() || parent_name.is_none() || parent_deleted_at.is_some(),
+            original_parent_id: parent_id,
+            original_parent_name: parent_name,
+            tip_count,
+            deleted_at,
+            purge_at: purge_at(deleted_at),
+        });
+    }
+
+    items.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+    Ok(items)
+}
+
+/// 彻底删除回收站中的笔记本及同批次内容，需在事务中调用
+async fn purge_category_inner(conn: &DbConnection, category_id: &str) -> Result<()> {
+    let deleted_at = get_deleted_at(conn, "categories", category_id)
+        .await?
+        .flatten()
+        .ok_or_else(|| anyhow!("Category is not in trash: {}", category_id))?;
+
+    let category_ids = collect_deleted_batch(conn, category_id, deleted_at).await?;
+
+    for cat_id in &category_ids {
+        let mut rows = conn.query(
+            "SELECT id FROM tips WHERE category_id = ?1 AND deleted_at = ?2",
+            params![cat_id.as_str(), deleted_at],
+        ).await?;
+        let mut tip_ids: Vec<String> = Vec::new();
+        while let Some(row) = rows.next().await? {
+            tip_ids.push(row.get(0)?);
+        }
+        for tip_id in &tip_ids {
+            delete_tip_with_dependencies(conn, tip_id).await?;
+        }
+
+        // 其他批次的笔记和子笔记本（如单独删除后仍在回收站的）脱离该笔记本，避免外键约束失败
+        conn.execute(
+            "UPDATE tips SET category_id = NULL WHERE category_id = ?1",
+            params![cat_id.as_str()],
+        ).await?;
+        conn.execute(
+            "UPDATE categories SET parent_id = NULL WHERE parent_id = ?1",
+            params![cat_id.as_str()],
+        ).await?;
+    }
+
+    for cat_id in &category_ids {
+        conn.execute("DELETE FROM categories WHERE id = ?1", params![cat_id.as_str()]).await?;
+    }
+
+    tracing::info!("Purged category {} and {} subcategories from trash", category_id, category_ids.len() - 1);
+    Ok(())
+}
+
+/// 彻底删除单个回收站条目，需在事务中调用
+///
+/// 远程库保留墓碑记录，不再同步物理删除。
+async fn purge_item_inner(conn: &DbConnection, item_type: TrashItemType, id: &str) -> Result<()> {
+    match item_type {
+        TrashItemType::Tip => {
+            if get_deleted_at(conn, "tips", id).await?.flatten().is_none() {
+                return Err(anyhow!("Tip is not in trash: {}", id));
+            }
+            delete_tip_with_dependencies(conn, id).await
+        }
+        TrashItemType::Category => purge_category_inner(conn, id).await,
+    }
+}
+
+/// 彻底删除回收站中的一组条目，返回删除的条目数
+async fn purge_items(conn: &DbConnection, items: &[TrashItem]) -> Result<usize> {
+    if items.is_empty() {
+        return Ok(0);
+    }
+
+    conn.execute("BEGIN TRANSACTION", ()).await?;
+
+    for item in items {
+        if let Err(e) = purge_item_inner(conn, item.item_type, &item.id).await {
+            conn.execute("ROLLBACK", ()).await?;
+            return Err(e);
+        }
+    }
+
+    conn.execute("COMMIT", ()).await?;
+    Ok(items.len())
+}
+
+/// 彻底删除回收站中的指定条目
+pub async fn purge_trash_item(conn: &DbConnection, item_type: TrashItemType, id: &str) -> Result<()> {
+    let item = list_trash(conn)
+        .await?
+        .into_iter()
+        .find(|item| item.item_type == item_type && item.id == id)
+        .ok_or_else(|| anyhow!("Item not found in trash: {}", id))?;
+
+    purge_items(conn, &[item]).await?;
+    Ok(())
+}
+
+/// 清空回收站
+pub async fn empty_trash(conn: &DbConnection) -> Result<usize> {
+    let items = list_trash(conn).await?;
+    let purged = purge_items(conn, &items).await?;
+    tracing::info!("Emptied trash: {} items purged", purged);
+    Ok(purged)
+}
+
+/// 清理超过保留天数的回收站条目
+pub async fn purge_expired_trash(conn: &DbConnection) -> Result<usize> {
+    let retention_days = get_trash_retention_days(conn).await?;
+    let cutoff = match purge_cutoff(Utc::now().timestamp_millis(), retention_days) {
+        Some(cutoff) => cutoff,
+        None => return Ok(0),
+    };
+
+    let expired: Vec<TrashItem> = list_trash(conn)
+        .await?
+        .into_iter()
+        .filter(|item| item.deleted_at < cutoff)
+        .collect();
+
+    let purged = purge_items(conn, &expired).await?;
+    if purged > 0 {
+        tracing::info!("Purged {} expired items from trash (retention {} days)", purged, retention_days);
+    }
+    Ok(purged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn purge_cutoff_disabled_when_zero() {
+        assert_eq!(purge_cutoff(1_000_000, 0), None);
+        assert_eq!(purge_cutoff(1_000_000, -1), None);
+    }
+
+    #[test]
+    fn purge_cutoff_subtracts_days() {
+        let now = 40 * DAY_MS;
+        assert_eq!(purge_cutoff(now, 30), Some(10 * DAY_MS));
+    }
+
+    #[tokio::test]
+    async fn trashed_tips_cannot_be_read_or_edited() {
+        let db = libsql::Builder::new_local(":memory:").build().await.unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE tips (
+                 id TEXT PRIMARY KEY, title TEXT NOT NULL, content TEXT NOT NULL, tip_type TEXT NOT NULL,
+                 language TEXT, category_id TEXT, created_at INTEGER NOT NULL, updated_at INTEGER NOT NULL,
+                 version INTEGER, last_synced_at INTEGER, sync_hash TEXT, is_encrypted INTEGER,
+                 encryption_key_id TEXT, encrypted_content TEXT, pinned INTEGER, favorite INTEGER,
+                 archived INTEGER, sort_key REAL, deleted_at INTEGER
+             );
+             CREATE TABLE tip_images (tip_id TEXT NOT NULL, deleted_at INTEGER);
+             CREATE TABLE tip_audio_files (tip_id TEXT NOT NULL, deleted_at INTEGER);
+             INSERT INTO tips (id, title, content, tip_type, created_at, updated_at)
+             VALUES ('t', 'Title', 'Body', 'markdown', 1, 1);",
+        ).await.unwrap();
+
+        let mut tip = crate::db::get_tip_by_id(&conn, "t").await.unwrap().unwrap();
+        soft_delete_tip(&conn, "t", 2).await.unwrap();
+        assert!(crate::db::get_tip_by_id(&conn, "t").await.unwrap().is_none());
+
+        tip.content = "Edited".to_string();
+        assert!(crate::db::update_tip(&conn, &tip).await.is_err());
+        let mut rows = conn.query("SELECT content FROM tips WHERE id = 't'", ()).await.unwrap();
+        assert_eq!(rows.next().await.unwrap().unwrap().get::<String>(0).unwrap(), "Body");
+    }
+}
@@ -22,8 +22,18 @@ use objc::{runtime::Object, *};
 // Directly import all public APIs provided by the api module
 use api::ai::conversations::{
     add_ai_message, clear_ai_conversation, create_ai_conversation, delete_ai_conversation,
-    list_ai_conversations, list_ai_messages, update_ai_conversation_title,
+    edit_ai_message, list_ai_conversations, list_ai_message_branches, list_ai_messages,
+    regenerate_ai_message, switch_ai_branch, update_ai_conversation_title,
 };
+use api::ai::embeddings::{get_embedding_index_status, rebuild_tip_embeddings, semantic_search_tips};
+use api::ai::keys::{
+    get_ai_key_protection_status, lock_ai_keys, set_ai_key_master_password, set_ai_key_sync,
+    unlock_ai_keys,
+};
+use api::ai::prompts::{
+    delete_prompt_runs, get_prompt_variables, list_prompt_runs, render_prompt_tip, run_prompt_tip,
+};
+use api::ai::rag::{send_rag_message, send_rag_message_stream};
 use api::ai::roles::{create_ai_role, delete_ai_role, get_ai_role, list_ai_roles, update_ai_role};
 use api::ai::service::{
     add_custom_model_config, delete_custom_model_config, get_ai_chat_models, get_ai_config,
@@ -31,6 +41,15 @@ use api::ai::service::{
     list_custom_model_configs, reload_ai_services, save_ai_config, set_default_ai_model,
     test_ai_connection, update_custom_model_config,
 };
+use api::ai::tools::{confirm_ai_tool_call, list_ai_tools, send_ai_message_with_tools};
+use api::ai::local::discover_local_models;
+use api::ai::resilience::{get_ai_provider_health, get_ai_resilience_config, reset_ai_provider_health, save_ai_resilience_config};
+use api::ai::suggest::{apply_tip_suggestions, suggest_metadata_for_untagged_tips, suggest_tip_metadata};
+use api::ai::writing::{apply_ai_writing_patch, run_ai_writing_action};
+use api::ai::usage::{
+    clear_ai_usage, delete_ai_budget, delete_ai_model_price, get_ai_budget_status,
+    list_ai_budgets, list_ai_model_prices, query_ai_usage, set_ai_budget, set_ai_model_price,
+};
 use api::audio::{
     analyze_audio_content, batch_analyze_tip_audio, batch_optimize_audio_files,
     batch_transcribe_tip_audio, build_audio_search_index, cleanup_audio_cache, delete_audio_file,
@@ -168,8 +187,19 @@ pub fn run() -> anyhow::Result<()> {
             // Set up the unified database manager here
             let rt = tokio::runtime::Runtime::new().unwrap();
             let unified_manager = rt.block_on(UnifiedDbManager::new(app_handle.clone()))?;
+
+            // 初始化 AI 密钥库并加密旧的明文密钥
+            if let Err(e) = rt.block_on(api::ai::keys::init_key_store(&app_handle, &unified_manager)) {
+                tracing::warn!("Failed to initialize AI key store: {}", e);
+            }
             app.manage(unified_manager);
 
+            // 回收站过期条目定时清理
+            api::trash::start_trash_purge_task(app_handle.clone());
+
+            // 同步合并冲突转发给前端
+            api::database::start_merge_conflict_forwarder(app_handle.clone());
+
             // Setup window close event handler
             if let Some(window) = app.get_webview_window("main") {
                 let window_clone = window.clone();
@@ -247,6 +277,35 @@ pub fn run() -> anyhow::Result<()> {
             delete_tip,
             search_tips,
             search_tips_summary,
+            search_tips_advanced,
+            list_tip_revisions,
+            get_tip_revision,
+            diff_tip_revisions,
+            restore_tip_revision,
+            get_backlinks,
+            get_outgoing_links,
+            get_dangling_links,
+            get_note_graph,
+            list_pinned_tips,
+            list_favorite_tips,
+            list_archived_tips,
+            set_tip_pinned,
+            set_tip_favorite,
+            set_tip_archived,
+            get_tip_properties,
+            set_tip_property,
+            delete_tip_property,
+            list_property_definitions,
+            set_property_definition,
+            move_tip,
+            // Trash APIs
+            list_trash,
+            restore_tip,
+            restore_category,
+            purge_trash_item,
+            empty_trash,
+            get_trash_retention_days,
+            set_trash_retention_days,
             get_tips_by_category,
             get_tips_by_category_recursive,
             browse_category,
@@ -262,10 +321,16 @@ pub fn run() -> anyhow::Result<()> {
             create_category,
             update_category,
             delete_category,
+            move_category,
             // Tag-related APIs
             get_all_tags,
             create_tag,
             delete_tag,
+            get_tags_with_counts,
+            rename_tag,
+            merge_tags,
+            set_tag_appearance,
+            list_tips_by_tag,
             // Import-related APIs
             import_from_directory,
             import_markdown_file,
@@ -283,6 +348,10 @@ pub fn run() -> anyhow::Result<()> {
             delete_ai_conversation,
             clear_ai_conversation,
             update_ai_conversation_title,
+            edit_ai_message,
+            regenerate_ai_message,
+            list_ai_message_branches,
+            switch_ai_branch,
             add_ai_message,
             // AI role-related APIs
             list_ai_roles,
@@ -296,11 +365,48 @@ pub fn run() -> anyhow::Result<()> {
             test_ai_connection,
             get_ai_chat_models,
             get_ai_embedding_models,
+            rebuild_tip_embeddings,
+            get_embedding_index_status,
+            semantic_search_tips,
+            send_rag_message,
+            send_rag_message_stream,
+            send_ai_message_with_tools,
+            get_prompt_variables,
+            render_prompt_tip,
+            run_prompt_tip,
+            list_prompt_runs,
+            delete_prompt_runs,
+            run_ai_writing_action,
+            apply_ai_writing_patch,
+            suggest_tip_metadata,
+            suggest_metadata_for_untagged_tips,
+            apply_tip_suggestions,
+            get_ai_resilience_config,
+            save_ai_resilience_config,
+            get_ai_provider_health,
+            reset_ai_provider_health,
+            discover_local_models,
+            confirm_ai_tool_call,
+            list_ai_tools,
             get_default_ai_model,
             set_default_ai_model,
             save_ai_config,
             get_ai_config,
             get_ai_usage_stats,
+            query_ai_usage,
+            clear_ai_usage,
+            list_ai_model_prices,
+            set_ai_model_price,
+            delete_ai_model_price,
+            list_ai_budgets,
+            set_ai_budget,
+            delete_ai_budget,
+            get_ai_budget_status,
+            get_ai_key_protection_status,
+            set_ai_key_master_password,
+            unlock_ai_keys,
+            lock_ai_keys,
+            set_ai_key_sync,
             reload_ai_services,
             get_ai_service_status,
             // Audio APIs
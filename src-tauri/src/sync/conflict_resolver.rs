@@ -234,6 +234,16 @@ impl EnhancedConflictResolver {
             is_critical: false,
         });
 
+        // 置顶/收藏/归档修改时会更新 updated_at，以较新的一端为准
+        for flag in ["pinned", "favorite", "archived"] {
+            self.field_priorities.insert(format!("tips.{}", flag), FieldPriorityConfig {
+                field_name: flag.to_string(),
+                priority_weight: 40,
+                merge_strategy: FieldMergeStrategy::NewerWins,
+                is_critical: false,
+            });
+        }
+
         // Categories 表的字段优先级
         self.field_priorities.insert("categories.name".to_string(), FieldPriorityConfig {
             field_name: "name".to_string(),
@@ -315,7 +325,7 @@ impl EnhancedConflictResolver {
             .map_err(|e| anyhow!("Failed to parse remote content as JSON: {}", e))?;
 
         // 检测字段级冲突
-        let field_conflicts = self.detect_field_conflicts(&local_json, &remote_json)?;
+        let field_conflicts = self.detect_field_conflicts(&record.table_name, &local_json, &remote_json)?;
 
         // 计算冲突严重级别
         let severity = self.calculate_conflict_severity(&field_conflicts, &record.table_name);
@@ -345,6 +355,7 @@ impl EnhancedConflictResolver {
     /// 检测字段级冲突
     fn detect_field_conflicts(
         &self,
+        table_name: &str,
         local_json: &serde_json::Value,
         remote_json: &serde_json::Value,
     ) -> Result<Vec<FieldConflict>> {
@@ -353,6 +364,9 @@ impl EnhancedConflictResolver {
         // 获取两个JSON对象的所有字段
         let local_obj = local_json.as_object().ok_or_else(|| anyhow!("Local content is not a JSON object"))?;
         let remote_obj = remote_json.as_object().ok_or_else(|| anyhow!("Remote content is not a JSON object"))?;
+        // 记录的更新时间，供非时间字段按较新的一端合并
+        let updated_at = |obj: &serde_json::Map<String, serde_json::Value>| obj.get("updated_at").and_then(|v| v.as_i64());
+        let record_times = updated_at(local_obj).zip(updated_at(remote_obj));
 
         // 收集所有字段名
         let mut all_fields: std::collections::HashSet<String> = local_obj.keys().cloned().collect();
@@ -365,7 +379,9 @@ impl EnhancedConflictResolver {
 
             if local_value != remote_value {
                 let conflict_type = self.determine_field_conflict_type(&local_value, &remote_value);
-                let suggested_resolution = self.suggest_field_resolution(&field_name, &local_value, &remote_value, conflict_type.clone());
+                let suggested_resolution = self.suggest_field_resolution(
+                    table_name, &field_name, &local_value, &remote_value, conflict_type.clone(), record_times,
+                );
 
                 field_conflicts.push(FieldConflict {
                     field_name: field_name.clone(),
@@ -380,6 +396,13 @@ impl EnhancedConflictResolver {
         Ok(field_conflicts)
     }
 
+    /// 查找字段配置，优先使用“表名.字段名”
+    fn field_config(&self, table_name: &str, field_name: &str) -> Option<&FieldPriorityConfig> {
+        self.field_priorities
+            .get(&format!("{}.{}", table_name, field_name))
+            .or_else(|| self.field_priorities.get(field_name))
+    }
+
     /// 确定字段冲突类型
     fn determine_field_conflict_type(
         &self,
@@ -399,23 +422,25 @@ impl EnhancedConflictResolver {
     /// 建议字段解决方案
     fn suggest_field_resolution(
         &self,
+        table_name: &str,
         field_name: &str,
         local_value: &serde_json::Value,
         remote_value: &serde_json::Value,
         conflict_type: FieldConflictType,
+        record_times: Option<(i64, i64)>,
     ) -> FieldResolution {
         // 查找字段特定的配置
-        let field_config = self.field_priorities.get(field_name);
+        let field_config = self.field_config(table_name, field_name);
 
         let (strategy, resolved_value, confidence) = match field_config {
             Some(config) => {
-                let (value, conf) = self.apply_field_merge_strategy(&config.merge_strategy, local_value, remote_value);
+                let (value, conf) = self.apply_field_merge_strategy(&config.merge_strategy, local_value, remote_value, record_times);
                 (config.merge_strategy.clone(), value, conf)
             }
             None => {
                 // 使用默认策略
                 let strategy = self.get_default_field_strategy(field_name, conflict_type);
-                let (value, conf) = self.apply_field_merge_strategy(&strategy, local_value, remote_value);
+                let (value, conf) = self.apply_field_merge_strategy(&strategy, local_value, remote_value, record_times);
                 (strategy, value, conf)
             }
         };
@@ -433,13 +458,14 @@ impl EnhancedConflictResolver {
         strategy: &FieldMergeStrategy,
         local_value: &serde_json::Value,
         remote_value: &serde_json::Value,
+        record_times: Option<(i64, i64)>,
     ) -> (serde_json::Value, u8) {
         match strategy {
             FieldMergeStrategy::LocalWins => (local_value.clone(), 85),
             FieldMergeStrategy::RemoteWins => (remote_value.clone(), 85),
             FieldMergeStrategy::NewerWins => {
                 // 尝试解析时间戳字段
-                self.resolve_newer_wins(local_value, remote_value)
+                self.resolve_newer_wins(local_value, remote_value, record_times)
             }
             FieldMergeStrategy::LongerWins => {
                 self.resolve_longer_wins(local_value, remote_value)
@@ -463,20 +489,15 @@ impl EnhancedConflictResolver {
         &self,
         local_value: &serde_json::Value,
         remote_value: &serde_json::Value,
+        record_times: Option<(i64, i64)>,
     ) -> (serde_json::Value, u8) {
-        // 尝试解析为时间戳
-        if let (Some(local_ts), Some(remote_ts)) = (
-            local_value.as_i64(),
-            remote_value.as_i64(),
-        ) {
-            if local_ts > remote_ts {
-                (local_value.clone(), 90)
-            } else {
-                (remote_value.clone(), 90)
-            }
-        } else {
-            // 无法解析为时间戳，使用本地值
-            (local_value.clone(), 60)
+        // 优先比较记录的更新时间（标记等字段的值本身不是时间），没有时把值当作时间戳比较
+        let times = record_times.or_else(|| local_value.as_i64().zip(remote_value.as_i64()));
+        match times {
+            Some((local_ts, remote_ts)) if local_ts > remote_ts => (local_value.clone(), 90),
+            Some(_) => (remote_value.clone(), 90),
+            // 无法判断新旧，使用本地值
+            None => (local_value.clone(), 60),
         }
     }
 
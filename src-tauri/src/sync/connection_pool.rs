@@ -35,7 +35,7 @@ impl DatabaseConfig {
                 // 优化 WAL 设置（本地数据库）
                 Self::optimize_wal_settings(&conn, true).await?;
                 
-                if let Err(e) = crate::db::operations::create_all_tables(&conn).await {
+                if let Err(e) = crate::db::operations::create_local_tables(&conn).await {
                     warn!("Failed to initialize local database schema: {}", e);
                 } else {
                     debug!("Local database schema initialized successfully");
@@ -11,6 +11,8 @@ use blake3;
 use sha2::{Sha256, Digest};
 // use crate::db::Database; // 使用 libsql::Database 替代
 use super::monitoring::{PerformanceMonitor, StructuredLogger};
+use super::table_descriptors::{self, TableSyncDescriptor, SYNC_TABLES};
+use super::three_way_merge;
 
 /// 增量同步管理器
 pub struct IncrementalSyncManager {
@@ -155,32 +157,15 @@ pub struct ChangeDetectionResult {
     pub total_changed: u64,
 }
 
-/// 记录数据枚举（用于安全传输）
+/// 记录数据（按同步列读取的整行，用于安全传输）
 #[derive(Debug, Clone)]
-pub enum RecordData {
-    Tip {
-        id: String,
-        title: String,
-        content: String,
-        tip_type: String,
-        language: Option<String>,
-        category_id: Option<String>,
-        created_at: i64,
-        updated_at: i64,
-    },
-    Category {
-        id: String,
-        name: String,
-        parent_id: Option<String>,
-        created_at: i64,
-        updated_at: i64,
-    },
-    Tag {
-        id: String,
-        name: String,
-        created_at: i64,
-        updated_at: i64,
-    },
+pub struct RecordData {
+    /// 记录键
+    pub record_key: String,
+    /// 列名
+    pub columns: Vec<String>,
+    /// 与列一一对应的值
+    pub values: Vec<libsql::Value>,
 }
 
 #[derive(Debug, Clone)]
@@ -199,6 +184,8 @@ pub struct ChangedRecord {
     pub remote_hash: Option<String>,
     /// 预估数据大小（字节）
     pub estimated_size: u64,
+    /// 来自变更日志时覆盖的序号范围（首条, 末条）
+    pub log_seqs: Option<(i64, i64)>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -213,6 +200,147 @@ pub enum ChangeType {
     Conflict,
 }
 
+/// 全量同步时间戳的缓存键
+const FULL_SYNC_KEY: &str = "__full_sync__";
+
+/// 查找表的同步描述
+fn descriptor(table_name: &str) -> Result<&'static TableSyncDescriptor> {
+    table_descriptors::descriptor_for(table_name)
+        .ok_or_else(|| anyhow!("Unsupported table for incremental sync: {}", table_name))
+}
+
+/// 两条记录在共有的列上是否有差异
+fn fields_differ(a: &serde_json::Map<String, serde_json::Value>, b: &serde_json::Map<String, serde_json::Value>) -> bool {
+    a.iter().any(|(key, value)| b.get(key).is_some_and(|other| other != value))
+}
+
+/// 合并一页变更日志：同一记录只保留最后一次操作，按同步描述的表顺序分组
+/// 单批同步结果：成功的记录键，以及失败的记录键和错误
+type BatchSyncOutcome = (Vec<String>, Vec<(String, String)>);
+
+fn coalesce_change_log(entries: &[crate::db::ChangeLogEntry]) -> Vec<(String, Vec<ChangedRecord>)> {
+    let mut by_table: HashMap<&str, Vec<ChangedRecord>> = HashMap::new();
+    let mut positions: HashMap<(&str, &str), usize> = HashMap::new();
+
+    for entry in entries {
+        let change_type = match entry.operation.as_str() {
+            "INSERT" => ChangeType::Insert,
+            "DELETE" => ChangeType::Delete,
+            _ => ChangeType::Update,
+        };
+        let records = by_table.entry(entry.table_name.as_str()).or_default();
+        match positions.get(&(entry.table_name.as_str(), entry.record_key.as_str())) {
+            Some(&index) => {
+                let record = &mut records[index];
+                record.change_type = change_type;
+                record.local_timestamp = entry.changed_at;
+                record.log_seqs = record.log_seqs.map(|(first, _)| (first, entry.seq));
+            }
+            None => {
+                positions.insert((entry.table_name.as_str(), entry.record_key.as_str()), records.len());
+                records.push(ChangedRecord {
+                    record_id: entry.record_key.clone(),
+                    change_type,
+                    local_timestamp: entry.changed_at,
+                    remote_timestamp: None,
+                    local_hash: None,
+                    remote_hash: None,
+                    estimated_size: 0,
+                    log_seqs: Some((entry.seq, entry.seq)),
+                });
+            }
+        }
+    }
+
+    SYNC_TABLES
+        .iter()
+        .filter_map(|d| by_table.remove(d.table).map(|records| (d.table.to_string(), records)))
+        .collect()
+}
+
+/// 同步一组变更日志条目，成功的清除失败记录，失败的累计次数
+async fn sync_logged_changes<F, Fut>(
+    conn: &Connection,
+    entries: &[crate::db::ChangeLogEntry],
+    sync_batch: &mut F,
+    stats: &mut IncrementalSyncStats,
+) -> Result<()>
+where
+    F: FnMut(String, Vec<ChangedRecord>) -> Fut,
+    Fut: std::future::Future<Output = BatchSyncOutcome>,
+{
+    for (table_name, records) in coalesce_change_log(entries) {
+        stats.checked_records += records.len() as u64;
+        stats.changed_records += records.len() as u64;
+
+        let (synced, failed) = sync_batch(table_name.clone(), records.clone()).await;
+        stats.synced_records += synced.len() as u64;
+        for record_key in &synced {
+            crate::db::clear_change_log_failure(conn, &table_name, record_key).await?;
+        }
+
+        for (record_key, error) in &failed {
+            let Some(record) = records.iter().find(|r| &r.record_id == record_key) else {
+                continue;
+            };
+            let operation = match record.change_type {
+                ChangeType::Insert => "INSERT",
+                ChangeType::Delete => "DELETE",
+                _ => "UPDATE",
+            };
+            let attempts = crate::db::record_change_log_failure(
+                conn, &table_name, record_key, operation, record.local_timestamp, error,
+            ).await?;
+            if attempts >= crate::db::MAX_CHANGE_LOG_ATTEMPTS {
+                error!("Giving up on {} {} after {} failed attempts: {}", table_name, record_key, attempts, error);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 先重试此前失败的记录，再从游标开始分页消费变更日志；每页结束后推进游标并压缩日志
+async fn consume_change_log<F, Fut>(
+    conn: &Connection,
+    cursor: i64,
+    page_size: i64,
+    mut sync_batch: F,
+) -> Result<IncrementalSyncStats>
+where
+    F: FnMut(String, Vec<ChangedRecord>) -> Fut,
+    Fut: std::future::Future<Output = BatchSyncOutcome>,
+{
+    let mut stats = IncrementalSyncStats::default();
+
+    let retries = crate::db::pending_change_log_failures(conn).await?;
+    if !retries.is_empty() {
+        debug!("Retrying {} previously failed changes", retries.len());
+        sync_logged_changes(conn, &retries, &mut sync_batch, &mut stats).await?;
+    }
+
+    let mut cursor = cursor;
+    loop {
+        let detection_start = Instant::now();
+        let entries = crate::db::read_change_log(conn, cursor, page_size).await?;
+        let Some(page_end) = entries.last().map(|e| e.seq) else {
+            break;
+        };
+        stats.detection_duration_ms += detection_start.elapsed().as_millis() as u64;
+
+        sync_logged_changes(conn, &entries, &mut sync_batch, &mut stats).await?;
+
+        crate::db::save_change_log_cursor(conn, page_end).await?;
+        crate::db::compact_change_log(conn, page_end).await?;
+        cursor = page_end;
+
+        if (entries.len() as i64) < page_size {
+            break;
+        }
+    }
+
+    Ok(stats)
+}
+
 impl IncrementalSyncManager {
     /// 创建新的增量同步管理器
     pub async fn new(
@@ -266,40 +394,22 @@ impl IncrementalSyncManager {
             0,
         );
 
-        let mut total_stats = IncrementalSyncStats::default();
-
-        // 检查是否需要强制全量同步
-        if self.should_force_full_sync().await? {
-            info!("Forcing full sync due to time interval");
-            return self.perform_full_sync().await;
-        }
-
-        // 逐表执行增量同步
-        let tables = vec!["categories", "tags", "tips", "tip_tags"];
-        
-        for table_name in tables {
-            info!("Processing incremental sync for table: {}", table_name);
-            
-            match self.sync_table_incremental(table_name).await {
-                Ok(table_stats) => {
-                    total_stats.checked_records += table_stats.checked_records;
-                    total_stats.changed_records += table_stats.changed_records;
-                    total_stats.synced_records += table_stats.synced_records;
-                    total_stats.skipped_records += table_stats.skipped_records;
-                    total_stats.time_saved_ms += table_stats.time_saved_ms;
-                    total_stats.data_saved_bytes += table_stats.data_saved_bytes;
-                    total_stats.detection_duration_ms += table_stats.detection_duration_ms;
-                    
-                    info!("Table {} sync completed: {} changed, {} synced, {} skipped", 
-                          table_name, table_stats.changed_records, 
-                          table_stats.synced_records, table_stats.skipped_records);
-                }
-                Err(e) => {
-                    error!("Failed to sync table {}: {}", table_name, e);
-                    // 继续处理其他表
-                }
+        // 从未消费过变更日志或到达强制间隔时执行全量扫描
+        let cursor = {
+            let local_conn = self.local_db.connect()?;
+            crate::db::get_change_log_cursor(&local_conn).await?
+        };
+        let force_full_sync = self.should_force_full_sync().await?;
+        let cursor = match cursor {
+            Some(cursor) if !force_full_sync => cursor,
+            _ => {
+                info!("Forcing full sync due to time interval or missing change log cursor");
+                return self.perform_full_sync().await;
             }
-        }
+        };
+
+        // 从游标开始消费变更日志
+        let mut total_stats = self.sync_from_change_log(cursor).await?;
 
         let sync_duration = sync_start.elapsed();
         total_stats.sync_duration_ms = sync_duration.as_millis() as u64;
@@ -357,8 +467,8 @@ impl IncrementalSyncManager {
         
         for batch in detection_result.changed_records.chunks(batch_size) {
             match self.sync_changed_records_batch(table_name, batch).await {
-                Ok(batch_synced) => {
-                    stats.synced_records += batch_synced;
+                Ok((synced, _)) => {
+                    stats.synced_records += synced.len() as u64;
                 }
                 Err(e) => {
                     error!("Failed to sync batch for table {}: {}", table_name, e);
@@ -383,7 +493,7 @@ impl IncrementalSyncManager {
         &self, 
         table_name: &str, 
         changed_records: &[ChangedRecord]
-    ) -> Result<u64> {
+    ) -> Result<BatchSyncOutcome> {
         // 使用排队机制，避免并发访问
         static SYNC_MUTEX: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
         let _lock = SYNC_MUTEX.lock().await;
@@ -392,18 +502,20 @@ impl IncrementalSyncManager {
         
         let mut synced_count = 0;
         let mut successful_records = Vec::new();
+        let mut failed_records = Vec::new();
 
         // 逐条同步，避免并发问题
         for record in changed_records {
             match self.sync_single_record_isolated(table_name, record).await {
                 Ok(_) => {
                     synced_count += 1;
-                    successful_records.push(record.record_id.clone());
+                    successful_records.push(record);
                     info!("Successfully synced record: {} in table {}", record.record_id, table_name);
                 }
                 Err(e) => {
                     warn!("Failed to sync record {} in table {}: {}", 
                           record.record_id, table_name, e);
+                    failed_records.push((record.record_id.clone(), e.to_string()));
                     // 继续处理其他记录
                 }
             }
@@ -419,7 +531,7 @@ impl IncrementalSyncManager {
         
         info!("Batch sync completed: {} of {} records synced in table {}", 
               synced_count, changed_records.len(), table_name);
-        Ok(synced_count)
+        Ok((successful_records.into_iter().map(|r| r.record_id.clone()).collect(), failed_records))
     }
 
     /// 完全隔离的单记录同步（彻底避免WAL冲突）
@@ -431,6 +543,9 @@ impl IncrementalSyncManager {
         // 采用序列化同步策略，确保连接完全隔离
         match changed_record.change_type {
             ChangeType::Insert | ChangeType::Update => {
+                if self.merge_if_diverged(table_name, &changed_record.record_id).await? {
+                    return Ok(());
+                }
                 self.sync_record_to_remote_isolated(table_name, &changed_record.record_id).await
             }
             ChangeType::Delete => {
@@ -464,6 +579,76 @@ impl IncrementalSyncManager {
         Ok(())
     }
 
+    /// 远程在上次同步后也被修改时，以共同祖先做三方合并并把结果写回两端；返回是否已处理
+    async fn merge_if_diverged(&self, table_name: &str, record_id: &str) -> Result<bool> {
+        let descriptor = descriptor(table_name)?;
+        if descriptor.merge_text_columns.is_empty() {
+            return Ok(false);
+        }
+
+        let local_conn = self.local_db.connect()?;
+        let Some(base) = crate::db::get_merge_base(&local_conn, table_name, record_id).await? else {
+            return Ok(false);
+        };
+        let base: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&base)?;
+
+        let remote_conn = {
+            let guard = self.remote_db.read().await;
+            guard.as_ref()
+                .ok_or_else(|| anyhow!("Remote database not connected"))?
+                .connect()?
+        };
+        let local = table_descriptors::read_row_json(&local_conn, descriptor, record_id).await?;
+        let remote = table_descriptors::read_row_json(&remote_conn, descriptor, record_id).await?;
+        let (Some(local), Some(remote)) = (local, remote) else {
+            return Ok(false);
+        };
+        if !fields_differ(&base, &remote) || !fields_differ(&local, &remote) {
+            return Ok(false);
+        }
+
+        let result = three_way_merge::merge_records(&base, &local, &remote, descriptor.merge_text_columns);
+        crate::db::begin_sync_apply(&local_conn).await?;
+        let applied = table_descriptors::update_row_json(&local_conn, descriptor, record_id, &result.merged).await;
+        crate::db::finish_sync_apply(&local_conn, applied).await?;
+        table_descriptors::update_row_json(&remote_conn, descriptor, record_id, &result.merged).await?;
+        self.save_merge_base(&local_conn, descriptor, record_id, Some(&result.merged)).await?;
+
+        if result.conflicts.is_empty() {
+            info!("Merged concurrent edits for record {} in table {}", record_id, table_name);
+        } else {
+            let fields: Vec<&str> = result.conflicts.iter().map(|c| c.field.as_str()).collect();
+            warn!("Concurrent edits for record {} in table {} left conflict markers in: {:?}",
+                  record_id, table_name, fields);
+            super::publish_merge_conflicts(super::MergeConflictNotice {
+                table_name: table_name.to_string(),
+                record_id: record_id.to_string(),
+                fields: result.conflicts,
+            });
+        }
+        Ok(true)
+    }
+
+    /// 以实际写入对端的内容作为三方合并的共同祖先，记录已不存在时清理；不参与合并的表直接跳过
+    async fn save_merge_base(
+        &self,
+        conn: &Connection,
+        descriptor: &TableSyncDescriptor,
+        record_id: &str,
+        written: Option<&serde_json::Map<String, serde_json::Value>>,
+    ) -> Result<()> {
+        if descriptor.merge_text_columns.is_empty() {
+            return Ok(());
+        }
+        match written {
+            Some(row) => {
+                let content = serde_json::to_string(row)?;
+                crate::db::save_merge_base(conn, descriptor.table, record_id, &content).await
+            }
+            None => crate::db::delete_merge_base(conn, descriptor.table, record_id).await,
+        }
+    }
+
     /// 从远程删除记录（完全隔离）
     async fn delete_record_from_remote_isolated(
         &self,
@@ -475,11 +660,30 @@ impl IncrementalSyncManager {
             .ok_or_else(|| anyhow!("Remote database not connected"))?
             .clone();
         
+        let local_conn = self.local_db.connect()?;
         let remote_conn = remote_db.connect()?;
-        remote_conn.execute(
-            &format!("DELETE FROM {} WHERE id = ?", table_name),
-            params![record_id]
-        ).await?;
+        self.propagate_delete(&local_conn, &remote_conn, table_name, record_id).await
+    }
+
+    /// 传播删除：带墓碑列的表在本地记录仍存在时推送整行（含墓碑），其他表或本地已彻底删除的记录直接删除远程记录
+    async fn propagate_delete(
+        &self,
+        local_conn: &Connection,
+        remote_conn: &Connection,
+        table_name: &str,
+        record_id: &str,
+    ) -> Result<()> {
+        let descriptor = descriptor(table_name)?;
+        if descriptor.tombstone_column.is_some() {
+            if let Some(written) = table_descriptors::copy_row(local_conn, remote_conn, descriptor, record_id).await? {
+                self.save_merge_base(local_conn, descriptor, record_id, Some(&written)).await?;
+                info!("Synced tombstone for record {} in remote table {}", record_id, table_name);
+                return Ok(());
+            }
+        }
+
+        table_descriptors::delete_row(remote_conn, descriptor, record_id).await?;
+        self.save_merge_base(local_conn, descriptor, record_id, None).await?;
         
         info!("Deleted record {} from remote table {}", record_id, table_name);
         Ok(())
@@ -504,7 +708,7 @@ impl IncrementalSyncManager {
                 self.sync_insert_record_safe(local_conn, remote_conn, &table_name, &record_id).await
             }
             ChangeType::Delete => {
-                self.sync_delete_record_safe(remote_conn, &table_name, &record_id).await
+                self.sync_delete_record_safe(local_conn, remote_conn, &table_name, &record_id).await
             }
             ChangeType::Conflict => {
                 // 对于冲突，默认使用本地优先策略
@@ -529,7 +733,7 @@ impl IncrementalSyncManager {
                 self.sync_update_record(local_conn, remote_conn, table_name, &changed_record.record_id).await
             }
             ChangeType::Delete => {
-                self.sync_delete_record(remote_conn, table_name, &changed_record.record_id).await
+                self.sync_delete_record(local_conn, remote_conn, table_name, &changed_record.record_id).await
             }
             ChangeType::Conflict => {
                 self.resolve_conflict_record(local_conn, remote_conn, table_name, changed_record).await
@@ -545,55 +749,7 @@ impl IncrementalSyncManager {
         table_name: &str,
         record_id: &str,
     ) -> Result<()> {
-        match table_name {
-            "tips" => {
-                if let Some(tip) = crate::db::operations::get_tip_by_id(local_conn, record_id).await? {
-                    remote_conn.execute(
-                        "INSERT OR REPLACE INTO tips (id, title, content, tip_type, language, category_id, created_at, updated_at) 
-                         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
-                        params![
-                            tip.id, tip.title, tip.content, 
-                            String::from(tip.tip_type), tip.language, tip.category_id,
-                            tip.created_at, tip.updated_at
-                        ]
-                    ).await?;
-                    info!("Synced tip to remote: {}", record_id);
-                }
-            }
-            "categories" => {
-                if let Some(category) = crate::db::operations::get_category_by_id(local_conn, record_id).await? {
-                    remote_conn.execute(
-                        "INSERT OR REPLACE INTO categories (id, name, parent_id, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
-                        params![category.id, category.name, category.parent_id, category.created_at, category.updated_at]
-                    ).await?;
-                    info!("Synced category to remote: {}", record_id);
-                }
-            }
-            "tags" => {
-                let mut rows = local_conn.query(
-                    "SELECT id, name, created_at, updated_at FROM tags WHERE id = ?",
-                    params![record_id]
-                ).await?;
-                
-                if let Some(row) = rows.next().await? {
-                    let tag_id: String = row.get(0)?;
-                    let tag_name: String = row.get(1)?;
-                    let created_at: i64 = row.get(2)?;
-                    let updated_at: i64 = row.get(3)?;
-                    
-                    remote_conn.execute(
-                        "INSERT OR REPLACE INTO tags (id, name, created_at, updated_at) VALUES (?, ?, ?, ?)",
-                        params![tag_id, tag_name, created_at, updated_at]
-                    ).await?;
-                    info!("Synced tag to remote: {}", record_id);
-                }
-            }
-            _ => {
-                return Err(anyhow!("Unsupported table for incremental sync: {}", table_name));
-            }
-        }
-        
-        Ok(())
+        self.sync_insert_record(local_conn, remote_conn, table_name, record_id).await
     }
 
     /// 读取记录数据
@@ -603,103 +759,56 @@ impl IncrementalSyncManager {
         table_name: &str,
         record_id: &str,
     ) -> Result<RecordData> {
-        match table_name {
-            "tips" => {
-                if let Some(tip) = crate::db::operations::get_tip_by_id(local_conn, record_id).await? {
-                    Ok(RecordData::Tip {
-                        id: tip.id,
-                        title: tip.title,
-                        content: tip.content,
-                        tip_type: String::from(tip.tip_type),
-                        language: tip.language,
-                        category_id: tip.category_id,
-                        created_at: tip.created_at,
-                        updated_at: tip.updated_at,
-                    })
-                } else {
-                    Err(anyhow!("Tip not found: {}", record_id))
-                }
-            }
-            "categories" => {
-                if let Some(category) = crate::db::operations::get_category_by_id(local_conn, record_id).await? {
-                    Ok(RecordData::Category {
-                        id: category.id,
-                        name: category.name,
-                        parent_id: category.parent_id,
-                        created_at: category.created_at,
-                        updated_at: category.updated_at,
-                    })
-                } else {
-                    Err(anyhow!("Category not found: {}", record_id))
-                }
-            }
-            "tags" => {
-                let mut rows = local_conn.query(
-                    "SELECT id, name, created_at, updated_at FROM tags WHERE id = ?",
-                    params![record_id]
-                ).await?;
-                
-                if let Some(row) = rows.next().await? {
-                    Ok(RecordData::Tag {
-                        id: row.get(0)?,
-                        name: row.get(1)?,
-                        created_at: row.get(2)?,
-                        updated_at: row.get(3)?,
-                    })
-                } else {
-                    Err(anyhow!("Tag not found: {}", record_id))
-                }
-            }
-            _ => Err(anyhow!("Unsupported table: {}", table_name)),
-        }
+        let descriptor = descriptor(table_name)?;
+        let columns = table_descriptors::synced_columns(local_conn, descriptor).await?;
+        let values = table_descriptors::read_row(local_conn, descriptor, &columns, record_id)
+            .await?
+            .ok_or_else(|| anyhow!("Record not found in {}: {}", table_name, record_id))?;
+
+        Ok(RecordData {
+            record_key: record_id.to_string(),
+            columns,
+            values,
+        })
     }
 
-    /// 写入记录到远程
+    /// 写入记录到远程（只写远程表中存在的列）
     async fn write_record_to_remote(
         &self,
         table_name: &str,
         record_data: RecordData,
     ) -> Result<()> {
+        let descriptor = descriptor(table_name)?;
         let guard = self.remote_db.read().await;
         let remote_db = guard.as_ref()
             .ok_or_else(|| anyhow!("Remote database not connected"))?
             .clone();
         
         let remote_conn = remote_db.connect()?;
+        let remote_columns = table_descriptors::table_columns(&remote_conn, table_name).await?;
+
+        let (columns, values): (Vec<String>, Vec<libsql::Value>) = record_data.columns
+            .into_iter()
+            .zip(record_data.values)
+            .filter(|(column, _)| remote_columns.iter().any(|c| c.eq_ignore_ascii_case(column)))
+            .unzip();
+        let written = table_descriptors::row_json(descriptor, &columns, &values);
+        table_descriptors::upsert_row(&remote_conn, descriptor, &columns, values).await?;
+
+        let local_conn = self.local_db.connect()?;
+        self.save_merge_base(&local_conn, descriptor, &record_data.record_key, Some(&written)).await?;
         
-        match record_data {
-            RecordData::Tip { id, title, content, tip_type, language, category_id, created_at, updated_at } => {
-                remote_conn.execute(
-                    "INSERT OR REPLACE INTO tips (id, title, content, tip_type, language, category_id, created_at, updated_at) 
-                     VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
-                    params![id, title, content, tip_type, language, category_id, created_at, updated_at]
-                ).await?;
-            }
-            RecordData::Category { id, name, parent_id, created_at, updated_at } => {
-                remote_conn.execute(
-                    "INSERT OR REPLACE INTO categories (id, name, parent_id, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
-                    params![id, name, parent_id, created_at, updated_at]
-                ).await?;
-            }
-            RecordData::Tag { id, name, created_at, updated_at } => {
-                remote_conn.execute(
-                    "INSERT OR REPLACE INTO tags (id, name, created_at, updated_at) VALUES (?, ?, ?, ?)",
-                    params![id, name, created_at, updated_at]
-                ).await?;
-            }
-        }
-        
-        info!("Successfully wrote record to remote table {}", table_name);
+        info!("Successfully wrote record {} to remote table {}", record_data.record_key, table_name);
         Ok(())
     }
 
-    /// 批量更新同步状态
+    /// 批量更新同步状态，并记录已同步内容的哈希
     async fn update_sync_status_batch(
         &self,
-        record_ids: &[String],
+        records: &[&ChangedRecord],
         table_name: &str,
     ) -> Result<()> {
-        if record_ids.is_empty() {
+        if records.is_empty() {
             return Ok(());
         }
         
@@ -707,12 +816,23 @@ impl IncrementalSyncManager {
         let now = chrono::Utc::now().timestamp_millis();
         
         // 批量更新
-        for record_id in record_ids {
+        for record in records {
             local_conn.execute(
                 "UPDATE sync_status SET sync_status = 'SYNCED', updated_at = ? 
                  WHERE table_name = ? AND record_id = ? AND sync_status = 'PENDING'",
-                params![now, table_name, record_id.clone()]
+                params![now, table_name, record.record_id.clone()]
             ).await?;
+
+            // 来自变更日志的记录未计算哈希，留给下次全量扫描校正
+            match (&record.change_type, record.local_hash.as_deref()) {
+                (ChangeType::Delete, _) => {
+                    table_descriptors::save_synced_hash(&local_conn, table_name, &record.record_id, None).await?;
+                }
+                (_, Some(hash)) => {
+                    table_descriptors::save_synced_hash(&local_conn, table_name, &record.record_id, Some(hash)).await?;
+                }
+                _ => {}
+            }
         }
         
         Ok(())
@@ -721,16 +841,12 @@ impl IncrementalSyncManager {
     /// 安全的删除记录同步  
     async fn sync_delete_record_safe(
         &self,
+        local_conn: &Connection,
         remote_conn: &Connection,
         table_name: &str,
         record_id: &str,
     ) -> Result<()> {
-        remote_conn.execute(
-            &format!("DELETE FROM {} WHERE id = ?", table_name),
-            params![record_id]
-        ).await?;
-        info!("Deleted record from remote: {} in table {}", record_id, table_name);
-        Ok(())
+        self.propagate_delete(local_conn, remote_conn, table_name, record_id).await
     }
 
     /// 同步插入记录
@@ -741,57 +857,11 @@ impl IncrementalSyncManager {
         table_name: &str,
         record_id: &str,
     ) -> Result<()> {
-        match table_name {
-            "tips" => {
-                if let Some(tip) = crate::db::operations::get_tip_by_id(local_conn, record_id).await? {
-                    remote_conn.execute(
-                        "INSERT OR REPLACE INTO tips (id, title, content, tip_type, language, category_id, created_at, updated_at, version, last_synced_at, sync_hash, is_encrypted, encryption_key_id, encrypted_content) 
-                         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
-                        params![
-                            tip.id, tip.title, tip.content, 
-                            String::from(tip.tip_type), tip.language, tip.category_id,
-                            tip.created_at, tip.updated_at, tip.version.unwrap_or(1), 
-                            tip.last_synced_at.unwrap_or(0), tip.sync_hash,
-                            tip.is_encrypted.unwrap_or(false), tip.encryption_key_id, tip.encrypted_content
-                        ]
-                    ).await?;
-                    info!("Synced tip to remote: {}", record_id);
-                }
-            }
-            "categories" => {
-                if let Some(category) = crate::db::operations::get_category_by_id(local_conn, record_id).await? {
-                    remote_conn.execute(
-                        "INSERT OR REPLACE INTO categories (id, name, parent_id, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
-                        params![category.id, category.name, category.parent_id, category.created_at, category.updated_at]
-                    ).await?;
-                    info!("Synced category to remote: {}", record_id);
-                }
-            }
-            "tags" => {
-                // 直接从数据库查询tag信息
-                let mut rows = local_conn.query(
-                    "SELECT id, name, created_at, updated_at FROM tags WHERE id = ?",
-                    params![record_id]
-                ).await?;
-                
-                if let Some(row) = rows.next().await? {
-                    let tag_id: String = row.get(0)?;
-                    let tag_name: String = row.get(1)?;
-                    let created_at: i64 = row.get(2)?;
-                    let updated_at: i64 = row.get(3)?;
-                    
-                    remote_conn.execute(
-                        "INSERT OR REPLACE INTO tags (id, name, created_at, updated_at) VALUES (?, ?, ?, ?)",
-                        params![tag_id, tag_name, created_at, updated_at]
-                    ).await?;
-                    info!("Synced tag to remote: {}", record_id);
-                }
-            }
-            _ => {
-                return Err(anyhow!("Unsupported table for incremental sync: {}", table_name));
-            }
+        let descriptor = descriptor(table_name)?;
+        if let Some(written) = table_descriptors::copy_row(local_conn, remote_conn, descriptor, record_id).await? {
+            self.save_merge_base(local_conn, descriptor, record_id, Some(&written)).await?;
+            info!("Synced record to remote table {}: {}", table_name, record_id);
         }
-        
         Ok(())
     }
 
@@ -810,16 +880,12 @@ impl IncrementalSyncManager {
     /// 同步删除记录
     async fn sync_delete_record(
         &self,
+        local_conn: &Connection,
         remote_conn: &Connection,
         table_name: &str,
         record_id: &str,
     ) -> Result<()> {
-        remote_conn.execute(
-            &format!("DELETE FROM {} WHERE id = ?", table_name),
-            params![record_id]
-        ).await?;
-        
-        Ok(())
+        self.propagate_delete(local_conn, remote_conn, table_name, record_id).await
     }
 
     /// 解决冲突记录
@@ -856,59 +922,12 @@ impl IncrementalSyncManager {
         table_name: &str,
         record_id: &str,
     ) -> Result<()> {
-        match table_name {
-            "tips" => {
-                let mut rows = remote_conn.query(
-                    "SELECT id, title, content, tip_type, language, category_id, created_at, updated_at 
-                     FROM tips WHERE id = ?",
-                    params![record_id]
-                ).await?;
-                
-                if let Some(row) = rows.next().await? {
-                    local_conn.execute(
-                        "INSERT OR REPLACE INTO tips (id, title, content, tip_type, language, category_id, created_at, updated_at) 
-                         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
-                        params![
-                            row.get::<String>(0)?, row.get::<String>(1)?, row.get::<String>(2)?,
-                            row.get::<String>(3)?, row.get::<Option<String>>(4)?, row.get::<Option<String>>(5)?,
-                            row.get::<i64>(6)?, row.get::<i64>(7)?
-                        ]
-                    ).await?;
-                }
-            }
-            "categories" => {
-                let mut rows = remote_conn.query(
-                    "SELECT id, name, parent_id FROM categories WHERE id = ?",
-                    params![record_id]
-                ).await?;
-                
-                if let Some(row) = rows.next().await? {
-                    local_conn.execute(
-                        "INSERT OR REPLACE INTO categories (id, name, parent_id) VALUES (?, ?, ?)",
-                        params![
-                            row.get::<String>(0)?, row.get::<String>(1)?, row.get::<Option<String>>(2)?
-                        ]
-                    ).await?;
-                }
-            }
-            "tags" => {
-                let mut rows = remote_conn.query(
-                    "SELECT id, name FROM tags WHERE id = ?",
-                    params![record_id]
-                ).await?;
-                
-                if let Some(row) = rows.next().await? {
-                    local_conn.execute(
-                        "INSERT OR REPLACE INTO tags (id, name) VALUES (?, ?)",
-                        params![row.get::<String>(0)?, row.get::<String>(1)?]
-                    ).await?;
-                }
-            }
-            _ => {
-                return Err(anyhow!("Unsupported table for remote sync: {}", table_name));
-            }
+        let descriptor = descriptor(table_name)?;
+        crate::db::begin_sync_apply(local_conn).await?;
+        let copied = table_descriptors::copy_row(remote_conn, local_conn, descriptor, record_id).await;
+        if let Some(written) = crate::db::finish_sync_apply(local_conn, copied).await? {
+            self.save_merge_base(local_conn, descriptor, record_id, Some(&written)).await?;
         }
-        
         Ok(())
     }
 
@@ -919,39 +938,84 @@ impl IncrementalSyncManager {
         drop(config);
 
         let timestamps = self.last_sync_timestamps.read().await;
-        let last_full_sync = timestamps.get("__full_sync__").copied().unwrap_or(0);
+        let last_full_sync = timestamps.get(FULL_SYNC_KEY).copied().unwrap_or(0);
         drop(timestamps);
 
         let now = Utc::now().timestamp_millis();
         Ok(now - last_full_sync > force_interval_ms as i64)
     }
 
-    /// 执行全量同步
+    /// 执行全量同步：逐表扫描并比较哈希，完成后把变更日志游标推进到扫描开始时的位置
     async fn perform_full_sync(&self) -> Result<IncrementalSyncStats> {
         info!("Performing forced full sync");
         
         let sync_start = Instant::now();
-        
-        // 记录全量同步时间戳
-        {
-            let mut timestamps = self.last_sync_timestamps.write().await;
-            timestamps.insert("__full_sync__".to_string(), Utc::now().timestamp_millis());
+        let local_conn = self.local_db.connect()?;
+        // 扫描覆盖此前的全部日志
+        let scan_seq = crate::db::max_change_seq(&local_conn).await?;
+
+        let mut total_stats = IncrementalSyncStats::default();
+        let mut complete = true;
+        for table_name in SYNC_TABLES.iter().map(|d| d.table) {
+            match self.sync_table_incremental(table_name).await {
+                Ok(table_stats) => {
+                    complete &= table_stats.synced_records == table_stats.changed_records;
+                    total_stats.checked_records += table_stats.checked_records;
+                    total_stats.changed_records += table_stats.changed_records;
+                    total_stats.synced_records += table_stats.synced_records;
+                    total_stats.skipped_records += table_stats.skipped_records;
+                    total_stats.time_saved_ms += table_stats.time_saved_ms;
+                    total_stats.data_saved_bytes += table_stats.data_saved_bytes;
+                    total_stats.detection_duration_ms += table_stats.detection_duration_ms;
+                }
+                Err(e) => {
+                    error!("Failed to fully sync table {}: {}", table_name, e);
+                    complete = false;
+                }
+            }
         }
 
-        // 这里可以调用标准的全量同步逻辑
-        // 为了示例，我们返回一个基础的统计
-        let sync_duration = sync_start.elapsed();
-        
-        Ok(IncrementalSyncStats {
-            checked_records: 0,
-            changed_records: 0,
-            synced_records: 0,
-            skipped_records: 0,
-            time_saved_ms: 0,
-            data_saved_bytes: 0,
-            detection_duration_ms: 0,
-            sync_duration_ms: sync_duration.as_millis() as u64,
+        // 有失败时保留游标，下次仍会重新扫描
+        if complete {
+            crate::db::save_change_log_cursor(&local_conn, scan_seq).await?;
+            let compacted = crate::db::compact_change_log(&local_conn, scan_seq).await?;
+            debug!("Compacted {} change log entries", compacted);
+            // 全量扫描已覆盖此前失败的记录
+            crate::db::clear_change_log_failures(&local_conn).await?;
+
+            // 记录全量同步时间戳
+            let now = Utc::now().timestamp_millis();
+            self.last_sync_timestamps.write().await.insert(FULL_SYNC_KEY.to_string(), now);
+            crate::db::save_setting(
+                &local_conn,
+                &format!("incremental_sync_timestamp_{}", FULL_SYNC_KEY),
+                &now.to_string(),
+            ).await?;
+        }
+
+        self.update_sync_timestamps().await?;
+
+        total_stats.sync_duration_ms = sync_start.elapsed().as_millis() as u64;
+        info!("Full sync completed: {} checked, {} changed, {} synced",
+              total_stats.checked_records, total_stats.changed_records, total_stats.synced_records);
+        Ok(total_stats)
+    }
+
+    /// 从游标开始分页消费变更日志；失败的记录转入重试表，游标照常推进并压缩日志
+    async fn sync_from_change_log(&self, cursor: i64) -> Result<IncrementalSyncStats> {
+        let page_size = self.config.read().await.max_batch_size.max(1) as i64;
+        let local_conn = self.local_db.connect()?;
+
+        consume_change_log(&local_conn, cursor, page_size, |table_name, records| async move {
+            match self.sync_changed_records_batch(&table_name, &records).await {
+                Ok(outcome) => outcome,
+                Err(e) => {
+                    error!("Failed to sync logged changes for table {}: {}", table_name, e);
+                    (Vec::new(), records.iter().map(|r| (r.record_id.clone(), e.to_string())).collect())
+                }
+            }
         })
+        .await
     }
 
     /// 加载同步时间戳
@@ -989,15 +1053,12 @@ impl IncrementalSyncManager {
         let mut timestamps = self.last_sync_timestamps.write().await;
         
         // 更新所有表的同步时间戳
-        for table_name in &["tips", "categories", "tags", "tip_tags"] {
+        for table_name in SYNC_TABLES.iter().map(|d| d.table) {
             timestamps.insert(table_name.to_string(), now);
             
             // 保存到数据库
             let setting_key = format!("incremental_sync_timestamp_{}", table_name);
-            conn.execute(
-                "INSERT OR REPLACE INTO app_settings (key, value) VALUES (?, ?)",
-                params![setting_key, now.to_string()]
-            ).await?;
+            crate::db::save_setting(&conn, &setting_key, &now.to_string()).await?;
         }
         
         info!("Updated sync timestamps for all tables");
@@ -1025,25 +1086,21 @@ impl ChangeDetector {
         }
     }
 
+    /// 按表的同步描述检测变更：比较当前行哈希与上次同步的哈希，并合并 sync_status 中待同步的操作
     pub async fn detect_changes(&self, table_name: &str) -> Result<ChangeDetectionResult> {
         let start_time = Utc::now().timestamp_millis();
+        let descriptor = table_descriptors::descriptor_for(table_name)
+            .ok_or_else(|| anyhow!("Unsupported table for change detection: {}", table_name))?;
         
         let conn = self.local_db.connect()?;
         
-        let changed_records = match table_name {
-            "tips" => self.detect_tips_changes(&conn).await?,
-            "categories" => self.detect_categories_changes(&conn).await?,
-            "tags" => self.detect_tags_changes(&conn).await?,
-            "tip_tags" => self.detect_tip_tags_changes(&conn).await?,
-            _ => {
-                return Err(anyhow!("Unsupported table for change detection: {}", table_name));
-            }
-        };
+        let (changed_records, total_checked) = self.detect_descriptor_changes(&conn, descriptor).await?;
 
         let end_time = Utc::now().timestamp_millis();
-        let total_checked = self.get_table_record_count(&conn, table_name).await?;
         let total_changed = changed_records.len() as u64;
 
+        info!("Detected {} changed records in {} ({} checked)", total_changed, table_name, total_checked);
+
         Ok(ChangeDetectionResult {
             table_name: table_name.to_string(),
             changed_records,
@@ -1054,100 +1111,98 @@ impl ChangeDetector {
         })
     }
 
-    async fn detect_tips_changes(&self, conn: &Connection) -> Result<Vec<ChangedRecord>> {
+    async fn detect_descriptor_changes(
+        &self,
+        conn: &Connection,
+        descriptor: &TableSyncDescriptor,
+    ) -> Result<(Vec<ChangedRecord>, u64)> {
+        let columns = table_descriptors::synced_columns(conn, descriptor).await?;
+        let hash_columns = descriptor.effective_hash_columns(&columns);
+        let mut synced_hashes = table_descriptors::load_synced_hashes(conn, descriptor.table).await?;
+        let mut pending = self.load_pending_operations(conn, descriptor.table).await?;
+
+        let key_count = descriptor.key_columns.len();
         let mut changed_records = Vec::new();
+        let mut total_checked = 0u64;
 
-        // 查找所有有同步记录且状态为PENDING的tips
-        let mut rows = conn.query(
-            "SELECT DISTINCT t.id, t.updated_at, ss.operation 
-             FROM tips t 
-             INNER JOIN sync_status ss ON t.id = ss.record_id 
-             WHERE ss.table_name = 'tips' AND ss.sync_status = 'PENDING'",
-            ()
-        ).await?;
-
+        let mut rows = conn.query(&descriptor.detection_sql(&hash_columns), ()).await?;
         while let Some(row) = rows.next().await? {
-            let id: String = row.get(0)?;
-            let updated_at: i64 = row.get(1)?;
-            let operation: String = row.get(2)?;
+            total_checked += 1;
 
-            let change_type = match operation.as_str() {
-                "INSERT" => ChangeType::Insert,
-                "UPDATE" => ChangeType::Update,
-                "DELETE" => ChangeType::Delete,
-                _ => ChangeType::Update,
+            let mut key_values = Vec::with_capacity(key_count);
+            for i in 0..key_count {
+                key_values.push(table_descriptors::key_value_to_string(&row.get_value(i as i32)?)?);
+            }
+            let record_key = descriptor.encode_key(&key_values);
+            let local_timestamp = match row.get_value(key_count as i32)? {
+                libsql::Value::Integer(ts) => ts,
+                _ => 0,
             };
 
-            changed_records.push(ChangedRecord {
-                record_id: id.clone(),
-                change_type,
-                local_timestamp: updated_at,
-                remote_timestamp: None,
-                local_hash: None,
-                remote_hash: None,
-                estimated_size: 1024, // 假设每个tip约1KB
-            });
-        }
-
-        info!("Detected {} pending tips for sync", changed_records.len());
-        Ok(changed_records)
-    }
-
-    async fn detect_categories_changes(&self, conn: &Connection) -> Result<Vec<ChangedRecord>> {
-        let mut changed_records = Vec::new();
-
-        // 查找所有有同步记录且状态为PENDING的categories
-        let mut rows = conn.query(
-            "SELECT DISTINCT c.id, c.updated_at, ss.operation 
-             FROM categories c 
-             INNER JOIN sync_status ss ON c.id = ss.record_id 
-             WHERE ss.table_name = 'categories' AND ss.sync_status = 'PENDING'",
-            ()
-        ).await?;
-
-        while let Some(row) = rows.next().await? {
-            let id: String = row.get(0)?;
-            let updated_at: i64 = row.get(1)?;
-            let operation: String = row.get(2)?;
-
-            let change_type = match operation.as_str() {
-                "INSERT" => ChangeType::Insert,
-                "UPDATE" => ChangeType::Update,
-                "DELETE" => ChangeType::Delete,
-                _ => ChangeType::Update,
+            let mut hashed = Vec::with_capacity(hash_columns.len());
+            for i in 0..hash_columns.len() {
+                hashed.push(row.get_value((key_count + 1 + i) as i32)?);
+            }
+            let local_hash = self.calculate_hash(&table_descriptors::row_fingerprint(&hashed));
+            let estimated_size = hashed.iter().map(table_descriptors::value_size).sum();
+
+            let synced_hash = synced_hashes.remove(&record_key);
+            let change_type = match (pending.remove(&record_key), synced_hash.as_deref()) {
+                (Some(operation), _) => Some(operation),
+                (None, None) => Some(ChangeType::Insert),
+                (None, Some(hash)) if hash != local_hash => Some(ChangeType::Update),
+                _ => None,
             };
 
+            if let Some(change_type) = change_type {
+                changed_records.push(ChangedRecord {
+                    record_id: record_key,
+                    change_type,
+                    local_timestamp,
+                    remote_timestamp: None,
+                    local_hash: Some(local_hash),
+                    remote_hash: synced_hash,
+                    estimated_size,
+                    log_seqs: None,
+                });
+            }
+        }
+
+        // 已同步过但本地已不存在的记录，以及本地已删除的待同步记录
+        let now = Utc::now().timestamp_millis();
+        let mut deleted: Vec<(String, Option<String>)> = synced_hashes.into_iter()
+            .map(|(key, hash)| (key, Some(hash)))
+            .collect();
+        deleted.extend(pending.into_keys().map(|key| (key, None)));
+        for (record_key, remote_hash) in deleted {
             changed_records.push(ChangedRecord {
-                record_id: id.clone(),
-                change_type,
-                local_timestamp: updated_at,
+                record_id: record_key,
+                change_type: ChangeType::Delete,
+                local_timestamp: now,
                 remote_timestamp: None,
                 local_hash: None,
-                remote_hash: None,
-                estimated_size: 256, // 假设每个category约256字节
+                remote_hash,
+                estimated_size: 0,
+                log_seqs: None,
             });
         }
 
-        info!("Detected {} pending categories for sync", changed_records.len());
-        Ok(changed_records)
+        Ok((changed_records, total_checked))
     }
 
-    async fn detect_tags_changes(&self, conn: &Connection) -> Result<Vec<ChangedRecord>> {
-        let mut changed_records = Vec::new();
-
-        // 查找所有有同步记录且状态为PENDING的tags
+    /// 读取 sync_status 中待同步的操作
+    async fn load_pending_operations(&self, conn: &Connection, table_name: &str) -> Result<HashMap<String, ChangeType>> {
         let mut rows = conn.query(
-            "SELECT DISTINCT t.id, t.updated_at, ss.operation 
-             FROM tags t 
-             INNER JOIN sync_status ss ON t.id = ss.record_id 
-             WHERE ss.table_name = 'tags' AND ss.sync_status = 'PENDING'",
-            ()
+            "SELECT record_id, operation FROM sync_status 
+             WHERE table_name = ? AND sync_status = 'PENDING' 
+             ORDER BY updated_at",
+            params![table_name]
         ).await?;
 
+        let mut pending = HashMap::new();
         while let Some(row) = rows.next().await? {
-            let id: String = row.get(0)?;
-            let updated_at: i64 = row.get(1)?;
-            let operation: String = row.get(2)?;
+            let record_id: String = row.get(0)?;
+            let operation: String = row.get(1)?;
 
             let change_type = match operation.as_str() {
                 "INSERT" => ChangeType::Insert,
@@ -1155,36 +1210,10 @@ impl ChangeDetector {
                 "DELETE" => ChangeType::Delete,
                 _ => ChangeType::Update,
             };
-
-            changed_records.push(ChangedRecord {
-                record_id: id.clone(),
-                change_type,
-                local_timestamp: updated_at,
-                remote_timestamp: None,
-                local_hash: None,
-                remote_hash: None,
-                estimated_size: 128, // 假设每个tag约128字节
-            });
-        }
-
-        info!("Detected {} pending tags for sync", changed_records.len());
-        Ok(changed_records)
-    }
-
-    async fn detect_tip_tags_changes(&self, _conn: &Connection) -> Result<Vec<ChangedRecord>> {
-        // tip_tags是关联表，暂时跳过变更检测
-        Ok(Vec::new())
-    }
-
-    async fn get_table_record_count(&self, conn: &Connection, table_name: &str) -> Result<u64> {
-        let sql = format!("SELECT COUNT(*) FROM {}", table_name);
-        let mut rows = conn.query(&sql, ()).await?;
-        
-        if let Some(row) = rows.next().await? {
-            Ok(row.get::<i64>(0).unwrap_or(0) as u64)
-        } else {
-            Ok(0)
+            // 同一记录有多条待同步操作时以最后一次为准
+            pending.insert(record_id, change_type);
         }
+        Ok(pending)
     }
 
     async fn get_last_sync_timestamp(&self, _table_name: &str) -> i64 {
@@ -1250,6 +1279,95 @@ mod tests {
         assert_eq!(stats.skipped_records, 0);
     }
 
+    fn log_entry(seq: i64, table_name: &str, record_key: &str, operation: &str) -> crate::db::ChangeLogEntry {
+        crate::db::ChangeLogEntry {
+            seq,
+            table_name: table_name.to_string(),
+            record_key: record_key.to_string(),
+            operation: operation.to_string(),
+            changed_at: seq * 1000,
+        }
+    }
+
+    #[test]
+    fn test_coalesce_change_log_keeps_last_operation() {
+        let entries = vec![
+            log_entry(1, "tips", "a", "INSERT"),
+            log_entry(2, "categories", "c", "UPDATE"),
+            log_entry(3, "tips", "a", "UPDATE"),
+            log_entry(4, "tips", "b", "INSERT"),
+            log_entry(5, "tips", "a", "DELETE"),
+        ];
+        let changes = coalesce_change_log(&entries);
+
+        // 按同步描述顺序：categories 在 tips 之前
+        assert_eq!(changes[0].0, "categories");
+        assert_eq!(changes[1].0, "tips");
+
+        let tips = &changes[1].1;
+        assert_eq!(tips.len(), 2);
+        assert_eq!(tips[0].record_id, "a");
+        assert_eq!(tips[0].change_type, ChangeType::Delete);
+        assert_eq!(tips[0].log_seqs, Some((1, 5)));
+        assert_eq!(tips[1].change_type, ChangeType::Insert);
+    }
+
+    #[test]
+    fn test_coalesce_change_log_skips_unknown_tables() {
+        let changes = coalesce_change_log(&[log_entry(1, "clipboard_history", "1", "INSERT")]);
+        assert!(changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failing_change_does_not_block_later_changes() {
+        let db = libsql::Builder::new_local(":memory:").build().await.unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute(
+            "CREATE TABLE app_settings (key TEXT PRIMARY KEY, value TEXT NOT NULL, created_at INTEGER NOT NULL, updated_at INTEGER NOT NULL)",
+            (),
+        ).await.unwrap();
+        crate::db::create_change_log_schema(&conn).await.unwrap();
+        conn.execute_batch(
+            "INSERT INTO change_log (table_name, record_key, operation, changed_at) VALUES
+                 ('tips', 'bad', 'INSERT', 1), ('tips', 'good1', 'INSERT', 2), ('tips', 'good2', 'INSERT', 3);",
+        ).await.unwrap();
+
+        let mut attempts: HashMap<String, usize> = HashMap::new();
+        let mut sync_batch = |_table: String, records: Vec<ChangedRecord>| {
+            let mut outcome: BatchSyncOutcome = (Vec::new(), Vec::new());
+            for record in records {
+                *attempts.entry(record.record_id.clone()).or_default() += 1;
+                if record.record_id == "bad" {
+                    outcome.1.push((record.record_id, "constraint failed".to_string()));
+                } else {
+                    outcome.0.push(record.record_id);
+                }
+            }
+            std::future::ready(outcome)
+        };
+
+        let stats = consume_change_log(&conn, 0, 2, &mut sync_batch).await.unwrap();
+        assert_eq!(stats.synced_records, 2);
+        assert_eq!(crate::db::get_change_log_cursor(&conn).await.unwrap(), Some(3));
+        assert!(crate::db::read_change_log(&conn, 0, 10).await.unwrap().is_empty());
+
+        // 之后的变更照常同步，失败的记录达到上限后不再重试
+        conn.execute(
+            "INSERT INTO change_log (table_name, record_key, operation, changed_at) VALUES ('tips', 'good3', 'UPDATE', 4)",
+            (),
+        ).await.unwrap();
+        for _ in 0..crate::db::MAX_CHANGE_LOG_ATTEMPTS + 2 {
+            consume_change_log(&conn, crate::db::get_change_log_cursor(&conn).await.unwrap().unwrap(), 2, &mut sync_batch)
+                .await
+                .unwrap();
+        }
+        assert_eq!(attempts["bad"], crate::db::MAX_CHANGE_LOG_ATTEMPTS as usize);
+        assert_eq!(attempts["good1"], 1);
+        assert_eq!(attempts["good3"], 1);
+        assert_eq!(crate::db::get_change_log_cursor(&conn).await.unwrap(), Some(4));
+        assert!(crate::db::pending_change_log_failures(&conn).await.unwrap().is_empty());
+    }
+
     #[test]
     fn test_change_type_equality() {
         assert_eq!(ChangeType::Insert, ChangeType::Insert);
@@ -17,6 +17,8 @@ pub mod monitoring;
 pub mod transaction_manager;
 pub mod conflict_resolver;
 pub mod incremental_sync;
+pub mod table_descriptors;
+pub mod three_way_merge;
 pub mod health_checker;
 pub mod libsql_sync_manager;
 pub mod libsql_adapter;
@@ -31,6 +33,8 @@ pub use conflict_resolver::{
     BatchConflictResolutionResult, FieldConflict, FieldMergeStrategy, ConflictSeverity, FieldConflictType
 };
 pub use incremental_sync::{IncrementalSyncManager, IncrementalSyncConfig, IncrementalSyncStats, ChangeDetector};
+pub use table_descriptors::{TableSyncDescriptor, SYNC_TABLES};
+pub use three_way_merge::{merge_text, merge_records, TextConflict, TextMergeResult, FieldTextConflicts, RecordMergeResult};
 pub use health_checker::{ConnectionHealthChecker, HealthCheckConfig, ConnectionStatus, DatabaseConnectionStatus, HealthCheckResult};
 pub use libsql_sync_manager::{LibSqlSyncManager, LibSqlSyncConfig, SyncResult as LibSqlSyncResult};
 pub use libsql_adapter::{LibSqlAdapter, test_libsql_connection};
@@ -51,6 +55,29 @@ pub struct ConflictData {
     pub remote_content: String,
 }
 
+/// 三方合并后仍有文本冲突的记录（合并结果已带冲突标记写入两端）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeConflictNotice {
+    pub table_name: String,
+    pub record_id: String,
+    pub fields: Vec<FieldTextConflicts>,
+}
+
+lazy_static::lazy_static! {
+    static ref MERGE_CONFLICTS: tokio::sync::broadcast::Sender<MergeConflictNotice> =
+        tokio::sync::broadcast::channel(64).0;
+}
+
+/// 订阅三方合并冲突，由应用层转发给前端
+pub fn subscribe_merge_conflicts() -> tokio::sync::broadcast::Receiver<MergeConflictNotice> {
+    MERGE_CONFLICTS.subscribe()
+}
+
+/// 发布三方合并冲突，没有订阅者时忽略
+pub(crate) fn publish_merge_conflicts(notice: MergeConflictNotice) {
+    let _ = MERGE_CONFLICTS.send(notice);
+}
+
 /// 冲突解决结果
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConflictResolutionResult {
@@ -853,11 +880,27 @@ impl Clone for SyncManager {
 
 /// 标记同步记录
 pub async fn mark_for_sync(
-    _conn: &Connection,
-    _table_name: &str,
-    _record_id: &str,
-    _operation: SyncOperation,
+    conn: &Connection,
+    table_name: &str,
+    record_id: &str,
+    operation: SyncOperation,
 ) -> Result<()> {
-    // TODO: 实现实际的同步标记逻辑
+    let operation = match operation {
+        SyncOperation::Insert => "INSERT",
+        SyncOperation::Update => "UPDATE",
+        SyncOperation::Delete => "DELETE",
+    };
+    let now = Utc::now().timestamp_millis();
+
+    // 每条记录只保留一个待同步操作，以最新操作为准
+    conn.execute(
+        "DELETE FROM sync_status WHERE table_name = ?1 AND record_id = ?2 AND sync_status = 'PENDING'",
+        libsql::params![table_name, record_id],
+    ).await?;
+    conn.execute(
+        "INSERT OR REPLACE INTO sync_status (id, table_name, record_id, operation, sync_status, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, 'PENDING', ?5, ?6)",
+        libsql::params![Uuid::new_v4().to_string(), table_name, record_id, operation, now, now],
+    ).await?;
     Ok(())
 } 
\ No newline at end of file
@@ -0,0 +1,551 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use libsql::{params, Connection, Value};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// 增量同步的单表描述
+#[derive(Debug, Clone, Copy)]
+pub struct TableSyncDescriptor {
+    /// 表名
+    pub table: &'static str,
+    /// 主键列，复合主键按顺序编码为 JSON 数组
+    pub key_columns: &'static [&'static str],
+    /// 参与变更哈希的列，为空时使用全部同步列
+    pub hash_columns: &'static [&'static str],
+    /// 大字段：检测时按内容哈希，同步时整体传输，不写入合并基准
+    pub blob_columns: &'static [&'static str],
+    /// 仅本地使用、不参与同步的列
+    pub excluded_columns: &'static [&'static str],
+    /// 记录变更时间的列
+    pub timestamp_column: Option<&'static str>,
+    /// 软删除列，删除以墓碑形式同步
+    pub tombstone_column: Option<&'static str>,
+    /// 行过滤条件，排除仅本机有效的数据
+    pub row_filter: Option<&'static str>,
+    /// 并发修改时做文本三方合并的列，非空时同步会保存共同祖先
+    pub merge_text_columns: &'static [&'static str],
+}
+
+const BASE: TableSyncDescriptor = TableSyncDescriptor {
+    table: "",
+    key_columns: &["id"],
+    hash_columns: &[],
+    blob_columns: &[],
+    excluded_columns: &[],
+    timestamp_column: Some("updated_at"),
+    tombstone_column: None,
+    row_filter: None,
+    merge_text_columns: &[],
+};
+
+/// 同步元数据列，只在本地维护
+const SYNC_META_COLUMNS: &[&str] = &["last_synced_at", "sync_hash"];
+
+/// 参与增量同步的用户数据表（按外键依赖排序）
+///
+/// 未列出的表为本机数据或派生数据：剪贴板历史、向量、链接和同步自身的元数据
+pub const SYNC_TABLES: &[TableSyncDescriptor] = &[
+    TableSyncDescriptor {
+        table: "categories",
+        excluded_columns: SYNC_META_COLUMNS,
+        tombstone_column: Some("deleted_at"),
+        ..BASE
+    },
+    TableSyncDescriptor { table: "tags", excluded_columns: SYNC_META_COLUMNS, ..BASE },
+    TableSyncDescriptor {
+        table: "tips",
+        excluded_columns: SYNC_META_COLUMNS,
+        tombstone_column: Some("deleted_at"),
+        merge_text_columns: &["title", "content"],
+        ..BASE
+    },
+    TableSyncDescriptor {
+        table: "tip_tags",
+        key_columns: &["tip_id", "tag_id"],
+        timestamp_column: None,
+        ..BASE
+    },
+    TableSyncDescriptor { table: "tip_properties", key_columns: &["tip_id", "key"], ..BASE },
+    TableSyncDescriptor { table: "tip_property_definitions", key_columns: &["key"], ..BASE },
+    TableSyncDescriptor { table: "tip_images", blob_columns: &["image_data"], ..BASE },
+    TableSyncDescriptor { table: "tip_audio_files", blob_columns: &["audio_data"], ..BASE },
+    TableSyncDescriptor { table: "tip_revisions", ..BASE },
+    TableSyncDescriptor { table: "ai_roles", ..BASE },
+    TableSyncDescriptor { table: "ai_conversations", ..BASE },
+    TableSyncDescriptor { table: "ai_messages", timestamp_column: Some("created_at"), ..BASE },
+    TableSyncDescriptor { table: "ai_usage", timestamp_column: Some("created_at"), ..BASE },
+    TableSyncDescriptor { table: "ai_model_prices", key_columns: &["provider", "model"], ..BASE },
+    TableSyncDescriptor { table: "ai_budgets", key_columns: &["scope"], ..BASE },
+    TableSyncDescriptor { table: "ai_prompt_runs", timestamp_column: Some("created_at"), ..BASE },
+    TableSyncDescriptor { table: "tip_templates", merge_text_columns: &["template", "description"], ..BASE },
+    TableSyncDescriptor {
+        table: "app_settings",
+        key_columns: &["key"],
+        row_filter: Some(
+            "key NOT LIKE 'incremental_sync_timestamp_%' AND key NOT LIKE 'discovered_models_%' \
+             AND key NOT IN ('database_type', 'global_shortcut', 'proxy_settings', 'network_settings', 'change_log_cursor', \
+             'ai_key_protection', 'ai_key_password_salt', 'ai_key_password_check')",
+        ),
+        ..BASE
+    },
+    TableSyncDescriptor { table: "encryption_keys", ..BASE },
+];
+
+/// 按表名查找同步描述
+pub fn descriptor_for(table_name: &str) -> Option<&'static TableSyncDescriptor> {
+    SYNC_TABLES.iter().find(|d| d.table == table_name)
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+impl TableSyncDescriptor {
+    /// 列是否参与同步
+    pub fn is_synced_column(&self, column: &str) -> bool {
+        !self.excluded_columns.iter().any(|c| c.eq_ignore_ascii_case(column))
+    }
+
+    /// 编码记录键：单列主键直接使用原值，与 sync_status.record_id 保持一致
+    pub fn encode_key(&self, values: &[String]) -> String {
+        if values.len() == 1 {
+            values[0].clone()
+        } else {
+            serde_json::to_string(values).unwrap_or_default()
+        }
+    }
+
+    /// 解码记录键
+    pub fn decode_key(&self, record_key: &str) -> Result<Vec<String>> {
+        if self.key_columns.len() == 1 {
+            return Ok(vec![record_key.to_string()]);
+        }
+        let values: Vec<String> = serde_json::from_str(record_key)
+            .map_err(|e| anyhow!("Invalid record key for {}: {} ({})", self.table, record_key, e))?;
+        if values.len() != self.key_columns.len() {
+            return Err(anyhow!("Record key for {} expects {} columns: {}", self.table, self.key_columns.len(), record_key));
+        }
+        Ok(values)
+    }
+
+    /// 主键匹配条件
+    fn key_predicate(&self) -> String {
+        self.key_columns
+            .iter()
+            .map(|c| format!("{} = ?", quote_ident(c)))
+            .collect::<Vec<_>>()
+            .join(" AND ")
+    }
+
+    /// 实际参与哈希的列
+    pub fn effective_hash_columns(&self, synced_columns: &[String]) -> Vec<String> {
+        if self.hash_columns.is_empty() {
+            synced_columns.to_vec()
+        } else {
+            self.hash_columns.iter().map(|c| c.to_string()).collect()
+        }
+    }
+
+    /// 变更检测查询：主键、时间戳、哈希列（大字段取原值，由指纹按内容哈希，等长替换也能检测到）
+    pub fn detection_sql(&self, hash_columns: &[String]) -> String {
+        let mut select: Vec<String> = self.key_columns.iter().map(|c| quote_ident(c)).collect();
+        select.push(self.timestamp_column.map(quote_ident).unwrap_or_else(|| "0".to_string()));
+        select.extend(hash_columns.iter().map(|c| quote_ident(c)));
+
+        let mut sql = format!("SELECT {} FROM {}", select.join(", "), self.table);
+        if let Some(filter) = self.row_filter {
+            sql.push_str(&format!(" WHERE {}", filter));
+        }
+        sql
+    }
+}
+
+/// 将一行的哈希列拼接为稳定的指纹文本
+pub fn row_fingerprint(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(|value| match value {
+            Value::Null => "\u{0}".to_string(),
+            Value::Integer(i) => format!("i{}", i),
+            Value::Real(r) => format!("r{}", r),
+            Value::Text(s) => format!("t{}", s),
+            Value::Blob(b) => format!("b{}", blake3::hash(b).to_hex()),
+        })
+        .collect::<Vec<_>>()
+        .join("\u{1f}")
+}
+
+/// 值的大致字节数
+pub fn value_size(value: &Value) -> u64 {
+    match value {
+        Value::Null => 0,
+        Value::Integer(_) | Value::Real(_) => 8,
+        Value::Text(s) => s.len() as u64,
+        Value::Blob(b) => b.len() as u64,
+    }
+}
+
+/// 主键值转为文本
+pub fn key_value_to_string(value: &Value) -> Result<String> {
+    match value {
+        Value::Text(s) => Ok(s.clone()),
+        Value::Integer(i) => Ok(i.to_string()),
+        other => Err(anyhow!("Unsupported key value: {:?}", other)),
+    }
+}
+
+/// 读取表的全部列名
+pub async fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut rows = conn.query(&format!("PRAGMA table_info({})", table), ()).await?;
+    let mut columns = Vec::new();
+    while let Some(row) = rows.next().await? {
+        columns.push(row.get::<String>(1)?);
+    }
+    Ok(columns)
+}
+
+/// 读取表中参与同步的列
+pub async fn synced_columns(conn: &Connection, descriptor: &TableSyncDescriptor) -> Result<Vec<String>> {
+    let columns = table_columns(conn, descriptor.table).await?;
+    if columns.is_empty() {
+        return Err(anyhow!("Table not found: {}", descriptor.table));
+    }
+    Ok(columns.into_iter().filter(|c| descriptor.is_synced_column(c)).collect())
+}
+
+/// 按主键读取一行的指定列
+pub async fn read_row(
+    conn: &Connection,
+    descriptor: &TableSyncDescriptor,
+    columns: &[String],
+    record_key: &str,
+) -> Result<Option<Vec<Value>>> {
+    let key_values = descriptor.decode_key(record_key)?;
+    let select = columns.iter().map(|c| quote_ident(c)).collect::<Vec<_>>().join(", ");
+    let sql = format!("SELECT {} FROM {} WHERE {}", select, descriptor.table, descriptor.key_predicate());
+
+    let mut rows = conn.query(&sql, libsql::params_from_iter(key_values)).await?;
+    match rows.next().await? {
+        Some(row) => {
+            let mut values = Vec::with_capacity(columns.len());
+            for i in 0..columns.len() {
+                values.push(row.get_value(i as i32)?);
+            }
+            Ok(Some(values))
+        }
+        None => Ok(None),
+    }
+}
+
+/// 写入一行的 SQL：主键冲突时原地更新非主键列，避免 REPLACE 先删后插触发子表级联删除
+fn upsert_sql(descriptor: &TableSyncDescriptor, columns: &[String]) -> String {
+    let names = columns.iter().map(|c| quote_ident(c)).collect::<Vec<_>>().join(", ");
+    let placeholders = vec!["?"; columns.len()].join(", ");
+    let conflict_target = descriptor.key_columns.iter().map(|c| quote_ident(c)).collect::<Vec<_>>().join(", ");
+    let assignments = columns
+        .iter()
+        .filter(|c| !descriptor.key_columns.iter().any(|k| k.eq_ignore_ascii_case(c)))
+        .map(|c| format!("{0} = excluded.{0}", quote_ident(c)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let action = if assignments.is_empty() {
+        "DO NOTHING".to_string()
+    } else {
+        format!("DO UPDATE SET {}", assignments)
+    };
+
+    format!(
+        "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT({}) {}",
+        descriptor.table, names, placeholders, conflict_target, action
+    )
+}
+
+/// 写入或更新一行
+pub async fn upsert_row(
+    conn: &Connection,
+    descriptor: &TableSyncDescriptor,
+    columns: &[String],
+    values: Vec<Value>,
+) -> Result<()> {
+    if columns.is_empty() || columns.len() != values.len() {
+        return Err(anyhow!("Column/value mismatch when writing {}", descriptor.table));
+    }
+    conn.execute(&upsert_sql(descriptor, columns), libsql::params_from_iter(values)).await?;
+    Ok(())
+}
+
+/// 按主键删除一行
+pub async fn delete_row(conn: &Connection, descriptor: &TableSyncDescriptor, record_key: &str) -> Result<()> {
+    let key_values = descriptor.decode_key(record_key)?;
+    conn.execute(
+        &format!("DELETE FROM {} WHERE {}", descriptor.table, descriptor.key_predicate()),
+        libsql::params_from_iter(key_values),
+    ).await?;
+    Ok(())
+}
+
+/// 将一条记录从源库复制到目标库，只传输两端都存在的同步列；返回写入的内容（不含大字段），源记录不存在时返回 None
+pub async fn copy_row(
+    from: &Connection,
+    to: &Connection,
+    descriptor: &TableSyncDescriptor,
+    record_key: &str,
+) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+    let target_columns = table_columns(to, descriptor.table).await?;
+    let columns: Vec<String> = synced_columns(from, descriptor)
+        .await?
+        .into_iter()
+        .filter(|c| target_columns.iter().any(|t| t.eq_ignore_ascii_case(c)))
+        .collect();
+
+    match read_row(from, descriptor, &columns, record_key).await? {
+        Some(values) => {
+            let written = row_json(descriptor, &columns, &values);
+            upsert_row(to, descriptor, &columns, values).await?;
+            Ok(Some(written))
+        }
+        None => Ok(None),
+    }
+}
+
+/// 数据库值转为 JSON，大字段不参与合并
+pub fn value_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Null | Value::Blob(_) => serde_json::Value::Null,
+        Value::Integer(i) => serde_json::Value::from(*i),
+        Value::Real(r) => serde_json::Number::from_f64(*r)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Value::Text(s) => serde_json::Value::String(s.clone()),
+    }
+}
+
+/// JSON 转为数据库值
+pub fn json_to_value(value: &serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Integer(*b as i64),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Real(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => Value::Text(s.clone()),
+        other => Value::Text(other.to_string()),
+    }
+}
+
+/// 将一行的列值转为 JSON 对象（不含大字段）
+pub fn row_json(
+    descriptor: &TableSyncDescriptor,
+    columns: &[String],
+    values: &[Value],
+) -> serde_json::Map<String, serde_json::Value> {
+    columns
+        .iter()
+        .zip(values)
+        .filter(|(column, _)| !descriptor.blob_columns.contains(&column.as_str()))
+        .map(|(column, value)| (column.clone(), value_to_json(value)))
+        .collect()
+}
+
+/// 以 JSON 对象读取一行的同步列（不含大字段）
+pub async fn read_row_json(
+    conn: &Connection,
+    descriptor: &TableSyncDescriptor,
+    record_key: &str,
+) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+    let columns: Vec<String> = synced_columns(conn, descriptor)
+        .await?
+        .into_iter()
+        .filter(|c| !descriptor.blob_columns.contains(&c.as_str()))
+        .collect();
+
+    Ok(read_row(conn, descriptor, &columns, record_key)
+        .await?
+        .map(|values| row_json(descriptor, &columns, &values)))
+}
+
+/// 按 JSON 对象更新已有的一行，只写目标表中存在的非主键同步列
+pub async fn update_row_json(
+    conn: &Connection,
+    descriptor: &TableSyncDescriptor,
+    record_key: &str,
+    object: &serde_json::Map<String, serde_json::Value>,
+) -> Result<()> {
+    let target_columns = table_columns(conn, descriptor.table).await?;
+    let (columns, mut values): (Vec<String>, Vec<Value>) = object
+        .iter()
+        .filter(|(column, _)| {
+            descriptor.is_synced_column(column)
+                && !descriptor.key_columns.contains(&column.as_str())
+                && target_columns.iter().any(|c| c.eq_ignore_ascii_case(column))
+        })
+        .map(|(column, value)| (quote_ident(column), json_to_value(value)))
+        .unzip();
+    if columns.is_empty() {
+        return Ok(());
+    }
+
+    values.extend(descriptor.decode_key(record_key)?.into_iter().map(Value::Text));
+    let assignments = columns.iter().map(|c| format!("{} = ?", c)).collect::<Vec<_>>().join(", ");
+    conn.execute(
+        &format!("UPDATE {} SET {} WHERE {}", descriptor.table, assignments, descriptor.key_predicate()),
+        libsql::params_from_iter(values),
+    ).await?;
+    Ok(())
+}
+
+/// 读取已同步记录的哈希（存放在 data_versions 表）
+pub async fn load_synced_hashes(conn: &Connection, table: &str) -> Result<HashMap<String, String>> {
+    let mut rows = conn.query(
+        "SELECT record_id, hash FROM data_versions WHERE table_name = ?",
+        params![table],
+    ).await?;
+    let mut hashes = HashMap::new();
+    while let Some(row) = rows.next().await? {
+        hashes.insert(row.get::<String>(0)?, row.get::<String>(1)?);
+    }
+    Ok(hashes)
+}
+
+/// 记录同步成功后的哈希，hash 为空表示记录已删除
+pub async fn save_synced_hash(conn: &Connection, table: &str, record_key: &str, hash: Option<&str>) -> Result<()> {
+    match hash {
+        Some(hash) => {
+            conn.execute(
+                "INSERT INTO data_versions (id, table_name, record_id, version, hash, created_at)
+                 VALUES (?, ?, ?, 1, ?, ?)
+                 ON CONFLICT(table_name, record_id) DO UPDATE SET
+                    version = version + 1, hash = excluded.hash, created_at = excluded.created_at",
+                params![Uuid::new_v4().to_string(), table, record_key, hash, Utc::now().timestamp_millis()],
+            ).await?;
+        }
+        None => {
+            conn.execute(
+                "DELETE FROM data_versions WHERE table_name = ? AND record_id = ?",
+                params![table, record_key],
+            ).await?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_descriptors_cover_user_tables() {
+        for table in ["tip_images", "tip_audio_files", "ai_roles", "ai_conversations", "ai_messages", "tip_templates", "app_settings"] {
+            assert!(descriptor_for(table).is_some(), "{} should be synced", table);
+        }
+        assert!(descriptor_for("clipboard_history").is_none());
+        assert!(descriptor_for("sync_status").is_none());
+    }
+
+    #[test]
+    fn test_record_key_round_trip() {
+        let tags = descriptor_for("tip_tags").unwrap();
+        let key = tags.encode_key(&["t1".to_string(), "g\"2".to_string()]);
+        assert_eq!(tags.decode_key(&key).unwrap(), vec!["t1".to_string(), "g\"2".to_string()]);
+        assert!(tags.decode_key("[\"only\"]").is_err());
+
+        let tips = descriptor_for("tips").unwrap();
+        assert_eq!(tips.encode_key(&["abc".to_string()]), "abc");
+        assert_eq!(tips.decode_key("abc").unwrap(), vec!["abc".to_string()]);
+    }
+
+    #[test]
+    fn test_detection_hashes_blob_content() {
+        let audio = descriptor_for("tip_audio_files").unwrap();
+        let sql = audio.detection_sql(&["audio_data".to_string(), "file_name".to_string()]);
+        assert!(sql.contains("\"audio_data\", \"file_name\""));
+        assert!(!sql.contains("length("));
+
+        // 等长但内容不同的音频指纹不同
+        let before = row_fingerprint(&[Value::Blob(vec![1, 2, 3]), Value::Text("a.mp3".to_string())]);
+        let after = row_fingerprint(&[Value::Blob(vec![3, 2, 1]), Value::Text("a.mp3".to_string())]);
+        assert_ne!(before, after);
+
+        let settings = descriptor_for("app_settings").unwrap();
+        assert!(settings.detection_sql(&[]).contains("WHERE key NOT LIKE"));
+    }
+
+    #[test]
+    fn test_excluded_columns() {
+        let tips = descriptor_for("tips").unwrap();
+        assert!(!tips.is_synced_column("sync_hash"));
+        assert!(tips.is_synced_column("content"));
+    }
+
+    #[test]
+    fn test_upsert_sql_updates_in_place() {
+        let tips = descriptor_for("tips").unwrap();
+        let sql = upsert_sql(tips, &["id".to_string(), "title".to_string()]);
+        assert!(!sql.contains("REPLACE"));
+        assert!(sql.ends_with("ON CONFLICT(\"id\") DO UPDATE SET \"title\" = excluded.\"title\""));
+
+        let tip_tags = descriptor_for("tip_tags").unwrap();
+        let sql = upsert_sql(tip_tags, &["tip_id".to_string(), "tag_id".to_string()]);
+        assert!(sql.ends_with("ON CONFLICT(\"tip_id\", \"tag_id\") DO NOTHING"));
+    }
+
+    async fn sync_test_db() -> Connection {
+        let db = libsql::Builder::new_local(":memory:").build().await.unwrap();
+        let conn = db.connect().unwrap();
+        conn.execute("PRAGMA foreign_keys=ON", ()).await.unwrap();
+        conn.execute_batch(
+            "CREATE TABLE tips (id TEXT PRIMARY KEY, title TEXT NOT NULL, updated_at INTEGER NOT NULL);
+             CREATE TABLE tags (id TEXT PRIMARY KEY, name TEXT NOT NULL);
+             CREATE TABLE tip_tags (
+                 tip_id TEXT NOT NULL,
+                 tag_id TEXT NOT NULL,
+                 PRIMARY KEY (tip_id, tag_id),
+                 FOREIGN KEY (tip_id) REFERENCES tips (id) ON DELETE CASCADE,
+                 FOREIGN KEY (tag_id) REFERENCES tags (id) ON DELETE CASCADE
+             );
+             INSERT INTO tips VALUES ('t1', 'old', 1);
+             INSERT INTO tags VALUES ('g1', 'rust');
+             INSERT INTO tip_tags VALUES ('t1', 'g1');",
+        ).await.unwrap();
+        conn
+    }
+
+    #[tokio::test]
+    async fn test_pushing_tip_keeps_remote_tags() {
+        let local = sync_test_db().await;
+        let remote = sync_test_db().await;
+        local.execute("UPDATE tips SET title = 'new', updated_at = 2 WHERE id = 't1'", ()).await.unwrap();
+
+        let tips = descriptor_for("tips").unwrap();
+        let written = copy_row(&local, &remote, tips, "t1").await.unwrap().unwrap();
+        assert_eq!(written.get("title"), Some(&serde_json::json!("new")));
+        let tip_tags = descriptor_for("tip_tags").unwrap();
+        assert!(copy_row(&local, &remote, tip_tags, &tip_tags.encode_key(&["t1".to_string(), "g1".to_string()])).await.unwrap().is_some());
+
+        let mut rows = remote.query("SELECT title FROM tips WHERE id = 't1'", ()).await.unwrap();
+        let title: String = rows.next().await.unwrap().unwrap().get(0).unwrap();
+        assert_eq!(title, "new");
+
+        let mut rows = remote.query("SELECT COUNT(*) FROM tip_tags WHERE tip_id = 't1'", ()).await.unwrap();
+        let count: i64 = rows.next().await.unwrap().unwrap().get(0).unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_json_value_round_trip() {
+        for value in [Value::Null, Value::Integer(3), Value::Real(1.5), Value::Text("x".to_string())] {
+            assert_eq!(json_to_value(&value_to_json(&value)), value);
+        }
+        assert_eq!(value_to_json(&Value::Blob(vec![1, 2])), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_row_fingerprint_distinguishes_types() {
+        let a = row_fingerprint(&[Value::Integer(1), Value::Null]);
+        let b = row_fingerprint(&[Value::Text("1".to_string()), Value::Null]);
+        assert_ne!(a, b);
+        assert_eq!(a, row_fingerprint(&[Value::Integer(1), Value::Null]));
+    }
+}
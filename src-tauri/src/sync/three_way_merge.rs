@@ -0,0 +1,414 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::db::revisions::{diff_sequences, DiffOp};
+
+const MARKER_LOCAL: &str = "<<<<<<< local";
+const MARKER_SEPARATOR: &str = "=======";
+const MARKER_REMOTE: &str = ">>>>>>> remote";
+
+/// 合并片段：可自动解决的内容或双方都修改了同一区域的冲突
+#[derive(Debug, Clone, PartialEq)]
+pub enum MergeChunk<T> {
+    Resolved(Vec<T>),
+    Conflict { base: Vec<T>, local: Vec<T>, remote: Vec<T> },
+}
+
+/// 文本冲突，供界面逐个处理
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TextConflict {
+    /// 冲突标记在合并结果中的起始行（从1开始）
+    pub line: usize,
+    pub base: String,
+    pub local: String,
+    pub remote: String,
+}
+
+/// 文本三方合并结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextMergeResult {
+    /// 合并后的文本，未能自动解决的部分带冲突标记
+    pub merged: String,
+    pub conflicts: Vec<TextConflict>,
+}
+
+impl TextMergeResult {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// 字段的文本冲突
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldTextConflicts {
+    pub field: String,
+    pub conflicts: Vec<TextConflict>,
+}
+
+/// 记录三方合并结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordMergeResult {
+    pub merged: Map<String, Value>,
+    pub conflicts: Vec<FieldTextConflicts>,
+}
+
+/// 一侧相对共同祖先的修改：用 lines 替换 base[start..end]
+#[derive(Debug)]
+struct Hunk<T> {
+    start: usize,
+    end: usize,
+    lines: Vec<T>,
+}
+
+fn collect_hunks<T: PartialEq + Clone>(base: &[T], other: &[T]) -> Vec<Hunk<T>> {
+    let mut hunks = Vec::new();
+    let mut current: Option<Hunk<T>> = None;
+    let mut base_pos = 0;
+
+    for op in diff_sequences(base, other) {
+        match op {
+            DiffOp::Equal { old, .. } => {
+                hunks.extend(current.take());
+                base_pos = old + 1;
+            }
+            DiffOp::Delete { old } => {
+                let hunk = current.get_or_insert_with(|| Hunk { start: old, end: old, lines: Vec::new() });
+                hunk.end = old + 1;
+                base_pos = old + 1;
+            }
+            DiffOp::Insert { new } => {
+                let hunk = current.get_or_insert_with(|| Hunk { start: base_pos, end: base_pos, lines: Vec::new() });
+                hunk.lines.push(other[new].clone());
+            }
+        }
+    }
+    hunks.extend(current);
+    hunks
+}
+
+/// 把一组修改应用到 base[start..end]
+fn apply_hunks<T: Clone>(base: &[T], start: usize, end: usize, hunks: &[Hunk<T>]) -> Vec<T> {
+    let mut out = Vec::new();
+    let mut pos = start;
+    for hunk in hunks {
+        out.extend_from_slice(&base[pos..hunk.start]);
+        out.extend(hunk.lines.iter().cloned());
+        pos = hunk.end;
+    }
+    out.extend_from_slice(&base[pos..end]);
+    out
+}
+
+/// 追加已解决的内容，与前一个已解决片段合并
+fn push_resolved<T>(chunks: &mut Vec<MergeChunk<T>>, items: Vec<T>) {
+    if items.is_empty() {
+        return;
+    }
+    match chunks.last_mut() {
+        Some(MergeChunk::Resolved(prev)) => prev.extend(items),
+        _ => chunks.push(MergeChunk::Resolved(items)),
+    }
+}
+
+/// 序列三方合并：两侧修改互不重叠（也不相邻）时自动合并，否则产生冲突片段
+pub fn merge_sequences<T: PartialEq + Clone>(base: &[T], local: &[T], remote: &[T]) -> Vec<MergeChunk<T>> {
+    let local_hunks = collect_hunks(base, local);
+    let remote_hunks = collect_hunks(base, remote);
+
+    let mut chunks: Vec<MergeChunk<T>> = Vec::new();
+
+    let (mut i, mut j, mut pos) = (0, 0, 0);
+    while i < local_hunks.len() || j < remote_hunks.len() {
+        let start = match (local_hunks.get(i), remote_hunks.get(j)) {
+            (Some(l), Some(r)) => l.start.min(r.start),
+            (Some(l), None) => l.start,
+            (None, Some(r)) => r.start,
+            (None, None) => unreachable!(),
+        };
+
+        // 把首尾相接的修改归为一组
+        let (group_i, group_j) = (i, j);
+        let mut end = start;
+        loop {
+            if let Some(hunk) = local_hunks.get(i).filter(|h| h.start <= end) {
+                end = end.max(hunk.end);
+                i += 1;
+            } else if let Some(hunk) = remote_hunks.get(j).filter(|h| h.start <= end) {
+                end = end.max(hunk.end);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        push_resolved(&mut chunks, base[pos..start].to_vec());
+        let local_group = &local_hunks[group_i..i];
+        let remote_group = &remote_hunks[group_j..j];
+        let local_region = apply_hunks(base, start, end, local_group);
+        let remote_region = apply_hunks(base, start, end, remote_group);
+
+        if remote_group.is_empty() || local_region == remote_region {
+            push_resolved(&mut chunks, local_region);
+        } else if local_group.is_empty() {
+            push_resolved(&mut chunks, remote_region);
+        } else {
+            chunks.push(MergeChunk::Conflict {
+                base: base[start..end].to_vec(),
+                local: local_region,
+                remote: remote_region,
+            });
+        }
+        pos = end;
+    }
+    push_resolved(&mut chunks, base[pos..].to_vec());
+    chunks
+}
+
+/// 按词切分：连续空白、连续 ASCII 字母数字为一个词，其余字符（含中文）逐字切分
+fn split_words(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut prev_class = None;
+    for (i, ch) in text.char_indices() {
+        let class = if ch.is_whitespace() {
+            0
+        } else if ch.is_ascii_alphanumeric() || ch == '_' {
+            1
+        } else {
+            2
+        };
+        if (class == 2 || prev_class != Some(class)) && i > start {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        prev_class = Some(class);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+/// 在重叠的行块内按词再合并一次，仍有冲突时返回 None
+fn merge_words(base: &str, local: &str, remote: &str) -> Option<String> {
+    let chunks = merge_sequences(&split_words(base), &split_words(local), &split_words(remote));
+    let mut merged = String::new();
+    for chunk in chunks {
+        match chunk {
+            MergeChunk::Resolved(words) => merged.extend(words),
+            MergeChunk::Conflict { .. } => return None,
+        }
+    }
+    Some(merged)
+}
+
+fn push_with_newline(out: &mut String, text: &str) {
+    out.push_str(text);
+    if !text.is_empty() && !text.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// 文本三方合并：先按行，重叠的行块再按词；仍冲突的部分输出冲突标记并记录到冲突列表
+pub fn merge_text(base: &str, local: &str, remote: &str) -> TextMergeResult {
+    if local == remote || remote == base {
+        return TextMergeResult { merged: local.to_string(), conflicts: Vec::new() };
+    }
+    if local == base {
+        return TextMergeResult { merged: remote.to_string(), conflicts: Vec::new() };
+    }
+
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let local_lines: Vec<&str> = local.split_inclusive('\n').collect();
+    let remote_lines: Vec<&str> = remote.split_inclusive('\n').collect();
+
+    let mut merged = String::new();
+    let mut conflicts = Vec::new();
+    for chunk in merge_sequences(&base_lines, &local_lines, &remote_lines) {
+        match chunk {
+            MergeChunk::Resolved(lines) => merged.extend(lines),
+            MergeChunk::Conflict { base, local, remote } => {
+                let (base, local, remote) = (base.concat(), local.concat(), remote.concat());
+                if let Some(words) = merge_words(&base, &local, &remote) {
+                    merged.push_str(&words);
+                    continue;
+                }
+
+                // 冲突标记需独占一行
+                if !merged.is_empty() && !merged.ends_with('\n') {
+                    merged.push('\n');
+                }
+                conflicts.push(TextConflict {
+                    line: merged.matches('\n').count() + 1,
+                    base,
+                    local: local.clone(),
+                    remote: remote.clone(),
+                });
+                merged.push_str(MARKER_LOCAL);
+                merged.push('\n');
+                push_with_newline(&mut merged, &local);
+                merged.push_str(MARKER_SEPARATOR);
+                merged.push('\n');
+                push_with_newline(&mut merged, &remote);
+                merged.push_str(MARKER_REMOTE);
+                merged.push('\n');
+            }
+        }
+    }
+
+    TextMergeResult { merged, conflicts }
+}
+
+/// 记录三方合并：只有一侧修改的字段取修改后的值，双方都修改的文本字段做文本合并，
+/// updated_at 取较大值，其余字段以本地为准
+pub fn merge_records(
+    base: &Map<String, Value>,
+    local: &Map<String, Value>,
+    remote: &Map<String, Value>,
+    text_fields: &[&str],
+) -> RecordMergeResult {
+    let mut merged = Map::new();
+    let mut conflicts = Vec::new();
+
+    let mut fields: Vec<&String> = local.keys().collect();
+    fields.extend(remote.keys().filter(|k| !local.contains_key(*k)));
+
+    for field in fields {
+        let (local_value, remote_value) = match (local.get(field), remote.get(field)) {
+            (Some(l), Some(r)) => (l, r),
+            // 只有一端有该列时无从比较
+            (Some(v), None) | (None, Some(v)) => {
+                merged.insert(field.clone(), v.clone());
+                continue;
+            }
+            (None, None) => continue,
+        };
+        let base_value = base.get(field).unwrap_or(&Value::Null);
+
+        let value = if local_value == remote_value || remote_value == base_value {
+            local_value.clone()
+        } else if local_value == base_value {
+            remote_value.clone()
+        } else if let (true, Some(l), Some(r)) = (
+            text_fields.contains(&field.as_str()),
+            local_value.as_str(),
+            remote_value.as_str(),
+        ) {
+            let result = merge_text(base_value.as_str().unwrap_or(""), l, r);
+            if !result.is_clean() {
+                conflicts.push(FieldTextConflicts { field: field.clone(), conflicts: result.conflicts });
+            }
+            Value::String(result.merged)
+        } else if field == "updated_at" {
+            match (local_value.as_i64(), remote_value.as_i64()) {
+                (Some(l), Some(r)) if r > l => remote_value.clone(),
+                _ => local_value.clone(),
+            }
+        } else {
+            local_value.clone()
+        };
+        merged.insert(field.clone(), value);
+    }
+
+    RecordMergeResult { merged, conflicts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn object(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn non_overlapping_line_edits_merge_cleanly() {
+        let base = "a\nb\nc\nd\ne\n";
+        let local = "A\nb\nc\nd\ne\n";
+        let remote = "a\nb\nc\nd\nE\n";
+        let result = merge_text(base, local, remote);
+        assert!(result.is_clean());
+        assert_eq!(result.merged, "A\nb\nc\nd\nE\n");
+    }
+
+    #[test]
+    fn identical_edits_are_not_conflicts() {
+        let result = merge_text("a\nb\n", "a\nx\n", "a\nx\n");
+        assert!(result.is_clean());
+        assert_eq!(result.merged, "a\nx\n");
+    }
+
+    #[test]
+    fn same_line_edits_merge_by_word() {
+        let base = "the quick brown fox\n";
+        let local = "the slow brown fox\n";
+        let remote = "the quick brown cat\n";
+        let result = merge_text(base, local, remote);
+        assert!(result.is_clean());
+        assert_eq!(result.merged, "the slow brown cat\n");
+    }
+
+    #[test]
+    fn overlapping_edits_produce_markers_and_conflict_list() {
+        let base = "a\nb\nc\n";
+        let local = "a\nlocal\nc\n";
+        let remote = "a\nremote\nc\n";
+        let result = merge_text(base, local, remote);
+        assert_eq!(
+            result.merged,
+            "a\n<<<<<<< local\nlocal\n=======\nremote\n>>>>>>> remote\nc\n"
+        );
+        assert_eq!(
+            result.conflicts,
+            vec![TextConflict {
+                line: 2,
+                base: "b\n".to_string(),
+                local: "local\n".to_string(),
+                remote: "remote\n".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn insertions_at_different_places_merge() {
+        let base = "a\nb\nc\nd\n";
+        let local = "a\nnew1\nb\nc\nd\n";
+        let remote = "a\nb\nc\nnew2\nd\n";
+        let result = merge_text(base, local, remote);
+        assert!(result.is_clean());
+        assert_eq!(result.merged, "a\nnew1\nb\nc\nnew2\nd\n");
+    }
+
+    #[test]
+    fn cjk_text_merges_per_character() {
+        let result = merge_text("今天天气很好\n", "今天天气很热\n", "明天天气很好\n");
+        assert!(result.is_clean());
+        assert_eq!(result.merged, "明天天气很热\n");
+    }
+
+    #[test]
+    fn record_merge_combines_fields() {
+        let base = object(json!({"id": "1", "title": "T", "content": "a\nb\nc\n", "pinned": 0, "updated_at": 10}));
+        let local = object(json!({"id": "1", "title": "T2", "content": "A\nb\nc\n", "pinned": 0, "updated_at": 20}));
+        let remote = object(json!({"id": "1", "title": "T", "content": "a\nb\nC\n", "pinned": 1, "updated_at": 30}));
+        let result = merge_records(&base, &local, &remote, &["title", "content"]);
+
+        assert!(result.conflicts.is_empty());
+        assert_eq!(result.merged["title"], json!("T2"));
+        assert_eq!(result.merged["content"], json!("A\nb\nC\n"));
+        assert_eq!(result.merged["pinned"], json!(1));
+        assert_eq!(result.merged["updated_at"], json!(30));
+    }
+
+    #[test]
+    fn record_merge_reports_text_conflicts() {
+        let base = object(json!({"content": "x\n"}));
+        let local = object(json!({"content": "y\n"}));
+        let remote = object(json!({"content": "z\n"}));
+        let result = merge_records(&base, &local, &remote, &["content"]);
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].field, "content");
+        assert!(result.merged["content"].as_str().unwrap().contains(MARKER_LOCAL));
+    }
+}
@@ -1,6 +1,7 @@
 import { defineStore } from 'pinia'
 import { ref, computed } from 'vue'
 import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
 
 // 数据类型定义
 export interface Tag {
@@ -28,6 +29,16 @@ export interface Tip {
   is_encrypted?: boolean
 }
 
+// 三方合并冲突，行号指合并结果中冲突标记的位置
+export interface MergeConflictNotice {
+  table_name: string
+  record_id: string
+  fields: {
+    field: string
+    conflicts: { line: number; base: string; local: string; remote: string }[]
+  }[]
+}
+
 export interface TipSummary {
   id: string
   title: string
@@ -589,9 +600,30 @@ export const useTipsStore = defineStore('tips', () => {
     }
   }
 
+  // 同步时并发修改的笔记三方合并后仍有冲突，内容中已带冲突标记；记录下来供界面提示并刷新笔记
+  const mergeConflicts = ref<MergeConflictNotice[]>([])
+  listen<MergeConflictNotice>('sync-merge-conflicts', async (event) => {
+    const notice = event.payload
+    if (notice.table_name !== 'tips') return
+    mergeConflicts.value = [...mergeConflicts.value.filter(c => c.record_id !== notice.record_id), notice]
+    if (tips.value.some(t => t.id === notice.record_id)) {
+      await fetchTip(notice.record_id)
+    }
+  })
+
+  // 重命名笔记后，后端改写了其他笔记中的链接，刷新已加载的这些笔记
+  listen<{ tip_id: string; tip_ids: string[] }>('tip-links-rewritten', async (event) => {
+    for (const id of event.payload.tip_ids) {
+      if (tips.value.some(t => t.id === id)) {
+        await fetchTip(id)
+      }
+    }
+  })
+
   return {
     // 状态
     tips,
+    mergeConflicts,
     categories,
     tags,
     isLoading,